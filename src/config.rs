@@ -0,0 +1,159 @@
+//! Startup settings read from the environment.
+//!
+//! Everything the bot needs from its environment is read once, validated and
+//! kept in a [`Config`]. A missing token or a malformed value stops the bot
+//! at startup with a message naming the variable and how to fix it, rather
+//! than a panic or a silently ignored setting.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use log::LevelFilter;
+
+/// Variable holding the bot token.
+pub const TOKEN_VAR: &str = "DISCORD_TOKEN";
+
+/// Variable holding the log level.
+pub const LOG_LEVEL_VAR: &str = "RUST_LOG";
+
+/// Variable holding the extra users allowed to run `/admin`.
+pub const ADMIN_IDS_VAR: &str = "ADMIN_IDS";
+
+/// Variable holding a server whose commands are registered directly.
+pub const TEST_GUILD_VAR: &str = "TEST_GUILD_ID";
+
+/// Log level used when `RUST_LOG` is not set.
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Error;
+
+/// A setting that keeps the bot from starting.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// `DISCORD_TOKEN` is not set or is blank.
+    MissingToken,
+    /// A variable is set to a value that cannot be used.
+    InvalidValue {
+        name: &'static str,
+        value: String,
+        expected: &'static str,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::MissingToken => write!(
+                formatter,
+                "{} is not set. Add `{} = your_token_here` to the environment or to a .env file in the working directory",
+                TOKEN_VAR, TOKEN_VAR
+            ),
+            ConfigError::InvalidValue { name, value, expected } => {
+                write!(formatter, "{} is set to `{}`, but it must be {}", name, value, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+/// Validated startup settings.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Config {
+    /// Token the bot logs in with.
+    pub discord_token: String,
+    /// Most verbose level that is logged.
+    pub log_level: LevelFilter,
+    /// Users besides the application owner who may run `/admin`.
+    pub admin_ids: HashSet<u64>,
+    /// Server to register commands in directly, where they update at once.
+    pub test_guild_id: Option<u64>,
+}
+
+impl Config {
+    /// Reads the settings from the process environment.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(&std::env::vars().collect())
+    }
+
+    /// Reads the settings from a map of variable names to values.
+    ///
+    /// Unset and blank variables take their defaults, except the token,
+    /// which is required.
+    pub fn from_vars(vars: &HashMap<String, String>) -> Result<Self, ConfigError> {
+        let get = |name: &str| vars.get(name).map(|value| value.trim()).filter(|value| !value.is_empty());
+
+        let discord_token = get(TOKEN_VAR).ok_or(ConfigError::MissingToken)?.to_string();
+
+        let log_level = match get(LOG_LEVEL_VAR) {
+            Some(value) => parse_log_level(value).ok_or_else(|| ConfigError::InvalidValue {
+                name: LOG_LEVEL_VAR,
+                value: value.to_string(),
+                expected: "one of off, error, warn, info, debug or trace",
+            })?,
+            None => DEFAULT_LOG_LEVEL,
+        };
+
+        let admin_ids = match get(ADMIN_IDS_VAR) {
+            Some(value) => parse_ids(value).ok_or_else(|| ConfigError::InvalidValue {
+                name: ADMIN_IDS_VAR,
+                value: value.to_string(),
+                expected: "a comma or space separated list of Discord user ids",
+            })?,
+            None => HashSet::new(),
+        };
+
+        let test_guild_id = get(TEST_GUILD_VAR)
+            .map(|value| {
+                parse_id(value).ok_or_else(|| ConfigError::InvalidValue {
+                    name: TEST_GUILD_VAR,
+                    value: value.to_string(),
+                    expected: "a Discord server id",
+                })
+            })
+            .transpose()?;
+
+        Ok(Self {
+            discord_token,
+            log_level,
+            admin_ids,
+            test_guild_id,
+        })
+    }
+}
+
+// The token is a secret, so it is left out of debug output
+impl fmt::Debug for Config {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter
+            .debug_struct("Config")
+            .field("discord_token", &"<redacted>")
+            .field("log_level", &self.log_level)
+            .field("admin_ids", &self.admin_ids)
+            .field("test_guild_id", &self.test_guild_id)
+            .finish()
+    }
+}
+
+fn parse_log_level(value: &str) -> Option<LevelFilter> {
+    match value.to_lowercase().as_str() {
+        "off" => Some(LevelFilter::Off),
+        "error" => Some(LevelFilter::Error),
+        "warn" => Some(LevelFilter::Warn),
+        "info" => Some(LevelFilter::Info),
+        "debug" => Some(LevelFilter::Debug),
+        "trace" => Some(LevelFilter::Trace),
+        _ => None,
+    }
+}
+
+/// Parses a Discord snowflake, which is never zero.
+fn parse_id(value: &str) -> Option<u64> {
+    value.parse().ok().filter(|&id| id != 0)
+}
+
+fn parse_ids(value: &str) -> Option<HashSet<u64>> {
+    value
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|id| !id.is_empty())
+        .map(parse_id)
+        .collect()
+}
@@ -0,0 +1,822 @@
+//! Static analysis of parsed programs.
+//!
+//! These helpers walk the AST without evaluating it, so they are safe to run
+//! on any parsed input.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use crate::core::ast_expression::Expression;
+use crate::core::ast_statement::Statement;
+use crate::core::builtins::find_builtin;
+use crate::core::error_types::{ControlFlowError, InterpreterError};
+use crate::core::operator::Operator;
+use crate::core::parser::{parse_program, ParsedProgram};
+use crate::core::shared_constants::shared_constant;
+use crate::core::symbol_manager::{global_constants, is_result_name, SymbolTable};
+
+/// Built-in functions whose result differs between calls with the same arguments.
+const IMPURE_BUILTINS: [&str; 1] = ["rand"];
+
+/// Counts how often each function or procedure is called in a statement.
+///
+/// Calls are counted wherever they appear, including in the arguments of
+/// other calls and in the bodies of function and procedure declarations.
+/// Built-in and user-defined callables are both counted by name.
+pub fn count_function_calls(statement: &Statement) -> HashMap<String, u32> {
+    let mut counts = HashMap::new();
+    count_statement_calls(statement, &mut counts);
+    counts
+}
+
+/// Adds the calls made in a statement to the counts.
+fn count_statement_calls(statement: &Statement, counts: &mut HashMap<String, u32>) {
+    match statement {
+        Statement::Expression(expr) => count_expression_calls(expr, counts),
+        Statement::Block(statements) => {
+            for statement in statements {
+                count_statement_calls(statement, counts);
+            }
+        }
+        Statement::If { condition, then_branch, else_branch } => {
+            count_expression_calls(condition, counts);
+            count_statement_calls(then_branch, counts);
+            if let Some(else_branch) = else_branch {
+                count_statement_calls(else_branch, counts);
+            }
+        }
+        Statement::While { condition, body } => {
+            count_expression_calls(condition, counts);
+            count_statement_calls(body, counts);
+        }
+        Statement::Defer(body) => count_statement_calls(body, counts),
+        Statement::Try { body, handler } => {
+            count_statement_calls(body, counts);
+            count_statement_calls(handler, counts);
+        }
+        Statement::Foreach { start, end, body, .. } => {
+            count_expression_calls(start, counts);
+            count_expression_calls(end, counts);
+            count_statement_calls(body, counts);
+        }
+        Statement::Return(Some(expr)) | Statement::End(Some(expr)) | Statement::Assert(expr) => count_expression_calls(expr, counts),
+        Statement::Let { initializer: Some(expr), .. } => count_expression_calls(expr, counts),
+        Statement::Const { initializer, .. } | Statement::Formula { expression: initializer, .. } => {
+            count_expression_calls(initializer, counts)
+        }
+        Statement::Function { body, .. } | Statement::Procedure { body, .. } => count_statement_calls(body, counts),
+        Statement::ProcedureCall { name, args } => {
+            *counts.entry(name.clone()).or_insert(0) += 1;
+            for arg in args {
+                count_expression_calls(arg, counts);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Adds the calls made in an expression to the counts.
+fn count_expression_calls(expr: &Expression, counts: &mut HashMap<String, u32>) {
+    match expr {
+        Expression::Literal(_) => {}
+        Expression::Operation(_, operands) => {
+            for operand in operands {
+                count_expression_calls(operand, counts);
+            }
+        }
+        Expression::FunctionCall(name, args) => {
+            *counts.entry(name.clone()).or_insert(0) += 1;
+            for arg in args {
+                count_expression_calls(arg, counts);
+            }
+        }
+    }
+}
+
+/// Returns whether a user-defined function always gives the same result for the same arguments.
+///
+/// A pure function only reads its parameters, its own local variables and
+/// global constants, only assigns to its parameters and locals, and only calls
+/// deterministic built-ins and other pure functions. Procedure calls, `end`,
+/// `import` and reads of outer variables all make a function impure, since
+/// their effects or values can change between calls.
+///
+/// Returns false if no function with the given name exists.
+pub fn is_pure_function(name: &str, functions: &HashMap<String, (Vec<String>, Statement)>) -> bool {
+    let mut visiting = HashSet::new();
+    function_is_pure(name, functions, &mut visiting)
+}
+
+/// Returns whether a function about to be declared would be pure, see [`is_pure_function`].
+///
+/// Calls to `name` count as the function calling itself, even if another
+/// function of that name is declared already.
+pub fn is_pure_definition(
+    name: &str,
+    params: &[String],
+    body: &Statement,
+    functions: &HashMap<String, (Vec<String>, Statement)>,
+) -> bool {
+    let mut visiting = HashSet::from([name]);
+    body_is_pure(params, body, functions, &mut visiting)
+}
+
+/// Checks a function's purity, assuming functions already being checked are pure.
+fn function_is_pure<'a>(
+    name: &'a str,
+    functions: &'a HashMap<String, (Vec<String>, Statement)>,
+    visiting: &mut HashSet<&'a str>,
+) -> bool {
+    let Some((params, body)) = functions.get(name) else {
+        return false;
+    };
+
+    // Recursive calls are pure as long as the rest of the body is
+    if !visiting.insert(name) {
+        return true;
+    }
+
+    body_is_pure(params, body, functions, visiting)
+}
+
+/// Checks the body of a function with the given parameters.
+fn body_is_pure<'a>(
+    params: &'a [String],
+    body: &'a Statement,
+    functions: &'a HashMap<String, (Vec<String>, Statement)>,
+    visiting: &mut HashSet<&'a str>,
+) -> bool {
+    let mut locals: HashSet<&str> = params.iter().map(String::as_str).collect();
+    collect_locals(body, &mut locals);
+
+    let mut checker = PurityChecker { functions, visiting, locals };
+    checker.statement_is_pure(body)
+}
+
+/// Collects the names declared inside a function body.
+fn collect_locals<'a>(statement: &'a Statement, locals: &mut HashSet<&'a str>) {
+    match statement {
+        Statement::Let { name, .. } | Statement::Const { name, .. } | Statement::Formula { name, .. } => {
+            locals.insert(name);
+        }
+        Statement::Foreach { variable, body, .. } => {
+            locals.insert(variable);
+            collect_locals(body, locals);
+        }
+        Statement::Block(statements) => {
+            for statement in statements {
+                collect_locals(statement, locals);
+            }
+        }
+        Statement::If { then_branch, else_branch, .. } => {
+            collect_locals(then_branch, locals);
+            if let Some(else_branch) = else_branch {
+                collect_locals(else_branch, locals);
+            }
+        }
+        Statement::While { body, .. } => collect_locals(body, locals),
+        Statement::Try { body, handler } => {
+            collect_locals(body, locals);
+            collect_locals(handler, locals);
+        }
+        _ => {}
+    }
+}
+
+/// Walks a function body looking for anything that breaks purity.
+struct PurityChecker<'a, 'v> {
+    functions: &'a HashMap<String, (Vec<String>, Statement)>,
+    visiting: &'v mut HashSet<&'a str>,
+    locals: HashSet<&'a str>,
+}
+
+impl<'a> PurityChecker<'a, '_> {
+    fn statement_is_pure(&mut self, statement: &'a Statement) -> bool {
+        match statement {
+            Statement::Expression(expr) => self.expression_is_pure(expr),
+            Statement::Block(statements) => statements.iter().all(|statement| self.statement_is_pure(statement)),
+            Statement::If { condition, then_branch, else_branch } => {
+                self.expression_is_pure(condition)
+                    && self.statement_is_pure(then_branch)
+                    && else_branch.as_ref().is_none_or(|else_branch| self.statement_is_pure(else_branch))
+            }
+            Statement::While { condition, body } => self.expression_is_pure(condition) && self.statement_is_pure(body),
+            Statement::Defer(body) => self.statement_is_pure(body),
+            Statement::Try { body, handler } => self.statement_is_pure(body) && self.statement_is_pure(handler),
+            Statement::Foreach { start, end, body, .. } => {
+                self.expression_is_pure(start) && self.expression_is_pure(end) && self.statement_is_pure(body)
+            }
+            Statement::Break | Statement::Continue | Statement::Return(None) => true,
+            Statement::Return(Some(expr)) | Statement::Assert(expr) => self.expression_is_pure(expr),
+            Statement::Let { initializer, .. } => initializer.as_ref().is_none_or(|expr| self.expression_is_pure(expr)),
+            Statement::Const { initializer, .. } => self.expression_is_pure(initializer),
+            Statement::Formula { expression, .. } => self.expression_is_pure(expression),
+            Statement::ProcedureCall { name, args } => {
+                self.call_is_pure(name) && args.iter().all(|arg| self.expression_is_pure(arg))
+            }
+            // Nested definitions, imports and `end` change state outside the call
+            Statement::Function { .. } | Statement::Procedure { .. } | Statement::Import(_) | Statement::End(_) => false,
+        }
+    }
+
+    fn expression_is_pure(&mut self, expr: &'a Expression) -> bool {
+        match expr {
+            Expression::Literal(text) => {
+                let is_identifier = text.starts_with(|c: char| c.is_alphabetic() || c == '_');
+                !is_identifier || self.locals.contains(text.as_str()) || global_constants().contains(text)
+            }
+            Expression::Operation(Operator::Assign, operands) => {
+                // Only local variables may be assigned
+                let target_is_local = match &operands[0] {
+                    Expression::Literal(target) => self.locals.contains(target.as_str()),
+                    _ => false,
+                };
+                target_is_local && operands.iter().skip(1).all(|operand| self.expression_is_pure(operand))
+            }
+            Expression::Operation(_, operands) => operands.iter().all(|operand| self.expression_is_pure(operand)),
+            Expression::FunctionCall(name, args) => {
+                self.call_is_pure(name) && args.iter().all(|arg| self.expression_is_pure(arg))
+            }
+        }
+    }
+
+    fn call_is_pure(&mut self, name: &'a str) -> bool {
+        // Recursive calls, including those of a function not declared yet
+        if self.visiting.contains(name) {
+            return true;
+        }
+        if self.functions.contains_key(name) {
+            return function_is_pure(name, self.functions, self.visiting);
+        }
+        find_builtin(name).is_some() && !IMPURE_BUILTINS.contains(&name)
+    }
+}
+
+/// Lists the functions that recurse on every path through their body.
+///
+/// A function is flagged when it can reach itself through calls to user
+/// functions, directly or through others as in `fn a(n) { b(n) }` and
+/// `fn b(n) { a(n) }`, and no path through its body avoids such a call. An
+/// `if` whose branches do not all recurse, or a `return` or `end` before the
+/// call, counts as a base case. Names are sorted.
+///
+/// This is a heuristic. It does not look at whether a base case can ever be
+/// reached, so `fn f(n) { if n != n { 0 } else { f(n) } }` is not flagged.
+/// Conditions and the operands of `&&` and `||` always count as evaluated,
+/// loop bodies never do, and calls through procedures are not followed.
+pub fn detect_unguarded_recursion(functions: &HashMap<String, (Vec<String>, Statement)>) -> Vec<String> {
+    // The call graph between user functions
+    let callees: HashMap<&str, Vec<&str>> = functions
+        .iter()
+        .map(|(name, (_, body))| {
+            let calls = count_function_calls(body);
+            let mut called = functions.keys().map(String::as_str).filter(|callee| calls.contains_key(*callee)).collect::<Vec<_>>();
+            called.sort_unstable();
+            (name.as_str(), called)
+        })
+        .collect();
+
+    let mut flagged = functions
+        .iter()
+        .filter(|(name, (_, body))| {
+            // Calls to any function that leads back here recurse
+            let recursive = callees
+                .keys()
+                .copied()
+                .filter(|callee| reaches(callee, name, &callees))
+                .collect::<HashSet<_>>();
+            recursive.contains(name.as_str()) && always_calls(body, &recursive)
+        })
+        .map(|(name, _)| name.clone())
+        .collect::<Vec<_>>();
+    flagged.sort();
+    flagged
+}
+
+/// Returns whether `from` calls `to`, directly or through other functions.
+fn reaches(from: &str, to: &str, callees: &HashMap<&str, Vec<&str>>) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![from];
+    while let Some(name) = pending.pop() {
+        for &callee in callees.get(name).into_iter().flatten() {
+            if callee == to {
+                return true;
+            }
+            if seen.insert(callee) {
+                pending.push(callee);
+            }
+        }
+    }
+    false
+}
+
+/// Returns whether every path through a statement calls one of the targets.
+fn always_calls(statement: &Statement, targets: &HashSet<&str>) -> bool {
+    match statement {
+        Statement::Expression(expr) | Statement::Assert(expr) | Statement::Const { initializer: expr, .. } => {
+            calls_any(expr, targets)
+        }
+        Statement::Return(Some(expr)) | Statement::End(Some(expr)) => calls_any(expr, targets),
+        Statement::Let { initializer: Some(expr), .. } => calls_any(expr, targets),
+        Statement::ProcedureCall { name, args } => {
+            targets.contains(name.as_str()) || args.iter().any(|arg| calls_any(arg, targets))
+        }
+        Statement::Block(statements) => {
+            for statement in statements {
+                if always_calls(statement, targets) {
+                    return true;
+                }
+                // Leaving the block before any call is a way out
+                if may_leave(statement) {
+                    return false;
+                }
+            }
+            false
+        }
+        Statement::If { condition, then_branch, else_branch } => {
+            calls_any(condition, targets)
+                || (always_calls(then_branch, targets)
+                    && else_branch.as_ref().is_some_and(|else_branch| always_calls(else_branch, targets)))
+        }
+        Statement::While { condition, .. } => calls_any(condition, targets),
+        Statement::Foreach { start, end, .. } => calls_any(start, targets) || calls_any(end, targets),
+        // An error in the body may skip its calls, so the handler must call too
+        Statement::Try { body, handler } => always_calls(body, targets) && always_calls(handler, targets),
+        _ => false,
+    }
+}
+
+/// Returns whether some path through a statement leaves the enclosing block early.
+fn may_leave(statement: &Statement) -> bool {
+    match statement {
+        Statement::Return(_) | Statement::End(_) | Statement::Break | Statement::Continue => true,
+        Statement::Block(statements) => statements.iter().any(may_leave),
+        Statement::If { then_branch, else_branch, .. } => {
+            may_leave(then_branch) || else_branch.as_deref().is_some_and(may_leave)
+        }
+        Statement::While { body, .. } | Statement::Foreach { body, .. } => may_leave(body),
+        Statement::Try { body, handler } => may_leave(body) || may_leave(handler),
+        _ => false,
+    }
+}
+
+/// Returns whether an expression calls one of the targets.
+fn calls_any(expr: &Expression, targets: &HashSet<&str>) -> bool {
+    match expr {
+        Expression::Literal(_) => false,
+        Expression::Operation(_, operands) => operands.iter().any(|operand| calls_any(operand, targets)),
+        Expression::FunctionCall(name, args) => {
+            targets.contains(name.as_str()) || args.iter().any(|arg| calls_any(arg, targets))
+        }
+    }
+}
+
+impl Expression {
+    /// Returns the names of the variables the expression reads.
+    ///
+    /// Assignment targets are only written, so `x = y + 1` reads just `y`,
+    /// while the desugared `x += 1` reads and writes `x`. Function names and
+    /// the unit names of `convert` are not variables. Global constants like
+    /// `PI` are included.
+    pub fn free_variables(&self) -> HashSet<String> {
+        let mut dependencies = Dependencies::default();
+        dependencies.expression(self);
+        dependencies.reads
+    }
+}
+
+impl Statement {
+    /// Returns the outer variables the statement reads and writes.
+    ///
+    /// Names declared inside the statement's blocks, loop variables and
+    /// parameters are local, so they appear in neither set. A top-level
+    /// `let`, `const` or formula declares into the enclosing scope and counts
+    /// as a write. Function and procedure declarations report what their
+    /// bodies read when called; only procedures write outer variables back.
+    pub fn reads_writes(&self) -> (HashSet<String>, HashSet<String>) {
+        let mut dependencies = Dependencies::default();
+        dependencies.statement(self);
+        (dependencies.reads, dependencies.writes)
+    }
+}
+
+/// Describes the first way a statement would change the context it runs in.
+///
+/// Declarations, imports and procedure calls anywhere in the statement count,
+/// as do assignments to variables the statement does not declare itself.
+/// Returns `None` when running the statement leaves the context as it was.
+pub(crate) fn find_side_effect(statement: &Statement) -> Option<String> {
+    let nested = match statement {
+        Statement::Let { name, .. } | Statement::Const { name, .. } | Statement::Formula { name, .. } => {
+            return Some(format!("declares `{}`", name));
+        }
+        Statement::Function { name, .. } | Statement::Procedure { name, .. } => return Some(format!("declares `{}`", name)),
+        Statement::Import(name) => return Some(format!("imports \"{}\"", name)),
+        Statement::ProcedureCall { name, .. } => return Some(format!("calls the procedure `{}`", name)),
+        Statement::Block(statements) => statements.iter().find_map(find_side_effect),
+        Statement::If { then_branch, else_branch, .. } => {
+            find_side_effect(then_branch).or_else(|| else_branch.as_deref().and_then(find_side_effect))
+        }
+        Statement::While { body, .. } | Statement::Foreach { body, .. } | Statement::Defer(body) => find_side_effect(body),
+        Statement::Try { body, handler } => find_side_effect(body).or_else(|| find_side_effect(handler)),
+        _ => None,
+    };
+
+    nested.or_else(|| {
+        let (_, writes) = statement.reads_writes();
+        writes.into_iter().min().map(|name| format!("assigns `{}`", name))
+    })
+}
+
+/// Checks whether the result of a program is a truth value, so that it can
+/// be shown as `true` or `false` instead of 1 or 0.
+///
+/// A result is a truth value when the last top-level statement gets it from
+/// a comparison, a logical operator, `bool` or `iszero`, as in `5 > 3`.
+/// Arithmetic on a truth value gives a number again, as in `(5 > 3) + 1`, and
+/// a truth value kept in a variable is a number once read back.
+pub fn is_boolean_result(input: &str) -> bool {
+    match parse_program(input) {
+        Ok(ParsedProgram::Expression(expr)) => is_boolean_expression(&expr),
+        Ok(ParsedProgram::Statements(statements)) => match statements.last() {
+            Some(Statement::Expression(expr))
+            | Some(Statement::Let { initializer: Some(expr), .. })
+            | Some(Statement::Const { initializer: expr, .. })
+            | Some(Statement::End(Some(expr))) => is_boolean_expression(expr),
+            // A call on its own, like `bool(x)`, is read as a procedure call
+            Some(Statement::ProcedureCall { name, .. }) => matches!(name.as_str(), "bool" | "iszero"),
+            _ => false,
+        },
+        Err(_) => false,
+    }
+}
+
+/// Checks whether an expression always evaluates to 1 or 0 as a truth value.
+fn is_boolean_expression(expr: &Expression) -> bool {
+    match expr {
+        Expression::Operation(Operator::Assign, operands) => operands.last().is_some_and(is_boolean_expression),
+        Expression::Operation(operator, _) => operator.is_boolean(),
+        Expression::FunctionCall(name, _) => matches!(name.as_str(), "bool" | "iszero"),
+        Expression::Literal(_) => false,
+    }
+}
+
+/// Lists the variables a program reads but neither it nor the context defines.
+///
+/// Declaration order is ignored, so a variable read before its `let` is not
+/// reported. Input that does not parse has no undefined variables.
+pub fn undefined_variables(input: &str, context: &SymbolTable<f32>) -> Vec<String> {
+    let (reads, writes) = match parse_program(input) {
+        Ok(ParsedProgram::Statements(statements)) => {
+            let mut dependencies = Dependencies::default();
+            for statement in &statements {
+                dependencies.statement(statement);
+            }
+            (dependencies.reads, dependencies.writes)
+        }
+        Ok(ParsedProgram::Expression(expr)) => (expr.free_variables(), HashSet::new()),
+        Err(_) => return Vec::new(),
+    };
+
+    let mut undefined: Vec<String> = reads
+        .into_iter()
+        .filter(|name| {
+            !writes.contains(name)
+                && !context.contains(name)
+                && !is_result_name(name)
+                && shared_constant(name).is_none()
+                && !global_constants().contains(name)
+        })
+        .collect();
+    undefined.sort();
+    undefined
+}
+
+/// Checks a program for mistakes that can be found without running it.
+///
+/// Reports the parse error of input that does not parse, and a procedure
+/// called where a value is needed, as in `let x = my_proc(3)`. Procedures
+/// count when they are in the context or declared anywhere in the program.
+pub fn validate(input: &str, context: &SymbolTable<f32>) -> Result<(), InterpreterError> {
+    let statements = match parse_program(input)? {
+        ParsedProgram::Statements(statements) => statements,
+        ParsedProgram::Expression(expr) => vec![Statement::Expression(expr)],
+    };
+
+    let mut procedures: HashSet<&str> = context.procedures.keys().map(String::as_str).collect();
+    for statement in &statements {
+        collect_procedures(statement, &mut procedures);
+    }
+
+    match statements.iter().find_map(|statement| procedure_in_statement(statement, &procedures)) {
+        Some(name) => Err(InterpreterError::Eval(ControlFlowError::ProcedureUsedAsValue { name: name.to_string() }.into())),
+        None => Ok(()),
+    }
+}
+
+/// Adds the names of the procedures a statement declares.
+fn collect_procedures<'a>(statement: &'a Statement, procedures: &mut HashSet<&'a str>) {
+    match statement {
+        Statement::Procedure { name, body, .. } => {
+            procedures.insert(name);
+            collect_procedures(body, procedures);
+        }
+        Statement::Function { body, .. } | Statement::While { body, .. } | Statement::Foreach { body, .. } | Statement::Defer(body) => {
+            collect_procedures(body, procedures)
+        }
+        Statement::Block(statements) => {
+            for statement in statements {
+                collect_procedures(statement, procedures);
+            }
+        }
+        Statement::If { then_branch, else_branch, .. } => {
+            collect_procedures(then_branch, procedures);
+            if let Some(else_branch) = else_branch {
+                collect_procedures(else_branch, procedures);
+            }
+        }
+        Statement::Try { body, handler } => {
+            collect_procedures(body, procedures);
+            collect_procedures(handler, procedures);
+        }
+        _ => {}
+    }
+}
+
+/// Returns the first procedure a statement calls where a value is needed.
+///
+/// A procedure call on its own is a statement, so only its arguments are checked.
+fn procedure_in_statement<'a>(statement: &'a Statement, procedures: &HashSet<&str>) -> Option<&'a str> {
+    let in_expr = |expr: &'a Expression| procedure_in_expression(expr, procedures);
+    let in_statement = |statement: &'a Statement| procedure_in_statement(statement, procedures);
+
+    match statement {
+        Statement::Expression(expr) | Statement::Assert(expr) => in_expr(expr),
+        Statement::Return(Some(expr)) | Statement::End(Some(expr)) => in_expr(expr),
+        Statement::Let { initializer: Some(expr), .. } => in_expr(expr),
+        Statement::Const { initializer, .. } | Statement::Formula { expression: initializer, .. } => in_expr(initializer),
+        Statement::Block(statements) => statements.iter().find_map(in_statement),
+        Statement::If { condition, then_branch, else_branch } => in_expr(condition)
+            .or_else(|| in_statement(then_branch))
+            .or_else(|| else_branch.as_deref().and_then(in_statement)),
+        Statement::While { condition, body } => in_expr(condition).or_else(|| in_statement(body)),
+        Statement::Foreach { start, end, body, .. } => in_expr(start).or_else(|| in_expr(end)).or_else(|| in_statement(body)),
+        Statement::Defer(body) | Statement::Function { body, .. } | Statement::Procedure { body, .. } => in_statement(body),
+        Statement::Try { body, handler } => in_statement(body).or_else(|| in_statement(handler)),
+        Statement::ProcedureCall { args, .. } => args.iter().find_map(in_expr),
+        _ => None,
+    }
+}
+
+/// Returns the first procedure called in an expression.
+fn procedure_in_expression<'a>(expr: &'a Expression, procedures: &HashSet<&str>) -> Option<&'a str> {
+    match expr {
+        Expression::Literal(_) => None,
+        Expression::Operation(_, operands) => operands.iter().find_map(|operand| procedure_in_expression(operand, procedures)),
+        Expression::FunctionCall(name, _) if procedures.contains(name.as_str()) => Some(name),
+        Expression::FunctionCall(_, args) => args.iter().find_map(|arg| procedure_in_expression(arg, procedures)),
+    }
+}
+
+/// Something a program does that is allowed but probably a mistake.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Warning {
+    /// A variable declared in a block that nothing after it reads.
+    UnusedVariable(String),
+
+    /// Statements after a `return`, `end`, `break` or `continue` in the same block.
+    ///
+    /// Carries the keyword that makes them unreachable.
+    UnreachableCode(&'static str),
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::UnusedVariable(name) => write!(formatter, "variable `{}` is declared but never used", name),
+            Warning::UnreachableCode(keyword) => write!(formatter, "code after `{}` is never run", keyword),
+        }
+    }
+}
+
+/// Lists the likely mistakes in a program without running it.
+///
+/// Variables declared at the top level stay in the session for later input,
+/// so only those declared in blocks and bodies are reported as unused, and
+/// names starting with `_` never are. Input that does not parse has no warnings.
+pub fn find_warnings(input: &str) -> Vec<Warning> {
+    let statements = match parse_program(input) {
+        Ok(ParsedProgram::Statements(statements)) => statements,
+        _ => return Vec::new(),
+    };
+
+    let mut warnings = Vec::new();
+    check_unreachable(&statements, &mut warnings);
+    for statement in &statements {
+        collect_warnings(statement, &mut warnings);
+    }
+    warnings
+}
+
+/// Adds the warnings for the blocks inside a statement.
+fn collect_warnings(statement: &Statement, warnings: &mut Vec<Warning>) {
+    match statement {
+        Statement::Block(statements) => {
+            check_unreachable(statements, warnings);
+            for (index, statement) in statements.iter().enumerate() {
+                if let Statement::Let { name, .. } = statement {
+                    let read = statements[index + 1..].iter().any(|later| later.reads_writes().0.contains(name));
+                    if !read && !name.starts_with('_') {
+                        warnings.push(Warning::UnusedVariable(name.clone()));
+                    }
+                }
+                collect_warnings(statement, warnings);
+            }
+        }
+        Statement::If { then_branch, else_branch, .. } => {
+            collect_warnings(then_branch, warnings);
+            if let Some(else_branch) = else_branch {
+                collect_warnings(else_branch, warnings);
+            }
+        }
+        Statement::Try { body, handler } => {
+            collect_warnings(body, warnings);
+            collect_warnings(handler, warnings);
+        }
+        Statement::While { body, .. }
+        | Statement::Foreach { body, .. }
+        | Statement::Defer(body)
+        | Statement::Function { body, .. }
+        | Statement::Procedure { body, .. } => collect_warnings(body, warnings),
+        _ => {}
+    }
+}
+
+/// Adds a warning when statements follow one that always leaves the block.
+fn check_unreachable(statements: &[Statement], warnings: &mut Vec<Warning>) {
+    let exit = statements.iter().enumerate().find_map(|(index, statement)| {
+        let keyword = match statement {
+            Statement::Return(_) => "return",
+            Statement::End(_) => "end",
+            Statement::Break => "break",
+            Statement::Continue => "continue",
+            _ => return None,
+        };
+        Some((index, keyword))
+    });
+
+    if let Some((index, keyword)) = exit {
+        if index + 1 < statements.len() {
+            warnings.push(Warning::UnreachableCode(keyword));
+        }
+    }
+}
+
+/// Collects reads and writes of outer variables while walking the AST.
+#[derive(Default)]
+struct Dependencies {
+    reads: HashSet<String>,
+    writes: HashSet<String>,
+    /// Names declared in each enclosing scope, innermost last.
+    scopes: Vec<HashSet<String>>,
+}
+
+impl Dependencies {
+    fn is_local(&self, name: &str) -> bool {
+        self.scopes.iter().any(|scope| scope.contains(name))
+    }
+
+    fn read(&mut self, name: &str) {
+        if !self.is_local(name) {
+            self.reads.insert(name.to_string());
+        }
+    }
+
+    fn write(&mut self, name: &str) {
+        if !self.is_local(name) {
+            self.writes.insert(name.to_string());
+        }
+    }
+
+    fn declare(&mut self, name: &str) {
+        match self.scopes.last_mut() {
+            Some(scope) => { scope.insert(name.to_string()); }
+            None => { self.writes.insert(name.to_string()); }
+        }
+    }
+
+    /// Walks a statement in a new scope holding the given names.
+    fn scoped<'a>(&mut self, names: impl IntoIterator<Item = &'a String>, statement: &Statement) {
+        self.scopes.push(names.into_iter().cloned().collect());
+        self.statement(statement);
+        self.scopes.pop();
+    }
+
+    fn statement(&mut self, statement: &Statement) {
+        match statement {
+            Statement::Expression(expr) | Statement::Assert(expr) => self.expression(expr),
+            Statement::Return(expr) | Statement::End(expr) => {
+                if let Some(expr) = expr {
+                    self.expression(expr);
+                }
+            }
+            Statement::Block(statements) => {
+                self.scopes.push(HashSet::new());
+                for statement in statements {
+                    self.statement(statement);
+                }
+                self.scopes.pop();
+            }
+            Statement::If { condition, then_branch, else_branch } => {
+                self.expression(condition);
+                self.scoped([], then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.scoped([], else_branch);
+                }
+            }
+            Statement::While { condition, body } => {
+                self.expression(condition);
+                self.scoped([], body);
+            }
+            Statement::Defer(body) => self.scoped([], body),
+            Statement::Try { body, handler } => {
+                self.scoped([], body);
+                self.scoped([], handler);
+            }
+            Statement::Foreach { variable, start, end, body } => {
+                self.expression(start);
+                self.expression(end);
+                self.scoped([variable], body);
+            }
+            Statement::Let { name, initializer } => {
+                if let Some(initializer) = initializer {
+                    self.expression(initializer);
+                }
+                self.declare(name);
+            }
+            Statement::Const { name, initializer, .. } | Statement::Formula { name, expression: initializer } => {
+                self.expression(initializer);
+                self.declare(name);
+            }
+            // A function body runs in a copy of the caller's scope, so its writes are lost
+            Statement::Function { params, body, .. } => {
+                let writes = std::mem::take(&mut self.writes);
+                self.scoped(params, body);
+                self.writes = writes;
+            }
+            Statement::Procedure { params, body, .. } => self.scoped(params, body),
+            // A bare call may be a function such as convert() or fold() with its own rules
+            Statement::ProcedureCall { name, args } => {
+                self.expression(&Expression::FunctionCall(name.clone(), args.clone()));
+            }
+            Statement::Break | Statement::Continue | Statement::Import(_) => {}
+        }
+    }
+
+    fn expression(&mut self, expr: &Expression) {
+        match expr {
+            Expression::Literal(text) => {
+                if text.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+                    self.read(text);
+                }
+            }
+            Expression::Operation(Operator::Assign, operands) => {
+                // The value is read before the target is written
+                for operand in &operands[1..] {
+                    self.expression(operand);
+                }
+                match &operands[0] {
+                    Expression::Literal(target) => self.write(target),
+                    target => self.expression(target),
+                }
+            }
+            Expression::Operation(_, operands) => {
+                for operand in operands {
+                    self.expression(operand);
+                }
+            }
+            // Only the value of convert(value, from, to) is a variable
+            Expression::FunctionCall(name, args) if name == "convert" => {
+                if let Some(value) = args.first() {
+                    self.expression(value);
+                }
+            }
+            // The name in typeof(name) is only looked up, so it may be undefined
+            Expression::FunctionCall(name, args) if name == "typeof" && matches!(args.as_slice(), [Expression::Literal(_)]) => {}
+            // The loop variable and `acc` are local to the body of fold(i, lo, hi, init, body)
+            Expression::FunctionCall(name, args) if name == "fold" && args.len() == 5 => {
+                for arg in &args[1..4] {
+                    self.expression(arg);
+                }
+                self.scopes.push(HashSet::from([args[0].to_source(), "acc".to_string()]));
+                self.expression(&args[4]);
+                self.scopes.pop();
+            }
+            Expression::FunctionCall(_, args) => {
+                for arg in args {
+                    self.expression(arg);
+                }
+            }
+        }
+    }
+}
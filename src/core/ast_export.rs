@@ -0,0 +1,502 @@
+//! Export of syntax trees for debugging and teaching.
+//!
+//! Expressions and statements can be turned into JSON, into a Graphviz DOT
+//! description, or into a text tree drawn with box-drawing characters.
+
+use serde_json::{json, Value};
+
+use crate::core::ast_expression::Expression;
+use crate::core::ast_statement::{format_pretty_sequence, Statement};
+use crate::core::error_types::ParseError;
+use crate::core::operator::Operator;
+use crate::core::parser::{parse_program, ParsedProgram};
+
+/// A labelled node of a syntax tree, shared by the DOT and text renderers.
+struct TreeNode {
+    label: String,
+    children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    fn leaf(label: impl Into<String>) -> Self {
+        Self { label: label.into(), children: Vec::new() }
+    }
+
+    fn new(label: impl Into<String>, children: Vec<TreeNode>) -> Self {
+        Self { label: label.into(), children }
+    }
+
+    /// Writes this node and its descendants as DOT statements.
+    fn write_dot(&self, output: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+        let label = self.label.replace('\\', "\\\\").replace('"', "\\\"");
+        output.push_str(&format!("  n{} [label=\"{}\"];\n", id, label));
+
+        for child in &self.children {
+            let child_id = child.write_dot(output, next_id);
+            output.push_str(&format!("  n{} -> n{};\n", id, child_id));
+        }
+
+        id
+    }
+
+    /// Writes the children of this node as text tree lines.
+    fn write_text(&self, output: &mut String, prefix: &str, depth: usize, max_depth: usize, max_children: usize) {
+        if depth >= max_depth {
+            if !self.children.is_empty() {
+                output.push_str(&format!("{}└── …\n", prefix));
+            }
+            return;
+        }
+
+        let shown = self.children.len().min(max_children);
+        let hidden = self.children.len() - shown;
+
+        for (index, child) in self.children.iter().take(shown).enumerate() {
+            let last = index + 1 == shown && hidden == 0;
+            let (branch, indent) = if last { ("└── ", "    ") } else { ("├── ", "│   ") };
+            output.push_str(&format!("{}{}{}\n", prefix, branch, child.label));
+            child.write_text(output, &format!("{}{}", prefix, indent), depth + 1, max_depth, max_children);
+        }
+
+        if hidden > 0 {
+            output.push_str(&format!("{}└── … {} more\n", prefix, hidden));
+        }
+    }
+
+    /// Renders the whole tree as DOT.
+    fn to_dot(&self) -> String {
+        let mut output = String::from("digraph AST {\n  node [shape=box];\n");
+        self.write_dot(&mut output, &mut 0);
+        output.push_str("}\n");
+        output
+    }
+
+    /// Renders the whole tree as text, capped in depth and children per node.
+    fn to_text_tree(&self, max_depth: usize, max_children: usize) -> String {
+        let mut output = format!("{}\n", self.label);
+        self.write_text(&mut output, "", 0, max_depth, max_children.max(1));
+        output
+    }
+}
+
+impl Expression {
+    /// Converts the expression to a JSON tree.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Expression::Literal(value) => json!({ "type": "literal", "value": value }),
+            Expression::Operation(operator, operands) => json!({
+                "type": "operation",
+                "operator": operator.symbol(),
+                "operands": operands.iter().map(Expression::to_json).collect::<Vec<_>>(),
+            }),
+            Expression::FunctionCall(name, args) => json!({
+                "type": "call",
+                "name": name,
+                "args": args.iter().map(Expression::to_json).collect::<Vec<_>>(),
+            }),
+        }
+    }
+
+    /// Converts the expression to a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        self.tree_node().to_dot()
+    }
+
+    /// Draws the expression as a text tree with box-drawing characters.
+    ///
+    /// Nodes deeper than `max_depth` and children beyond `max_children` per
+    /// node are elided with `…`, which keeps the output small enough for chat.
+    pub fn to_text_tree(&self, max_depth: usize, max_children: usize) -> String {
+        self.tree_node().to_text_tree(max_depth, max_children)
+    }
+
+    /// Counts the nodes of the expression tree.
+    pub fn node_count(&self) -> usize {
+        match self {
+            Expression::Literal(_) => 1,
+            Expression::Operation(_, operands) => 1 + operands.iter().map(Expression::node_count).sum::<usize>(),
+            Expression::FunctionCall(_, args) => 1 + args.iter().map(Expression::node_count).sum::<usize>(),
+        }
+    }
+
+    /// Formats the expression as calculator source, e.g. `PI * r * r`.
+    ///
+    /// Parentheses are only added where precedence or associativity needs
+    /// them, so the output parses back to the same tree.
+    pub fn to_source(&self) -> String {
+        self.format_source(false)
+    }
+
+    /// Formats the expression with every nested operation in parentheses.
+    ///
+    /// Shows how the input was grouped, e.g. `2 + 3 * 4` as `2 + (3 * 4)`.
+    pub fn to_explicit_source(&self) -> String {
+        self.format_source(true)
+    }
+
+    fn format_source(&self, explicit: bool) -> String {
+        match self {
+            Expression::Literal(value) => value.clone(),
+            Expression::FunctionCall(name, args) => format!(
+                "{}({})",
+                name,
+                args.iter().map(|arg| arg.format_source(explicit)).collect::<Vec<_>>().join(", "),
+            ),
+            Expression::Operation(operator, operands) if operands.len() == 1 => {
+                let operand = &operands[0];
+                match operand {
+                    Expression::Operation(_, inner) if inner.len() > 1 => {
+                        format!("{}({})", operator.symbol(), operand.format_source(explicit))
+                    }
+                    _ => format!("{}{}", operator.symbol(), operand.format_source(explicit)),
+                }
+            }
+            Expression::Operation(operator, operands) => {
+                let (precedence, _, left_assoc) = operator.infix_binding_power().unwrap_or((0.0, 0.0, true));
+
+                // Wraps a child whose operator binds more loosely than this one, or
+                // any operation at all when the grouping is made explicit, except
+                // the sides of an assignment, whose grouping is never in doubt
+                let child = |expr: &Expression, on_left: bool| match expr {
+                    Expression::Operation(_, _) if explicit && *operator != Operator::Assign => {
+                        format!("({})", expr.format_source(explicit))
+                    }
+                    Expression::Operation(inner, inner_operands) if inner_operands.len() > 1 => {
+                        let (inner_precedence, _, _) = inner.infix_binding_power().unwrap_or((0.0, 0.0, true));
+                        let same_side = on_left == left_assoc;
+                        if inner_precedence < precedence || (inner_precedence == precedence && !same_side) {
+                            format!("({})", expr.format_source(explicit))
+                        } else {
+                            expr.format_source(explicit)
+                        }
+                    }
+                    _ => expr.format_source(explicit),
+                };
+
+                let separator = if *operator == Operator::Dot { String::from(".") } else { format!(" {} ", operator.symbol()) };
+                operands
+                    .iter()
+                    .enumerate()
+                    .map(|(index, operand)| child(operand, index == 0))
+                    .collect::<Vec<_>>()
+                    .join(&separator)
+            }
+        }
+    }
+
+    fn tree_node(&self) -> TreeNode {
+        match self {
+            Expression::Literal(value) => TreeNode::leaf(value.clone()),
+            Expression::Operation(operator, operands) => TreeNode::new(
+                operator.symbol(),
+                operands.iter().map(Expression::tree_node).collect(),
+            ),
+            Expression::FunctionCall(name, args) => TreeNode::new(
+                format!("{}()", name),
+                args.iter().map(Expression::tree_node).collect(),
+            ),
+        }
+    }
+}
+
+impl Statement {
+    /// Converts the statement to a JSON tree.
+    pub fn to_json(&self) -> Value {
+        let optional = |expr: &Option<Expression>| expr.as_ref().map_or(Value::Null, Expression::to_json);
+
+        match self {
+            Statement::Expression(expr) => json!({ "type": "expression", "expression": expr.to_json() }),
+            Statement::Block(statements) => json!({
+                "type": "block",
+                "statements": statements.iter().map(Statement::to_json).collect::<Vec<_>>(),
+            }),
+            Statement::If { condition, then_branch, else_branch } => json!({
+                "type": "if",
+                "condition": condition.to_json(),
+                "then": then_branch.to_json(),
+                "else": else_branch.as_ref().map_or(Value::Null, |branch| branch.to_json()),
+            }),
+            Statement::While { condition, body } => json!({
+                "type": "while",
+                "condition": condition.to_json(),
+                "body": body.to_json(),
+            }),
+            Statement::Foreach { variable, start, end, body } => json!({
+                "type": "foreach",
+                "variable": variable,
+                "start": start.to_json(),
+                "end": end.to_json(),
+                "body": body.to_json(),
+            }),
+            Statement::Break => json!({ "type": "break" }),
+            Statement::Continue => json!({ "type": "continue" }),
+            Statement::Return(value) => json!({ "type": "return", "value": optional(value) }),
+            Statement::End(value) => json!({ "type": "end", "value": optional(value) }),
+            Statement::Assert(condition) => json!({ "type": "assert", "condition": condition.to_json() }),
+            Statement::Defer(body) => json!({ "type": "defer", "body": body.to_json() }),
+            Statement::Try { body, handler } => json!({
+                "type": "try",
+                "body": body.to_json(),
+                "handler": handler.to_json(),
+            }),
+            Statement::Let { name, initializer } => json!({
+                "type": "let",
+                "name": name,
+                "initializer": optional(initializer),
+            }),
+            Statement::Formula { name, expression } => json!({
+                "type": "formula",
+                "name": name,
+                "expression": expression.to_json(),
+            }),
+            Statement::Const { name, initializer, global } => json!({
+                "type": "const",
+                "name": name,
+                "initializer": initializer.to_json(),
+                "global": global,
+            }),
+            Statement::Function { name, params, body, memo } => json!({
+                "type": "function",
+                "name": name,
+                "params": params,
+                "memo": memo,
+                "body": body.to_json(),
+            }),
+            Statement::Procedure { name, params, body } => json!({
+                "type": "procedure",
+                "name": name,
+                "params": params,
+                "body": body.to_json(),
+            }),
+            Statement::ProcedureCall { name, args } => json!({
+                "type": "procedure_call",
+                "name": name,
+                "args": args.iter().map(Expression::to_json).collect::<Vec<_>>(),
+            }),
+            Statement::Import(name) => json!({ "type": "import", "name": name }),
+        }
+    }
+
+    /// Counts the nodes of the statement tree, expressions included.
+    ///
+    /// Used to bound how much code a session can keep in its functions and
+    /// procedures.
+    pub fn node_count(&self) -> usize {
+        let optional = |expr: &Option<Expression>| expr.as_ref().map_or(0, Expression::node_count);
+
+        1 + match self {
+            Statement::Expression(expr) | Statement::Assert(expr) => expr.node_count(),
+            Statement::Block(statements) => statements.iter().map(Statement::node_count).sum(),
+            Statement::If { condition, then_branch, else_branch } => {
+                condition.node_count() + then_branch.node_count() + else_branch.as_ref().map_or(0, |branch| branch.node_count())
+            }
+            Statement::While { condition, body } => condition.node_count() + body.node_count(),
+            Statement::Foreach { start, end, body, .. } => start.node_count() + end.node_count() + body.node_count(),
+            Statement::Break | Statement::Continue | Statement::Import(_) => 0,
+            Statement::Return(value) | Statement::End(value) => optional(value),
+            Statement::Defer(body) => body.node_count(),
+            Statement::Try { body, handler } => body.node_count() + handler.node_count(),
+            Statement::Let { initializer, .. } => optional(initializer),
+            Statement::Formula { expression, .. } => expression.node_count(),
+            Statement::Const { initializer, .. } => initializer.node_count(),
+            Statement::Function { body, .. } | Statement::Procedure { body, .. } => body.node_count(),
+            Statement::ProcedureCall { args, .. } => args.iter().map(Expression::node_count).sum(),
+        }
+    }
+
+    /// Formats the statement as one line of source with every nested
+    /// operation in parentheses, see [`Expression::to_explicit_source`].
+    ///
+    /// Bodies are always written as blocks.
+    pub fn to_explicit_source(&self) -> String {
+        let optional = |keyword: &str, expr: &Option<Expression>| match expr {
+            Some(expr) => format!("{} {}", keyword, expr.to_explicit_source()),
+            None => keyword.to_string(),
+        };
+        let body = |statement: &Statement| match statement {
+            Statement::Block(_) => statement.to_explicit_source(),
+            _ => format!("{{ {} }}", statement.to_explicit_source()),
+        };
+
+        match self {
+            Statement::Expression(expr) => expr.to_explicit_source(),
+            Statement::Block(statements) if statements.is_empty() => "{ }".to_string(),
+            Statement::Block(statements) => format!(
+                "{{ {} }}",
+                statements.iter().map(Statement::to_explicit_source).collect::<Vec<_>>().join("; "),
+            ),
+            Statement::If { condition, then_branch, else_branch } => format!(
+                "if {} {}{}",
+                condition.to_explicit_source(),
+                body(then_branch),
+                else_branch.as_ref().map_or(String::new(), |branch| format!(" else {}", body(branch))),
+            ),
+            Statement::While { condition, body: loop_body } => {
+                format!("while {} {}", condition.to_explicit_source(), body(loop_body))
+            }
+            Statement::Foreach { variable, start, end, body: loop_body } => format!(
+                "foreach {} in {} .. {} {}",
+                variable,
+                start.to_explicit_source(),
+                end.to_explicit_source(),
+                body(loop_body),
+            ),
+            Statement::Break => "break".to_string(),
+            Statement::Continue => "continue".to_string(),
+            Statement::Return(value) => optional("return", value),
+            Statement::End(value) => optional("end", value),
+            Statement::Assert(condition) => format!("assert {}", condition.to_explicit_source()),
+            Statement::Defer(deferred) => format!("defer {}", deferred.to_explicit_source()),
+            Statement::Try { body: try_body, handler } => format!("try {} catch {}", body(try_body), body(handler)),
+            Statement::Let { name, initializer: Some(initializer) } => {
+                format!("let {} = {}", name, initializer.to_explicit_source())
+            }
+            Statement::Let { name, initializer: None } => format!("let {}", name),
+            Statement::Formula { name, expression } => format!("let {} := {}", name, expression.to_explicit_source()),
+            Statement::Const { name, initializer, global } => {
+                format!("const {}{} = {}", if *global { "global " } else { "" }, name, initializer.to_explicit_source())
+            }
+            Statement::Function { name, params, body: function_body, memo } => format!(
+                "{}fn {}({}) {}",
+                if *memo { "memo " } else { "" },
+                name,
+                params.join(", "),
+                body(function_body),
+            ),
+            Statement::Procedure { name, params, body: procedure_body } => {
+                format!("proc {}({}) {}", name, params.join(", "), body(procedure_body))
+            }
+            Statement::ProcedureCall { name, args } => {
+                Expression::FunctionCall(name.clone(), args.clone()).to_explicit_source()
+            }
+            Statement::Import(name) => format!("import \"{}\"", name),
+        }
+    }
+
+    /// Formats the statement as one line of source, with bodies elided as `{ … }`.
+    ///
+    /// Used to label statements in execution traces, e.g. `while i < 10 { … }`.
+    pub fn summary(&self) -> String {
+        let optional = |keyword: &str, expr: &Option<Expression>| match expr {
+            Some(expr) => format!("{} {}", keyword, expr.to_source()),
+            None => keyword.to_string(),
+        };
+
+        match self {
+            Statement::Expression(expr) => expr.to_source(),
+            Statement::Block(_) => "{ … }".to_string(),
+            Statement::If { condition, else_branch, .. } => format!(
+                "if {} {{ … }}{}",
+                condition.to_source(),
+                if else_branch.is_some() { " else { … }" } else { "" },
+            ),
+            Statement::While { condition, .. } => format!("while {} {{ … }}", condition.to_source()),
+            Statement::Foreach { variable, start, end, .. } => {
+                format!("foreach {} in {} .. {} {{ … }}", variable, start.to_source(), end.to_source())
+            }
+            Statement::Break => "break".to_string(),
+            Statement::Continue => "continue".to_string(),
+            Statement::Return(value) => optional("return", value),
+            Statement::End(value) => optional("end", value),
+            Statement::Assert(condition) => format!("assert {}", condition.to_source()),
+            Statement::Defer(body) => format!("defer {}", body.summary()),
+            Statement::Try { .. } => "try { … } catch { … }".to_string(),
+            Statement::Let { name, initializer: Some(initializer) } => format!("let {} = {}", name, initializer.to_source()),
+            Statement::Let { name, initializer: None } => format!("let {}", name),
+            Statement::Formula { name, expression } => format!("let {} := {}", name, expression.to_source()),
+            Statement::Const { name, initializer, global } => {
+                format!("const {}{} = {}", if *global { "global " } else { "" }, name, initializer.to_source())
+            }
+            Statement::Function { name, params, memo, .. } => {
+                format!("{}fn {}({}) {{ … }}", if *memo { "memo " } else { "" }, name, params.join(", "))
+            }
+            Statement::Procedure { name, params, .. } => format!("proc {}({}) {{ … }}", name, params.join(", ")),
+            Statement::ProcedureCall { name, args } => Expression::FunctionCall(name.clone(), args.clone()).to_source(),
+            Statement::Import(name) => format!("import \"{}\"", name),
+        }
+    }
+
+    /// Converts the statement to a Graphviz DOT graph.
+    pub fn to_dot(&self) -> String {
+        self.tree_node().to_dot()
+    }
+
+    /// Draws the statement as a text tree with box-drawing characters.
+    ///
+    /// See [`Expression::to_text_tree`] for how the caps apply.
+    pub fn to_text_tree(&self, max_depth: usize, max_children: usize) -> String {
+        self.tree_node().to_text_tree(max_depth, max_children)
+    }
+
+    fn tree_node(&self) -> TreeNode {
+        let optional = |label: &str, expr: &Option<Expression>| match expr {
+            Some(expr) => TreeNode::new(label, vec![expr.tree_node()]),
+            None => TreeNode::leaf(label),
+        };
+
+        match self {
+            Statement::Expression(expr) => expr.tree_node(),
+            Statement::Block(statements) => TreeNode::new("block", statements.iter().map(Statement::tree_node).collect()),
+            Statement::If { condition, then_branch, else_branch } => {
+                let mut children = vec![condition.tree_node(), then_branch.tree_node()];
+                children.extend(else_branch.as_ref().map(|branch| branch.tree_node()));
+                TreeNode::new("if", children)
+            }
+            Statement::While { condition, body } => TreeNode::new("while", vec![condition.tree_node(), body.tree_node()]),
+            Statement::Foreach { variable, start, end, body } => TreeNode::new(
+                format!("foreach {}", variable),
+                vec![start.tree_node(), end.tree_node(), body.tree_node()],
+            ),
+            Statement::Break => TreeNode::leaf("break"),
+            Statement::Continue => TreeNode::leaf("continue"),
+            Statement::Return(value) => optional("return", value),
+            Statement::End(value) => optional("end", value),
+            Statement::Assert(condition) => TreeNode::new("assert", vec![condition.tree_node()]),
+            Statement::Defer(body) => TreeNode::new("defer", vec![body.tree_node()]),
+            Statement::Try { body, handler } => TreeNode::new("try", vec![body.tree_node(), handler.tree_node()]),
+            Statement::Let { name, initializer } => optional(&format!("let {}", name), initializer),
+            Statement::Formula { name, expression } => TreeNode::new(format!("let {} :=", name), vec![expression.tree_node()]),
+            Statement::Const { name, initializer, global } => TreeNode::new(
+                format!("const {}{}", if *global { "global " } else { "" }, name),
+                vec![initializer.tree_node()],
+            ),
+            Statement::Function { name, params, body, memo } => TreeNode::new(
+                format!("{}fn {}({})", if *memo { "memo " } else { "" }, name, params.join(", ")),
+                vec![body.tree_node()],
+            ),
+            Statement::Procedure { name, params, body } => TreeNode::new(
+                format!("proc {}({})", name, params.join(", ")),
+                vec![body.tree_node()],
+            ),
+            Statement::ProcedureCall { name, args } => TreeNode::new(
+                format!("{}()", name),
+                args.iter().map(Expression::tree_node).collect(),
+            ),
+            Statement::Import(name) => TreeNode::leaf(format!("import \"{}\"", name)),
+        }
+    }
+}
+
+/// Parses the input and formats it with every nested operation in
+/// parentheses, one top-level statement per line.
+///
+/// Lets users check how their input was grouped, e.g. `2+3*4` gives
+/// `2 + (3 * 4)`.
+pub fn normalize_source(input: &str) -> Result<String, ParseError> {
+    let lines = match parse_program(input)? {
+        ParsedProgram::Statements(statements) => statements.iter().map(Statement::to_explicit_source).collect(),
+        ParsedProgram::Expression(expr) => vec![expr.to_explicit_source()],
+    };
+    Ok(lines.join("\n"))
+}
+
+/// Parses the input and formats it with consistent indentation, one
+/// top-level statement per line, see [`format_pretty`](crate::core::format_pretty).
+pub fn format_source(input: &str) -> Result<String, ParseError> {
+    Ok(match parse_program(input)? {
+        ParsedProgram::Statements(statements) => format_pretty_sequence(&statements, 0),
+        ParsedProgram::Expression(expr) => expr.to_source(),
+    })
+}
@@ -1,9 +1,15 @@
 use std::fmt;
-use crate::core::lexical_analyzer::{Token, Tokenizer};
+use crate::core::lexical_analyzer::{decode_radix_literal, is_identifier, Token, Tokenizer};
+use crate::core::operator::Operator;
 use crate::core::error_types::{ParseError, EvalError, MathError, SymbolError, ControlFlowError};
-use crate::core::symbol_manager::{SymbolTable, global_constants};
-use crate::core::ast_statement::{Statement, ControlFlow};
-use rand::Rng;
+use crate::core::symbol_manager::{is_result_name, BindingKind, SymbolTable, global_constants};
+use crate::core::shared_constants::shared_constant;
+use crate::core::ast_statement::{count_iteration, enter_call, leave_call, ControlFlow, Statement, TailOutcome};
+use crate::core::builtins::{call_builtin, find_builtin};
+use crate::core::units::convert_units;
+use crate::core::analysis::is_pure_function;
+use crate::core::metrics::record_cache_lookup;
+use crate::core::execution_state::{with_formula_state, with_memo_state, with_result_history};
 
 /// AST node for expressions.
 ///
@@ -16,11 +22,11 @@ pub enum Expression {
     /// A literal string: numeric or variable identifier.
     Literal(String),
 
-    /// An operation: operator character and operand subexpressions.
+    /// An operation: operator and operand subexpressions.
     /// 
     /// The first element in the Vec is the left-hand operand for binary operators,
     /// or the only operand for unary operators.
-    Operation(char, Vec<Expression>),
+    Operation(Operator, Vec<Expression>),
 
     /// A function call: function name and argument expressions.
     FunctionCall(String, Vec<Expression>),
@@ -57,99 +63,188 @@ impl Expression {
     /// This is the core of the Pratt parsing algorithm.
     ///
     /// It uses binding power (precedence) to determine how expressions should be grouped.
+    /// Each call is one level of nesting, as is each operator applied to the
+    /// expression so far, so runs of parentheses, prefix operators or long
+    /// operator chains fail with [`ParseError::TooDeeplyNested`] past the
+    /// tokenizer's limit.
     pub fn parse(tokenizer: &mut Tokenizer, min_bp: f32) -> Result<Self, ParseError> {
-        // Phase 1: Parse the left-hand side (LHS) operand or prefix expression
-        let mut lhs = match tokenizer.peek_token() {
+        tokenizer.enter_nesting()?;
+        let result = Self::parse_level(tokenizer, min_bp);
+        tokenizer.leave_nesting();
+        result
+    }
+
+    /// Parses one level of an expression; see [`Expression::parse`].
+    fn parse_level(tokenizer: &mut Tokenizer, min_bp: f32) -> Result<Self, ParseError> {
+        // The phases are separate functions to keep each nesting level's stack frame small
+        let lhs = Self::parse_prefix(tokenizer)?;
+        Self::parse_infix(tokenizer, lhs, min_bp)
+    }
+
+    /// Continues an expression whose first operand is already parsed.
+    ///
+    /// A statement that starts with a call only knows it is part of a larger
+    /// expression, as in `f(2) + 3`, once it sees the operator after the call.
+    pub(crate) fn parse_continuation(tokenizer: &mut Tokenizer, lhs: Self) -> Result<Self, ParseError> {
+        tokenizer.enter_nesting()?;
+        let result = Self::parse_infix(tokenizer, lhs, 0.0);
+        tokenizer.leave_nesting();
+        result
+    }
+
+    /// Checks whether a token after an operand continues the expression.
+    pub(crate) fn continues_after_operand(token: &Token) -> bool {
+        let continues = |operator: Operator| {
+            operator.infix_binding_power().is_some() || operator.postfix_binding_power().is_some()
+        };
+        matches!(token, Token::AugAssign(_)) || token_operator(token).is_some_and(continues)
+    }
+
+    /// Phase 1: Parse the left-hand side (LHS) operand or prefix expression.
+    fn parse_prefix(tokenizer: &mut Tokenizer) -> Result<Self, ParseError> {
+        let lhs = match tokenizer.peek_token() {
             // Keywords are not allowed in expressions, but we'll handle them in statement parsing
-            Token::Keyword(_) => {
-                let statement = Statement::parse(tokenizer)?;
-                match statement {
-                    Statement::Expression(expr) => expr,
-                    _ => return Err(ParseError::UnexpectedToken(format!("{:?}", tokenizer.peek_token()))),
-                }
-            },
+            Token::Keyword(_) => Self::parse_keyword(tokenizer)?,
 
             // Grouped expression; parse expressions inside parentheses
-            Token::Operator('(') => {
+            Token::Punctuation('(') => {
                 tokenizer.next_token(); // consume '('
                 let expr = Self::parse(tokenizer, 0.0)?;
                 match tokenizer.next_token() {
-                    Token::Operator(')') => expr,
+                    Token::Punctuation(')') => expr,
                     _ => return Err(ParseError::UnmatchedParenthesis),
                 }
             }
 
             // Prefix operator or unary/root expression (e.g., -a, √a, a √ b)
-            Token::Operator(op) if prefix_binding_power(*op).is_some() => {
-                let prefix_op = *op;
-                tokenizer.next_token(); // consume operator
-                let binding_power = prefix_binding_power(prefix_op).unwrap();
-
-                // Parse the operand following the prefix operator
-                let first_operand = Self::parse(tokenizer, binding_power)?;
-                let mut operands = vec![first_operand];
-
-                // Special case for √ operator that may accept a second operand (e.g., a √ b)
-                if prefix_op == '√' && matches!(
-                    tokenizer.peek_token(),
-                    Token::Literal(_) | Token::Operator('(') | Token::Operator('√')
-                ) {
-                    operands.push(Self::parse(tokenizer, binding_power)?);
-                }
-
-                Expression::Operation(prefix_op, operands)
+            token if token_operator(token).and_then(Operator::prefix_binding_power).is_some() => {
+                let prefix_op = token_operator(token).unwrap();
+                Self::parse_prefix_operator(tokenizer, prefix_op)?
             }
 
-            // Literal token
-            Token::Literal(_) => {
-                if let Token::Literal(lit) = tokenizer.next_token() {
-                    // Check for function call (literal followed by open parenthesis)
-                    if tokenizer.peek_token() == &Token::Operator('(') {
-                        tokenizer.next_token(); // consume '('
-                        let mut args = Vec::new();
-
-                        // Parse argument list
-                        if tokenizer.peek_token() != &Token::Operator(')') {
-                            loop {
-                                args.push(Self::parse(tokenizer, 0.0)?);
-                                if tokenizer.peek_token() == &Token::Operator(',') {
-                                    tokenizer.next_token(); // consume ','
-                                } else {
-                                    break;
-                                }
-                            }
-                        }
-                        
-                        // Ensure closing parenthesis
-                        if tokenizer.peek_token() != &Token::Operator(')') {
-                            return Err(ParseError::UnmatchedParenthesis);
-                        }
-                        tokenizer.next_token(); // consume ')'
-                        
-                        Expression::FunctionCall(lit, args)
-                    } else {
-                        Expression::Literal(lit)
-                    }
-                } else {
-                    unreachable!("Expected literal after Token::Literal")
-                }
-            }
+            // Literal token, possibly starting a function call
+            Token::Literal(_) => Self::parse_literal(tokenizer)?,
 
             // Any unexpected token at the beginning of an expression
-            unexpected => return Err(ParseError::UnexpectedToken(format!("{:?}", unexpected))),
+            unexpected => return Err(ParseError::UnexpectedToken(format!("{}", unexpected))),
         };
 
-        // Phase 2: Parse infix and augmented operators (while loop for right recursion)
-        // This continues grabbing operators and right-hand expressions as long as
-        // the precedence/binding power is sufficient
+        Ok(lhs)
+    }
+
+    /// Parses a statement in expression position, which must be an expression.
+    fn parse_keyword(tokenizer: &mut Tokenizer) -> Result<Self, ParseError> {
+        match Statement::parse(tokenizer)? {
+            Statement::Expression(expr) => Ok(expr),
+            _ => Err(ParseError::UnexpectedToken(format!("{}", tokenizer.peek_token()))),
+        }
+    }
+
+    /// Parses a prefix operator and its operand (e.g., -a, √a, a √ b).
+    fn parse_prefix_operator(tokenizer: &mut Tokenizer, prefix_op: Operator) -> Result<Self, ParseError> {
+        tokenizer.next_token(); // consume operator
+        let binding_power = prefix_op.prefix_binding_power().unwrap();
+
+        // Parse the operand following the prefix operator
+        let first_operand = Self::parse(tokenizer, binding_power)?;
+        let mut operands = vec![first_operand];
+
+        // Special case for √ operator that may accept a second operand (e.g., a √ b)
+        if prefix_op == Operator::Root && matches!(
+            tokenizer.peek_token(),
+            Token::Literal(_) | Token::Punctuation('(') | Token::Operator(Operator::Root)
+        ) {
+            operands.push(Self::parse(tokenizer, binding_power)?);
+        }
+
+        Ok(Expression::Operation(prefix_op, operands))
+    }
+
+    /// Parses a literal, or a function call when it is followed by `(`.
+    fn parse_literal(tokenizer: &mut Tokenizer) -> Result<Self, ParseError> {
+        let Token::Literal(lit) = tokenizer.next_token() else {
+            unreachable!("Expected literal after Token::Literal")
+        };
+
+        // Reject malformed hex and binary literals before they reach evaluation
+        if let Some(Err(msg)) = decode_radix_literal(&lit) {
+            return Err(ParseError::InvalidNumber(msg));
+        }
+
+        // Check for function call (literal followed by open parenthesis)
+        if tokenizer.peek_token() != &Token::Punctuation('(') {
+            return Ok(Expression::Literal(lit));
+        }
+        tokenizer.next_token(); // consume '('
+        let mut args = Vec::new();
+
+        // Parse argument list, which may end with a single trailing comma
+        if tokenizer.peek_token() != &Token::Punctuation(')') {
+            loop {
+                expect_list_item(tokenizer, "argument", &lit, args.len())?;
+                args.push(Self::parse(tokenizer, 0.0)?);
+                if tokenizer.peek_token() != &Token::Punctuation(',') {
+                    break;
+                }
+                tokenizer.next_token(); // consume ','
+                if tokenizer.peek_token() == &Token::Punctuation(')') {
+                    break;
+                }
+            }
+        }
+        
+        // Ensure closing parenthesis
+        if tokenizer.peek_token() != &Token::Punctuation(')') {
+            return Err(ParseError::UnmatchedParenthesis);
+        }
+        tokenizer.next_token(); // consume ')'
+        
+        Ok(Expression::FunctionCall(lit, args))
+    }
+
+    /// Phase 2: Parse infix and augmented operators (while loop for right recursion).
+    ///
+    /// This continues grabbing operators and right-hand expressions as long as
+    /// the precedence/binding power is sufficient.
+    ///
+    /// The loop does not recurse, but each operator it applies puts the
+    /// expression so far one level deeper, as in `1 + 1 + 1`. Those levels
+    /// count towards the nesting limit too, since evaluating the result
+    /// recurses once per level.
+    fn parse_infix(tokenizer: &mut Tokenizer, lhs: Self, min_bp: f32) -> Result<Self, ParseError> {
+        let mut levels = 0;
+        let result = Self::parse_operators(tokenizer, lhs, min_bp, &mut levels);
+        for _ in 0..levels {
+            tokenizer.leave_nesting();
+        }
+        result
+    }
+
+    /// Applies operators to `lhs` for [`Expression::parse_infix`], counting
+    /// the levels of nesting it entered in `levels`.
+    fn parse_operators(tokenizer: &mut Tokenizer, mut lhs: Self, min_bp: f32, levels: &mut usize) -> Result<Self, ParseError> {
         loop {
             match tokenizer.peek_token() {
                 // End of expression or expression group
-                Token::EndOfInput | Token::Operator(')') | Token::Operator(',') | Token::Operator(';') => break,
+                Token::EndOfInput | Token::Punctuation(')') | Token::Punctuation(',') | Token::Punctuation(';') => break,
+
+                // Postfix factorial (e.g., 5!); a `!` after an operand is never NOT,
+                // and `!=`, `!^`, `!&` and `!|` are already separate tokens
+                token if token_operator(token).and_then(Operator::postfix_binding_power).is_some() => {
+                    if token_operator(token).and_then(Operator::postfix_binding_power).unwrap() < min_bp {
+                        break;
+                    }
+
+                    tokenizer.next_token(); // consume operator
+                    tokenizer.enter_nesting()?;
+                    *levels += 1;
+                    lhs = Expression::FunctionCall("fact".to_string(), vec![lhs]);
+                }
 
                 // Infix operators (e.g., +, -, *, /, ^, etc.)
-                Token::Operator(op) if infix_binding_power(*op).is_some() => {
-                    let (left_bp, right_bp, is_left_associative) = infix_binding_power(*op).unwrap();
+                token if token_operator(token).and_then(Operator::infix_binding_power).is_some() => {
+                    let operator = token_operator(token).unwrap();
+                    let (left_bp, right_bp, is_left_associative) = operator.infix_binding_power().unwrap();
 
                     // Stop parsing if operator precedence is lower than current context
                     // The binding power check ensures proper precedence handling
@@ -159,11 +254,12 @@ impl Expression {
                         break;
                     }
                     
-                    let operator = *op;
                     tokenizer.next_token(); // consume operator
                     
                     // Recursively parse the right-hand side with the appropriate binding power
                     let rhs = Self::parse(tokenizer, right_bp)?;
+                    tokenizer.enter_nesting()?;
+                    *levels += 1;
                     
                     // Combine the left and right expressions with the operator
                     lhs = Expression::Operation(operator, vec![lhs, rhs]);
@@ -171,15 +267,19 @@ impl Expression {
 
                 // Augmented assignment (e.g., +=, -=, *=, and etc.)
                 Token::AugAssign(_) => {
-                    if let Token::AugAssign(aug_op_str) = tokenizer.next_token() {
-                        // Extract actual operator from the augmented assignment (e.g., "+=" -> '+')
-                        let base_op = aug_op_str.chars().next().unwrap();
+                    if let Token::AugAssign(base_op) = tokenizer.next_token() {
+                        // Only a variable can be updated in place
+                        if !matches!(&lhs, Expression::Literal(name) if name.starts_with(|c: char| c.is_alphabetic() || c == '_')) {
+                            return Err(ParseError::SyntaxError(format!(
+                                "'{}=' needs a variable on its left, found '{}'", base_op, lhs.to_source()
+                            )));
+                        }
                         let rhs = Self::parse(tokenizer, 0.0)?;
 
                         // Desugar x += y => x = x + y
-                        // The actual check for variable existence will happen during evaluation
+                        // The target is checked before the sum is evaluated, see `check_assignment_target`
                         lhs = Expression::Operation(
-                            '=',
+                            Operator::Assign,
                             vec![lhs.clone(), Expression::Operation(base_op, vec![lhs, rhs])],
                         );
                     }
@@ -196,10 +296,10 @@ impl Expression {
     /// Identifies if this is an assignment operation.
     #[allow(dead_code)]
     pub fn is_assignment(&self) -> Option<(String, Expression)> {
-        if let Expression::Operation(op_char, operands) = self {
+        if let Expression::Operation(operator, operands) = self {
             if operands.len() == 2 {
                 if let Expression::Literal(var_name) = &operands[0] {
-                    if *op_char == '=' {
+                    if *operator == Operator::Assign {
                         let rhs_expr = operands[1].clone();
                         return Some((var_name.clone(), rhs_expr));
                     }
@@ -218,44 +318,58 @@ impl Expression {
         match self {
             // Literal: number or variable
             Expression::Literal(text) => {
-                // Hexadecimal (0xFF)
-                if let Some(hex_digits) = text.strip_prefix("0x") {
-                    let value = u32::from_str_radix(hex_digits, 16)
-                        .map_err(|_| MathError::InvalidExponentiation)? as f32;
-                    return Ok(value);
-                }
-                // Binary (0b1010)
-                if let Some(bin_digits) = text.strip_prefix("0b") {
-                    let value = u32::from_str_radix(bin_digits, 2)
-                        .map_err(|_| MathError::InvalidExponentiation)? as f32;
-                    return Ok(value);
+                // Hexadecimal (0xFF) or binary (0b1010), already checked by the parser
+                if let Some(decoded) = decode_radix_literal(text) {
+                    return decoded.map(|value| value as f32).map_err(|msg| MathError::DomainError(msg).into());
                 }
                 // Decimal literal
                 if let Ok(decimal_value) = text.parse::<f32>() {
                     return Ok(decimal_value);
                 }
+                // Imaginary literal (2i), which needs complex mode
+                if text.strip_suffix('i').is_some_and(|digits| digits.parse::<f32>().is_ok()) {
+                    return Err(MathError::DomainError(format!("imaginary number '{}' needs complex mode", text)).into());
+                }
                 // Variable lookup - first check local context
                 if let Some(value) = context.get(text) {
                     return Ok(*value);
                 }
+                // Formulas are re-evaluated against the current context
+                if let Some(formula) = context.get_formula(text) {
+                    return evaluate_formula(text, formula, context);
+                }
+                // Then results of earlier top-level statements, like `_1` or `_`
+                if is_result_name(text) {
+                    return with_result_history(|history| history.get(text))
+                        .ok_or_else(|| SymbolError::VariableNotFound(text.clone()).into());
+                }
+                // Then constants shared by every session
+                if let Some(value) = shared_constant(text) {
+                    return Ok(value);
+                }
                 // Then check global constants
                 if let Some(value) = global_constants().get(text) {
                     return Ok(value);
                 }
-                // Not found anywhere
-                Err(SymbolError::VariableNotFound(text.clone()).into())
+                // A callable read without parentheses gets a hint instead
+                let kind = if context.procedures.contains_key(text) {
+                    "procedure"
+                } else if context.functions.contains_key(text) || find_builtin(text).is_some() {
+                    "function"
+                } else {
+                    // Not found anywhere
+                    return Err(SymbolError::VariableNotFound(text.clone()).into());
+                };
+                Err(SymbolError::CallableUsedAsVariable { name: text.clone(), kind: kind.to_string() }.into())
             }
 
             // Infix or prefix operation (unary, binary, root)
             Expression::Operation(operator, operands) => {
-                // Special handling for assignment operator
-                if *operator == '=' && operands.len() == 2 {
-                    if let Expression::Literal(var_name) = &operands[0] {
-                        // Check if we're trying to assign to a global constant
-                        if global_constants().contains(var_name) {
-                            return Err(SymbolError::ImmutableConstant(var_name.clone()).into());
-                        }
-                    }
+                // Assignments cannot write here, but check the target and only yield the value,
+                // so `x += 1` on an undeclared `x` is not reported as a missing read
+                if let (Operator::Assign, [Expression::Literal(var_name), value]) = (*operator, operands.as_slice()) {
+                    check_assignment_target(var_name, context)?;
+                    return value.evaluate(context);
                 }
 
                 // Evaluate left operand (always present)
@@ -268,92 +382,39 @@ impl Expression {
                     0.0
                 };
 
-                match *operator {
-                    // Arithmetic operations
-                    '+' => Ok(left_val + right_val),
-                    '-' if operands.len() == 1 => Ok(-left_val), // unary minus
-                    '-' => Ok(left_val - right_val),
-                    '*' => Ok(left_val * right_val),
-
-                    // Division with zero check
-                    '/' => {
-                        if right_val == 0.0 {
-                            Err(MathError::DivisionByZero.into())
-                        } else {
-                            Ok(left_val / right_val)
-                        }
-                    }
+                apply_operator(*operator, operands.len() == 1, left_val, right_val)
+                    .map_err(|error| name_divisor(error, *operator, operands))
+            }
 
-                    // Modulo with zero check
-                    '%' => {
-                        if right_val == 0.0 {
-                            Err(MathError::ModuloByZero.into())
-                        } else {
-                            Ok(left_val % right_val)
-                        }
+            // Function call
+            Expression::FunctionCall(name, args) => {
+                // Procedures have no value, they can only be called as statements
+                if context.procedures.contains_key(name) {
+                    return Err(ControlFlowError::ProcedureUsedAsValue { name: name.clone() }.into());
+                }
+                
+                // Unit names in convert() are looked up in the unit table, not as variables
+                if name == "convert" {
+                    if let [value, Expression::Literal(from), Expression::Literal(to)] = args.as_slice() {
+                        return Ok(convert_units(value.evaluate(context)?, from, to)?);
                     }
+                }
 
-                    // Exponentiation, check for invalid negative base + fractional exponent
-                    '^' => {
-                        if left_val < 0.0 && right_val.fract() != 0.0 {
-                            Err(MathError::InvalidExponentiation.into())
-                        } else {
-                            Ok(left_val.powf(right_val))
-                        }
+                // The body of fold() is evaluated once per step, not up front
+                if name == "fold" {
+                    if let [Expression::Literal(variable), lo, hi, init, body] = args.as_slice() {
+                        let bounds = (lo.evaluate(context)?, hi.evaluate(context)?);
+                        return evaluate_fold(variable, bounds, init.evaluate(context)?, body, context);
                     }
+                }
 
-                    // Root operation, expects exactly two operands
-                    '√' => {
-                        if operands.len() != 2 {
-                            return Err(MathError::InvalidExponentiation.into());
-                        }
-                        let degree = left_val;
-                        let radicand = right_val;
-                        if degree == 0.0 {
-                            Err(MathError::ZerothRoot.into())
-                        } else if radicand < 0.0 && (1.0_f32 / degree).fract() != 0.0 {
-                            Err(MathError::NegativeRoot.into())
-                        } else {
-                            Ok(radicand.powf(1.0 / degree))
+                // A name given to typeof() is looked up, not read, so it may be undefined
+                if name == "typeof" {
+                    if let [Expression::Literal(target)] = args.as_slice() {
+                        if is_identifier(target) {
+                            return Ok(type_of_name(target, context));
                         }
                     }
-
-                    // Logical operators
-                    '&' => Ok(if left_val != 0.0 && right_val != 0.0 { 1.0 } else { 0.0 }), // AND
-                    '|' => Ok(if left_val != 0.0 || right_val != 0.0 { 1.0 } else { 0.0 }), // OR
-                    'x' => Ok(if (left_val != 0.0) != (right_val != 0.0) { 1.0 } else { 0.0 }), // XOR
-                    'q' => Ok(if (left_val != 0.0) == (right_val != 0.0) { 1.0 } else { 0.0 }), // XNOR
-                    'a' => Ok(if !(left_val != 0.0 && right_val != 0.0) { 1.0 } else { 0.0 }), // NAND
-                    'o' => Ok(if !(left_val != 0.0 || right_val != 0.0) { 1.0 } else { 0.0 }), // NOR
-                    '!' => Ok(if left_val == 0.0 { 1.0 } else { 0.0 }), // NOT (unary)
-
-                    // Comparison operators
-                    '>' => Ok(if left_val > right_val { 1.0 } else { 0.0 }),
-                    '<' => Ok(if left_val < right_val { 1.0 } else { 0.0 }),
-                    'g' => Ok(if left_val >= right_val { 1.0 } else { 0.0 }), // >=
-                    'l' => Ok(if left_val <= right_val { 1.0 } else { 0.0 }), // <=
-                    'e' => Ok(if (left_val - right_val).abs() < f32::EPSILON { 1.0 } else { 0.0 }), // ==
-                    'n' => Ok(if (left_val - right_val).abs() >= f32::EPSILON { 1.0 } else { 0.0 }), // !=
-
-                    // Dot-access operator, returns the right-hand side
-                    '.' => Ok(right_val),
-
-                    // Assignment operator
-                    '=' => Ok(right_val),
-
-                    // Unsupported operator
-                    other => Err(MathError::UnsupportedOperator(other).into()),
-                }
-            }
-
-            // Function call
-            Expression::FunctionCall(name, args) => {
-                // First, check if it's a procedure call
-                if context.procedures.contains_key(name) {
-                    // Return error - procedure calls must be handled as statements
-                    return Err(ControlFlowError::UnimplementedFeature(
-                        format!("Procedure '{}' cannot be called as a function expression", name)
-                    ).into());
                 }
                 
                 // Evaluate all arguments first
@@ -362,143 +423,430 @@ impl Expression {
                     evaluated_args.push(arg.evaluate(context)?);
                 }
                 
-                // Check for built-in functions first
-                match name.as_str() {
-                    "sin"   => Ok(evaluated_args[0].sin()),
-                    "cos"   => Ok(evaluated_args[0].cos()),
-                    "tan"   => Ok(evaluated_args[0].tan()),
-                    // Additional trigonometric functions
-                    "cot"   => {
-                        let tan_val = evaluated_args[0].tan();
-                        if tan_val == 0.0 {
-                            Err(MathError::UnsupportedFunction("Division by zero in cotangent".to_string()).into())
-                        } else {
-                            Ok(1.0 / tan_val)
-                        }
-                    },
-                    "sec"   => {
-                        let cos_val = evaluated_args[0].cos();
-                        if cos_val == 0.0 {
-                            Err(MathError::UnsupportedFunction("Division by zero in secant".to_string()).into())
-                        } else {
-                            Ok(1.0 / cos_val)
-                        }
-                    },
-                    "csc"   => {
-                        let sin_val = evaluated_args[0].sin();
-                        if sin_val == 0.0 {
-                            Err(MathError::UnsupportedFunction("Division by zero in cosecant".to_string()).into())
-                        } else {
-                            Ok(1.0 / sin_val)
-                        }
-                    },
-                    // Inverse trigonometric functions
-                    "asin"  => Ok(evaluated_args[0].asin()),
-                    "acos"  => Ok(evaluated_args[0].acos()),
-                    "atan"  => Ok(evaluated_args[0].atan()),
-                    "atan2" => {
-                        if evaluated_args.len() != 2 {
-                            return Err(MathError::UnsupportedFunction("atan2 requires two arguments: y, x".to_string()).into());
-                        }
-                        let y = evaluated_args[0];
-                        let x = evaluated_args[1];
-                        Ok(y.atan2(x))
-                    },
-                    "log"   => Ok(evaluated_args[0].ln()),
-                    "sqrt"  => Ok(evaluated_args[0].sqrt()),
-                    "abs"   => Ok(evaluated_args[0].abs()),
-                    "max"   => Ok(evaluated_args[0].max(evaluated_args[1])),
-                    "min"   => Ok(evaluated_args[0].min(evaluated_args[1])),
-                    "rand"  => {
-                        let mut rng = rand::thread_rng();
-                        if evaluated_args.is_empty() {
-                            // rand() with no args: returns a value between 0 and 1
-                            Ok(rng.gen::<f32>())
-                        } else if evaluated_args.len() == 1 {
-                            // rand(max): returns a value between 0 and max
-                            let max = evaluated_args[0];
-                            Ok(rng.gen::<f32>() * max)
-                        } else if evaluated_args.len() == 2 {
-                            // rand(min, max): returns a value between min and max
-                            let min = evaluated_args[0];
-                            let max = evaluated_args[1];
-                            if min >= max {
-                                return Err(MathError::UnsupportedFunction("min must be less than max".to_string()).into());
-                            }
-                            Ok(rng.gen_range(min..max))
-                        } else {
-                            Err(MathError::UnsupportedFunction("rand() accepts 0, 1, or 2 arguments".to_string()).into())
-                        }
-                    },
-                    // If not a built-in function, check for user-defined functions
-                    _ => {
-                        if let Some((params, body)) = context.get_function(name) {
-                            // Create a new scope for function execution
-                            let mut function_scope = context.new_scope();
-                            
-                            // Check argument count matches parameter count
-                            if evaluated_args.len() != params.len() {
-                                return Err(ControlFlowError::WrongArgumentCount {
-                                    name: name.clone(),
-                                    expected: params.len(),
-                                    got: evaluated_args.len(),
-                                }.into());
-                            }
-                            
-                            // Bind evaluated arguments to parameters
-                            for (i, &arg_value) in evaluated_args.iter().enumerate() {
-                                function_scope.set_variable(params[i].clone(), arg_value)?;
-                            }
-                            
-                            // Execute the function body
-                            match body.evaluate(&mut function_scope)? {
-                                (Some(value), ControlFlow::Return) => Ok(value),
-                                (Some(value), _) => Ok(value),  // Return the last value if no explicit return
-                                (None, _) => Ok(0.0),  // Default return value if none specified
-                            }
-                        } else {
-                            Err(ControlFlowError::FunctionOrProcedureNotFound {
-                                name: name.clone(),
-                            }.into())
-                        }
+                call_function(name, evaluated_args, context)
+            }
+        }
+    }
+
+    /// Evaluates the expression, writing any assignments in it to the context.
+    ///
+    /// An assignment is an expression whose value is the assigned value, so
+    /// `a = b = 5` sets both variables and `max(b = 3, 1)` sets `b`. Targets
+    /// must already be declared and cannot be constants. [`Expression::evaluate`]
+    /// cannot write, so there an assignment only yields its value.
+    pub fn evaluate_mut(&self, context: &mut SymbolTable<f32>) -> Result<f32, EvalError> {
+        if !self.contains_assignment() {
+            return self.evaluate(context);
+        }
+
+        match self {
+            Expression::Operation(Operator::Assign, operands) => match operands.as_slice() {
+                [Expression::Literal(var_name), value] => {
+                    check_assignment_target(var_name, context)?;
+
+                    // Right-associative, so `x = y = 5` assigns `y` first
+                    let value = value.evaluate_mut(context)?;
+                    context.set_variable(var_name.clone(), value)?;
+                    Ok(value)
+                }
+                // Only variables can be assigned to
+                _ => self.evaluate(context),
+            },
+
+            Expression::Operation(operator, operands) => {
+                let left_val = operands[0].evaluate_mut(context)?;
+                let right_val = match operands.get(1) {
+                    Some(operand) => operand.evaluate_mut(context)?,
+                    None => 0.0,
+                };
+
+                apply_operator(*operator, operands.len() == 1, left_val, right_val)
+                    .map_err(|error| name_divisor(error, *operator, operands))
+            }
+
+            Expression::FunctionCall(name, args) => {
+                if context.procedures.contains_key(name) {
+                    return Err(ControlFlowError::ProcedureUsedAsValue { name: name.clone() }.into());
+                }
+
+                // Only the converted value can assign, the unit names are not values
+                if name == "convert" {
+                    if let [value, Expression::Literal(from), Expression::Literal(to)] = args.as_slice() {
+                        return Ok(convert_units(value.evaluate_mut(context)?, from, to)?);
                     }
                 }
+
+                // Assignments in the body of fold() only write to its own scope
+                if name == "fold" {
+                    if let [Expression::Literal(variable), lo, hi, init, body] = args.as_slice() {
+                        let bounds = (lo.evaluate_mut(context)?, hi.evaluate_mut(context)?);
+                        return evaluate_fold(variable, bounds, init.evaluate_mut(context)?, body, context);
+                    }
+                }
+
+                let evaluated_args = args
+                    .iter()
+                    .map(|arg| arg.evaluate_mut(context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call_function(name, evaluated_args, context)
+            }
+
+            Expression::Literal(_) => self.evaluate(context),
+        }
+    }
+
+    /// Returns whether an assignment appears anywhere in the expression.
+    fn contains_assignment(&self) -> bool {
+        match self {
+            Expression::Literal(_) => false,
+            Expression::Operation(Operator::Assign, _) => true,
+            Expression::Operation(_, operands) | Expression::FunctionCall(_, operands) => {
+                operands.iter().any(Expression::contains_assignment)
             }
         }
     }
 }
 
-/// Defines precedence and associativity.
+/// Reports a comma where the next item of an argument or parameter list
+/// should be, as in `f(,1)` or `f(1,,2)`.
 ///
-/// Returns a tuple of (left_binding_power, right_binding_power, is_left_associative).
-/// 
-/// Higher binding power means higher precedence.
-pub fn infix_binding_power(op: char) -> Option<(f32, f32, bool)> {
-    // For left associative operators, left_bp < right_bp
-    // For right associative operators, left_bp > right_bp
-    match op {
-        '=' => Some((0.2, 0.1, false)),        // right-associative
-        '&' | '|' | 'x' | 'q' | 'a' | 'o' => Some((0.3, 0.4, true)), // logical operators
-        '<' | '>' | 'g' | 'l' | 'e' | 'n' => Some((0.5, 0.6, true)), // comparison operators
-        '+' | '-' => Some((1.0, 1.1, true)),   // left-associative
-        '*' | '/' | '%' => Some((2.0, 2.1, true)),
-        '.' => Some((5.0, 5.1, true)),         // dot has higher precedence now
-        '^' | '√' => Some((4.0, 3.9, false)),  // power remains the same
-        _ => None,
+/// `item` names what the list holds, `owner` the function it belongs to and
+/// `index` how many items were read before.
+pub(crate) fn expect_list_item(tokenizer: &mut Tokenizer, item: &str, owner: &str, index: usize) -> Result<(), ParseError> {
+    if tokenizer.peek_token() != &Token::Punctuation(',') {
+        return Ok(());
+    }
+    Err(ParseError::Expected {
+        expected: format!("{} {} of '{}'", item, index + 1, owner),
+        found: if index == 0 { "a comma" } else { "a second comma" }.to_string(),
+    })
+}
+
+/// Checks that a variable can be assigned to before its new value is evaluated.
+///
+/// Results of earlier statements, global and shared constants are refused, as are names never declared with `let`,
+/// which also covers `x += 1` reading an undeclared `x`. Local constants are
+/// refused when written, since assigning their current value is allowed.
+fn check_assignment_target(name: &str, context: &SymbolTable<f32>) -> Result<(), EvalError> {
+    if is_result_name(name) {
+        return Err(SymbolError::InvalidIdentifier(name.to_string()).into());
+    }
+    if global_constants().contains(name) {
+        return Err(SymbolError::ImmutableConstant(name.to_string()).into());
+    }
+    if !context.contains(name) {
+        if shared_constant(name).is_some() {
+            return Err(SymbolError::ImmutableConstant(name.to_string()).into());
+        }
+        return Err(SymbolError::UndeclaredVariable(name.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Names the divisor of a division or modulo by zero, e.g. `count - offset`.
+///
+/// Operators apply to values, so the failing operation fills in its right
+/// operand's source here. Other errors are returned unchanged.
+pub(crate) fn name_divisor(error: EvalError, operator: Operator, operands: &[Expression]) -> EvalError {
+    match (error, operator, operands) {
+        (EvalError::MathError(MathError::DivisionByZero(None)), Operator::Divide, [_, divisor]) => {
+            MathError::DivisionByZero(Some(divisor.to_source())).into()
+        }
+        (EvalError::MathError(MathError::ModuloByZero(None)), Operator::Modulo, [_, divisor]) => {
+            MathError::ModuloByZero(Some(divisor.to_source())).into()
+        }
+        (error, _, _) => error,
+    }
+}
+
+/// Applies an operator to evaluated real operands.
+///
+/// Unary operators only use `left`. Assignment is resolved by the caller,
+/// so `=` just yields the assigned value.
+pub(crate) fn apply_operator(operator: Operator, unary: bool, left_val: f32, right_val: f32) -> Result<f32, EvalError> {
+    match operator {
+        // Arithmetic operations
+        Operator::Add => Ok(left_val + right_val),
+        Operator::Subtract if unary => Ok(-left_val), // unary minus
+        Operator::Subtract => Ok(left_val - right_val),
+        Operator::Multiply => Ok(left_val * right_val),
+
+        // Division with zero check
+        Operator::Divide => {
+            if right_val == 0.0 {
+                Err(MathError::DivisionByZero(None).into())
+            } else {
+                Ok(left_val / right_val)
+            }
+        }
+
+        // Modulo with zero check
+        Operator::Modulo => {
+            if right_val == 0.0 {
+                Err(MathError::ModuloByZero(None).into())
+            } else {
+                Ok(left_val % right_val)
+            }
+        }
+
+        // Exponentiation, check for invalid negative base + fractional exponent
+        Operator::Power => {
+            if left_val < 0.0 && right_val.fract() != 0.0 {
+                Err(MathError::InvalidExponentiation.into())
+            } else {
+                Ok(left_val.powf(right_val))
+            }
+        }
+
+        // Root operation, expects exactly two operands
+        Operator::Root => {
+            if unary {
+                return Err(MathError::InvalidExponentiation.into());
+            }
+            nth_root(left_val, right_val)
+        }
+
+        // Logical operators
+        Operator::And => Ok(if left_val != 0.0 && right_val != 0.0 { 1.0 } else { 0.0 }), // AND
+        Operator::Or => Ok(if left_val != 0.0 || right_val != 0.0 { 1.0 } else { 0.0 }), // OR
+        Operator::Xor => Ok(if (left_val != 0.0) != (right_val != 0.0) { 1.0 } else { 0.0 }), // XOR
+        Operator::Xnor => Ok(if (left_val != 0.0) == (right_val != 0.0) { 1.0 } else { 0.0 }), // XNOR
+        Operator::Nand => Ok(if !(left_val != 0.0 && right_val != 0.0) { 1.0 } else { 0.0 }), // NAND
+        Operator::Nor => Ok(if !(left_val != 0.0 || right_val != 0.0) { 1.0 } else { 0.0 }), // NOR
+        Operator::Not => Ok(if left_val == 0.0 { 1.0 } else { 0.0 }), // NOT (unary)
+
+        // Comparison operators
+        Operator::Greater => Ok(if left_val > right_val { 1.0 } else { 0.0 }),
+        Operator::Less => Ok(if left_val < right_val { 1.0 } else { 0.0 }),
+        Operator::GreaterEqual => Ok(if left_val >= right_val { 1.0 } else { 0.0 }), // >=
+        Operator::LessEqual => Ok(if left_val <= right_val { 1.0 } else { 0.0 }), // <=
+        Operator::Equal => Ok(if (left_val - right_val).abs() < f32::EPSILON { 1.0 } else { 0.0 }), // ==
+        Operator::NotEqual => Ok(if (left_val - right_val).abs() >= f32::EPSILON { 1.0 } else { 0.0 }), // !=
+
+        // Dot-access operator, returns the right-hand side
+        Operator::Dot => Ok(right_val),
+
+        // Assignment operator
+        Operator::Assign => Ok(right_val),
+    }
+}
+
+/// Most steps a single `fold` may run.
+const MAX_FOLD_STEPS: f32 = 1_000_000.0;
+
+/// Takes the `degree`-th root of `radicand`, for both `b √ a` and `root(n, x)`.
+///
+/// Odd integer degrees of negative numbers have a real root, so `3 √ -8` is
+/// -2; every other root of a negative number is an error.
+pub(crate) fn nth_root(degree: f32, radicand: f32) -> Result<f32, EvalError> {
+    if degree == 0.0 {
+        return Err(MathError::ZerothRoot.into());
     }
+
+    let root = |x: f32| match degree {
+        2.0 => x.sqrt(),
+        3.0 => x.cbrt(),
+        _ => x.powf(1.0 / degree),
+    };
+
+    if radicand >= 0.0 || (1.0 / degree).fract() == 0.0 {
+        Ok(root(radicand))
+    } else if degree.fract() == 0.0 && degree % 2.0 != 0.0 {
+        Ok(-root(-radicand))
+    } else {
+        Err(MathError::NegativeRoot.into())
+    }
+}
+
+/// Returns the `typeof` code of a name, found where reading the name would look.
+///
+/// See [`BindingKind::code`] for the codes. Names that are not bound give 0.
+fn type_of_name(name: &str, context: &SymbolTable<f32>) -> f32 {
+    if let Some(binding) = context.describe(name) {
+        return binding.kind().code();
+    }
+
+    if is_result_name(name) && with_result_history(|history| history.get(name)).is_some() {
+        BindingKind::Variable.code()
+    } else if shared_constant(name).is_some() || global_constants().contains(name) {
+        BindingKind::Constant.code()
+    } else if find_builtin(name).is_some() {
+        BindingKind::Function.code()
+    } else {
+        0.0
+    }
+}
+
+/// Runs the body of `fold(variable, lo, hi, init, body)`.
+///
+/// The body is evaluated for `variable` = lo, lo + 1, … while it is at most
+/// hi, with `acc` holding the previous result, or `init` on the first step.
+/// An empty range gives `init`. Each step counts as a loop iteration of the
+/// execution, so nested folds share the limit instead of multiplying it.
+fn evaluate_fold(
+    variable: &str,
+    (lo, hi): (f32, f32),
+    init: f32,
+    body: &Expression,
+    context: &SymbolTable<f32>,
+) -> Result<f32, EvalError> {
+    if !variable.starts_with(|c: char| c.is_alphabetic() || c == '_') {
+        return Err(SymbolError::InvalidIdentifier(variable.to_string()).into());
+    }
+
+    let steps = if hi >= lo { (hi - lo).floor() + 1.0 } else { 0.0 };
+    if steps.is_nan() || steps > MAX_FOLD_STEPS {
+        return Err(MathError::DomainError(format!("fold() can run at most {} steps", MAX_FOLD_STEPS)).into());
+    }
+
+    let mut scope = context.new_scope();
+    let mut acc = init;
+    for step in 0..steps as u32 {
+        count_iteration()?;
+        scope.set_variable(variable.to_string(), lo + step as f32)?;
+        scope.set_variable("acc".to_string(), acc)?;
+        acc = body.evaluate_mut(&mut scope)?;
+    }
+    Ok(acc)
 }
 
-/// Determines how tightly unary ops bind.
+/// Calls a built-in or user-defined function with already evaluated arguments.
 ///
-/// Returns the binding power for prefix operators.
+/// Built-in functions take precedence over user functions of the same name.
+pub(crate) fn call_function(name: &str, args: Vec<f32>, context: &SymbolTable<f32>) -> Result<f32, EvalError> {
+    if let Some(result) = call_builtin(name, &args) {
+        return result;
+    }
+
+    // Otherwise run the user-defined function in a fresh scope
+    enter_call()?;
+    let result = call_user_function(name.to_string(), args, context.call_scope());
+    leave_call();
+    result
+}
+
+/// Calls a user-defined function with already evaluated arguments.
+///
+/// A call in tail position of a function body (its last value or a `return`)
+/// does not recurse on the Rust stack: the body hands back the callee and its
+/// arguments, and the call runs in this loop instead. Results are the same as
+/// plain recursion, so deep tail-recursive accumulators do not overflow.
+///
+/// Each tail call counts as a loop iteration of the execution, so a chain
+/// that never ends, like `fn f(n) { f(n) }`, stops at the loop iteration limit.
+/// Tail calls do not count towards the call depth limit, since they do not
+/// nest.
+fn call_user_function(mut name: String, mut args: Vec<f32>, mut scope: SymbolTable<f32>) -> Result<f32, EvalError> {
+    // Every call in a tail-call chain produces the same final value, but only
+    // the call that was made is cached so a long chain does not fill the caches
+    let mut memo_key = None;
+    let mut memo_call = None;
+    let memo_cache = scope.memo_cache.share();
+    let mut tail_called = false;
+
+    let value = loop {
+        // Functions declared with `memo fn` keep their results for the session
+        let memoized = memo_cache.is_memoized(&name);
+        if memoized {
+            if let Some(value) = memo_cache.get(&name, &args) {
+                record_cache_lookup(true);
+                break value;
+            }
+            if !tail_called {
+                memo_call = Some((name.clone(), args.clone()));
+            }
+        }
+
+        // Pure functions give the same result for the same arguments,
+        // so their results are cached for the rest of the execution
+        let key = (name.clone(), args.iter().map(|arg| arg.to_bits()).collect::<Vec<_>>());
+        let is_pure = scope.functions.contains_key(&name) && with_memo_state(|memo| {
+            *memo.purity
+                .entry(name.clone())
+                .or_insert_with(|| is_pure_function(&name, &scope.functions))
+        });
+        if is_pure {
+            if let Some(value) = with_memo_state(|memo| memo.results.get(&key).copied()) {
+                record_cache_lookup(true);
+                break value;
+            }
+            if !tail_called {
+                memo_key = Some(key);
+            }
+        }
+        if memoized || is_pure {
+            record_cache_lookup(false);
+        }
+
+        let Some((params, body)) = scope.get_function(&name) else {
+            // Calling a variable is a different mistake from calling nothing
+            if scope.get(&name).is_some() || global_constants().get(&name).is_some() {
+                return Err(SymbolError::NotCallable(name).into());
+            }
+            return Err(ControlFlowError::FunctionOrProcedureNotFound { name }.into());
+        };
+
+        // Check argument count matches parameter count
+        if args.len() != params.len() {
+            return Err(ControlFlowError::WrongArgumentCount {
+                name,
+                expected: params.len(),
+                got: args.len(),
+            }.into());
+        }
+
+        // Bind evaluated arguments to parameters
+        for (param, &arg_value) in params.iter().zip(args.iter()) {
+            scope.set_variable(param.clone(), arg_value)?;
+        }
+
+        // Execute the function body, following tail calls without recursing
+        match body.evaluate_tail(&mut scope)? {
+            TailOutcome::Done(value, control_flow) => {
+                // A `return` anywhere in the body carries the function's value
+                let value = match control_flow {
+                    ControlFlow::Return(returned) => returned,
+                    _ => value,
+                };
+                break value.unwrap_or(0.0);  // Default return value if none specified
+            }
+            TailOutcome::Call { name: callee, args: callee_args, scope: callee_scope } => {
+                count_iteration()?;
+                tail_called = true;
+                name = callee;
+                args = callee_args;
+                scope = *callee_scope;
+            }
+        }
+    };
+
+    if let Some(key) = memo_key {
+        with_memo_state(|memo| memo.results.insert(key, value));
+    }
+    if let Some((name, args)) = memo_call {
+        memo_cache.insert(&name, &args, value);
+    }
+    Ok(value)
+}
+
+/// Evaluates a formula (`let name := expr`) in the context it is read from.
 ///
-/// Higher values mean the operator binds tighter to its operand.
-pub fn prefix_binding_power(op: char) -> Option<f32> {
-    match op {
-        '-' | '+' => Some(20.0),
-        '!' => Some(20.0),  // logical NOT
-        // root is a unary prefix
-        '√' => Some(20.0),
+/// A formula that is read again while it is being evaluated, e.g. through a
+/// function body, is reported as a cycle instead of recursing forever.
+fn evaluate_formula(name: &str, formula: &Expression, context: &SymbolTable<f32>) -> Result<f32, EvalError> {
+    if !with_formula_state(|state| state.evaluating.insert(name.to_string())) {
+        return Err(SymbolError::CyclicFormula(name.to_string()).into());
+    }
+
+    let result = formula.evaluate(context);
+    with_formula_state(|state| state.evaluating.remove(name));
+    result
+}
+
+/// Returns the operator a token stands for, if it is one.
+fn token_operator(token: &Token) -> Option<Operator> {
+    match token {
+        Token::Operator(operator) => Some(*operator),
         _ => None,
     }
-} 
+}
@@ -1,10 +1,42 @@
 use std::collections::HashSet;
 
 use crate::core::lexical_analyzer::{Token, Tokenizer};
-use crate::core::ast_expression::Expression;
+use crate::core::ast_expression::{expect_list_item, Expression};
+use crate::core::operator::Operator;
 use crate::core::symbol_manager::SymbolTable;
-use crate::core::error_types::{ParseError, EvalError, SymbolError, ControlFlowError};
-use crate::core::execution_state::with_exit_state;
+use crate::core::error_types::{ParseError, EvalError, ControlFlowError, SymbolError};
+use crate::core::execution_state::{stack_position, with_exit_state, with_memo_state, with_trace_state, with_work_state, MemoState};
+use crate::core::snippet_registry::import_snippet;
+use crate::core::shared_constants::publish_shared_constant;
+use crate::core::analysis::is_pure_definition;
+use crate::core::builtins::find_builtin;
+use crate::core::metrics::{current_limit, Limit};
+
+/// Most loop iterations and tail calls one execution may run.
+///
+/// A loop whose condition never becomes false, like `while 1 { }`, would
+/// otherwise run forever and hold up everything waiting on the session.
+pub(crate) const MAX_LOOP_ITERATIONS: usize = 100_000;
+
+/// Most user function and procedure calls that may be running at once.
+///
+/// Every call that is not a tail call recurses on the stack, so a recursion
+/// without a base case, or with a very deep one, would overflow it.
+pub(crate) const MAX_CALL_DEPTH: usize = 200;
+
+/// Most stack, in bytes, that nested calls may use during one execution.
+///
+/// How much stack a call takes depends on its body and on the build, so
+/// calls also stop once they have used this much, even below
+/// [`MAX_CALL_DEPTH`]. This leaves a quarter of a 2 MiB thread stack, the
+/// smallest the bot runs on, for the caller and the innermost call.
+const MAX_CALL_STACK: usize = 1536 * 1024;
+
+/// Longest one execution may run, in milliseconds.
+///
+/// The other limits bound how much a script may do, but not how long each
+/// step takes, e.g. a recursion without a cache that calls itself twice.
+pub(crate) const MAX_EXECUTION_MILLIS: usize = 10_000;
 
 /// Statement types in the language.
 #[derive(Clone, Debug)]
@@ -28,6 +60,14 @@ pub enum Statement {
         body: Box<Statement>,
     },
 
+    /// A loop over a numeric range, excluding the end, with a step of 1.
+    Foreach {
+        variable: String,
+        start: Expression,
+        end: Expression,
+        body: Box<Statement>,
+    },
+
     /// A break statement.
     Break,
 
@@ -40,16 +80,46 @@ pub enum Statement {
     /// An end statement with optional value.
     End(Option<Expression>),
 
+    /// An assertion that aborts execution when its condition is zero.
+    Assert(Expression),
+
+    /// A statement run when the enclosing block exits, in reverse order of declaration.
+    Defer(Box<Statement>),
+
+    /// A body whose runtime errors run a handler instead of stopping the script.
+    ///
+    /// A body that fails leaves no changes behind, so the handler starts from
+    /// the variables as they were before `try`. Control flow like `break`
+    /// and `end` is not an error and passes through untouched.
+    Try {
+        body: Box<Statement>,
+        handler: Box<Statement>,
+    },
+
     /// A variable declaration with optional initializer.
     Let {
         name: String,
         initializer: Option<Expression>,
     },
     
+    /// A formula declaration (`let name := expr`), re-evaluated on every read.
+    Formula {
+        name: String,
+        expression: Expression,
+    },
+
     /// A constant declaration with required initializer.
+    ///
+    /// A `global` constant (`const global NAME = value`) is published for
+    /// every session instead of being declared in the current scope.
+    ///
+    /// Like `let`, a constant belongs to the block it is declared in. Each
+    /// iteration of a loop runs its body in a new scope, so a `const` there can
+    /// take a different value every time round.
     Const {
         name: String,
         initializer: Expression,
+        global: bool,
     },
 
     /// A function declaration with parameters and body.
@@ -57,6 +127,8 @@ pub enum Statement {
         name: String,
         params: Vec<String>,
         body: Box<Statement>,
+        /// Declared with `memo fn`, so results are cached per argument tuple.
+        memo: bool,
     },
 
     /// A procedure declaration with parameters and body.
@@ -66,11 +138,18 @@ pub enum Statement {
         body: Box<Statement>,
     },
 
-    /// A call to a procedure.
+    /// A call written as a statement, like `name(args)`.
+    ///
+    /// Whether it calls a procedure or a function is decided when it runs.
+    /// A procedure's return value is dropped, while a function's value
+    /// becomes the statement's value, so a bare call can end a script.
     ProcedureCall {
         name: String,
         args: Vec<Expression>,
     },
+
+    /// An import of a published snippet's definitions by name.
+    Import(String),
 }
 
 // Add this enum to track control flow state between nested structures
@@ -79,13 +158,30 @@ pub enum ControlFlow {
     Normal,
     Break,
     Continue,
-    Return,
+    /// A `return` or `end` with its value, passed up untouched through
+    /// blocks and loops until a function or procedure call consumes it.
+    Return(Option<f32>),
+}
+
+/// Result of evaluating a function body with tail calls deferred.
+pub(crate) enum TailOutcome {
+    /// The body finished with a value and control flow, as from `evaluate`.
+    Done(Option<f32>, ControlFlow),
+
+    /// The body ends by calling a user-defined function, which the caller runs
+    /// in the given scope instead of recursing.
+    Call {
+        name: String,
+        args: Vec<f32>,
+        scope: Box<SymbolTable<f32>>,
+    },
 }
 
 /// Collects all variable names defined in a statement (let/const declarations)
 fn collect_declared_vars(stmt: &Statement, vars: &mut HashSet<String>) {
     match stmt {
         Statement::Let { name, .. } => { vars.insert(name.clone()); },
+        Statement::Formula { name, .. } => { vars.insert(name.clone()); },
         Statement::Const { name, .. } => { vars.insert(name.clone()); },
         Statement::Block(statements) => {
             for s in statements {
@@ -101,13 +197,180 @@ fn collect_declared_vars(stmt: &Statement, vars: &mut HashSet<String>) {
         Statement::While { body, .. } => {
             collect_declared_vars(body, vars);
         },
+        Statement::Try { body, handler } => {
+            collect_declared_vars(body, vars);
+            collect_declared_vars(handler, vars);
+        },
+        Statement::Foreach { variable, body, .. } => {
+            vars.insert(variable.clone());
+            collect_declared_vars(body, vars);
+        },
         _ => {}
     }
 }
 
+/// Copies variables changed by one loop iteration back to the parent context.
+///
+/// Variables declared by the loop (including its loop variable) stay local to the iteration.
+fn copy_back_loop_changes(
+    loop_statement: &Statement,
+    loop_context: &SymbolTable<f32>,
+    context: &mut SymbolTable<f32>,
+) -> Result<(), EvalError> {
+    // Track variables defined in this loop to avoid shadowing issues
+    let mut defined_vars = HashSet::new();
+    collect_declared_vars(loop_statement, &mut defined_vars);
+
+    copy_back_changes(&defined_vars, loop_context, context)
+}
+
+/// What a loop does after one iteration of its body.
+enum LoopStep {
+    /// Go on with the next iteration (the body finished or hit `continue`).
+    Next,
+
+    /// Leave the loop (the body hit `break`).
+    Exit,
+
+    /// Leave the loop and propagate a return/end and its value to the caller.
+    Return(Option<f32>),
+}
+
+/// Counts one more iteration of a loop, fold step or tail call, failing once it would
+/// go past the loop iteration limit, [`MAX_LOOP_ITERATIONS`] unless overridden.
+///
+/// Iterations are counted for the whole execution rather than per loop, so
+/// nested loops and folds share the limit instead of multiplying it.
+pub(crate) fn count_iteration() -> Result<(), EvalError> {
+    check_time()?;
+    let limit = current_limit(Limit::LoopIterations);
+    with_work_state(|work| {
+        if work.iterations >= limit {
+            return Err(ControlFlowError::TooManyIterations { limit }.into());
+        }
+        work.iterations += 1;
+        Ok(())
+    })
+}
+
+/// Starts a user function or procedure call, failing once it would go past
+/// the call depth limit, [`MAX_CALL_DEPTH`] unless overridden, or use more
+/// than [`MAX_CALL_STACK`] of the stack. Both report the call depth limit,
+/// since that is the one a server can change.
+///
+/// Every successful call must be ended with [`leave_call`], also when it fails.
+pub(crate) fn enter_call() -> Result<(), EvalError> {
+    check_time()?;
+    let limit = current_limit(Limit::CallDepth);
+    let position = stack_position();
+    with_work_state(|work| {
+        let stack_used = work.stack_base.map_or(0, |base| base.abs_diff(position));
+        if work.call_depth >= limit || stack_used > MAX_CALL_STACK {
+            return Err(ControlFlowError::CallTooDeep { limit }.into());
+        }
+        work.call_depth += 1;
+        Ok(())
+    })
+}
+
+/// Ends a call started with [`enter_call`].
+pub(crate) fn leave_call() {
+    with_work_state(|work| work.call_depth = work.call_depth.saturating_sub(1));
+}
+
+/// Fails once the execution has run for longer than the time limit,
+/// [`MAX_EXECUTION_MILLIS`] unless overridden.
+///
+/// Only executions started with [`WorkState::start`](crate::core::execution_state::WorkState::start) are timed.
+fn check_time() -> Result<(), EvalError> {
+    let Some(started) = with_work_state(|work| work.started) else {
+        return Ok(());
+    };
+    let limit = current_limit(Limit::TimeLimit);
+    if started.elapsed().as_millis() > limit as u128 {
+        return Err(ControlFlowError::TimedOut { limit_ms: limit }.into());
+    }
+    Ok(())
+}
+
+/// Runs one iteration of a loop body and interprets its control flow.
+///
+/// Every loop kind goes through this so that `break` and `continue` behave the
+/// same no matter how deeply they are nested in blocks or branches. The
+/// iteration scope is prepared by the caller (e.g. with a loop variable bound).
+fn run_loop_iteration(
+    loop_statement: &Statement,
+    body: &Statement,
+    mut loop_context: SymbolTable<f32>,
+    context: &mut SymbolTable<f32>,
+    last_value: &mut Option<f32>,
+) -> Result<LoopStep, EvalError> {
+    // Evaluate the body with control flow information
+    let (value, control_flow) = body.evaluate(&mut loop_context)?;
+
+    // Update the last value if one was returned
+    if let Some(v) = value {
+        *last_value = Some(v);
+    }
+
+    // Copy variables from the loop iteration back to the parent context
+    copy_back_loop_changes(loop_statement, &loop_context, context)?;
+
+    Ok(match control_flow {
+        ControlFlow::Normal | ControlFlow::Continue => LoopStep::Next,
+        ControlFlow::Break => LoopStep::Exit,
+        ControlFlow::Return(value) => LoopStep::Return(value),
+    })
+}
+
+/// Copies variables changed in an inner scope back to the parent context.
+///
+/// Variables in `defined_vars` were declared by the inner scope and stay local to it.
+/// Constants are never copied, so a `const` in a loop body or branch is declared
+/// afresh each time the body runs and is not visible once it ends.
+fn copy_back_changes(
+    defined_vars: &HashSet<String>,
+    scope: &SymbolTable<f32>,
+    context: &mut SymbolTable<f32>,
+) -> Result<(), EvalError> {
+    for (key, value) in scope.values.iter() {
+        // Skip variables defined in the inner scope (including shadowed ones)
+        if defined_vars.contains(key) {
+            continue;
+        }
+        
+        // Skip variables that haven't changed
+        if context.get(key) == Some(value) {
+            continue;
+        }
+        
+        // Constants never leave their scope, whether declared in it or in the parent
+        if scope.is_constant(key) || context.is_constant(key) {
+            continue;
+        }
+        
+        // Only update variables that already exist in the outer scope
+        if context.contains(key) {
+            context.set_variable(key.clone(), *value)?;
+        }
+    }
+
+    Ok(())
+}
+
 impl Statement {
     /// Parse a single statement from the token stream.
+    ///
+    /// Nested blocks and branches count towards the tokenizer's nesting limit.
     pub fn parse(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        tokenizer.enter_nesting()?;
+        let result = Self::parse_level(tokenizer);
+        tokenizer.leave_nesting();
+        result
+    }
+
+    /// Parses one statement at the current nesting level; see [`Statement::parse`].
+    fn parse_level(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
         if tokenizer.peek_token() == &Token::EndOfInput {
             return Err(ParseError::EmptyInput);
         }
@@ -116,61 +379,89 @@ impl Statement {
         let statement = match tokenizer.peek_token().clone() {
             Token::Keyword(keyword) => {
                 tokenizer.next_token(); // consume keyword
-                match keyword.as_str() {
-                    "if" => Self::parse_if_statement(tokenizer)?,
-                    "while" => Self::parse_while_statement(tokenizer)?,
-                    "break" => Statement::Break,
-                    "continue" => Statement::Continue,
-                    "return" => Self::parse_return_statement(tokenizer)?,
-                    "let" => Self::parse_let_statement(tokenizer)?,
-                    "const" => Self::parse_const_statement(tokenizer)?,
-                    "end" => Self::parse_end_statement(tokenizer)?,
-                    "fn" => Self::parse_function_statement(tokenizer)?,
-                    "proc" => Self::parse_procedure_statement(tokenizer)?,
-                    _ => return Err(ParseError::UnexpectedToken(keyword)),
-                }
+                Self::parse_keyword_statement(tokenizer, keyword)
             }
-            Token::Operator('{') => Self::parse_block_statement(tokenizer)?,
-            Token::Literal(lit) => {
-                // Check for procedure or function call
+            Token::Punctuation('{') => Self::parse_block_statement(tokenizer),
+            Token::Literal(lit) if tokenizer.peek_token_at(1) == &Token::Punctuation('(') => {
+                // Parse a call (could be procedure or function, determined at evaluation time)
                 tokenizer.next_token(); // consume the literal
-                
-                if tokenizer.peek_token() == &Token::Operator('(') {
-                    // Parse a call (could be procedure or function, determined at evaluation time)
-                    Self::call_procedure(tokenizer, lit)?
-                } else {
-                    // Not a call, just an expression starting with a literal
-                    let expr = Expression::Literal(lit);
-                    Statement::Expression(expr)
+                match Self::call_procedure(tokenizer, lit)? {
+                    // An operator after the call makes it part of an expression, e.g. `f(2) + 3`
+                    Statement::ProcedureCall { name, args } if Expression::continues_after_operand(tokenizer.peek_token()) => {
+                        Expression::parse_continuation(tokenizer, Expression::FunctionCall(name, args)).map(Statement::Expression)
+                    }
+                    call => Ok(call),
                 }
             },
-            _ => {
-                // Try parsing as an expression first
-                let expression = Expression::parse(tokenizer, 0.0)?;
-                Statement::Expression(expression)
-            }
-        };
+            // Try parsing as an expression first
+            _ => Expression::parse(tokenizer, 0.0).map(Statement::Expression),
+        }?;
 
         // Skip any trailing semicolon
-        if tokenizer.peek_token() == &Token::Operator(';') {
+        if tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
         Ok(statement)
     }
     
+    /// Parse the statement introduced by a keyword, after the keyword.
+    ///
+    /// Each arm returns its result directly, keeping this frame small since
+    /// nested statements recurse through it.
+    fn parse_keyword_statement(tokenizer: &mut Tokenizer, keyword: String) -> Result<Statement, ParseError> {
+        match keyword.as_str() {
+            "if" => Self::parse_if_statement(tokenizer),
+            "while" => Self::parse_while_statement(tokenizer),
+            "foreach" => Self::parse_foreach_statement(tokenizer),
+            "break" => Ok(Statement::Break),
+            "continue" => Ok(Statement::Continue),
+            "return" => Self::parse_return_statement(tokenizer),
+            "let" => Self::parse_let_statement(tokenizer),
+            "const" => Self::parse_const_statement(tokenizer),
+            "end" => Self::parse_end_statement(tokenizer),
+            "assert" => Expression::parse(tokenizer, 0.0).map(Statement::Assert),
+            "defer" => Self::parse_defer_statement(tokenizer),
+            "try" => Self::parse_try_statement(tokenizer),
+            "fn" => Self::parse_function_statement(tokenizer, false),
+            "memo" => match tokenizer.next_token() {
+                Token::Keyword(keyword) if keyword == "fn" => Self::parse_function_statement(tokenizer, true),
+                unexpected => Err(ParseError::Expected {
+                    expected: "'fn' after 'memo'".to_string(),
+                    found: unexpected.to_string(),
+                }),
+            },
+            "proc" => Self::parse_procedure_statement(tokenizer),
+            "import" => Self::parse_import_statement(tokenizer),
+            _ => Err(ParseError::UnexpectedToken(keyword)),
+        }
+    }
+    
     /// Parse a block statement.
+    ///
+    /// The block is a level of nesting of its own, on top of the statement it belongs to.
     fn parse_block_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
         tokenizer.next_token(); // consume '{'
+        tokenizer.enter_nesting()?;
+        let statements = Self::parse_block_contents(tokenizer);
+        tokenizer.leave_nesting();
+        let statements = statements?;
+
+        tokenizer.next_token(); // consume '}'
+        Ok(Statement::Block(statements))
+    }
+
+    /// Parse the statements of a block, up to its closing brace.
+    fn parse_block_contents(tokenizer: &mut Tokenizer) -> Result<Vec<Statement>, ParseError> {
         let mut statements = Vec::new();
 
-        while tokenizer.peek_token() != &Token::Operator('}') {
+        while tokenizer.peek_token() != &Token::Punctuation('}') {
             if tokenizer.peek_token() == &Token::EndOfInput {
                 return Err(ParseError::ExpectedBlock);
             }
 
             // Skip empty statements (lone semicolons)
-            if tokenizer.peek_token() == &Token::Operator(';') {
+            if tokenizer.peek_token() == &Token::Punctuation(';') {
                 tokenizer.next_token();
                 continue;
             }
@@ -180,8 +471,15 @@ impl Statement {
             statements.push(statement);
         }
 
-        tokenizer.next_token(); // consume '}'
-        Ok(Statement::Block(statements))
+        Ok(statements)
+    }
+    
+    /// Parse the body of a branch or loop: a block, or else a single statement.
+    fn parse_body(tokenizer: &mut Tokenizer) -> Result<Box<Statement>, ParseError> {
+        match tokenizer.peek_token() {
+            Token::Punctuation('{') => Self::parse_block_statement(tokenizer),
+            _ => Self::parse(tokenizer),
+        }.map(Box::new)
     }
     
     /// Parse an if statement.
@@ -189,21 +487,15 @@ impl Statement {
         let condition = Expression::parse(tokenizer, 0.0)?;
         
         // Skip any semicolons after the condition
-        while tokenizer.peek_token() == &Token::Operator(';') {
+        while tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
         // Handle the then branch
-        let then_branch = match tokenizer.peek_token() {
-            Token::Operator('{') => Box::new(Self::parse_block_statement(tokenizer)?),
-            _ => {
-                // If no block, parse a single statement
-                Box::new(Self::parse(tokenizer)?)
-            }
-        };
+        let then_branch = Self::parse_body(tokenizer)?;
 
         // Skip any semicolons after the then branch
-        while tokenizer.peek_token() == &Token::Operator(';') {
+        while tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
@@ -211,14 +503,8 @@ impl Statement {
         let else_branch = if let Token::Keyword(keyword) = tokenizer.peek_token() {
             if keyword == "else" {
                 tokenizer.next_token(); // consume 'else'
-                match tokenizer.peek_token() {
-                    Token::Operator('{') => Some(Box::new(Self::parse_block_statement(tokenizer)?)),
-                    Token::Keyword(ref kw) if kw == "if" => {
-                        tokenizer.next_token(); // consume 'if'
-                        Some(Box::new(Self::parse_if_statement(tokenizer)?))
-                    }
-                    _ => Some(Box::new(Self::parse(tokenizer)?))
-                }
+                // An `else if` chain nests through `parse`, so it counts towards the nesting limit
+                Some(Self::parse_body(tokenizer)?)
             } else {
                 None
             }
@@ -238,18 +524,12 @@ impl Statement {
         let condition = Expression::parse(tokenizer, 0.0)?;
         
         // Skip any semicolons after the condition
-        while tokenizer.peek_token() == &Token::Operator(';') {
+        while tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
         // Handle the body
-        let body = match tokenizer.peek_token() {
-            Token::Operator('{') => Box::new(Self::parse_block_statement(tokenizer)?),
-            _ => {
-                // If no block, parse a single statement
-                Box::new(Self::parse(tokenizer)?)
-            }
-        };
+        let body = Self::parse_body(tokenizer)?;
 
         Ok(Statement::While {
             condition,
@@ -257,9 +537,56 @@ impl Statement {
         })
     }
     
+    /// Parse a foreach statement: `foreach <var> in <lo> .. <hi> <body>`.
+    fn parse_foreach_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        let variable = match tokenizer.next_token() {
+            Token::Literal(name) => name,
+            unexpected => return Err(ParseError::Expected {
+                expected: "loop variable name".to_string(),
+                found: unexpected.to_string(),
+            }),
+        };
+
+        match tokenizer.next_token() {
+            Token::Keyword(keyword) if keyword == "in" => {},
+            unexpected => return Err(ParseError::Expected {
+                expected: "keyword 'in'".to_string(),
+                found: unexpected.to_string(),
+            }),
+        }
+
+        let start = Expression::parse(tokenizer, 0.0)?;
+
+        match tokenizer.next_token() {
+            Token::Range => {},
+            unexpected => return Err(ParseError::Expected {
+                expected: "range operator '..'".to_string(),
+                found: unexpected.to_string(),
+            }),
+        }
+
+        let end = Expression::parse(tokenizer, 0.0)?;
+
+        // Skip any semicolons after the range
+        while tokenizer.peek_token() == &Token::Punctuation(';') {
+            tokenizer.next_token();
+        }
+
+        // Handle the body
+        let body = Self::parse_body(tokenizer)?;
+
+        Ok(Statement::Foreach {
+            variable,
+            start,
+            end,
+            body,
+        })
+    }
+    
     /// Parse a return statement.
     fn parse_return_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
-        let expression = if tokenizer.peek_token() == &Token::EndOfInput || tokenizer.peek_token() == &Token::Operator(';') {
+        // A bare `return` may also be the last statement of a block
+        let expression = if matches!(tokenizer.peek_token(), Token::EndOfInput | Token::Punctuation(';' | '}')) {
             None
         } else {
             Some(Expression::parse(tokenizer, 0.0)?)
@@ -269,7 +596,8 @@ impl Statement {
     
     /// Parse an end statement.
     fn parse_end_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
-        let expression = if tokenizer.peek_token() == &Token::EndOfInput || tokenizer.peek_token() == &Token::Operator(';') {
+        // A bare `end` may also be the last statement of a block
+        let expression = if matches!(tokenizer.peek_token(), Token::EndOfInput | Token::Punctuation(';' | '}')) {
             None
         } else {
             Some(Expression::parse(tokenizer, 0.0)?)
@@ -277,14 +605,53 @@ impl Statement {
         Ok(Statement::End(expression))
     }
     
+    /// Parse a defer statement.
+    fn parse_defer_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        Ok(Statement::Defer(Self::parse_body(tokenizer)?))
+    }
+    
+    /// Parse a try statement: `try <body> catch <handler>`.
+    fn parse_try_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        let body = Self::parse_body(tokenizer)?;
+
+        // Skip any semicolons after the body
+        while tokenizer.peek_token() == &Token::Punctuation(';') {
+            tokenizer.next_token();
+        }
+
+        match tokenizer.next_token() {
+            Token::Keyword(keyword) if keyword == "catch" => {},
+            unexpected => return Err(ParseError::Expected {
+                expected: "'catch' after the 'try' body".to_string(),
+                found: unexpected.to_string(),
+            }),
+        }
+
+        let handler = Self::parse_body(tokenizer)?;
+
+        Ok(Statement::Try { body, handler })
+    }
+    
     /// Parse a let statement.
     fn parse_let_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
         let name = match tokenizer.next_token() {
             Token::Literal(name) => name,
-            token => return Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            token => return Err(ParseError::UnexpectedToken(format!("{}", token))),
         };
 
-        let initializer = if let Token::Operator('=') = tokenizer.peek_token() {
+        // Formula binding: let name := expr
+        if let Token::FormulaAssign = tokenizer.peek_token() {
+            tokenizer.next_token(); // consume ':='
+            let expression = Expression::parse(tokenizer, 0.0)?;
+
+            if tokenizer.peek_token() == &Token::Punctuation(';') {
+                tokenizer.next_token();
+            }
+
+            return Ok(Statement::Formula { name, expression });
+        }
+
+        let initializer = if let Token::Operator(Operator::Assign) = tokenizer.peek_token() {
             tokenizer.next_token(); // consume '='
             Some(Expression::parse(tokenizer, 0.0)?)
         } else {
@@ -292,7 +659,7 @@ impl Statement {
         };
 
         // Skip any trailing semicolon
-        if tokenizer.peek_token() == &Token::Operator(';') {
+        if tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
@@ -301,13 +668,22 @@ impl Statement {
 
     /// Parse a const statement.
     fn parse_const_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        // `global` only qualifies the constant when a name follows, so `const global = 1` still works
+        let global = matches!(
+            (tokenizer.peek_token(), tokenizer.peek_token_at(1)),
+            (Token::Literal(qualifier), Token::Literal(_)) if qualifier == "global"
+        );
+        if global {
+            tokenizer.next_token();
+        }
+
         let name = match tokenizer.next_token() {
             Token::Literal(name) => name,
-            token => return Err(ParseError::UnexpectedToken(format!("{:?}", token))),
+            token => return Err(ParseError::UnexpectedToken(format!("{}", token))),
         };
 
         // For const declaration, '=' is required followed by initializer
-        if tokenizer.peek_token() != &Token::Operator('=') {
+        if tokenizer.peek_token() != &Token::Operator(Operator::Assign) {
             return Err(ParseError::ExpectedOperator("=".to_string()));
         }
         
@@ -315,30 +691,30 @@ impl Statement {
         let initializer = Expression::parse(tokenizer, 0.0)?;
 
         // Skip any trailing semicolon
-        if tokenizer.peek_token() == &Token::Operator(';') {
+        if tokenizer.peek_token() == &Token::Punctuation(';') {
             tokenizer.next_token();
         }
 
-        Ok(Statement::Const { name, initializer })
+        Ok(Statement::Const { name, initializer, global })
     }
 
-    /// Parse a function declaration statement.
-    fn parse_function_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+    /// Parse a function declaration statement, after `fn` or `memo fn`.
+    fn parse_function_statement(tokenizer: &mut Tokenizer, memo: bool) -> Result<Statement, ParseError> {
         // Parse function name
         let name = match tokenizer.next_token() {
             Token::Literal(lit) => lit,
             unexpected => return Err(ParseError::Expected {
                 expected: "function name".to_string(),
-                found: format!("{:?}", unexpected),
+                found: unexpected.to_string(),
             }),
         };
         
         // Parse opening parenthesis for parameter list
         match tokenizer.next_token() {
-            Token::Operator('(') => {},
+            Token::Punctuation('(') => {},
             unexpected => return Err(ParseError::Expected {
                 expected: "opening parenthesis '('".to_string(),
-                found: format!("{:?}", unexpected),
+                found: unexpected.to_string(),
             }),
         }
         
@@ -346,26 +722,31 @@ impl Statement {
         let mut params = Vec::new();
         
         // Empty parameter list case
-        if tokenizer.peek_token() == &Token::Operator(')') {
+        if tokenizer.peek_token() == &Token::Punctuation(')') {
             tokenizer.next_token(); // consume closing paren
         } else {
-            // Non-empty parameter list
+            // Non-empty parameter list, which may end with a single trailing comma
             loop {
+                expect_list_item(tokenizer, "parameter", &name, params.len())?;
                 match tokenizer.next_token() {
                     Token::Literal(param) => params.push(param),
                     unexpected => return Err(ParseError::Expected {
                         expected: "parameter name".to_string(),
-                        found: format!("{:?}", unexpected),
+                        found: unexpected.to_string(),
                     }),
                 }
                 
                 // Check for parameter delimiter or end of list
                 match tokenizer.next_token() {
-                    Token::Operator(',') => continue, // More parameters
-                    Token::Operator(')') => break,    // End of parameter list
+                    Token::Punctuation(',') if tokenizer.peek_token() == &Token::Punctuation(')') => {
+                        tokenizer.next_token(); // consume closing paren
+                        break;
+                    }
+                    Token::Punctuation(',') => continue, // More parameters
+                    Token::Punctuation(')') => break,    // End of parameter list
                     unexpected => return Err(ParseError::Expected {
                         expected: "comma ',' or closing parenthesis ')'".to_string(),
-                        found: format!("{:?}", unexpected),
+                        found: unexpected.to_string(),
                     }),
                 }
             }
@@ -373,14 +754,14 @@ impl Statement {
         
         // Parse function body (must be a block)
         let body = match tokenizer.peek_token() {
-            Token::Operator('{') => Box::new(Self::parse_block_statement(tokenizer)?),
+            Token::Punctuation('{') => Box::new(Self::parse_block_statement(tokenizer)?),
             unexpected => return Err(ParseError::Expected {
                 expected: "function body block".to_string(),
-                found: format!("{:?}", *unexpected),
+                found: unexpected.to_string(),
             }),
         };
         
-        Ok(Statement::Function { name, params, body })
+        Ok(Statement::Function { name, params, body, memo })
     }
     
     /// Parse a procedure declaration statement.
@@ -390,16 +771,16 @@ impl Statement {
             Token::Literal(lit) => lit,
             unexpected => return Err(ParseError::Expected {
                 expected: "procedure name".to_string(),
-                found: format!("{:?}", unexpected),
+                found: unexpected.to_string(),
             }),
         };
         
         // Parse opening parenthesis for parameter list
         match tokenizer.next_token() {
-            Token::Operator('(') => {},
+            Token::Punctuation('(') => {},
             unexpected => return Err(ParseError::Expected {
                 expected: "opening parenthesis '('".to_string(),
-                found: format!("{:?}", unexpected),
+                found: unexpected.to_string(),
             }),
         }
         
@@ -407,26 +788,31 @@ impl Statement {
         let mut params = Vec::new();
         
         // Empty parameter list case
-        if tokenizer.peek_token() == &Token::Operator(')') {
+        if tokenizer.peek_token() == &Token::Punctuation(')') {
             tokenizer.next_token(); // consume closing paren
         } else {
-            // Non-empty parameter list
+            // Non-empty parameter list, which may end with a single trailing comma
             loop {
+                expect_list_item(tokenizer, "parameter", &name, params.len())?;
                 match tokenizer.next_token() {
                     Token::Literal(param) => params.push(param),
                     unexpected => return Err(ParseError::Expected {
                         expected: "parameter name".to_string(),
-                        found: format!("{:?}", unexpected),
+                        found: unexpected.to_string(),
                     }),
                 }
                 
                 // Check for parameter delimiter or end of list
                 match tokenizer.next_token() {
-                    Token::Operator(',') => continue, // More parameters
-                    Token::Operator(')') => break,    // End of parameter list
+                    Token::Punctuation(',') if tokenizer.peek_token() == &Token::Punctuation(')') => {
+                        tokenizer.next_token(); // consume closing paren
+                        break;
+                    }
+                    Token::Punctuation(',') => continue, // More parameters
+                    Token::Punctuation(')') => break,    // End of parameter list
                     unexpected => return Err(ParseError::Expected {
                         expected: "comma ',' or closing parenthesis ')'".to_string(),
-                        found: format!("{:?}", unexpected),
+                        found: unexpected.to_string(),
                     }),
                 }
             }
@@ -434,16 +820,27 @@ impl Statement {
         
         // Parse procedure body (must be a block)
         let body = match tokenizer.peek_token() {
-            Token::Operator('{') => Box::new(Self::parse_block_statement(tokenizer)?),
+            Token::Punctuation('{') => Box::new(Self::parse_block_statement(tokenizer)?),
             unexpected => return Err(ParseError::Expected {
                 expected: "procedure body block".to_string(),
-                found: format!("{:?}", *unexpected),
+                found: unexpected.to_string(),
             }),
         };
         
         Ok(Statement::Procedure { name, params, body })
     }
     
+    /// Parse an import statement.
+    fn parse_import_statement(tokenizer: &mut Tokenizer) -> Result<Statement, ParseError> {
+        match tokenizer.next_token() {
+            Token::Text(name) => Ok(Statement::Import(name)),
+            unexpected => Err(ParseError::Expected {
+                expected: "snippet name in double quotes".to_string(),
+                found: unexpected.to_string(),
+            }),
+        }
+    }
+    
     /// Helper method to parse a procedure call.
     fn call_procedure(tokenizer: &mut Tokenizer, name: String) -> Result<Statement, ParseError> {
         tokenizer.next_token(); // consume '('
@@ -452,20 +849,25 @@ impl Statement {
         let mut args = Vec::new();
         
         // Empty argument list case
-        if tokenizer.peek_token() == &Token::Operator(')') {
+        if tokenizer.peek_token() == &Token::Punctuation(')') {
             tokenizer.next_token(); // consume closing paren
         } else {
-            // Non-empty argument list
+            // Non-empty argument list, which may end with a single trailing comma
             loop {
+                expect_list_item(tokenizer, "argument", &name, args.len())?;
                 args.push(Expression::parse(tokenizer, 0.0)?);
                 
                 // Check for argument delimiter or end of list
                 match tokenizer.next_token() {
-                    Token::Operator(',') => continue, // More arguments
-                    Token::Operator(')') => break,    // End of argument list
+                    Token::Punctuation(',') if tokenizer.peek_token() == &Token::Punctuation(')') => {
+                        tokenizer.next_token(); // consume closing paren
+                        break;
+                    }
+                    Token::Punctuation(',') => continue, // More arguments
+                    Token::Punctuation(')') => break,    // End of argument list
                     unexpected => return Err(ParseError::Expected {
                         expected: "comma ',' or closing parenthesis ')'".to_string(),
-                        found: format!("{:?}", unexpected),
+                        found: unexpected.to_string(),
                     }),
                 }
             }
@@ -474,213 +876,174 @@ impl Statement {
         Ok(Statement::ProcedureCall { name, args })
     }
 
-    /// Evaluate a statement in the given context.
-    pub fn evaluate(&self, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+    /// Evaluate a function body statement, deferring a call in tail position.
+    ///
+    /// Tail position is the last statement of a block, either branch of an if
+    /// statement in tail position, or a `return`. A call there to a user-defined
+    /// function has its arguments evaluated and is handed back as
+    /// [`TailOutcome::Call`] rather than being run on the Rust stack.
+    pub(crate) fn evaluate_tail(&self, context: &mut SymbolTable<f32>) -> Result<TailOutcome, EvalError> {
         match self {
-            Statement::Expression(expr) => {
-                // If this is an assignment, check if the variable exists before evaluation
-                if let Expression::Operation('=', operands) = expr {
-                    if let Expression::Literal(var_name) = &operands[0] {
-                        // Check if variable exists before assignment
-                        if !context.contains(var_name) {
-                            return Err(SymbolError::UndeclaredVariable(var_name.clone()).into());
-                        }
-                        
-                        // Variable exists, evaluate and update
-                        let value = operands[1].evaluate(context)?;
-                        context.set_variable(var_name.clone(), value)?;
-                        return Ok((Some(value), ControlFlow::Normal));
-                    }
-                }
-                
-                // Not an assignment or handled above
-                let value = expr.evaluate(context)?;
-                Ok((Some(value), ControlFlow::Normal))
+            // Deferred statements run after the last one, so it is not in tail position
+            Statement::Block(statements) if statements.iter().any(|statement| matches!(statement, Statement::Defer(_))) => {
+                let (value, control_flow) = self.evaluate(context)?;
+                Ok(TailOutcome::Done(value, control_flow))
             }
 
             Statement::Block(statements) => {
-                // Create a new scope by cloning the current context
+                let Some((last, rest)) = statements.split_last() else {
+                    return Ok(TailOutcome::Done(None, ControlFlow::Normal));
+                };
                 let mut block_context = context.new_scope();
                 let mut last_value = None;
-                let mut control_flow = ControlFlow::Normal;
 
-                // Keep track of variables defined in this block
-                let mut block_vars = HashSet::new();
-
-                // Pre-scan statements to find all variables defined in this block
-                for statement in statements {
-                    collect_declared_vars(statement, &mut block_vars);
-                }
-
-                // Evaluate each statement in the block with the new context
-                for statement in statements {
-                    // Evaluate the current statement
-                    let (value, stmt_flow) = statement.evaluate(&mut block_context)?;
-                    
-                    // Update the last value if one was returned
+                // Everything but the last statement runs as usual
+                for statement in rest {
+                    let (value, control_flow) = statement.evaluate(&mut block_context)?;
                     if let Some(v) = value {
                         last_value = Some(v);
                     }
-                    
-                    // Handle control flow
-                    if stmt_flow != ControlFlow::Normal {
-                        control_flow = stmt_flow;
-                        break;
+                    if control_flow != ControlFlow::Normal {
+                        Self::copy_back_block(statements, &block_context, context)?;
+                        return Ok(TailOutcome::Done(last_value, control_flow));
                     }
                 }
 
-                // Copy back only variables that were not defined in this block
-                for (key, value) in block_context.values.iter() {
-                    // Skip variables defined in this block (including shadowed ones)
-                    if block_vars.contains(key) {
-                        continue;
-                    }
-                    
-                    // Skip variables that haven't changed
-                    if context.get(key) == Some(value) {
-                        continue;
-                    }
-                    
-                    // Don't modify constants from the parent scope
-                    if context.is_constant(key) {
-                        continue;
-                    }
-                    
-                    // Only update variables that already exist in the outer scope
-                    if context.contains(key) {
-                        context.set_variable(key.clone(), value.clone())?;
+                match last.evaluate_tail(&mut block_context)? {
+                    TailOutcome::Done(value, control_flow) => {
+                        Self::copy_back_block(statements, &block_context, context)?;
+                        Ok(TailOutcome::Done(value.or(last_value), control_flow))
                     }
+                    call => Ok(call),
                 }
-
-                Ok((last_value, control_flow))
             }
 
             Statement::If { condition, then_branch, else_branch } => {
-                let condition_value = condition.evaluate(context)?;
-                if condition_value != 0.0 {
-                    // Create a new scope for the then branch
-                    let mut then_context = context.new_scope();
-                    let (result, control_flow) = then_branch.evaluate(&mut then_context)?;
-
-                    // Track variables defined in this block to avoid shadowing issues
-                    let mut defined_vars = HashSet::new();
-                    collect_declared_vars(then_branch, &mut defined_vars);
-
-                    // Copy variables from the then branch back to the parent context
-                    for (key, value) in then_context.values.iter() {
-                        // Skip variables defined in this block (including shadowed ones)
-                        if defined_vars.contains(key) {
-                            continue;
-                        }
-                        
-                        // Skip variables that haven't changed
-                        if context.get(key) == Some(value) {
-                            continue;
-                        }
-                        
-                        // Don't modify constants from the parent scope
-                        if context.is_constant(key) {
-                            continue;
-                        }
-                        
-                        // Only update variables that already exist in the outer scope
-                        if context.contains(key) {
-                            context.set_variable(key.clone(), value.clone())?;
-                        }
-                    }
-                    
-                    Ok((result, control_flow))
+                let branch = if condition.evaluate_mut(context)? != 0.0 {
+                    then_branch
                 } else if let Some(else_br) = else_branch {
-                    // Create a new scope for the else branch
-                    let mut else_context = context.new_scope();
-                    let (result, control_flow) = else_br.evaluate(&mut else_context)?;
-
-                    // Track variables defined in this block to avoid shadowing issues
-                    let mut defined_vars = HashSet::new();
-                    collect_declared_vars(else_br, &mut defined_vars);
-
-                    // Copy variables from the else branch back to the parent context
-                    for (key, value) in else_context.values.iter() {
-                        // Skip variables defined in this block (including shadowed ones)
-                        if defined_vars.contains(key) {
-                            continue;
-                        }
-                        
-                        // Skip variables that haven't changed
-                        if context.get(key) == Some(value) {
-                            continue;
-                        }
-                        
-                        // Don't modify constants from the parent scope
-                        if context.is_constant(key) {
-                            continue;
-                        }
-                        
-                        // Only update variables that already exist in the outer scope
-                        if context.contains(key) {
-                            context.set_variable(key.clone(), value.clone())?;
-                        }
-                    }
-                    
-                    Ok((result, control_flow))
+                    else_br
                 } else {
-                    Ok((Some(0.0), ControlFlow::Normal))
+                    return Ok(TailOutcome::Done(Some(0.0), ControlFlow::Normal));
+                };
+
+                let mut branch_context = context.new_scope();
+                match branch.evaluate_tail(&mut branch_context)? {
+                    TailOutcome::Done(value, control_flow) => {
+                        let mut defined_vars = HashSet::new();
+                        collect_declared_vars(branch, &mut defined_vars);
+                        copy_back_changes(&defined_vars, &branch_context, context)?;
+                        Ok(TailOutcome::Done(value, control_flow))
+                    }
+                    call => Ok(call),
                 }
             }
 
-            Statement::While { condition, body } => {
-                let mut last_value = None;
-                while condition.evaluate(context)? != 0.0 {
-                    // Create a new scope for each iteration
-                    let mut loop_context = context.new_scope();
-                    
-                    // Evaluate the body with control flow information
-                    let (value, control_flow) = body.evaluate(&mut loop_context)?;
-                    
-                    // Update the last value if one was returned
-                    if let Some(v) = value {
-                        last_value = Some(v);
-                    }
+            Statement::Expression(Expression::FunctionCall(name, args))
+            | Statement::ProcedureCall { name, args } if Self::is_tail_callable(name, context) => {
+                Self::defer_call(name, args, context)
+            }
 
-                    // Track variables defined in this block to avoid shadowing issues
-                    let mut defined_vars = HashSet::new();
-                    collect_declared_vars(body, &mut defined_vars);
-
-                    // Copy variables from the loop iteration back to the parent context
-                    for (key, value) in loop_context.values.iter() {
-                        // Skip variables defined in this block (including shadowed ones)
-                        if defined_vars.contains(key) {
-                            continue;
-                        }
-                        
-                        // Skip variables that haven't changed
-                        if context.get(key) == Some(value) {
-                            continue;
-                        }
-                        
-                        // Don't modify constants from the parent scope
-                        if context.is_constant(key) {
-                            continue;
-                        }
-                        
-                        // Only update variables that already exist in the outer scope
-                        if context.contains(key) {
-                            context.set_variable(key.clone(), value.clone())?;
-                        }
-                    }
-                    
-                    // Handle control flow instructions
-                    match control_flow {
-                        ControlFlow::Break => break,
-                        ControlFlow::Continue => continue,
-                        ControlFlow::Return => return Ok((last_value, ControlFlow::Return)),
-                        ControlFlow::Normal => {}
-                    }
+            Statement::Return(Some(Expression::FunctionCall(name, args))) if Self::is_tail_callable(name, context) => {
+                let call = Self::defer_call(name, args, context)?;
+                if !context.is_in_callable() {
+                    return Err(ControlFlowError::InvalidReturnStatement("Use 'end' instead of 'return' outside of functions/procedures".to_string()).into());
                 }
-                
-                Ok((last_value, ControlFlow::Normal))
+                Ok(call)
+            }
+
+            _ => {
+                let (value, control_flow) = self.evaluate(context)?;
+                Ok(TailOutcome::Done(value, control_flow))
+            }
+        }
+    }
+
+    /// Checks whether a call can be deferred as a tail call.
+    ///
+    /// Only user-defined functions qualify: built-ins take precedence over them
+    /// and procedures cannot produce values.
+    fn is_tail_callable(name: &str, context: &SymbolTable<f32>) -> bool {
+        context.functions.contains_key(name)
+            && !context.procedures.contains_key(name)
+            && find_builtin(name).is_none()
+    }
+
+    /// Evaluates the arguments of a tail call and hands the call back.
+    fn defer_call(name: &str, args: &[Expression], context: &mut SymbolTable<f32>) -> Result<TailOutcome, EvalError> {
+        let mut evaluated_args = Vec::with_capacity(args.len());
+        for arg in args {
+            evaluated_args.push(arg.evaluate_mut(context)?);
+        }
+
+        Ok(TailOutcome::Call {
+            name: name.to_string(),
+            args: evaluated_args,
+            scope: Box::new(context.call_scope()),
+        })
+    }
+
+    /// Copies changes made in a block scope back to its parent.
+    fn copy_back_block(
+        statements: &[Statement],
+        block_context: &SymbolTable<f32>,
+        context: &mut SymbolTable<f32>,
+    ) -> Result<(), EvalError> {
+        let mut block_vars = HashSet::new();
+        for statement in statements {
+            collect_declared_vars(statement, &mut block_vars);
+        }
+        copy_back_changes(&block_vars, block_context, context)
+    }
+
+    /// Runs the bodies of `defer` statements, most recent first.
+    ///
+    /// Control flow inside a deferred body only leaves that body, so a block
+    /// that is unwinding still runs the rest of its deferred statements.
+    pub(crate) fn run_deferred(deferred: &[&Statement], context: &mut SymbolTable<f32>) -> Result<(), EvalError> {
+        for body in deferred.iter().rev() {
+            body.evaluate(context)?;
+        }
+        Ok(())
+    }
+
+    /// Evaluate a statement in the given context.
+    ///
+    /// While tracing is enabled every statement except a plain block is
+    /// recorded with its outcome; see [`trace_execution`](crate::core::trace_execution).
+    pub fn evaluate(&self, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        let traced = !matches!(self, Statement::Block(_)) && with_trace_state(|state| state.enabled);
+        if !traced {
+            return self.evaluate_untraced(context);
+        }
+
+        let entry = with_trace_state(|state| state.enter(self.summary()));
+        let result = self.evaluate_untraced(context);
+        with_trace_state(|state| state.leave(entry, &result));
+        result
+    }
+
+    /// Evaluates a statement without recording it; see [`Statement::evaluate`].
+    fn evaluate_untraced(&self, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        // Statements with scopes of their own are evaluated in separate methods,
+        // which keeps the stack used by every level of a recursive call small
+        match self {
+            Statement::Expression(expr) => {
+                // Assignments (including chained and nested ones) write through to the context
+                let value = expr.evaluate_mut(context)?;
+                Ok((Some(value), ControlFlow::Normal))
             }
 
+            Statement::Block(statements) => Self::evaluate_block(statements, context),
+
+            Statement::If { condition, then_branch, else_branch } => Self::evaluate_if(condition, then_branch, else_branch.as_deref(), context),
+
+            Statement::While { condition, body } => self.evaluate_while(condition, body, context),
+
+            Statement::Foreach { variable, start, end, body } => self.evaluate_foreach(variable, start, end, body, context),
+
+            Statement::Try { body, handler } => Self::evaluate_try(body, handler, context),
+
             Statement::Break => Ok((None, ControlFlow::Break)),
             
             Statement::Continue => Ok((None, ControlFlow::Continue)),
@@ -688,7 +1051,7 @@ impl Statement {
             Statement::Return(expr) => {
                 // Return is used for both functions and procedures
                 let value = if let Some(expr) = expr {
-                    Some(expr.evaluate(context)?)
+                    Some(expr.evaluate_mut(context)?)
                 } else {
                     None
                 };
@@ -698,16 +1061,18 @@ impl Statement {
                     return Err(ControlFlowError::InvalidReturnStatement("Use 'end' instead of 'return' outside of functions/procedures".to_string()).into());
                 }
                 
-                Ok((value, ControlFlow::Return))
+                Ok((value, ControlFlow::Return(value)))
             }
 
             Statement::Let { name, initializer } => {
                 let value = if let Some(init) = initializer {
-                    init.evaluate(context)?
+                    init.evaluate_mut(context)?
                 } else {
                     0.0
                 };
 
+                context.declare_name(name, "Variable")?;
+
                 // If we're in a block scope and the variable already exists in the parent scope,
                 // only update it in the current scope
                 let is_block_scope = context.contains(name.as_str());
@@ -721,15 +1086,39 @@ impl Statement {
                 Ok((Some(value), ControlFlow::Normal))
             }
 
-            Statement::Const { name, initializer } => {
-                let value = initializer.evaluate(context)?;
-                context.declare_constant(name.clone(), value)?;
+            Statement::Formula { name, expression } => {
+                context.declare_name(name, "Variable")?;
+                context.declare_formula(name.clone(), expression.clone())?;
+                Ok((None, ControlFlow::Normal))
+            }
+
+            Statement::Const { name, initializer, global } => {
+                let value = initializer.evaluate_mut(context)?;
+                if *global {
+                    publish_shared_constant(name, value)?;
+                } else {
+                    context.declare_name(name, "Constant")?;
+                    context.declare_constant(name.clone(), value)?;
+                }
                 Ok((Some(value), ControlFlow::Normal))
             }
 
+            // Outside a block there is nothing to wait for, so the body runs at once
+            Statement::Defer(body) => {
+                body.evaluate(context)?;
+                Ok((None, ControlFlow::Normal))
+            }
+
+            Statement::Assert(condition) => {
+                if condition.evaluate_mut(context)? == 0.0 {
+                    return Err(ControlFlowError::AssertionFailed(condition.to_source()).into());
+                }
+                Ok((None, ControlFlow::Normal))
+            }
+
             Statement::End(expr) => {
                 let value = match expr {
-                    Some(expr) => Some(expr.evaluate(context)?),
+                    Some(expr) => Some(expr.evaluate_mut(context)?),
                     None => None,
                 };
                 
@@ -740,12 +1129,26 @@ impl Statement {
                 });
                 
                 // Return the value and a Return control flow to stop execution
-                Ok((value, ControlFlow::Return))
+                Ok((value, ControlFlow::Return(value)))
             }
 
-            Statement::Function { name, params, body } => {
+            Statement::Function { name, params, body, memo } => {
+                // Cached results are only right for functions whose result
+                // depends on nothing but their arguments
+                if *memo && !is_pure_definition(name, params, body, &context.functions) {
+                    return Err(SymbolError::ImpureMemoFunction(name.clone()).into());
+                }
+
                 // Define function in the current scope
                 context.declare_function(name.clone(), params.clone(), *body.clone())?;
+                if *memo {
+                    context.memo_cache.register(name);
+                } else {
+                    context.memo_cache.unregister(name);
+                }
+
+                // Cached results and purity may belong to an earlier function with this name
+                with_memo_state(MemoState::reset);
                 Ok((None, ControlFlow::Normal))
             }
 
@@ -755,67 +1158,306 @@ impl Statement {
                 Ok((None, ControlFlow::Normal))
             }
 
-            Statement::ProcedureCall { name, args } => {
-                // First check if this is a procedure
-                if let Some((params, body)) = context.get_procedure(name) {
-                    // Create a new scope for the procedure execution
-                    let mut proc_scope = context.new_scope();
-                    
-                    // Check argument count
-                    if args.len() != params.len() {
-                        return Err(ControlFlowError::WrongArgumentCount {
-                            name: name.clone(),
-                            expected: params.len(),
-                            got: args.len(),
-                        }.into());
-                    }
-                    
-                    // Evaluate arguments and bind to parameters
-                    for (i, arg) in args.iter().enumerate() {
-                        let arg_value = arg.evaluate(context)?;
-                        proc_scope.set_variable(params[i].clone(), arg_value)?;
-                    }
-                    
-                    // Execute the procedure body and ignore any return value
-                    match body.evaluate(&mut proc_scope)? {
-                        (_, ControlFlow::Return) => Ok((None, ControlFlow::Normal)),
-                        (_, ControlFlow::Normal) => Ok((None, ControlFlow::Normal)),
-                        (_, control_flow) => Ok((None, control_flow)), // Pass along other control flow
-                    }
-                } 
-                // Then check if it's a function
-                else if let Some((params, body)) = context.get_function(name) {
-                    // Create a new scope for the function execution
-                    let mut func_scope = context.new_scope();
-                    
-                    // Check argument count
-                    if args.len() != params.len() {
-                        return Err(ControlFlowError::WrongArgumentCount {
-                            name: name.clone(),
-                            expected: params.len(),
-                            got: args.len(),
-                        }.into());
-                    }
-                    
-                    // Evaluate arguments and bind to parameters
-                    for (i, arg) in args.iter().enumerate() {
-                        let arg_value = arg.evaluate(context)?;
-                        func_scope.set_variable(params[i].clone(), arg_value)?;
-                    }
-                    
-                    // Execute the function body and convert to expression
-                    match body.evaluate(&mut func_scope)? {
-                        (Some(value), _) => Ok((Some(value), ControlFlow::Normal)),
-                        (None, _) => Ok((Some(0.0), ControlFlow::Normal)), // Default return value
-                    }
-                }
-                else {
-                    // Neither a procedure nor a function
-                    Err(ControlFlowError::FunctionOrProcedureNotFound {
-                        name: name.clone(),
-                    }.into())
-                }
+            Statement::ProcedureCall { name, args } => Self::evaluate_procedure_call(name, args, context),
+
+            Statement::Import(name) => {
+                // Bring the snippet's definitions into the current scope
+                import_snippet(name, context)?;
+                Ok((None, ControlFlow::Normal))
             }
         }
     }
+
+    /// Evaluates a block in a scope of its own, running its deferred statements on the way out.
+    fn evaluate_block(statements: &[Statement], context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        // Create a new scope by cloning the current context
+        let mut block_context = context.new_scope();
+        let mut last_value = None;
+        let mut control_flow = ControlFlow::Normal;
+
+        // Keep track of variables defined in this block
+        let mut block_vars = HashSet::new();
+
+        // Pre-scan statements to find all variables defined in this block
+        for statement in statements {
+            collect_declared_vars(statement, &mut block_vars);
+        }
+
+        // Deferred statements wait until the block exits
+        let mut deferred = Vec::new();
+
+        // Evaluate each statement in the block with the new context
+        for statement in statements {
+            if let Statement::Defer(body) = statement {
+                deferred.push(body.as_ref());
+                continue;
+            }
+
+            // Evaluate the current statement
+            let (value, stmt_flow) = statement.evaluate(&mut block_context)?;
+            
+            // Update the last value if one was returned
+            if let Some(v) = value {
+                last_value = Some(v);
+            }
+            
+            // Handle control flow
+            if stmt_flow != ControlFlow::Normal {
+                control_flow = stmt_flow;
+                break;
+            }
+        }
+
+        // Run deferred statements on the way out, including after break, return and end
+        Self::run_deferred(&deferred, &mut block_context)?;
+
+        // Copy back only variables that were not defined in this block
+        copy_back_changes(&block_vars, &block_context, context)?;
+
+        Ok((last_value, control_flow))
+    }
+
+    /// Evaluates the branch of an `if` that its condition picks, in a scope of its own.
+    fn evaluate_if(condition: &Expression, then_branch: &Statement, else_branch: Option<&Statement>, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        let condition_value = condition.evaluate_mut(context)?;
+        if condition_value != 0.0 {
+            // Create a new scope for the then branch
+            let mut then_context = context.new_scope();
+            let (result, control_flow) = then_branch.evaluate(&mut then_context)?;
+
+            // Track variables defined in this block to avoid shadowing issues
+            let mut defined_vars = HashSet::new();
+            collect_declared_vars(then_branch, &mut defined_vars);
+
+            // Copy variables from the then branch back to the parent context
+            copy_back_changes(&defined_vars, &then_context, context)?;
+            
+            Ok((result, control_flow))
+        } else if let Some(else_br) = else_branch {
+            // Create a new scope for the else branch
+            let mut else_context = context.new_scope();
+            let (result, control_flow) = else_br.evaluate(&mut else_context)?;
+
+            // Track variables defined in this block to avoid shadowing issues
+            let mut defined_vars = HashSet::new();
+            collect_declared_vars(else_br, &mut defined_vars);
+
+            // Copy variables from the else branch back to the parent context
+            copy_back_changes(&defined_vars, &else_context, context)?;
+            
+            Ok((result, control_flow))
+        } else {
+            Ok((Some(0.0), ControlFlow::Normal))
+        }
+    }
+
+    /// Runs a `while` loop until its condition is false or its body leaves it.
+    fn evaluate_while(&self, condition: &Expression, body: &Statement, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        let mut last_value = None;
+        while condition.evaluate_mut(context)? != 0.0 {
+            count_iteration()?;
+
+            // Create a new scope for each iteration
+            let loop_context = context.new_scope();
+
+            match run_loop_iteration(self, body, loop_context, context, &mut last_value)? {
+                LoopStep::Next => {}
+                LoopStep::Exit => break,
+                LoopStep::Return(value) => return Ok((value, ControlFlow::Return(value))),
+            }
+        }
+        
+        Ok((last_value, ControlFlow::Normal))
+    }
+
+    /// Runs a `foreach` loop over a range, binding the loop variable in each iteration.
+    fn evaluate_foreach(&self, variable: &str, start: &Expression, end: &Expression, body: &Statement, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        let start = start.evaluate_mut(context)?;
+        let end = end.evaluate_mut(context)?;
+        let mut last_value = None;
+        
+        let mut index = start;
+        while index < end {
+            count_iteration()?;
+
+            // Create a new scope for each iteration with the loop variable bound in it
+            let mut loop_context = context.new_scope();
+            loop_context.set_variable(variable.to_string(), index)?;
+
+            match run_loop_iteration(self, body, loop_context, context, &mut last_value)? {
+                LoopStep::Next => {}
+                LoopStep::Exit => break,
+                LoopStep::Return(value) => return Ok((value, ControlFlow::Return(value))),
+            }
+            
+            index += 1.0;
+        }
+        
+        Ok((last_value, ControlFlow::Normal))
+    }
+
+    /// Runs the body of a `try`, and its handler if the body fails with an error that may be caught.
+    fn evaluate_try(body: &Statement, handler: &Statement, context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        // The body runs in a scope of its own, which is dropped if it fails
+        let mut body_context = context.new_scope();
+        let (branch, branch_context, outcome) = match body.evaluate(&mut body_context) {
+            Ok(outcome) => (body, body_context, outcome),
+            Err(error) if error.is_catchable() => {
+                let mut handler_context = context.new_scope();
+                let outcome = handler.evaluate(&mut handler_context)?;
+                (handler, handler_context, outcome)
+            }
+            Err(error) => return Err(error),
+        };
+
+        // Copy variables from whichever part finished back to the parent context
+        let mut defined_vars = HashSet::new();
+        collect_declared_vars(branch, &mut defined_vars);
+        copy_back_changes(&defined_vars, &branch_context, context)?;
+
+        Ok(outcome)
+    }
+
+    /// Calls a procedure, or a function whose value is kept when there is no procedure with the name.
+    fn evaluate_procedure_call(name: &str, args: &[Expression], context: &mut SymbolTable<f32>) -> Result<(Option<f32>, ControlFlow), EvalError> {
+        // First check if this is a procedure
+        if let Some((params, body)) = context.get_procedure(name) {
+            // Create a new scope for the procedure execution
+            let mut proc_scope = context.call_scope();
+            
+            // Check argument count
+            if args.len() != params.len() {
+                return Err(ControlFlowError::WrongArgumentCount {
+                    name: name.to_string(),
+                    expected: params.len(),
+                    got: args.len(),
+                }.into());
+            }
+            
+            // Evaluate arguments and bind to parameters
+            for (i, arg) in args.iter().enumerate() {
+                let arg_value = arg.evaluate_mut(context)?;
+                proc_scope.set_variable(params[i].clone(), arg_value)?;
+            }
+            
+            // Execute the procedure body and ignore any return value
+            enter_call()?;
+            let outcome = body.evaluate(&mut proc_scope);
+            leave_call();
+            let (_, control_flow) = outcome?;
+
+            // Procedures act through side effects, so outer variables they
+            // changed are written back. Parameters stay local.
+            let mut defined_vars: HashSet<String> = params.into_iter().collect();
+            collect_declared_vars(&body, &mut defined_vars);
+            copy_back_changes(&defined_vars, &proc_scope, context)?;
+
+            match control_flow {
+                ControlFlow::Return(_) | ControlFlow::Normal => Ok((None, ControlFlow::Normal)),
+                control_flow => Ok((None, control_flow)), // Pass along other control flow
+            }
+        } 
+        // Otherwise it's a user-defined or built-in function whose value is kept
+        else {
+            let value = Expression::FunctionCall(name.to_string(), args.to_vec()).evaluate_mut(context)?;
+            Ok((Some(value), ControlFlow::Normal))
+        }
+    }
 } 
+
+/// The text added for each level of indentation by [`format_pretty`].
+const INDENT: &str = "    ";
+
+/// Formats a statement as indented source, indented by `indent` levels.
+///
+/// Blocks put each statement on a line of its own, one level deeper than
+/// the line that opens them, and bodies are always written as blocks.
+/// Statements without a body are separated by `;`, which is left out after
+/// the last one. Parsing the result gives the same statement back.
+pub fn format_pretty(statement: &Statement, indent: usize) -> String {
+    format!("{}{}", INDENT.repeat(indent), pretty_inline(statement, indent))
+}
+
+/// Formats statements that follow each other, one per line.
+pub(crate) fn format_pretty_sequence(statements: &[Statement], indent: usize) -> String {
+    let mut lines = Vec::with_capacity(statements.len());
+    for (index, statement) in statements.iter().enumerate() {
+        let mut line = format_pretty(statement, indent);
+        if index + 1 < statements.len() && !has_body(statement) {
+            line.push(';');
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+/// Returns true if the statement ends with a block, which needs no `;` after it.
+fn has_body(statement: &Statement) -> bool {
+    match statement {
+        Statement::Defer(body) => has_body(body),
+        Statement::Block(_)
+        | Statement::If { .. }
+        | Statement::While { .. }
+        | Statement::Foreach { .. }
+        | Statement::Try { .. }
+        | Statement::Function { .. }
+        | Statement::Procedure { .. } => true,
+        _ => false,
+    }
+}
+
+/// Formats a statement that starts on a line already indented by `indent` levels.
+fn pretty_inline(statement: &Statement, indent: usize) -> String {
+    match statement {
+        Statement::Block(statements) => pretty_block(statements, indent),
+        Statement::If { condition, then_branch, else_branch } => {
+            let else_part = match else_branch.as_deref() {
+                // `else if` chains stay flat instead of nesting a block per branch
+                Some(branch @ Statement::If { .. }) => format!(" else {}", pretty_inline(branch, indent)),
+                Some(branch) => format!(" else {}", pretty_body(branch, indent)),
+                None => String::new(),
+            };
+            format!("if {} {}{}", condition.to_source(), pretty_body(then_branch, indent), else_part)
+        }
+        Statement::While { condition, body } => format!("while {} {}", condition.to_source(), pretty_body(body, indent)),
+        Statement::Foreach { variable, start, end, body } => format!(
+            "foreach {} in {} .. {} {}",
+            variable,
+            start.to_source(),
+            end.to_source(),
+            pretty_body(body, indent),
+        ),
+        Statement::Defer(body) => format!("defer {}", pretty_inline(body, indent)),
+        Statement::Try { body, handler } => {
+            format!("try {} catch {}", pretty_body(body, indent), pretty_body(handler, indent))
+        }
+        Statement::Function { name, params, body, memo } => format!(
+            "{}fn {}({}) {}",
+            if *memo { "memo " } else { "" },
+            name,
+            params.join(", "),
+            pretty_body(body, indent),
+        ),
+        Statement::Procedure { name, params, body } => {
+            format!("proc {}({}) {}", name, params.join(", "), pretty_body(body, indent))
+        }
+        // The remaining statements have no body, so their summary is their full source
+        _ => statement.summary(),
+    }
+}
+
+/// Formats the body of a branch, loop or definition as a block.
+fn pretty_body(body: &Statement, indent: usize) -> String {
+    match body {
+        Statement::Block(statements) => pretty_block(statements, indent),
+        statement => pretty_block(std::slice::from_ref(statement), indent),
+    }
+}
+
+/// Formats a block whose braces are at `indent` levels.
+fn pretty_block(statements: &[Statement], indent: usize) -> String {
+    if statements.is_empty() {
+        return "{ }".to_string();
+    }
+    format!("{{\n{}\n{}}}", format_pretty_sequence(statements, indent + 1), INDENT.repeat(indent))
+}
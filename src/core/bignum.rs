@@ -0,0 +1,163 @@
+//! Exact big integers for results beyond the float range.
+//!
+//! An `f32` holds integers exactly only up to 2^24, so `2^100` is rounded and
+//! `fact(40)` overflows. When a real result is an integer that large, the
+//! expression is evaluated again with big integers. Addition, subtraction,
+//! multiplication, powers with non-negative integer exponents and `fact`
+//! stay exact; anything else demotes the value back to a float.
+
+use num_bigint::BigInt;
+
+use crate::core::ast_expression::{apply_operator, call_function, Expression};
+use crate::core::error_types::{EvalError, MathError};
+use crate::core::lexical_analyzer::decode_radix_literal;
+use crate::core::operator::Operator;
+use crate::core::symbol_manager::SymbolTable;
+
+/// Largest integer an `f32` holds exactly.
+pub const F32_EXACT_LIMIT: f32 = 16_777_216.0;
+
+/// Largest big integer built by a power, in bits (about 300,000 digits).
+const MAX_BITS: u64 = 1_000_000;
+
+/// Largest n whose factorial is computed exactly.
+const MAX_BIG_FACTORIAL: u32 = 10_000;
+
+/// Returns whether a real result may be an integer too large for `f32` to hold exactly.
+///
+/// Results that overflowed count too, including `NaN` from `inf - inf`.
+pub(crate) fn needs_promotion(result: &Result<f32, EvalError>) -> bool {
+    match result {
+        Ok(value) => !value.is_finite() || (value.fract() == 0.0 && value.abs() > F32_EXACT_LIMIT),
+        Err(EvalError::MathError(MathError::Overflow)) => true,
+        Err(_) => false,
+    }
+}
+
+/// Converts a big integer to the nearest float, infinite when out of range.
+pub(crate) fn big_to_f32(value: &BigInt) -> f32 {
+    value.to_string().parse().unwrap_or(f32::INFINITY)
+}
+
+/// A number during big evaluation: an exact integer, or a float once demoted.
+enum Number {
+    Big(BigInt),
+    Float(f32),
+}
+
+impl Number {
+    fn to_f32(&self) -> f32 {
+        match self {
+            Number::Big(value) => big_to_f32(value),
+            Number::Float(value) => *value,
+        }
+    }
+
+    fn as_big(&self) -> Option<&BigInt> {
+        match self {
+            Number::Big(value) => Some(value),
+            Number::Float(_) => None,
+        }
+    }
+
+    fn from_float(value: f32) -> Self {
+        if value.fract() == 0.0 && value.abs() <= F32_EXACT_LIMIT {
+            Number::Big(BigInt::from(value as i64))
+        } else {
+            Number::Float(value)
+        }
+    }
+}
+
+impl Expression {
+    /// Evaluates the expression with big integers.
+    ///
+    /// Returns `None` when part of the expression has no exact integer
+    /// result, e.g. `2^100 / 3`, so the caller keeps the float result.
+    pub(crate) fn evaluate_big(&self, context: &SymbolTable<f32>) -> Result<Option<BigInt>, EvalError> {
+        match evaluate(self, context)? {
+            Number::Big(value) => Ok(Some(value)),
+            Number::Float(_) => Ok(None),
+        }
+    }
+}
+
+fn evaluate(expression: &Expression, context: &SymbolTable<f32>) -> Result<Number, EvalError> {
+    match expression {
+        Expression::Literal(text) => {
+            if !text.is_empty() && text.chars().all(|c| c.is_ascii_digit()) {
+                if let Ok(value) = text.parse::<BigInt>() {
+                    return Ok(Number::Big(value));
+                }
+            }
+            if let Some(Ok(value)) = decode_radix_literal(text) {
+                return Ok(Number::Big(BigInt::from(value)));
+            }
+            // Variables and constants only hold floats
+            expression.evaluate(context).map(Number::from_float)
+        }
+
+        Expression::Operation(Operator::Assign, _) => expression.evaluate(context).map(Number::from_float),
+
+        Expression::Operation(operator, operands) => {
+            let values = operands
+                .iter()
+                .map(|operand| evaluate(operand, context))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let exact = match (values[0].as_big(), values.get(1).map(Number::as_big)) {
+                (Some(value), None) => big_unary(*operator, value),
+                (Some(left), Some(Some(right))) => big_binary(*operator, left, right),
+                _ => None,
+            };
+
+            match exact {
+                Some(value) => Ok(Number::Big(value)),
+                None => {
+                    let right = values.get(1).map_or(0.0, Number::to_f32);
+                    apply_operator(*operator, values.len() == 1, values[0].to_f32(), right).map(Number::Float)
+                }
+            }
+        }
+
+        // Unit names, the loop of fold() and the name in typeof() are not values, so they stay real
+        Expression::FunctionCall(name, _) if matches!(name.as_str(), "convert" | "fold" | "typeof") => expression.evaluate(context).map(Number::from_float),
+
+        Expression::FunctionCall(name, args) => {
+            let values = args
+                .iter()
+                .map(|arg| evaluate(arg, context))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            if let (true, [Number::Big(n)]) = (name == "fact", values.as_slice()) {
+                if let Some(n) = u32::try_from(n).ok().filter(|&n| n <= MAX_BIG_FACTORIAL) {
+                    return Ok(Number::Big((2..=n).map(BigInt::from).product()));
+                }
+            }
+
+            let floats = values.iter().map(Number::to_f32).collect();
+            call_function(name, floats, context).map(Number::Float)
+        }
+    }
+}
+
+fn big_unary(operator: Operator, value: &BigInt) -> Option<BigInt> {
+    match operator {
+        Operator::Add => Some(value.clone()),
+        Operator::Subtract => Some(-value),
+        _ => None,
+    }
+}
+
+fn big_binary(operator: Operator, left: &BigInt, right: &BigInt) -> Option<BigInt> {
+    match operator {
+        Operator::Add => Some(left + right),
+        Operator::Subtract => Some(left - right),
+        Operator::Multiply => Some(left * right),
+        Operator::Power => {
+            let exponent = u32::try_from(right).ok()?;
+            (left.bits() * u64::from(exponent) <= MAX_BITS).then(|| left.pow(exponent))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,415 @@
+//! Built-in function table and dispatch.
+//!
+//! Every built-in function is described once in `BUILTINS`, which drives both
+//! evaluation and the generated help content.
+
+use std::fmt;
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+
+use crate::core::ast_expression::nth_root;
+use crate::core::error_types::{ControlFlowError, EvalError, MathError};
+use crate::core::execution_state::with_random_state;
+use crate::core::symbol_manager::BindingKind;
+
+/// Groups of built-in functions, used to lay out help content.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinCategory {
+    Trigonometric,
+    AdditionalTrigonometric,
+    InverseTrigonometric,
+    Math,
+    MinMax,
+    Interpolation,
+    Integer,
+    Bit,
+    Special,
+    Complex,
+    Conversion,
+    Random,
+}
+
+impl BuiltinCategory {
+    /// All categories, in the order they are presented.
+    pub const ALL: [BuiltinCategory; 12] = [
+        BuiltinCategory::Trigonometric,
+        BuiltinCategory::AdditionalTrigonometric,
+        BuiltinCategory::InverseTrigonometric,
+        BuiltinCategory::Math,
+        BuiltinCategory::MinMax,
+        BuiltinCategory::Interpolation,
+        BuiltinCategory::Integer,
+        BuiltinCategory::Bit,
+        BuiltinCategory::Special,
+        BuiltinCategory::Complex,
+        BuiltinCategory::Conversion,
+        BuiltinCategory::Random,
+    ];
+
+    /// Returns a human-readable title for the category.
+    pub fn title(&self) -> &'static str {
+        match self {
+            BuiltinCategory::Trigonometric => "Basic Trigonometric",
+            BuiltinCategory::AdditionalTrigonometric => "Additional Trigonometric",
+            BuiltinCategory::InverseTrigonometric => "Inverse Trigonometric",
+            BuiltinCategory::Math => "Math Functions",
+            BuiltinCategory::MinMax => "Min/Max Functions",
+            BuiltinCategory::Interpolation => "Interpolation",
+            BuiltinCategory::Integer => "Integer Functions",
+            BuiltinCategory::Bit => "Bit Operations",
+            BuiltinCategory::Special => "Special Functions",
+            BuiltinCategory::Complex => "Complex Numbers",
+            BuiltinCategory::Conversion => "Unit Conversion",
+            BuiltinCategory::Random => "Random Number Generator",
+        }
+    }
+}
+
+impl fmt::Display for BuiltinCategory {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.title())
+    }
+}
+
+/// Describes a single built-in function.
+#[derive(Debug, Clone)]
+pub struct BuiltinSpec {
+    /// The name the function is called by.
+    pub name: &'static str,
+
+    /// The accepted number of arguments.
+    pub arity: RangeInclusive<usize>,
+
+    /// How the function is called, e.g. `atan2(y, x)`.
+    pub signature: &'static str,
+
+    /// A one-line description of what the function computes.
+    pub summary: &'static str,
+
+    /// An example call.
+    pub example: &'static str,
+
+    /// The group the function belongs to.
+    pub category: BuiltinCategory,
+}
+
+/// The table of all built-in functions.
+static BUILTINS: [BuiltinSpec; 40] = [
+    BuiltinSpec { name: "sin", arity: 1..=1, signature: "sin(x)", summary: "Sine of x (radians)", example: "sin(PI / 2)", category: BuiltinCategory::Trigonometric },
+    BuiltinSpec { name: "cos", arity: 1..=1, signature: "cos(x)", summary: "Cosine of x (radians)", example: "cos(0)", category: BuiltinCategory::Trigonometric },
+    BuiltinSpec { name: "tan", arity: 1..=1, signature: "tan(x)", summary: "Tangent of x (radians)", example: "tan(PI / 4)", category: BuiltinCategory::Trigonometric },
+    BuiltinSpec { name: "cot", arity: 1..=1, signature: "cot(x)", summary: "Cotangent of x (radians)", example: "cot(PI / 4)", category: BuiltinCategory::AdditionalTrigonometric },
+    BuiltinSpec { name: "sec", arity: 1..=1, signature: "sec(x)", summary: "Secant of x (radians)", example: "sec(0)", category: BuiltinCategory::AdditionalTrigonometric },
+    BuiltinSpec { name: "csc", arity: 1..=1, signature: "csc(x)", summary: "Cosecant of x (radians)", example: "csc(PI / 2)", category: BuiltinCategory::AdditionalTrigonometric },
+    BuiltinSpec { name: "asin", arity: 1..=1, signature: "asin(x)", summary: "Arc sine (inverse sine)", example: "asin(1)", category: BuiltinCategory::InverseTrigonometric },
+    BuiltinSpec { name: "acos", arity: 1..=1, signature: "acos(x)", summary: "Arc cosine (inverse cosine)", example: "acos(1)", category: BuiltinCategory::InverseTrigonometric },
+    BuiltinSpec { name: "atan", arity: 1..=1, signature: "atan(x)", summary: "Arc tangent (inverse tangent)", example: "atan(1)", category: BuiltinCategory::InverseTrigonometric },
+    BuiltinSpec { name: "atan2", arity: 2..=2, signature: "atan2(y, x)", summary: "Arc tangent of y/x with quadrant", example: "atan2(1, -1)", category: BuiltinCategory::InverseTrigonometric },
+    BuiltinSpec { name: "log", arity: 1..=1, signature: "log(x)", summary: "Natural logarithm of x", example: "log(E)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "sqrt", arity: 1..=1, signature: "sqrt(x)", summary: "Square root of x", example: "sqrt(25)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "root", arity: 2..=2, signature: "root(n, x)", summary: "n-th root of x, negative for odd n and negative x", example: "root(3, -8)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "percent", arity: 2..=2, signature: "percent(p, whole)", summary: "p percent of whole, p / 100 * whole (unlike a % b, the remainder)", example: "percent(50, 200)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "fold", arity: 5..=5, signature: "fold(i, lo, hi, init, body)", summary: "Runs body for i = lo to hi, with acc the previous result (init at first)", example: "fold(i, 1, 5, 0, acc + i)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "abs", arity: 1..=1, signature: "abs(x)", summary: "Absolute value of x", example: "abs(-10)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "round", arity: 1..=1, signature: "round(x)", summary: "x rounded to the nearest whole number, halves away from zero (2.5 gives 3)", example: "round(2.5)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "round_even", arity: 1..=1, signature: "round_even(x)", summary: "x rounded to the nearest whole number, halves to the even one (2.5 gives 2, 3.5 gives 4)", example: "round_even(2.5)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "bool", arity: 1..=1, signature: "bool(x)", summary: "true (1) if x is not zero, false (0) if it is", example: "bool(0.5)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "iszero", arity: 1..=1, signature: "iszero(x)", summary: "true (1) if x is zero, false (0) if it is not", example: "iszero(0)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "typeof", arity: 1..=1, signature: "typeof(x)", summary: "What a name is: 0 undefined, 1 variable or number, 2 constant, 3 formula, 4 function, 5 procedure", example: "typeof(PI)", category: BuiltinCategory::Math },
+    BuiltinSpec { name: "min", arity: 2..=2, signature: "min(x, y)", summary: "Minimum of x and y", example: "min(3, 7)", category: BuiltinCategory::MinMax },
+    BuiltinSpec { name: "max", arity: 2..=2, signature: "max(x, y)", summary: "Maximum of x and y", example: "max(3, 7)", category: BuiltinCategory::MinMax },
+    BuiltinSpec { name: "saturate", arity: 1..=1, signature: "saturate(x)", summary: "x clamped to the range 0 to 1", example: "saturate(1.5)", category: BuiltinCategory::Interpolation },
+    BuiltinSpec { name: "lerp", arity: 3..=3, signature: "lerp(a, b, t)", summary: "Linear interpolation a + (b - a) * t", example: "lerp(0, 10, 0.25)", category: BuiltinCategory::Interpolation },
+    BuiltinSpec { name: "smoothstep", arity: 3..=3, signature: "smoothstep(edge0, edge1, x)", summary: "Smooth Hermite step from 0 at edge0 to 1 at edge1", example: "smoothstep(0, 1, 0.5)", category: BuiltinCategory::Interpolation },
+    BuiltinSpec { name: "wrap", arity: 3..=3, signature: "wrap(x, lo, hi)", summary: "x wrapped into the range lo (included) to hi (excluded), like an angle", example: "wrap(3 * PI / 2, -PI, PI)", category: BuiltinCategory::Interpolation },
+    BuiltinSpec { name: "fact", arity: 1..=1, signature: "fact(n)", summary: "Factorial of a whole number n", example: "fact(5)", category: BuiltinCategory::Integer },
+    BuiltinSpec { name: "gcd", arity: 1..=usize::MAX, signature: "gcd(a, b, ...)", summary: "Greatest common divisor of one or more integers", example: "gcd(24, 36, 60)", category: BuiltinCategory::Integer },
+    BuiltinSpec { name: "lcm", arity: 1..=usize::MAX, signature: "lcm(a, b, ...)", summary: "Least common multiple of one or more integers", example: "lcm(4, 6, 10)", category: BuiltinCategory::Integer },
+    BuiltinSpec { name: "popcount", arity: 1..=1, signature: "popcount(x)", summary: "Number of set bits in x as a 32-bit unsigned integer", example: "popcount(7)", category: BuiltinCategory::Bit },
+    BuiltinSpec { name: "clz", arity: 1..=1, signature: "clz(x)", summary: "Leading zero bits of x as a 32-bit unsigned integer", example: "clz(1)", category: BuiltinCategory::Bit },
+    BuiltinSpec { name: "ctz", arity: 1..=1, signature: "ctz(x)", summary: "Trailing zero bits of x as a 32-bit unsigned integer, 32 for 0", example: "ctz(8)", category: BuiltinCategory::Bit },
+    BuiltinSpec { name: "sinc", arity: 1..=1, signature: "sinc(x)", summary: "sin(x) / x, with sinc(0) = 1", example: "sinc(PI / 2)", category: BuiltinCategory::Special },
+    BuiltinSpec { name: "gamma", arity: 1..=1, signature: "gamma(x)", summary: "Gamma function, gamma(n) = (n - 1)! for whole n", example: "gamma(5)", category: BuiltinCategory::Special },
+    BuiltinSpec { name: "erf", arity: 1..=1, signature: "erf(x)", summary: "Gauss error function", example: "erf(1)", category: BuiltinCategory::Special },
+    BuiltinSpec { name: "arg", arity: 1..=1, signature: "arg(z)", summary: "Angle of z in the complex plane (0 or PI for reals)", example: "arg(-1)", category: BuiltinCategory::Complex },
+    BuiltinSpec { name: "conj", arity: 1..=1, signature: "conj(z)", summary: "Complex conjugate of z (z itself for reals)", example: "conj(3)", category: BuiltinCategory::Complex },
+    BuiltinSpec { name: "convert", arity: 3..=3, signature: "convert(value, from, to)", summary: "Converts between units of length, mass, time, temperature or data size", example: "convert(5, km, mi)", category: BuiltinCategory::Conversion },
+    BuiltinSpec { name: "rand", arity: 0..=2, signature: "rand([min,] [max])", summary: "Random number between 0 and 1, 0 and max, or min and max", example: "rand(1, 6)", category: BuiltinCategory::Random },
+];
+
+/// Returns the specs of all built-in functions.
+pub fn list_builtins() -> &'static [BuiltinSpec] {
+    &BUILTINS
+}
+
+/// Finds the spec of a built-in function by name.
+pub fn find_builtin(name: &str) -> Option<&'static BuiltinSpec> {
+    BUILTINS.iter().find(|spec| spec.name == name)
+}
+
+/// The largest n whose factorial still fits in an `f32`.
+const MAX_FACTORIAL: i64 = 34;
+
+/// Converts an argument to an integer, refusing values with a fractional part.
+///
+/// Values within epsilon of a whole number are accepted, so results of float
+/// arithmetic like `0.1 * 30` still count as integers.
+pub(crate) fn truncate_to_int(value: f32) -> Result<i64, MathError> {
+    let rounded = value.round();
+    let tolerance = f32::EPSILON * value.abs().max(1.0);
+
+    if !value.is_finite() || (value - rounded).abs() > tolerance || rounded.abs() >= i64::MAX as f32 {
+        return Err(MathError::DomainError("expected an integer argument".to_string()));
+    }
+
+    Ok(rounded as i64)
+}
+
+/// Converts an argument to a 32-bit unsigned integer for the bit operations.
+fn to_u32(value: f32) -> Result<u32, MathError> {
+    let int = truncate_to_int(value)?;
+    u32::try_from(int).map_err(|_| MathError::DomainError(format!("expected an integer from 0 to {}", u32::MAX)))
+}
+
+/// Coefficients of the Lanczos approximation with g = 7 and 9 terms.
+const LANCZOS_G: f64 = 7.0;
+const LANCZOS_COEFFICIENTS: [f64; 9] = [
+    0.999_999_999_999_809_9,
+    676.520_368_121_885_1,
+    -1_259.139_216_722_402_8,
+    771.323_428_777_653_1,
+    -176.615_029_162_140_6,
+    12.507_343_278_686_905,
+    -0.138_571_095_265_720_12,
+    9.984_369_578_019_572e-6,
+    1.505_632_735_149_311_6e-7,
+];
+
+/// Gamma function by the Lanczos approximation.
+///
+/// Arguments below 1/2 use the reflection formula. Zero and the negative
+/// integers are poles and give a domain error.
+fn gamma(x: f64) -> Result<f64, MathError> {
+    if x <= 0.0 && x.fract() == 0.0 {
+        return Err(MathError::DomainError("gamma is undefined for zero and negative integers".to_string()));
+    }
+
+    if x < 0.5 {
+        // Reflection: gamma(x) * gamma(1 - x) = pi / sin(pi * x)
+        let pi = std::f64::consts::PI;
+        return Ok(pi / ((pi * x).sin() * gamma(1.0 - x)?));
+    }
+
+    let x = x - 1.0;
+    let sum = LANCZOS_COEFFICIENTS[1..]
+        .iter()
+        .enumerate()
+        .fold(LANCZOS_COEFFICIENTS[0], |sum, (index, coefficient)| sum + coefficient / (x + index as f64 + 1.0));
+    let t = x + LANCZOS_G + 0.5;
+
+    Ok((2.0 * std::f64::consts::PI).sqrt() * t.powf(x + 0.5) * (-t).exp() * sum)
+}
+
+/// Error function by its Taylor series.
+///
+/// Beyond |x| = 4 the function is 1 to well within `f32` precision, and the
+/// series would lose accuracy, so it returns ±1 there.
+fn erf(x: f64) -> f64 {
+    if x.abs() >= 4.0 {
+        return x.signum();
+    }
+
+    // erf(x) = 2/sqrt(pi) * sum of (-1)^n x^(2n+1) / (n! (2n+1))
+    let mut term = x;
+    let mut sum = x;
+    let mut n = 0.0;
+    while term.abs() > 1e-17 * sum.abs().max(f64::MIN_POSITIVE) {
+        n += 1.0;
+        term *= -x * x / n;
+        sum += term / (2.0 * n + 1.0);
+    }
+
+    2.0 / std::f64::consts::PI.sqrt() * sum
+}
+
+/// Greatest common divisor of two integers, always non-negative.
+fn gcd(a: i128, b: i128) -> i128 {
+    let (mut a, mut b) = (a.unsigned_abs(), b.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a as i128
+}
+
+/// Least common multiple of two integers, always non-negative, or `None` on overflow.
+fn lcm(a: i128, b: i128) -> Option<i128> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a.abs() / gcd(a, b)).checked_mul(b.abs())
+}
+
+/// Converts every argument to an integer, failing on the first that is not one.
+fn integer_args(args: &[f32]) -> Result<Vec<i128>, MathError> {
+    args.iter().map(|&arg| truncate_to_int(arg).map(i128::from)).collect()
+}
+
+/// Calls a built-in function with already evaluated arguments.
+///
+/// Returns `None` if no built-in function has the given name. The number of
+/// arguments is checked against the function's spec before dispatching, so
+/// the arms below can index their arguments freely.
+pub(crate) fn call_builtin(name: &str, args: &[f32]) -> Option<Result<f32, EvalError>> {
+    let spec = find_builtin(name)?;
+    if !spec.arity.contains(&args.len()) {
+        // Report the nearest accepted count for functions with optional arguments
+        let expected = if args.len() < *spec.arity.start() { *spec.arity.start() } else { *spec.arity.end() };
+        return Some(Err(ControlFlowError::WrongArgumentCount {
+            name: name.to_string(),
+            expected,
+            got: args.len(),
+        }.into()));
+    }
+
+    let result = match name {
+        "sin"   => Ok(args[0].sin()),
+        "cos"   => Ok(args[0].cos()),
+        "tan"   => Ok(args[0].tan()),
+        // Additional trigonometric functions
+        "cot"   => {
+            let tan_val = args[0].tan();
+            if tan_val == 0.0 {
+                Err(MathError::UnsupportedFunction("Division by zero in cotangent".to_string()).into())
+            } else {
+                Ok(1.0 / tan_val)
+            }
+        },
+        "sec"   => {
+            let cos_val = args[0].cos();
+            if cos_val == 0.0 {
+                Err(MathError::UnsupportedFunction("Division by zero in secant".to_string()).into())
+            } else {
+                Ok(1.0 / cos_val)
+            }
+        },
+        "csc"   => {
+            let sin_val = args[0].sin();
+            if sin_val == 0.0 {
+                Err(MathError::UnsupportedFunction("Division by zero in cosecant".to_string()).into())
+            } else {
+                Ok(1.0 / sin_val)
+            }
+        },
+        // Inverse trigonometric functions
+        "asin"  => Ok(args[0].asin()),
+        "acos"  => Ok(args[0].acos()),
+        "atan"  => Ok(args[0].atan()),
+        "atan2" => {
+            if args.len() != 2 {
+                return Some(Err(MathError::UnsupportedFunction("atan2 requires two arguments: y, x".to_string()).into()));
+            }
+            let y = args[0];
+            let x = args[1];
+            Ok(y.atan2(x))
+        },
+        "log"   => Ok(args[0].ln()),
+        "sqrt"  => Ok(args[0].sqrt()),
+        "root"  => nth_root(args[0], args[1]),
+        "abs"   => Ok(args[0].abs()),
+        "round" => Ok(args[0].round()),
+        // Banker's rounding, which does not drift upwards when summing many halves
+        "round_even" => Ok(args[0].round_ties_even()),
+        "percent" => match args[0] / 100.0 * args[1] {
+            value if value.is_infinite() && args.iter().all(|arg| arg.is_finite()) => Err(MathError::Overflow.into()),
+            value => Ok(value),
+        },
+        "bool"  => Ok(if args[0] != 0.0 { 1.0 } else { 0.0 }),
+        "iszero" => Ok(if args[0] == 0.0 { 1.0 } else { 0.0 }),
+        "max"   => Ok(args[0].max(args[1])),
+        "min"   => Ok(args[0].min(args[1])),
+        // Interpolation
+        "saturate" => Ok(args[0].clamp(0.0, 1.0)),
+        "lerp"  => Ok(args[0] + (args[1] - args[0]) * args[2]),
+        "smoothstep" => {
+            if args[0] == args[1] {
+                return Some(Err(MathError::DomainError("smoothstep needs two different edges".to_string()).into()));
+            }
+            let t = ((args[2] - args[0]) / (args[1] - args[0])).clamp(0.0, 1.0);
+            Ok(t * t * (3.0 - 2.0 * t))
+        },
+        "wrap" => {
+            let (value, lo, hi) = (args[0], args[1], args[2]);
+            // Also rejects NaN bounds
+            if lo.partial_cmp(&hi) != Some(std::cmp::Ordering::Less) {
+                return Some(Err(MathError::DomainError("wrap needs lo to be less than hi".to_string()).into()));
+            }
+            let wrapped = lo + (value - lo).rem_euclid(hi - lo);
+            // Rounding can land exactly on hi, which belongs to the start of the range
+            Ok(if wrapped >= hi { lo } else { wrapped })
+        },
+        "fact"  => match truncate_to_int(args[0]) {
+            Ok(n) if n < 0 => Err(MathError::DomainError("factorial of a negative number".to_string()).into()),
+            Ok(n) if n > MAX_FACTORIAL => Err(MathError::Overflow.into()),
+            Ok(n) => Ok((2..=n).map(|k| k as f64).product::<f64>() as f32),
+            Err(error) => Err(error.into()),
+        },
+        // Both fold over all their arguments, so one argument gives its absolute value
+        "gcd"   => integer_args(args)
+            .map(|ints| ints.into_iter().fold(0, gcd) as f32)
+            .map_err(Into::into),
+        "lcm"   => integer_args(args)
+            .and_then(|ints| ints.into_iter().try_fold(1, lcm).ok_or(MathError::Overflow))
+            .map(|value| value as f32)
+            .map_err(Into::into),
+        // Bit operations
+        "popcount" => to_u32(args[0]).map(|n| n.count_ones() as f32).map_err(Into::into),
+        "clz"   => to_u32(args[0]).map(|n| n.leading_zeros() as f32).map_err(Into::into),
+        "ctz"   => to_u32(args[0]).map(|n| n.trailing_zeros() as f32).map_err(Into::into),
+        // Special functions
+        "sinc"  => Ok(if args[0] == 0.0 { 1.0 } else { args[0].sin() / args[0] }),
+        "gamma" => match gamma(args[0] as f64) {
+            Ok(value) if (value as f32).is_finite() => Ok(value as f32),
+            Ok(_) => Err(MathError::Overflow.into()),
+            Err(error) => Err(error.into()),
+        },
+        "erf"   => Ok(erf(args[0] as f64) as f32),
+        // Complex helpers; complex arguments are handled in complex mode
+        "arg"   => Ok(if args[0] < 0.0 { std::f32::consts::PI } else { 0.0 }),
+        "conj"  => Ok(args[0]),
+        // Reached only when the loop variable is not a plain name; see `Expression::evaluate`
+        "fold" => Err(MathError::UnsupportedFunction("fold() expects a variable name, two bounds, a start value and a body, e.g. fold(i, 1, 5, 0, acc + i)".to_string()).into()),
+        // Reached only for values other than plain names, which are all numbers; see `Expression::evaluate`
+        "typeof" => Ok(BindingKind::Variable.code()),
+        // Reached only when the units are not plain names; see `Expression::evaluate`
+        "convert" => Err(MathError::UnsupportedFunction("convert() expects a value and two unit names, e.g. convert(5, km, mi)".to_string()).into()),
+        "rand"  => {
+            if args.iter().any(|arg| !arg.is_finite()) {
+                return Some(Err(MathError::DomainError("rand() needs finite bounds".to_string()).into()));
+            }
+            // Also catches bounds so far apart that the width of the range overflows
+            if args.len() == 2 && !(args[0] < args[1] && (args[1] - args[0]).is_finite()) {
+                return Some(Err(MathError::UnsupportedFunction("min must be less than max".to_string()).into()));
+            }
+            // Draw from the execution's generator, which is seeded in deterministic runs
+            with_random_state(|state| state.with_rng(|rng| {
+                if args.is_empty() {
+                    // rand() with no args: returns a value between 0 and 1
+                    Ok(rng.gen::<f32>())
+                } else if args.len() == 1 {
+                    // rand(max): returns a value between 0 and max
+                    let max = args[0];
+                    Ok(rng.gen::<f32>() * max)
+                } else {
+                    // rand(min, max): returns a value between min and max
+                    let min = args[0];
+                    let max = args[1];
+                    Ok(rng.gen_range(min..max))
+                }
+            }))
+        },
+        _ => return None,
+    };
+
+    Some(result)
+}
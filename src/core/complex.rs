@@ -0,0 +1,341 @@
+//! Complex number evaluation for the opt-in complex mode.
+//!
+//! Real mode stays the default. In complex mode an expression evaluates to
+//! a [`Value`], `i` is the imaginary unit and `2i` is an imaginary literal.
+//! Variables, constants and user functions stay real: they are used as they
+//! are whenever all of their inputs are real.
+
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::core::ast_expression::{apply_operator, call_function, name_divisor, nth_root, Expression};
+use num_bigint::BigInt;
+
+use crate::core::bignum::big_to_f32;
+use crate::core::error_types::{EvalError, MathError};
+use crate::core::operator::Operator;
+use crate::core::symbol_manager::SymbolTable;
+
+/// Options that change how an expression is evaluated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EvalOptions {
+    /// Evaluate with complex numbers, so `sqrt(-4)` gives `2i` instead of `NaN`.
+    pub complex: bool,
+
+    /// Evaluate with exact fractions, so `1/3 + 1/6` gives `1/2`.
+    pub exact: bool,
+
+    /// In exact mode, fail instead of approximating results that are not exact.
+    pub strict_exact: bool,
+}
+
+/// The result of an evaluation: a real, complex, exact rational or big integer number.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Real(f32),
+    Complex(f32, f32),
+    /// A fraction in lowest terms with a positive denominator, from exact mode.
+    Rational(i64, i64),
+    /// An integer too large for `f32` to hold exactly.
+    BigInt(BigInt),
+}
+
+impl Value {
+    /// Builds a value from its parts, dropping an imaginary part of zero.
+    pub fn from_parts(re: f32, im: f32) -> Self {
+        if im == 0.0 { Value::Real(re) } else { Value::Complex(re, im) }
+    }
+
+    /// Returns the real part, approximated for fractions.
+    pub fn re(&self) -> f32 {
+        match self {
+            Value::Real(re) | Value::Complex(re, _) => *re,
+            Value::Rational(num, den) => (*num as f64 / *den as f64) as f32,
+            Value::BigInt(value) => big_to_f32(value),
+        }
+    }
+
+    /// Returns the imaginary part, zero for real values.
+    pub fn im(&self) -> f32 {
+        match self {
+            Value::Real(_) | Value::Rational(..) | Value::BigInt(_) => 0.0,
+            Value::Complex(_, im) => *im,
+        }
+    }
+
+    /// Returns the value as a real number, or `None` if it has an imaginary part.
+    pub fn as_real(&self) -> Option<f32> {
+        match self {
+            Value::Real(value) => Some(*value),
+            Value::Rational(..) | Value::BigInt(_) => Some(self.re()),
+            Value::Complex(..) => None,
+        }
+    }
+
+    /// Formats the value, as `true` or `false` if `boolean` says it is a truth value.
+    ///
+    /// The hint comes from [`is_boolean_result`](crate::core::is_boolean_result).
+    /// Values other than 1 and 0 are formatted as numbers either way.
+    pub fn format_with_hint(&self, boolean: bool) -> String {
+        match *self {
+            Value::Real(1.0) | Value::Rational(1, 1) if boolean => "true".to_string(),
+            Value::Real(0.0) | Value::Rational(0, 1) if boolean => "false".to_string(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    /// Formats values like `3`, `2i`, `-i`, `3 - 2i` and `1/2 ≈ 0.5`.
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (re, im) = match *self {
+            Value::Real(value) => return write!(formatter, "{}", value),
+            Value::Rational(num, 1) => return write!(formatter, "{}", num),
+            Value::Rational(num, den) => return write!(formatter, "{}/{} ≈ {}", num, den, self.re()),
+            Value::BigInt(ref value) => return write!(formatter, "{}", value),
+            Value::Complex(re, im) => (re, im),
+        };
+
+        let magnitude = match im.abs() {
+            1.0 => String::new(),
+            other => other.to_string(),
+        };
+
+        if re == 0.0 {
+            let sign = if im < 0.0 { "-" } else { "" };
+            write!(formatter, "{}{}i", sign, magnitude)
+        } else {
+            let sign = if im < 0.0 { '-' } else { '+' };
+            write!(formatter, "{} {} {}i", re, sign, magnitude)
+        }
+    }
+}
+
+/// A complex number used for the arithmetic of complex mode.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Complex {
+    re: f32,
+    im: f32,
+}
+
+impl Complex {
+    fn new(re: f32, im: f32) -> Self {
+        Self { re, im }
+    }
+
+    fn abs(self) -> f32 {
+        self.re.hypot(self.im)
+    }
+
+    fn arg(self) -> f32 {
+        self.im.atan2(self.re)
+    }
+
+    fn exp(self) -> Self {
+        let scale = self.re.exp();
+        Self::new(scale * self.im.cos(), scale * self.im.sin())
+    }
+
+    /// Principal natural logarithm.
+    fn ln(self) -> Self {
+        Self::new(self.abs().ln(), self.arg())
+    }
+
+    /// Principal square root, exact for negative reals like -4.
+    fn sqrt(self) -> Self {
+        let modulus = self.abs();
+        let re = ((modulus + self.re) / 2.0).sqrt();
+        let im = ((modulus - self.re) / 2.0).sqrt();
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    /// Principal power `self ^ exponent`.
+    fn pow(self, exponent: Self) -> Self {
+        if self.re == 0.0 && self.im == 0.0 {
+            let one = exponent.re == 0.0 && exponent.im == 0.0;
+            return Self::new(if one { 1.0 } else { 0.0 }, 0.0);
+        }
+        (exponent * self.ln()).exp()
+    }
+
+    fn checked_div(self, divisor: Self) -> Result<Self, EvalError> {
+        if divisor.re == 0.0 && divisor.im == 0.0 {
+            return Err(MathError::DivisionByZero(None).into());
+        }
+        Ok(self / divisor)
+    }
+}
+
+impl From<Value> for Complex {
+    fn from(value: Value) -> Self {
+        Self::new(value.re(), value.im())
+    }
+}
+
+impl From<Complex> for Value {
+    fn from(value: Complex) -> Self {
+        Value::from_parts(value.re, value.im)
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Self::new(
+            self.re * other.re - self.im * other.im,
+            self.re * other.im + self.im * other.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        let denominator = other.re * other.re + other.im * other.im;
+        Self::new(
+            (self.re * other.re + self.im * other.im) / denominator,
+            (self.im * other.re - self.re * other.im) / denominator,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+impl Expression {
+    /// Evaluates the expression in complex mode.
+    ///
+    /// Real inputs give the same results as [`Expression::evaluate`], except
+    /// where real mode has no answer: `sqrt(-4)` is `2i` and `(-8) ^ (1/3)`
+    /// is the principal complex root.
+    pub fn evaluate_complex(&self, context: &SymbolTable<f32>) -> Result<Value, EvalError> {
+        match self {
+            Expression::Literal(text) => {
+                if text == "i" {
+                    return Ok(Value::Complex(0.0, 1.0));
+                }
+                if let Some(Ok(imaginary)) = text.strip_suffix('i').map(str::parse::<f32>) {
+                    return Ok(Value::from_parts(0.0, imaginary));
+                }
+                self.evaluate(context).map(Value::Real)
+            }
+
+            // Variables only hold real values
+            Expression::Operation(Operator::Assign, _) => self.evaluate(context).map(Value::Real),
+
+            Expression::Operation(operator, operands) => {
+                let values = operands
+                    .iter()
+                    .map(|operand| operand.evaluate_complex(context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                apply_complex_operator(*operator, &values).map_err(|error| name_divisor(error, *operator, operands))
+            }
+
+            // Unit names, the loop of fold() and the name in typeof() are not values, so they stay real
+            Expression::FunctionCall(name, _) if matches!(name.as_str(), "convert" | "fold" | "typeof") => self.evaluate(context).map(Value::Real),
+
+            Expression::FunctionCall(name, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| arg.evaluate_complex(context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                call_complex_function(name, &values, context)
+            }
+        }
+    }
+}
+
+/// Applies an operator in complex mode, deferring to real mode for real operands.
+fn apply_complex_operator(operator: Operator, values: &[Value]) -> Result<Value, EvalError> {
+    let unary = values.len() == 1;
+    let left = values[0].clone();
+    let right = values.get(1).cloned().unwrap_or(Value::Real(0.0));
+
+    if let (Some(l), Some(r)) = (left.as_real(), right.as_real()) {
+        // Only powers and roots of negative numbers leave the real line
+        let leaves_real_line = match operator {
+            Operator::Power => l < 0.0 && r.fract() != 0.0,
+            Operator::Root if !unary => l != 0.0 && nth_root(l, r).is_err(),
+            _ => false,
+        };
+        if !leaves_real_line {
+            return apply_operator(operator, unary, l, r).map(Value::Real);
+        }
+    }
+
+    let (l, r) = (Complex::from(left), Complex::from(right));
+    let result = match operator {
+        Operator::Add if unary => l,
+        Operator::Subtract if unary => -l,
+        Operator::Add => l + r,
+        Operator::Subtract => l - r,
+        Operator::Multiply => l * r,
+        Operator::Divide => l.checked_div(r)?,
+        Operator::Power => l.pow(r),
+        Operator::Root if !unary => r.pow(Complex::new(1.0, 0.0).checked_div(l)?),
+        // Equality compares both parts with the same tolerance as real mode
+        Operator::Equal | Operator::NotEqual => {
+            let equal = (l.re - r.re).abs() < f32::EPSILON && (l.im - r.im).abs() < f32::EPSILON;
+            return Ok(Value::Real(if equal == (operator == Operator::Equal) { 1.0 } else { 0.0 }));
+        }
+        other => {
+            return Err(MathError::DomainError(format!(
+                "operator '{}' is not defined for complex numbers",
+                other,
+            )).into());
+        }
+    };
+
+    Ok(result.into())
+}
+
+/// Calls a function in complex mode.
+///
+/// `sqrt`, `log`, `abs`, `arg` and `conj` accept complex arguments.
+/// Any other function is called in real mode and refuses complex arguments.
+fn call_complex_function(name: &str, values: &[Value], context: &SymbolTable<f32>) -> Result<Value, EvalError> {
+    if let [value] = values {
+        let z = Complex::from(value.clone());
+        let negative_real = value.as_real().is_some_and(|x| x < 0.0);
+
+        match name {
+            "sqrt" if value.as_real().is_none() || negative_real => return Ok(z.sqrt().into()),
+            "log" if value.as_real().is_none() || negative_real => return Ok(z.ln().into()),
+            "abs" => return Ok(Value::Real(z.abs())),
+            "arg" => return Ok(Value::Real(if z.re == 0.0 && z.im == 0.0 { 0.0 } else { z.arg() })),
+            "conj" => return Ok(Value::from_parts(z.re, -z.im)),
+            _ => {}
+        }
+    }
+
+    let reals = values
+        .iter()
+        .map(Value::as_real)
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| MathError::DomainError(format!("{}() does not accept complex arguments", name)))?;
+
+    call_function(name, reals, context).map(Value::Real)
+}
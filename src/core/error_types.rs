@@ -2,6 +2,7 @@
 
 use std::fmt;
 use std::error::Error;
+use crate::core::symbol_manager::{is_result_name, reserved_names};
 
 /// Error during parsing of a token stream into an AST.
 #[derive(Debug, Clone)]
@@ -41,6 +42,9 @@ pub enum ParseError {
     
     /// Syntax error with message.
     SyntaxError(String),
+
+    /// Input nested deeper than the parser's limit.
+    TooDeeplyNested(usize),
     
     /// Expected something but found something else.
     Expected {
@@ -68,6 +72,7 @@ impl fmt::Display for ParseError {
             ParseError::EmptyBlock => write!(formatter, "Empty code block. A block should contain at least one statement."),
             ParseError::InvalidNumber(msg) => write!(formatter, "Invalid number format: {}", msg),
             ParseError::SyntaxError(msg) => write!(formatter, "Syntax error: {}", msg),
+            ParseError::TooDeeplyNested(limit) => write!(formatter, "Input is nested too deeply. At most {} levels of parentheses, blocks or operators are allowed; split long chains like `a + b + c + …` into variables.", limit),
             ParseError::Expected { expected, found } => write!(formatter, "Expected {}, but found {} instead.", expected, found),
         }
     }
@@ -106,11 +111,13 @@ pub enum ExecutionError {
     /// Maximum execution time exceeded
     TimeoutExceeded,
     
-    /// Maximum iterations exceeded
-    MaxIterationsExceeded,
-    
     /// General execution error with message
     ExecutionFailed(String),
+
+    /// Input that would change the variables where only reading is allowed.
+    ///
+    /// Carries what the input does, e.g. ``assigns `x` ``.
+    SideEffect(String),
     
     /// Error propagated from expression evaluation
     EvaluationError(EvalError),
@@ -119,11 +126,11 @@ pub enum ExecutionError {
 /// Errors related to mathematical operations
 #[derive(Debug, Clone)]
 pub enum MathError {
-    /// Division by zero was attempted.
-    DivisionByZero,
+    /// Division by zero was attempted, with the source of the divisor when known.
+    DivisionByZero(Option<String>),
 
-    /// Modulo by zero was attempted.
-    ModuloByZero,
+    /// Modulo by zero was attempted, with the source of the divisor when known.
+    ModuloByZero(Option<String>),
 
     /// Exponentiation was performed with invalid operands.
     InvalidExponentiation,
@@ -134,7 +141,7 @@ pub enum MathError {
     /// A function call was made to an unsupported function.
     UnsupportedFunction(String),
 
-    /// A root operation on a negative number was attempted.
+    /// An even or fractional root of a negative number was attempted.
     NegativeRoot,
 
     /// A zeroth root was attempted (mathematically undefined).
@@ -148,6 +155,15 @@ pub enum MathError {
     
     /// Result is not a number (NaN)
     NotANumber,
+
+    /// A unit name is not in the unit table
+    UnknownUnit(String),
+
+    /// A conversion between units of different dimensions
+    IncompatibleUnits { from: String, to: String },
+
+    /// An operation without an exact result in strict exact mode
+    InexactResult(String),
 }
 
 /// Errors related to variables and symbols
@@ -167,6 +183,67 @@ pub enum SymbolError {
     
     /// Invalid variable or constant name
     InvalidIdentifier(String),
+    
+    /// No snippet has been published under the imported name
+    SnippetNotFound(String),
+    
+    /// A snippet name is already taken by another user
+    SnippetOwnedByAnotherUser(String),
+
+    /// A snippet name is longer than names may be
+    SnippetNameTooLong {
+        /// The most characters a snippet name may have
+        limit: usize,
+    },
+
+    /// A user has published as many snippets as they may
+    TooManySnippets {
+        /// The most snippets one user may publish
+        limit: usize,
+    },
+
+    /// A global constant name was published by another user
+    SharedConstantOwnedByAnotherUser(String),
+
+    /// A global constant was declared where it cannot be published
+    GlobalConstantNotAllowed(String),
+
+    /// A user has published as many global constants as they may
+    TooManySharedConstants {
+        /// The most constants one user may publish
+        limit: usize,
+    },
+
+    /// A formula depends on its own value
+    CyclicFormula(String),
+
+    /// A `memo fn` function whose result can change between calls with the same arguments
+    ImpureMemoFunction(String),
+
+    /// A variable or constant was called like a function
+    NotCallable(String),
+
+    /// A function or procedure was read like a variable
+    CallableUsedAsVariable {
+        /// Name of the callable item
+        name: String,
+        /// Type of the callable item ("function" or "procedure")
+        kind: String,
+    },
+
+    /// A function or procedure body is too large to keep
+    LimitExceeded {
+        /// Name of the callable item
+        name: String,
+        /// Type of the callable item ("function" or "procedure")
+        kind: String,
+        /// Syntax nodes the body has, or all stored bodies would have together
+        nodes: usize,
+        /// The limit the nodes are over
+        limit: usize,
+        /// Whether the limit is on all stored bodies together rather than this one
+        session_total: bool,
+    },
 }
 
 /// Errors related to control flow
@@ -204,6 +281,15 @@ pub enum ControlFlowError {
         name: String,
     },
     
+    /// A procedure was called where a value is needed.
+    ///
+    /// Procedures do not return values, so they can only be called as
+    /// statements of their own.
+    ProcedureUsedAsValue {
+        /// Name of the procedure
+        name: String,
+    },
+    
     /// Wrong number of arguments in function call.
     WrongArgumentCount {
         /// Name of the function
@@ -213,6 +299,69 @@ pub enum ControlFlowError {
         /// Actual number of arguments
         got: usize,
     },
+    
+    /// A snippet imports itself, directly or through other snippets.
+    RecursiveImport {
+        /// Name of the snippet that closes the cycle
+        name: String,
+    },
+    
+    /// Imports were nested deeper than the import depth limit.
+    ImportTooDeep {
+        /// Name of the snippet that would have gone past the limit
+        name: String,
+        /// The most imports that may be nested
+        limit: usize,
+    },
+    
+    /// An execution ran more loop iterations than the loop iteration limit.
+    TooManyIterations {
+        /// The most iterations one execution may run
+        limit: usize,
+    },
+
+    /// Calls were nested deeper than the call depth limit.
+    CallTooDeep {
+        /// The most calls that may be nested
+        limit: usize,
+    },
+
+    /// An execution ran for longer than the time limit.
+    TimedOut {
+        /// The time limit in milliseconds
+        limit_ms: usize,
+    },
+
+    /// A snippet could not be imported.
+    ImportFailed {
+        /// Name of the snippet
+        name: String,
+        /// Why the import failed
+        reason: String,
+    },
+    
+    /// An `assert` statement found its condition false.
+    ///
+    /// Carries the source text of the condition. Execution reports it as
+    /// `ExecutionError::ExecutionFailed`.
+    AssertionFailed(String),
+}
+
+impl EvalError {
+    /// Checks whether a `try` statement may catch the error.
+    ///
+    /// Errors from reaching an execution limit are not caught, since
+    /// catching them inside a loop would multiply the limit.
+    pub fn is_catchable(&self) -> bool {
+        !matches!(
+            self,
+            EvalError::ControlFlowError(
+                ControlFlowError::TooManyIterations { .. }
+                    | ControlFlowError::CallTooDeep { .. }
+                    | ControlFlowError::TimedOut { .. }
+            )
+        )
+    }
 }
 
 impl Error for EvalError {}
@@ -224,16 +373,21 @@ impl Error for ControlFlowError {}
 impl fmt::Display for MathError {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            MathError::DivisionByZero => write!(formatter, "Division by zero error. Cannot divide by zero."),
-            MathError::ModuloByZero => write!(formatter, "Modulo by zero error. Cannot compute modulo with zero divisor."),
+            MathError::DivisionByZero(None) => write!(formatter, "Division by zero error. Cannot divide by zero."),
+            MathError::DivisionByZero(Some(divisor)) => write!(formatter, "Division by zero error: divisor `{}` evaluated to 0.", divisor),
+            MathError::ModuloByZero(None) => write!(formatter, "Modulo by zero error. Cannot compute modulo with zero divisor."),
+            MathError::ModuloByZero(Some(divisor)) => write!(formatter, "Modulo by zero error: divisor `{}` evaluated to 0.", divisor),
             MathError::InvalidExponentiation => write!(formatter, "Invalid exponentiation. Cannot raise a negative number to a fractional power."),
             MathError::UnsupportedOperator(op) => write!(formatter, "Unsupported operator: {}", op),
             MathError::UnsupportedFunction(func) => write!(formatter, "Unsupported function: {}", func),
-            MathError::NegativeRoot => write!(formatter, "Cannot compute roots of negative numbers with non-integer degree."),
+            MathError::NegativeRoot => write!(formatter, "Cannot compute even or fractional roots of negative numbers."),
             MathError::ZerothRoot => write!(formatter, "Cannot compute the zeroth root of a number (mathematically undefined)."),
             MathError::DomainError(msg) => write!(formatter, "Math domain error: {}", msg),
             MathError::Overflow => write!(formatter, "Numerical overflow or underflow occurred."),
             MathError::NotANumber => write!(formatter, "Operation resulted in not-a-number (NaN)."),
+            MathError::UnknownUnit(unit) => write!(formatter, "Unknown unit: '{}'.", unit),
+            MathError::IncompatibleUnits { from, to } => write!(formatter, "Cannot convert {} to {}. Both units must measure the same quantity.", from, to),
+            MathError::InexactResult(expression) => write!(formatter, "'{}' has no exact result. Turn off strict exact mode to approximate it.", expression),
         }
     }
 }
@@ -241,11 +395,27 @@ impl fmt::Display for MathError {
 impl fmt::Display for SymbolError {
     fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            SymbolError::VariableNotFound(name) if is_result_name(name) => write!(formatter, "'{}' refers to the result of an earlier top-level statement of the script, but there is no such result yet.", name),
             SymbolError::VariableNotFound(variable) => write!(formatter, "Variable '{}' not found. Make sure it is defined before use.", variable),
             SymbolError::UndeclaredVariable(variable) => write!(formatter, "Undeclared variable: '{}'. Variables must be declared with 'let' before assignment.", variable),
             SymbolError::ImmutableConstant(variable) => write!(formatter, "Cannot modify constant: '{}'. Constants declared with 'const' are immutable.", variable),
             SymbolError::Redefinition(variable) => write!(formatter, "Redefinition of '{}' in the same scope.", variable),
+            SymbolError::InvalidIdentifier(name) if reserved_names().contains(name.as_str()) => write!(formatter, "'{}' is a keyword or the name of a built-in function, so it cannot name a variable or constant. Choose a different name.", name),
+            SymbolError::InvalidIdentifier(name) if is_result_name(name) => write!(formatter, "'{}' holds the result of an earlier statement of the script, so it cannot be declared or assigned. Choose a different name.", name),
             SymbolError::InvalidIdentifier(name) => write!(formatter, "Invalid identifier name: '{}'.", name),
+            SymbolError::SnippetNotFound(name) => write!(formatter, "No published snippet named '{}' was found. Make sure it has been published before importing it.", name),
+            SymbolError::SnippetOwnedByAnotherUser(name) => write!(formatter, "The snippet name '{}' is already taken by another user. Choose a different name.", name),
+            SymbolError::SnippetNameTooLong { limit } => write!(formatter, "Snippet names can be at most {} characters long.", limit),
+            SymbolError::TooManySnippets { limit } => write!(formatter, "You have already published {} snippets, the most one user may publish. Republish one of your names to replace it.", limit),
+            SymbolError::SharedConstantOwnedByAnotherUser(name) => write!(formatter, "The global constant '{}' was published by another user and cannot be changed. Choose a different name.", name),
+            SymbolError::GlobalConstantNotAllowed(name) => write!(formatter, "Global constant '{}' cannot be declared here. Global constants can only be published by code run with /execute.", name),
+            SymbolError::TooManySharedConstants { limit } => write!(formatter, "You have already published {} global constants here, the most one user may publish.", limit),
+            SymbolError::CyclicFormula(name) => write!(formatter, "Formula '{}' depends on itself. Break the cycle by assigning a value to one of the names involved.", name),
+            SymbolError::ImpureMemoFunction(name) => write!(formatter, "'{}' cannot be a memo fn, since its result can change between calls with the same arguments. Only read its parameters, locals and built-in constants, and do not call rand() or procedures, or drop 'memo'.", name),
+            SymbolError::NotCallable(name) => write!(formatter, "'{}' is a variable, not a function. Remove the parentheses to read its value.", name),
+            SymbolError::CallableUsedAsVariable { name, kind } => write!(formatter, "'{}' is a {}, not a variable. Did you mean '{}(...)'?", name, kind, name),
+            SymbolError::LimitExceeded { name, kind, nodes, limit, session_total: false } => write!(formatter, "The {} '{}' is too large: its body has {} syntax nodes, over the limit of {}. Split it into smaller functions.", kind, name, nodes, limit),
+            SymbolError::LimitExceeded { name, kind, nodes, limit, session_total: true } => write!(formatter, "Cannot keep the {} '{}': your functions and procedures would have {} syntax nodes together, over the limit of {}. Remove some you no longer need with /remove.", kind, name, nodes, limit),
         }
     }
 }
@@ -261,7 +431,15 @@ impl fmt::Display for ControlFlowError {
             ControlFlowError::UnimplementedFeature(msg) => write!(formatter, "Unimplemented feature: {}", msg),
             ControlFlowError::FunctionOrProcedureAlreadyDefined { name, kind } => write!(formatter, "{} '{}' already defined in the same scope.", kind, name),
             ControlFlowError::FunctionOrProcedureNotFound { name } => write!(formatter, "No callable item named '{}' was found. Make sure the function or procedure is defined before calling it.", name),
+            ControlFlowError::ProcedureUsedAsValue { name } => write!(formatter, "Procedure '{}' does not return a value, so it cannot be used in an expression. Call it as a statement on its own, as in '{}(...)', or declare it with 'fn' to return a value.", name, name),
             ControlFlowError::WrongArgumentCount { name, expected, got } => write!(formatter, "Callable '{}' called with wrong number of arguments. Expected {}, got {}.", name, expected, got),
+            ControlFlowError::RecursiveImport { name } => write!(formatter, "Recursive import of snippet '{}'. A snippet cannot import itself, directly or indirectly.", name),
+            ControlFlowError::ImportTooDeep { name, limit } => write!(formatter, "Failed to import snippet '{}': imports cannot be nested more than {} levels deep.", name, limit),
+            ControlFlowError::TooManyIterations { limit } => write!(formatter, "The code ran {} loop iterations without finishing and was stopped. Make sure every loop's condition eventually becomes false, or leave it with 'break'.", limit),
+            ControlFlowError::CallTooDeep { limit } => write!(formatter, "Calls were nested more than {} levels deep and were stopped. Make sure recursion reaches a base case, or make the recursive call the last thing the function does.", limit),
+            ControlFlowError::TimedOut { limit_ms } => write!(formatter, "The code ran for longer than {} ms and was stopped.", limit_ms),
+            ControlFlowError::ImportFailed { name, reason } => write!(formatter, "Failed to import snippet '{}': {}", name, reason),
+            ControlFlowError::AssertionFailed(condition) => write!(formatter, "assertion failed: {}", condition),
         }
     }
 }
@@ -285,8 +463,8 @@ impl fmt::Display for ExecutionError {
             },
             ExecutionError::StackOverflow => write!(formatter, "Stack overflow: execution too deeply nested"),
             ExecutionError::TimeoutExceeded => write!(formatter, "Execution timeout exceeded"),
-            ExecutionError::MaxIterationsExceeded => write!(formatter, "Maximum iterations exceeded"),
             ExecutionError::ExecutionFailed(msg) => write!(formatter, "Execution failed: {}", msg),
+            ExecutionError::SideEffect(effect) => write!(formatter, "Only expressions that change nothing are allowed here, but the input {}", effect),
             ExecutionError::EvaluationError(err) => write!(formatter, "Evaluation error: {}", err),
         }
     }
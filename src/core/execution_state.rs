@@ -1,8 +1,46 @@
-//! Execution state management for script termination.
-//! 
-//! This module manages state for early termination of scripts.
+//! Execution state management for script termination, imports, memoization,
+//! work and cache counters, random numbers and tracing.
+//!
+//! The state of the script a thread is executing lives in one thread-local
+//! [`ExecutionState`], so executions on different threads, such as workers
+//! evaluating against the same [`FrozenSymbols`](crate::core::FrozenSymbols),
+//! never observe each other's exit, import, memoization or random number state.
 
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Instant;
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+use crate::core::ast_statement::ControlFlow;
+use crate::core::metrics::LimitOverrides;
+use crate::core::shared_constants::SharedConstants;
+use crate::core::snippet_registry::SnippetRegistry;
+
+/// Everything the executing script keeps outside its symbol table.
+///
+/// Each part has its own cell, so one part can be used while another is
+/// borrowed, for example the work count while the limits are read.
+#[derive(Default)]
+struct ExecutionState {
+    exit: RefCell<ExitState>,
+    import: RefCell<ImportState>,
+    shared_constants: RefCell<SharedConstantState>,
+    memo: RefCell<MemoState>,
+    work: RefCell<WorkState>,
+    cache_stats: RefCell<CacheStats>,
+    formulas: RefCell<FormulaState>,
+    results: RefCell<ResultHistory>,
+    limits: RefCell<LimitOverrides>,
+    random: RefCell<RandomState>,
+    trace: RefCell<TraceState>,
+}
+
+thread_local! {
+    static EXECUTION_STATE: ExecutionState = ExecutionState::default();
+}
 
 /// Represents the state when an end statement is executed.
 ///
@@ -32,18 +70,373 @@ impl ExitState {
     }
 }
 
-// Thread-local storage for the exit state
-thread_local! {
-    static EXIT_STATE: RefCell<ExitState> = RefCell::new(ExitState::default());
-}
-
 /// Provides access to the current exit state for the executing script.
 ///
-/// This function allows controlled access to the thread-local exit state,
+/// This function allows controlled access to the thread's exit state,
 /// enabling operations like checking if an exit occurred or setting exit values.
 pub fn with_exit_state<F, R>(f: F) -> R
 where
     F: FnOnce(&mut ExitState) -> R,
 {
-    EXIT_STATE.with(|cell| f(&mut *cell.borrow_mut()))
-} 
+    EXECUTION_STATE.with(|state| f(&mut state.exit.borrow_mut()))
+}
+
+/// Tracks the snippets available to `import` during a single execution.
+///
+/// The registry is installed by `execute_with_imports` and cleared afterwards,
+/// the stack holds the snippets currently being imported to reject cycles.
+#[derive(Clone, Debug, Default)]
+pub struct ImportState {
+    /// Published snippets that may be imported.
+    pub registry: SnippetRegistry,
+    
+    /// Names of the snippets currently being imported, innermost last.
+    pub stack: Vec<String>,
+    
+    /// Names of the snippets already imported during this execution.
+    pub imported: HashSet<String>,
+}
+
+/// Provides access to the import state for the executing script.
+pub fn with_import_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut ImportState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.import.borrow_mut()))
+}
+
+/// Holds the constants shared by every session during a single execution.
+///
+/// The table is installed by `with_shared_constants`, which also sets the
+/// author that `const global` publishes as, or by `reading_shared_constants`
+/// without one.
+#[derive(Clone, Debug, Default)]
+pub struct SharedConstantState {
+    /// Published constants that scripts may read.
+    pub constants: SharedConstants,
+
+    /// The user that new constants are published as, `None` when publishing is not allowed.
+    pub author: Option<u64>,
+}
+
+/// Provides access to the shared constants for the executing script.
+pub fn with_shared_constant_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut SharedConstantState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.shared_constants.borrow_mut()))
+}
+
+/// Caches results of pure user-defined functions during a single execution.
+///
+/// Results are keyed by the function name and the bit patterns of the
+/// evaluated arguments. The cache is cleared at the start and end of every
+/// execution and whenever a function is defined, so a result never outlives
+/// the definition it came from.
+#[derive(Clone, Debug, Default)]
+pub struct MemoState {
+    /// Cached results per function name and argument bits.
+    pub results: HashMap<(String, Vec<u32>), f32>,
+    
+    /// Whether each function called so far was found to be pure.
+    pub purity: HashMap<String, bool>,
+}
+
+impl MemoState {
+    /// Clears all cached results and purity checks.
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+/// Provides access to the memoization cache for the executing script.
+pub fn with_memo_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut MemoState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.memo.borrow_mut()))
+}
+
+/// Counts the work a single execution has done so far.
+///
+/// Every loop iteration and tail call adds to the same count, so the loop
+/// iteration limit bounds the whole execution and nested loops cannot
+/// multiply it. The state is started at the start of every execution and
+/// cleared at its end.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorkState {
+    /// Loop iterations and tail calls run so far.
+    pub iterations: usize,
+
+    /// User function and procedure calls currently running.
+    pub call_depth: usize,
+
+    /// When the execution started, `None` outside an execution.
+    pub started: Option<Instant>,
+
+    /// Where the stack was when the execution started, `None` outside an execution.
+    pub stack_base: Option<usize>,
+}
+
+impl WorkState {
+    /// Clears the counts and starts timing a new execution.
+    pub fn start(&mut self) {
+        *self = WorkState {
+            started: Some(Instant::now()),
+            stack_base: Some(stack_position()),
+            ..Default::default()
+        };
+    }
+
+    /// Clears the counts and stops timing.
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+}
+
+/// Returns the address of a local variable, to tell how much stack is in use.
+#[inline(never)]
+pub(crate) fn stack_position() -> usize {
+    let marker = 0u8;
+    std::ptr::addr_of!(marker) as usize
+}
+
+/// Provides access to the work count of the executing script.
+pub fn with_work_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut WorkState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.work.borrow_mut()))
+}
+
+/// Counts calls answered from a result cache during a measured execution.
+///
+/// Both `memo fn` results and the results of pure functions count. A call
+/// counts once, as a hit if either cache had its result and a miss otherwise.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CacheStats {
+    /// Calls whose result was cached.
+    pub hits: u64,
+
+    /// Calls that could have been cached but had to run.
+    pub misses: u64,
+}
+
+/// Provides access to the cache counters of the executing script.
+pub fn with_cache_stats<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut CacheStats) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.cache_stats.borrow_mut()))
+}
+
+/// Tracks the formulas (`let name := expr`) currently being evaluated.
+///
+/// Cycles between formulas are rejected when they are declared, but a cycle
+/// can also pass through a function body, which is only visible while the
+/// formula is evaluated.
+#[derive(Clone, Debug, Default)]
+pub struct FormulaState {
+    /// Names of the formulas being evaluated.
+    pub evaluating: HashSet<String>,
+}
+
+/// Provides access to the formula state for the current thread.
+pub fn with_formula_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut FormulaState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.formulas.borrow_mut()))
+}
+
+/// The values of the top-level statements a script has run so far.
+///
+/// `_1`, `_2`, … read the value of the first, second, … statement of the
+/// script and `_` the latest value any of them produced. The history is
+/// cleared at the start and end of every execution.
+#[derive(Clone, Debug, Default)]
+pub struct ResultHistory {
+    /// The value of each top-level statement in order, `None` for those without one.
+    pub results: Vec<Option<f32>>,
+}
+
+impl ResultHistory {
+    /// Looks up a result by its name, `_` or `_N`.
+    ///
+    /// Returns `None` for a statement that has not run yet or left no value.
+    pub fn get(&self, name: &str) -> Option<f32> {
+        match name.strip_prefix('_')? {
+            "" => self.results.iter().rev().find_map(|result| *result),
+            position => {
+                let index = position.parse::<usize>().ok()?.checked_sub(1)?;
+                self.results.get(index).copied().flatten()
+            }
+        }
+    }
+}
+
+/// Provides access to the result history of the executing script.
+pub fn with_result_history<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut ResultHistory) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.results.borrow_mut()))
+}
+
+/// Provides access to the limits that replace the defaults on this thread.
+pub fn with_limit_overrides<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut LimitOverrides) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.limits.borrow_mut()))
+}
+
+/// The random number generator used by `rand()`.
+///
+/// Outside a seeded execution numbers come from the thread's entropy-seeded
+/// generator. A seeded execution installs its own generator, so running the
+/// same script with the same seed draws the same numbers.
+#[derive(Clone, Debug, Default)]
+pub struct RandomState {
+    /// The generator of a seeded execution.
+    seeded: Option<StdRng>,
+}
+
+impl RandomState {
+    /// Installs a generator seeded with `seed`.
+    pub fn reseed(&mut self, seed: u64) {
+        self.seeded = Some(StdRng::seed_from_u64(seed));
+    }
+
+    /// Goes back to drawing from the thread's generator.
+    pub fn reset(&mut self) {
+        *self = Default::default();
+    }
+
+    /// Runs `f` with the seeded generator, or the thread's generator if unseeded.
+    pub fn with_rng<R>(&mut self, f: impl FnOnce(&mut dyn RngCore) -> R) -> R {
+        match &mut self.seeded {
+            Some(rng) => f(rng),
+            None => f(&mut rand::thread_rng()),
+        }
+    }
+}
+
+/// Provides access to the random number generator for the executing script.
+pub fn with_random_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut RandomState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.random.borrow_mut()))
+}
+
+/// Most statements recorded in one trace; later ones are only counted.
+const MAX_TRACE_ENTRIES: usize = 1000;
+
+/// One statement run while tracing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceEntry {
+    /// How many traced statements enclose this one.
+    pub depth: usize,
+
+    /// The statement as one line of source; see [`Statement::summary`](crate::core::Statement::summary).
+    pub source: String,
+
+    /// The value the statement produced.
+    pub value: Option<f32>,
+
+    /// How the statement passed on control, e.g. [`ControlFlow::Break`].
+    pub control_flow: ControlFlow,
+
+    /// Whether the statement failed with an error.
+    pub failed: bool,
+}
+
+impl fmt::Display for TraceEntry {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}{}", "  ".repeat(self.depth), self.source)?;
+        if self.failed {
+            return write!(formatter, " → error");
+        }
+        if let Some(value) = self.value {
+            write!(formatter, " → {}", value)?;
+        }
+        match self.control_flow {
+            ControlFlow::Normal => Ok(()),
+            ControlFlow::Break => write!(formatter, " (break)"),
+            ControlFlow::Continue => write!(formatter, " (continue)"),
+            ControlFlow::Return(_) => write!(formatter, " (return)"),
+        }
+    }
+}
+
+/// The statements recorded by a traced execution, in the order they started.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Trace {
+    /// The recorded statements.
+    pub entries: Vec<TraceEntry>,
+
+    /// How many statements ran after the trace was full.
+    pub omitted: usize,
+}
+
+/// Records statements as they run, when tracing is enabled.
+///
+/// Tracing is off by default, so untraced executions only pay for checking
+/// `enabled` once per statement.
+#[derive(Clone, Debug, Default)]
+pub struct TraceState {
+    /// Whether statements are recorded.
+    pub enabled: bool,
+
+    /// How many traced statements are currently running.
+    pub depth: usize,
+
+    /// The statements recorded so far.
+    pub trace: Trace,
+}
+
+impl TraceState {
+    /// Records the start of a statement, returning its entry if it was kept.
+    pub fn enter(&mut self, source: String) -> Option<usize> {
+        let depth = self.depth;
+        self.depth += 1;
+
+        if self.trace.entries.len() >= MAX_TRACE_ENTRIES {
+            self.trace.omitted += 1;
+            return None;
+        }
+        self.trace.entries.push(TraceEntry {
+            depth,
+            source,
+            value: None,
+            control_flow: ControlFlow::Normal,
+            failed: false,
+        });
+        Some(self.trace.entries.len() - 1)
+    }
+
+    /// Records the outcome of the statement started by the matching [`TraceState::enter`].
+    pub fn leave<E>(&mut self, entry: Option<usize>, outcome: &Result<(Option<f32>, ControlFlow), E>) {
+        self.depth -= 1;
+
+        let Some(entry) = entry.and_then(|index| self.trace.entries.get_mut(index)) else {
+            return;
+        };
+        match outcome {
+            Ok((value, control_flow)) => {
+                entry.value = *value;
+                entry.control_flow = *control_flow;
+            }
+            Err(_) => entry.failed = true,
+        }
+    }
+}
+
+/// Provides access to the trace of the executing script.
+pub fn with_trace_state<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut TraceState) -> R,
+{
+    EXECUTION_STATE.with(|state| f(&mut state.trace.borrow_mut()))
+}
@@ -1,40 +1,350 @@
-use crate::core::error_types::{InterpreterError, ExecutionError, ControlFlowError};
-// use crate::core::ast_expression::Expression;
+use crate::core::error_types::{InterpreterError, ExecutionError, ControlFlowError, EvalError, MathError, ParseError, SymbolError};
+use crate::core::ast_expression::Expression;
+use crate::core::lexical_analyzer::{Token, Tokenizer};
 // use crate::core::ast_statement::Statement;
-use crate::core::ast_statement::ControlFlow;
-use crate::core::symbol_manager::SymbolTable;
-use crate::core::execution_state::with_exit_state;
+use crate::core::ast_statement::{ControlFlow, Statement};
+use crate::core::symbol_manager::{SymbolTable, global_constants};
+use crate::core::execution_state::{with_exit_state, with_import_state, with_memo_state, with_random_state, with_result_history, with_trace_state, with_work_state, ImportState, MemoState, RandomState, ResultHistory, Trace, TraceState, WorkState};
+use crate::core::snippet_registry::SnippetRegistry;
+use crate::core::complex::{EvalOptions, Value};
+use crate::core::bignum::needs_promotion;
 use crate::core::parser::{parse_expression, parse_program, ParsedProgram};
+use crate::core::analysis::{find_side_effect, find_warnings};
+use std::sync::Arc;
 
 //=============================================================================
 // Expression evaluation (pure calculations)
 //=============================================================================
 
-/// Evaluates a single arithmetic expression.
+/// Evaluates an expression, or a short script, and returns its value.
 ///
-/// Takes an input string and evaluates it using the provided symbol table.
+/// The input runs through the same pipeline as [`execute`], so parsing,
+/// semicolons, assignments and errors behave the same. It runs on a copy of
+/// the variables, and `let` bindings and assignments only last for this call.
+/// The only other difference is that the input must leave a value to show.
 pub fn evaluate(input: &str, context: &SymbolTable<f32>) -> Result<f32, InterpreterError> {
-    // Parse as an expression using the parser module
+    if trim_separators(input).is_empty() {
+        return Err(InterpreterError::Parse(ParseError::EmptyInput));
+    }
+
+    let mut scope = context.clone();
+    execute(input, &mut scope).and_then(value_to_show)
+}
+
+/// Turns the value a script left into the value [`evaluate`] shows.
+fn value_to_show(value: Option<f32>) -> Result<f32, InterpreterError> {
+    value.ok_or_else(|| {
+        let message = "the statements leave no value to show; end with an expression, or use execute to keep the variables";
+        InterpreterError::Exec(ExecutionError::InvalidStatement(message.to_string()))
+    })
+}
+
+/// Evaluates a single expression with the given options.
+///
+/// With `options.complex` the expression is evaluated with complex numbers
+/// and with `options.exact` with exact fractions. The two cannot be combined.
+/// Without either this is the same as [`evaluate`].
+pub fn evaluate_with_options(input: &str, context: &SymbolTable<f32>, options: EvalOptions) -> Result<Value, InterpreterError> {
+    if options.complex && options.exact {
+        let error = MathError::DomainError("complex and exact modes cannot be combined".to_string());
+        return Err(InterpreterError::Eval(error.into()));
+    }
+    if !options.complex && !options.exact {
+        return evaluate_real(input, context);
+    }
+
+    let input = trim_separators(input);
     let expr = match parse_expression(input) {
         Ok(expr) => expr,
+        Err(_) if is_statement_input(input) => {
+            let message = "complex and exact modes take a single expression; use execute to run statements";
+            return Err(InterpreterError::Exec(ExecutionError::InvalidStatement(message.to_string())));
+        }
         Err(err) => return Err(InterpreterError::Parse(err)),
     };
-    
-    // Evaluate the expression
-    match expr.evaluate(context) {
-        Ok(result) => Ok(result),
-        Err(err) => Err(InterpreterError::Eval(err)),
+
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::start);
+    let result = if options.complex {
+        expr.evaluate_complex(context)
+    } else {
+        expr.evaluate_exact(context, options.strict_exact)
+    };
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::reset);
+
+    result.map_err(InterpreterError::Eval)
+}
+
+/// Evaluates in real mode, promoting integers beyond the float range to big integers.
+///
+/// Only a single expression is promoted, and when part of it has no exact
+/// integer result the float result is kept, e.g. `2^100 / 3`.
+fn evaluate_real(input: &str, context: &SymbolTable<f32>) -> Result<Value, InterpreterError> {
+    let result = evaluate(input, context);
+    let overflowed = match &result {
+        Ok(value) => needs_promotion(&Ok(*value)),
+        Err(InterpreterError::Eval(error)) => needs_promotion(&Err(error.clone())),
+        Err(_) => false,
+    };
+
+    if overflowed {
+        if let Ok(expr) = parse_expression(trim_separators(input)) {
+            with_memo_state(MemoState::reset);
+            with_work_state(WorkState::start);
+            let promoted = expr.evaluate_big(context);
+            with_memo_state(MemoState::reset);
+            with_work_state(WorkState::reset);
+
+            if let Ok(Some(value)) = promoted {
+                return Ok(Value::BigInt(value));
+            }
+        }
+    }
+
+    result.map(Value::Real)
+}
+
+/// Evaluates input that cannot change the context, without copying it.
+///
+/// Meant for previews and other what-if evaluations. Input that assigns,
+/// declares, imports or calls a procedure is rejected with
+/// [`ExecutionError::SideEffect`] before anything runs. A single expression
+/// is evaluated directly and other statements, such as an `if` with no
+/// assignments, are executed once, both in a scratch scope that shares the
+/// context's maps. Results of `memo fn` calls are cached in that scope only.
+pub fn evaluate_pure(input: &str, context: &SymbolTable<f32>) -> Result<f32, InterpreterError> {
+    let input = trim_separators(input);
+    if input.is_empty() {
+        return Err(InterpreterError::Parse(ParseError::EmptyInput));
+    }
+
+    let statements = match parse_program(input)? {
+        ParsedProgram::Statements(statements) => statements,
+        ParsedProgram::Expression(expr) => vec![Statement::Expression(expr)],
+    };
+    if let Some(effect) = statements.iter().find_map(find_side_effect) {
+        return Err(InterpreterError::Exec(ExecutionError::SideEffect(effect)));
+    }
+    let mut scope = context.detached_scope();
+    let expr = match statements.as_slice() {
+        [Statement::Expression(expr)] => expr,
+        _ => return execute(input, &mut scope).and_then(value_to_show),
+    };
+
+    with_exit_state(|state| *state = Default::default());
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::start);
+    let result = expr.evaluate(&scope);
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::reset);
+
+    Ok(result?)
+}
+
+/// Returns whether [`evaluate`] runs the input as statements instead of as an expression.
+///
+/// This is the case for input like `let x = 5; x * 2`, which is not a single
+/// expression but parses as a script. A trailing semicolon alone, as in `2+2;`,
+/// does not count.
+pub fn is_statement_input(input: &str) -> bool {
+    let input = trim_separators(input);
+    !input.is_empty()
+        && parse_expression(input).is_err()
+        && matches!(parse_program(input), Ok(ParsedProgram::Statements(_)))
+}
+
+/// Returns whether the input has nothing to run: only whitespace, comments
+/// and semicolons.
+///
+/// [`execute`] runs such input to no value, while [`evaluate`] has no value
+/// to give and reports it as empty.
+pub fn is_blank_input(input: &str) -> bool {
+    let mut tokenizer = Tokenizer::from_input(input);
+    while tokenizer.peek_token() == &Token::Punctuation(';') {
+        tokenizer.next_token();
+    }
+    tokenizer.peek_token() == &Token::EndOfInput
+}
+
+/// Strips whitespace and statement separators around an input.
+fn trim_separators(input: &str) -> &str {
+    input.trim_matches(|c: char| c == ';' || c.is_whitespace())
+}
+
+/// Evaluates a parsed expression once for every value of a single variable.
+///
+/// The expression is parsed by the caller once, and all points share one
+/// scratch scope derived from `base`, so a sweep over many values does not
+/// allocate per point. Results are in the same order as `values`.
+///
+/// Each point may run as many loop iterations as an execution, while the
+/// time limit covers the whole batch.
+pub fn evaluate_batch(
+    expr: &Expression,
+    var: &str,
+    values: &[f32],
+    base: &SymbolTable<f32>,
+) -> Vec<Result<f32, EvalError>> {
+    let mut scope = base.new_scope();
+    let mut results = Vec::with_capacity(values.len());
+
+    // Memoized function results stay valid across the whole batch
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::start);
+
+    for &value in values {
+        // Each point may run as many loop iterations as an execution
+        with_work_state(|work| work.iterations = 0);
+        results.push(bind_batch_variable(&mut scope, var, value).and_then(|()| expr.evaluate(&scope)));
+    }
+
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::reset);
+    results
+}
+
+/// Evaluates a parsed expression once for every row of variable bindings.
+///
+/// Each row binds some variables by name on top of `base`. Bindings from one
+/// row do not carry over to the next, and binding a constant fails that row.
+pub fn evaluate_batch_rows(
+    expr: &Expression,
+    rows: &[&[(&str, f32)]],
+    base: &SymbolTable<f32>,
+) -> Vec<Result<f32, EvalError>> {
+    let mut scope = base.new_scope();
+    let mut results = Vec::with_capacity(rows.len());
+    let mut previous: &[(&str, f32)] = &[];
+
+    // Memoized function results stay valid across the whole batch
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::start);
+
+    for row in rows {
+        // Restore what the previous row bound and this one doesn't rebind
+        for &(name, _) in previous {
+            if row.iter().any(|&(bound, _)| bound == name) {
+                continue;
+            }
+            let values = Arc::make_mut(&mut scope.values);
+            match base.get(name) {
+                Some(&original) => { values.insert(name.to_string(), original); }
+                None => { values.remove(name); }
+            }
+        }
+        previous = row;
+
+        with_work_state(|work| work.iterations = 0);
+        let bound = row.iter().try_for_each(|&(name, value)| bind_batch_variable(&mut scope, name, value));
+        results.push(bound.and_then(|()| expr.evaluate(&scope)));
+    }
+
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::reset);
+    results
+}
+
+/// Binds a variable in a batch scope, overwriting an existing entry in place.
+fn bind_batch_variable(scope: &mut SymbolTable<f32>, name: &str, value: f32) -> Result<(), EvalError> {
+    if global_constants().contains(name) || scope.is_constant(name) {
+        return Err(SymbolError::ImmutableConstant(name.to_string()).into());
     }
+
+    let values = Arc::make_mut(&mut scope.values);
+    match values.get_mut(name) {
+        Some(slot) => *slot = value,
+        None => { values.insert(name.to_string(), value); }
+    }
+    Ok(())
 }
 
 //=============================================================================
 // Script execution (expressions, statements, control flow, etc.)
 //=============================================================================
 
+/// The outcome of running a script, for callers that need more than its value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ExecutionResult {
+    /// The value of the last statement, or the value given to `end`.
+    pub value: Option<f32>,
+
+    /// Whether the script stopped at an `end` statement.
+    pub ended: bool,
+
+    /// Lines the script wrote, in order.
+    ///
+    /// The language has no statement that writes output yet, so this is
+    /// always empty for now.
+    pub output: Vec<String>,
+}
+
+/// Options that change how a script is executed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecOptions {
+    /// Refuse to run scripts with warnings, such as unused variables or unreachable code.
+    pub strict: bool,
+}
+
+impl ExecOptions {
+    /// Runs the checks these options ask for, before any of the script runs.
+    ///
+    /// In strict mode, every warning from [`find_warnings`] is listed in an
+    /// [`ExecutionError::ExecutionFailed`].
+    pub fn check(&self, input: &str) -> Result<(), InterpreterError> {
+        if !self.strict {
+            return Ok(());
+        }
+
+        let warnings = find_warnings(input);
+        if warnings.is_empty() {
+            return Ok(());
+        }
+
+        let list = warnings.iter().map(|warning| format!("\n- {}", warning)).collect::<String>();
+        Err(ExecutionError::ExecutionFailed(format!("strict mode does not run code with warnings:{}", list)).into())
+    }
+}
+
 /// Executes a script or code block with statements and expressions.
 /// 
 /// Handles variable declarations, control flow, and other language features.
+/// See [`execute_detailed`] to also learn how the script finished.
 pub fn execute(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32>, InterpreterError> {
+    execute_detailed(input, context).map(|result| result.value)
+}
+
+/// Executes a script like [`execute`] and reports how it finished.
+pub fn execute_detailed(input: &str, context: &mut SymbolTable<f32>) -> Result<ExecutionResult, InterpreterError> {
+    // Memoized function results, `_1`, `_2`, … and the work count only live for a single execution
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::start);
+    with_result_history(|history| *history = ResultHistory::default());
+    let result = run_program(input, context);
+    let ended = with_exit_state(|state| state.occurred);
+    with_memo_state(MemoState::reset);
+    with_work_state(WorkState::reset);
+    with_result_history(|history| *history = ResultHistory::default());
+
+    Ok(ExecutionResult {
+        value: result?,
+        ended,
+        output: Vec::new(),
+    })
+}
+
+/// Executes a script like [`execute`] with the given options.
+pub fn execute_with_options(
+    input: &str,
+    context: &mut SymbolTable<f32>,
+    options: ExecOptions,
+) -> Result<Option<f32>, InterpreterError> {
+    options.check(input)?;
+    execute(input, context)
+}
+
+/// Parses and runs a program, honoring `end` statements.
+fn run_program(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32>, InterpreterError> {
     // Reset exit state at the start of execution
     with_exit_state(|state| {
         *state = Default::default();
@@ -47,15 +357,26 @@ pub fn execute(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32
             let mut last_value: Option<f32> = None;
             let mut result = Ok(None);
             
-            for (_i, statement) in statements.iter().enumerate() {
+            // Top-level deferred statements run when the program finishes
+            let mut deferred = Vec::new();
+            
+            for statement in statements.iter() {
                 // Check if an exit statement has been processed
                 if with_exit_state(|state| state.occurred) {
                     break;
                 }
                 
+                if let Statement::Defer(body) = statement {
+                    deferred.push(body.as_ref());
+                    // Deferred statements keep their place in the numbering of `_1`, `_2`, …
+                    with_result_history(|history| history.results.push(None));
+                    continue;
+                }
+                
                 match statement.evaluate(context) {
                     Ok((value, control_flow)) => {
                         last_value = value;
+                        with_result_history(|history| history.results.push(value));
                         
                         // Handle control flow outside proper context
                         match control_flow {
@@ -71,13 +392,18 @@ pub fn execute(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32
                                 ));
                                 break;
                             },
-                            ControlFlow::Return => {
+                            ControlFlow::Return(_) => {
                                 // Stop execution on return (used by end statement at global scope)
                                 break;
                             },
                             _ => {}
                         }
                     },
+                    // A failed assertion aborts the whole execution
+                    Err(EvalError::ControlFlowError(ControlFlowError::AssertionFailed(condition))) => {
+                        result = Err(ExecutionError::ExecutionFailed(format!("assertion failed: {}", condition)));
+                        break;
+                    }
                     Err(error) => {
                         result = Err(ExecutionError::EvaluationError(error));
                         break;
@@ -85,9 +411,11 @@ pub fn execute(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32
                 }
             }
             
-            // If no errors occurred, update the result with the last value
+            // If no errors occurred, run deferred statements and update the result with the last value
             if result.is_ok() {
-                result = Ok(last_value);
+                result = Statement::run_deferred(&deferred, context)
+                    .map(|_| last_value)
+                    .map_err(ExecutionError::EvaluationError);
             }
             
             // Check exit state to determine what to return
@@ -124,3 +452,122 @@ pub fn execute(input: &str, context: &mut SymbolTable<f32>) -> Result<Option<f32
         Err(err) => Err(InterpreterError::Parse(err)),
     }
 }
+
+/// Executes a script that may import snippets from the given registry.
+///
+/// The registry is shared with the execution rather than copied, and only
+/// visible to `import` statements for the duration of this call.
+pub fn execute_with_imports(
+    input: &str,
+    context: &mut SymbolTable<f32>,
+    registry: &SnippetRegistry,
+) -> Result<Option<f32>, InterpreterError> {
+    with_import_state(|state| {
+        *state = ImportState {
+            registry: registry.clone(),
+            ..Default::default()
+        };
+    });
+    
+    let result = execute(input, context);
+    
+    // Don't keep the registry around for later executions
+    with_import_state(|state| {
+        *state = ImportState::default();
+    });
+    
+    result
+}
+
+//=============================================================================
+// Traced execution (statement by statement)
+//=============================================================================
+
+/// Runs `f` with every statement it executes recorded in a trace.
+///
+/// Returns what `f` returned and the statements in the order they started,
+/// each with its nesting depth and outcome. Long traces keep their first
+/// 1000 statements and count the rest.
+pub fn trace_execution<R>(f: impl FnOnce() -> R) -> (R, Trace) {
+    with_trace_state(|state| {
+        *state = TraceState {
+            enabled: true,
+            ..Default::default()
+        };
+    });
+
+    let result = f();
+
+    // Stop tracing and hand over what was recorded
+    let trace = with_trace_state(|state| std::mem::take(state).trace);
+    (result, trace)
+}
+
+//=============================================================================
+// Seeded execution (reproducible random numbers)
+//=============================================================================
+
+/// Runs `f` with `rand()` drawing from a generator seeded with `seed`.
+fn with_seed<R>(seed: u64, f: impl FnOnce() -> R) -> R {
+    with_random_state(|state| state.reseed(seed));
+    let result = f();
+    with_random_state(RandomState::reset);
+    result
+}
+
+/// Executes a script with `rand()` drawing from a generator seeded with `seed`.
+///
+/// Running the same script on the same variables with the same seed gives
+/// the same result.
+pub fn execute_seeded(input: &str, context: &mut SymbolTable<f32>, seed: u64) -> Result<Option<f32>, InterpreterError> {
+    with_seed(seed, || execute(input, context))
+}
+
+/// The outcome of running a script twice with the same seed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DeterminismCheck {
+    /// The seed both runs used.
+    pub seed: u64,
+
+    /// The result of the first run.
+    pub first: Option<f32>,
+
+    /// The result of the second run.
+    pub second: Option<f32>,
+
+    /// Whether both runs gave the same result and left the same variables.
+    pub deterministic: bool,
+}
+
+/// Runs a script twice with the same seed and compares the outcomes.
+///
+/// Both runs start from their own copy of `context`, which is left unchanged.
+/// Results and variables are compared bit for bit, so `NaN` matches itself.
+/// An error in either run is returned as is.
+pub fn verify_deterministic(
+    input: &str,
+    context: &SymbolTable<f32>,
+    registry: &SnippetRegistry,
+    seed: u64,
+) -> Result<DeterminismCheck, InterpreterError> {
+    let run = || {
+        let mut scope = context.clone();
+        with_seed(seed, || execute_with_imports(input, &mut scope, registry)).map(|result| (result, scope))
+    };
+
+    let (first, first_scope) = run()?;
+    let (second, second_scope) = run()?;
+
+    let same_result = first.map(f32::to_bits) == second.map(f32::to_bits);
+    let same_variables = first_scope.values.len() == second_scope.values.len()
+        && first_scope.values.iter().all(|(name, value)| {
+            second_scope.get(name).map(|other| other.to_bits()) == Some(value.to_bits())
+        });
+
+    Ok(DeterminismCheck {
+        seed,
+        first,
+        second,
+        deterministic: same_result && same_variables,
+    })
+}
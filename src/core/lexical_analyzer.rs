@@ -2,31 +2,138 @@
 //! 
 //! This module converts raw input text into tokens for the parser.
 
+use std::borrow::Cow;
+use std::fmt;
 use std::iter::Peekable;
 use std::str::Chars;
 use crate::core::error_types::ParseError;
+use crate::core::operator::Operator;
+use crate::core::metrics::{current_limit, Limit};
+
+/// Words the tokenizer reads as keywords rather than names.
+pub(crate) const KEYWORDS: [&str; 21] = [
+    "if", "else", "while", "break", "continue",
+    "return", "let", "const", "true", "false", "end",
+    "fn", "proc", "import", "foreach", "in", "assert", "memo", "defer",
+    "try", "catch"
+];
+
+/// Checks that text is read as a single name: a letter or `_` followed by
+/// letters, digits and `_`.
+pub(crate) fn is_identifier(text: &str) -> bool {
+    text.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && text.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Default limit on how deeply expressions and statements may nest.
+///
+/// Parsing recurses once per level, so without a limit a long run of `(`
+/// would overflow the stack.
+pub const DEFAULT_MAX_DEPTH: usize = 500;
 
 /// A token in the expression language.
 ///
 /// Includes literals, operators, keywords, and structural elements.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum Token {
-    /// A numeric literal: decimal (123, 3.14), hex (0xFF), binary (0b101).
+    /// A numeric literal: decimal (123, 3.14), hex (0xFF), binary (0b101), imaginary (2i).
     Literal(String),
 
-    /// A single-character operator, e.g., '+', '-', '^', '√', '.'.
-    Operator(char),
+    /// An operator such as `+`, `>=` or `!^`, see [`Operator`].
+    Operator(Operator),
+
+    /// Punctuation: parentheses, braces, `;` and `,`.
+    Punctuation(char),
+
+    /// The `..` between the bounds of a `foreach` range.
+    Range,
 
-    /// An augmented assignment operator, e.g., "+=", "-=", "*=", "/=", "%=".
-    AugAssign(String),
+    /// The `:=` that binds a formula in `let name := expr`.
+    FormulaAssign,
+
+    /// An augmented assignment such as `+=`, holding the arithmetic operator it applies.
+    ///
+    /// Only `+=`, `-=`, `*=`, `/=`, `%=` and `^=` are augmented assignments;
+    /// comparisons ending in `=` never are.
+    AugAssign(Operator),
 
     /// Keywords for control flow and declarations.
     Keyword(String),
 
+    /// A double-quoted text literal, e.g., the snippet name in `import "name"`.
+    Text(String),
+
     /// End of input marker.
     EndOfInput,
 }
 
+impl fmt::Display for Token {
+    /// Writes the token as it appears in source, e.g. `>=`.
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Literal(text) | Token::Keyword(text) => write!(formatter, "{}", text),
+            Token::AugAssign(operator) => write!(formatter, "{}=", operator),
+            Token::Operator(operator) => write!(formatter, "{}", operator),
+            Token::Punctuation(character) => write!(formatter, "{}", character),
+            Token::Range => write!(formatter, ".."),
+            Token::FormulaAssign => write!(formatter, ":="),
+            Token::Text(text) => write!(formatter, "\"{}\"", text),
+            Token::EndOfInput => write!(formatter, "end of input"),
+        }
+    }
+}
+
+/// Rewrites common Unicode math symbols as the ASCII operators they stand for.
+///
+/// `×`, `⋅` and `∙` become `*`, `÷` and `∕` become `/`, the minus sign `−`
+/// becomes `-`, `≤`, `≥` and `≠` become `<=`, `>=` and `!=`, and a run of
+/// superscripts becomes a power, so `2²` is `2^2` and `x⁻¹` is `x^-1`. `√`
+/// is an operator of its own and stays. Plain ASCII input is returned as is.
+pub fn normalize_math_symbols(input: &str) -> Cow<'_, str> {
+    if input.is_ascii() {
+        return Cow::Borrowed(input);
+    }
+
+    let mut output = String::with_capacity(input.len());
+    let mut in_superscript = false;
+    for c in input.chars() {
+        if let Some(plain) = superscript_to_ascii(c) {
+            if !in_superscript {
+                output.push('^');
+                in_superscript = true;
+            }
+            output.push(plain);
+            continue;
+        }
+        in_superscript = false;
+
+        match c {
+            '×' | '⋅' | '∙' => output.push('*'),
+            '÷' | '∕' => output.push('/'),
+            '\u{2212}' => output.push('-'),
+            '≤' => output.push_str("<="),
+            '≥' => output.push_str(">="),
+            '≠' => output.push_str("!="),
+            _ => output.push(c),
+        }
+    }
+    Cow::Owned(output)
+}
+
+/// Maps a superscript digit or sign to its plain form.
+fn superscript_to_ascii(c: char) -> Option<char> {
+    match c {
+        '⁰' => Some('0'),
+        '¹' => Some('1'),
+        '²' => Some('2'),
+        '³' => Some('3'),
+        '⁴'..='⁹' => char::from_u32(c as u32 - '⁴' as u32 + '4' as u32),
+        '⁻' => Some('-'),
+        '⁺' => Some('+'),
+        _ => None,
+    }
+}
+
 /// Tokenizer splits the raw input string into a sequence of tokens.
 ///
 /// The tokenizer performs lexical analysis on the input string, converting
@@ -38,6 +145,18 @@ pub struct Tokenizer {
     
     /// Current position in the token stream.
     position: usize,
+
+    /// Current nesting depth of the parser.
+    depth: usize,
+
+    /// Nesting depth past which parsing fails, [`DEFAULT_MAX_DEPTH`] unless overridden.
+    max_depth: usize,
+
+    /// The first character the lexer did not recognize and its position.
+    unrecognized: Option<(char, usize)>,
+
+    /// Whether unrecognized characters are dropped instead of reported.
+    skip_unrecognized: bool,
 }
 
 impl Tokenizer {
@@ -45,29 +164,53 @@ impl Tokenizer {
     ///
     /// This method processes the input string and produces a sequence of tokens
     /// by recognizing patterns like numbers, identifiers, operators, etc.
+    /// Unicode math symbols are rewritten first, see [`normalize_math_symbols`].
     pub fn from_input(input: &str) -> Self {
         let mut tokenizer = Self {
             token_list: Vec::new(),
             position: 0,
+            depth: 0,
+            max_depth: current_limit(Limit::NestingDepth),
+            unrecognized: None,
+            skip_unrecognized: false,
         };
         
-        tokenizer.tokenize(input);
+        tokenizer.tokenize(&normalize_math_symbols(input));
         
         tokenizer
     }
     
+    /// Sets how deeply expressions and statements may nest before parsing fails.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Drops characters the lexer does not recognize instead of reporting them.
+    pub fn skipping_unrecognized(mut self) -> Self {
+        self.skip_unrecognized = true;
+        self
+    }
+
+    /// Fails with a syntax error naming the first unrecognized character.
+    ///
+    /// The parser checks this before parsing, so `2 @ 3` is reported rather
+    /// than read as `2 3`. Positions count characters from 1.
+    pub(crate) fn check_unrecognized(&self) -> Result<(), ParseError> {
+        match self.unrecognized {
+            Some((character, position)) if !self.skip_unrecognized => Err(ParseError::SyntaxError(format!(
+                "unrecognized character '{}' at position {}",
+                character, position,
+            ))),
+            _ => Ok(()),
+        }
+    }
+    
     /// Tokenizes the input string into a sequence of tokens.
     fn tokenize(&mut self, input: &str) {
         let mut token_list = Vec::with_capacity(input.len() / 2); // Reasonable estimate
         let mut chars_iter = input.chars().peekable();
 
-        // Keywords that the tokenizer should recognize
-        const KEYWORDS: [&str; 13] = [
-            "if", "else", "while", "break", "continue", 
-            "return", "let", "const", "true", "false", "end",
-            "fn", "proc"
-        ];
-
         while let Some(&current_char) = chars_iter.peek() {
             // Skip whitespace between tokens
             if current_char.is_whitespace() {
@@ -82,9 +225,14 @@ impl Tokenizer {
                         continue;
                     }
                     
-                    // If not a comment, treat as division operator
+                    // If not a comment, treat as division (or its augmented form)
                     chars_iter.next();
-                    token_list.push(Token::Operator('/'));
+                    if chars_iter.peek() == Some(&'=') {
+                        chars_iter.next();
+                        token_list.push(Token::AugAssign(Operator::Divide));
+                    } else {
+                        token_list.push(Token::Operator(Operator::Divide));
+                    }
                 },
                 
                 // Handle numeric literals
@@ -99,13 +247,22 @@ impl Tokenizer {
                     let mut lookahead = chars_iter.clone();
                     lookahead.next(); // Skip the '.'
                     
-                    if lookahead.next().map_or(false, |c| c.is_ascii_digit()) {
-                        let literal = self.parse_number_with_leading_dot(&mut chars_iter);
-                        token_list.push(Token::Literal(literal));
-                    } else {
+                    match lookahead.next() {
+                        Some(c) if c.is_ascii_digit() => {
+                            let literal = self.parse_number_with_leading_dot(&mut chars_iter);
+                            token_list.push(Token::Literal(literal));
+                        },
+                        // Range operator: ..
+                        Some('.') => {
+                            chars_iter.next();
+                            chars_iter.next();
+                            token_list.push(Token::Range);
+                        },
                         // Just a dot operator
-                        chars_iter.next();
-                        token_list.push(Token::Operator('.'));
+                        _ => {
+                            chars_iter.next();
+                            token_list.push(Token::Operator(Operator::Dot));
+                        },
                     }
                 },
                 
@@ -122,64 +279,127 @@ impl Tokenizer {
                             _ => token_list.push(Token::Keyword(text)),
                         }
                     }
-                    // Check if it's a function or procedure call (function or procedure name followed by an opening parenthesis)
-                    else if let Some(&paren_char) = chars_iter.peek() {
-                        if paren_char == '(' {
-                            token_list.push(Token::Literal(text));
-                        } else {
-                            token_list.push(Token::Literal(text));
-                        }
-                    } else {
+                    // Identifiers (including function and procedure names) are literals
+                    else {
                         token_list.push(Token::Literal(text));
                     }
                 },
                 
+                // Double-quoted text (unterminated text runs to the end of input)
+                '"' => {
+                    chars_iter.next(); // Skip opening quote
+                    let text: String = chars_iter.by_ref().take_while(|&ch| ch != '"').collect();
+                    token_list.push(Token::Text(text));
+                },
+                
                 // Single-character punctuation
                 '(' | ')' | '{' | '}' | ';' | ',' => {
                     chars_iter.next();
-                    token_list.push(Token::Operator(current_char));
+                    token_list.push(Token::Punctuation(current_char));
                 },
                 
                 // Operators that could be part of augmented assignments
                 '+' | '-' | '*' | '%' | '^' => {
                     chars_iter.next();
                     
-                    if let Some(&next_char) = chars_iter.peek() {
-                        if next_char == '=' {
+                    let operator = match current_char {
+                        '+' => Operator::Add,
+                        '-' => Operator::Subtract,
+                        '*' => Operator::Multiply,
+                        '%' => Operator::Modulo,
+                        _ => Operator::Power,
+                    };
+                    match chars_iter.peek() {
+                        Some('=') => {
                             chars_iter.next();
-                            token_list.push(Token::AugAssign(format!("{}=", current_char)));
-                        } else {
-                            token_list.push(Token::Operator(current_char));
-                        }
-                    } else {
-                        token_list.push(Token::Operator(current_char));
+                            token_list.push(Token::AugAssign(operator));
+                        },
+                        // Logical XOR: ^^
+                        Some('^') if current_char == '^' => {
+                            chars_iter.next();
+                            token_list.push(Token::Operator(Operator::Xor));
+                        },
+                        _ => token_list.push(Token::Operator(operator)),
                     }
                 },
                 
-                // Comparison operators
-                '=' | '<' | '>' | '!' => {
+                // Assignment and comparison operators
+                '=' | '<' | '>' => {
                     chars_iter.next();
                     
-                    if let Some(&next_char) = chars_iter.peek() {
-                        if next_char == '=' {
-                            chars_iter.next();
-                            token_list.push(Token::AugAssign(format!("{}=", current_char)));
-                        } else {
-                            token_list.push(Token::Operator(current_char));
-                        }
+                    let doubled = chars_iter.peek() == Some(&'=');
+                    if doubled {
+                        chars_iter.next();
+                    }
+                    let operator = match (current_char, doubled) {
+                        ('=', true) => Operator::Equal,
+                        ('<', true) => Operator::LessEqual,
+                        (_, true) => Operator::GreaterEqual,
+                        ('=', false) => Operator::Assign,
+                        ('<', false) => Operator::Less,
+                        (_, false) => Operator::Greater,
+                    };
+                    token_list.push(Token::Operator(operator));
+                },
+                
+                // Logical NOT and the negated operators: != !^ !& !|
+                '!' => {
+                    chars_iter.next();
+                    
+                    let operator = match chars_iter.peek() {
+                        Some('=') => Some(Operator::NotEqual),
+                        Some('^') => Some(Operator::Xnor),
+                        Some('&') => Some(Operator::Nand),
+                        Some('|') => Some(Operator::Nor),
+                        _ => None,
+                    };
+                    
+                    if let Some(operator) = operator {
+                        chars_iter.next();
+                        token_list.push(Token::Operator(operator));
                     } else {
-                        token_list.push(Token::Operator(current_char));
+                        token_list.push(Token::Operator(Operator::Not));
+                    }
+                },
+                
+                // Logical AND/OR, accepted both single and doubled (&, &&, |, ||)
+                '&' | '|' => {
+                    chars_iter.next();
+                    
+                    if chars_iter.peek() == Some(&current_char) {
+                        chars_iter.next();
                     }
+                    let operator = if current_char == '&' { Operator::And } else { Operator::Or };
+                    token_list.push(Token::Operator(operator));
                 },
                 
+                // Formula binding: :=; a lone colon is unrecognized
+                ':' => {
+                    let position = input.chars().count() - chars_iter.clone().count() + 1;
+                    chars_iter.next();
+
+                    if chars_iter.peek() == Some(&'=') {
+                        chars_iter.next();
+                        token_list.push(Token::FormulaAssign);
+                    } else if self.unrecognized.is_none() {
+                        self.unrecognized = Some((current_char, position));
+                    }
+                },
+
                 // Other recognized operators
                 '√' => {
                     chars_iter.next();
-                    token_list.push(Token::Operator('√'));
+                    token_list.push(Token::Operator(Operator::Root));
                 },
                 
-                // Skip unrecognized characters (could add error reporting here)
-                _ => { chars_iter.next(); },
+                // Unrecognized characters are skipped and the first is remembered for the parser
+                _ => {
+                    if self.unrecognized.is_none() {
+                        let position = input.chars().count() - chars_iter.clone().count() + 1;
+                        self.unrecognized = Some((current_char, position));
+                    }
+                    chars_iter.next();
+                },
             }
         }
         
@@ -247,30 +467,16 @@ impl Tokenizer {
                         chars.next(); // Consume 'x'
                         number.push('x');
                         
-                        // Parse hex digits
-                        while let Some(&ch) = chars.peek() {
-                            if ch.is_ascii_hexdigit() {
-                                number.push(ch);
-                                chars.next();
-                            } else {
-                                break;
-                            }
-                        }
+                        // Take every alphanumeric so a typo like 0xGG is reported whole
+                        self.parse_radix_digits(chars, &mut number);
                         return number;
                     },
                     'b' | 'B' => {
                         chars.next(); // Consume 'b'
                         number.push('b');
                         
-                        // Parse binary digits
-                        while let Some(&ch) = chars.peek() {
-                            if ch == '0' || ch == '1' {
-                                number.push(ch);
-                                chars.next();
-                            } else {
-                                break;
-                            }
-                        }
+                        // Take every alphanumeric so a typo like 0b102 is reported whole
+                        self.parse_radix_digits(chars, &mut number);
                         return number;
                     },
                     _ => {}
@@ -282,6 +488,20 @@ impl Tokenizer {
         self.parse_decimal_digits(chars, &mut number, false)
     }
     
+    /// Collects the digits of a hex or binary literal, valid or not.
+    ///
+    /// The digits are checked by [`decode_radix_literal`] when the literal is parsed.
+    fn parse_radix_digits(&self, chars: &mut Peekable<Chars>, number: &mut String) {
+        while let Some(&ch) = chars.peek() {
+            if ch.is_ascii_alphanumeric() {
+                number.push(ch);
+                chars.next();
+            } else {
+                break;
+            }
+        }
+    }
+    
     /// Parses a numeric literal starting with a decimal point.
     fn parse_number_with_leading_dot(&self, chars: &mut Peekable<Chars>) -> String {
         let mut number = String::new();
@@ -304,7 +524,7 @@ impl Tokenizer {
                 let mut lookahead = chars.clone();
                 lookahead.next(); // Skip the dot
                 
-                if lookahead.next().map_or(false, |c| c.is_ascii_digit()) {
+                if lookahead.next().is_some_and(|c| c.is_ascii_digit()) {
                     dot_encountered = true;
                     number.push('.');
                     chars.next();
@@ -317,6 +537,17 @@ impl Tokenizer {
             }
         }
         
+        // Imaginary suffix (2i, 0.5i), only meaningful in complex mode
+        if chars.peek() == Some(&'i') {
+            let mut lookahead = chars.clone();
+            lookahead.next(); // Skip the 'i'
+            
+            if !lookahead.next().is_some_and(|c| c.is_ascii_alphanumeric() || c == '_') {
+                number.push('i');
+                chars.next();
+            }
+        }
+        
         number.clone()
     }
     
@@ -356,6 +587,11 @@ impl Tokenizer {
         }
     }
     
+    /// Looks `offset` tokens past the current one without advancing the position.
+    pub fn peek_token_at(&self, offset: usize) -> &Token {
+        self.token_list.get(self.position + offset).unwrap_or(&Token::EndOfInput)
+    }
+    
     /// Checks if the next token is of a specific type.
     pub fn check(&self, expected: &Token) -> bool {
         self.peek_token() == expected
@@ -368,14 +604,57 @@ impl Tokenizer {
             Ok(token)
         } else {
             Err(ParseError::Expected { 
-                expected: format!("{:?}", expected),
-                found: format!("{:?}", token),
+                expected: expected.to_string(),
+                found: token.to_string(),
             })
         }
     }
     
+    /// Enters one level of nesting, failing with [`ParseError::TooDeeplyNested`] past the limit.
+    ///
+    /// Every successful call must be paired with [`Tokenizer::leave_nesting`].
+    pub(crate) fn enter_nesting(&mut self) -> Result<(), ParseError> {
+        if self.depth >= self.max_depth {
+            return Err(ParseError::TooDeeplyNested(self.max_depth));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+    
+    /// Leaves a level of nesting entered with [`Tokenizer::enter_nesting`].
+    pub(crate) fn leave_nesting(&mut self) {
+        self.depth -= 1;
+    }
+    
     /// Resets the tokenizer position back to the beginning.
     pub fn reset(&mut self) {
         self.position = 0;
+        self.depth = 0;
     }
 } 
+
+/// Decodes a hex (`0xFF`) or binary (`0b1010`) literal.
+///
+/// Returns `None` for any other literal. Malformed literals, such as `0x`
+/// without digits or `0b12`, and values wider than 64 bits are errors
+/// describing the literal as written.
+pub(crate) fn decode_radix_literal(text: &str) -> Option<Result<u64, String>> {
+    let (digits, radix, kind) = if let Some(digits) = text.strip_prefix("0x") {
+        (digits, 16, "hexadecimal")
+    } else if let Some(digits) = text.strip_prefix("0b") {
+        (digits, 2, "binary")
+    } else {
+        return None;
+    };
+
+    if digits.is_empty() {
+        return Some(Err(format!("'{}' has no digits after the prefix", text)));
+    }
+    if let Some(bad) = digits.chars().find(|c| !c.is_digit(radix)) {
+        return Some(Err(format!("'{}' contains '{}', which is not a {} digit", text, bad, kind)));
+    }
+
+    Some(u64::from_str_radix(digits, radix).map_err(|_| {
+        format!("'{}' is out of range; hex and binary literals hold at most 64 bits", text)
+    }))
+}
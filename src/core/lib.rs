@@ -3,22 +3,45 @@
 // Submodules
 mod lexical_analyzer;
 mod ast_expression;
+mod operator;
 mod ast_statement;
 mod symbol_manager;
 mod parser;
 mod interpreter;
 mod error_types;
 mod execution_state;
+mod snippet_registry;
+mod shared_constants;
+mod builtins;
+mod analysis;
+mod ast_export;
+mod units;
+mod complex;
+mod rational;
+mod practice;
+mod bignum;
+mod metrics;
 
 // Re-exports for public API
-pub use lexical_analyzer::Tokenizer;
+pub use lexical_analyzer::{normalize_math_symbols, Token, Tokenizer, DEFAULT_MAX_DEPTH};
 pub use ast_expression::Expression;
-pub use ast_statement::Statement;
-pub use symbol_manager::SymbolTable;
+pub use operator::Operator;
+pub use ast_statement::{format_pretty, ControlFlow, Statement};
+pub use symbol_manager::{is_result_name, reserved_names, validate_identifier, BindingInfo, BindingKind, SymbolTable, SymbolTableSnapshot, FrozenSymbols, MAX_CALLABLE_NODES, MAX_MEMO_RESULTS, MAX_MEMO_RESULTS_PER_FUNCTION, MAX_TOTAL_CALLABLE_NODES};
 pub use parser::Parser;
-pub use interpreter::{evaluate, execute};
-pub use execution_state::ExitState;
-pub use error_types::{ParseError, EvalError, ExecutionError, InterpreterError};
+pub use interpreter::{evaluate, evaluate_pure, evaluate_with_options, is_blank_input, is_statement_input, evaluate_batch, evaluate_batch_rows, execute, execute_detailed, execute_seeded, execute_with_imports, execute_with_options, trace_execution, verify_deterministic, DeterminismCheck, ExecOptions, ExecutionResult};
+pub use execution_state::{ExitState, Trace, TraceEntry};
+pub use ast_export::{format_source, normalize_source};
+pub use snippet_registry::{Snippet, SnippetRegistry, MAX_SNIPPETS_PER_AUTHOR, MAX_SNIPPET_NAME_LENGTH};
+pub use shared_constants::{reading_shared_constants, with_shared_constants, SharedConstant, SharedConstants, MAX_SHARED_CONSTANTS_PER_AUTHOR};
+pub use builtins::{BuiltinSpec, BuiltinCategory, list_builtins, find_builtin};
+pub use complex::{EvalOptions, Value};
+pub use bignum::F32_EXACT_LIMIT;
+pub use practice::{generate_expression, generate_expression_with, DEFAULT_OPERATORS};
+pub use units::{convert_units, find_unit, list_units, Dimension, Unit};
+pub use metrics::{measure, with_limits, ExecutionReport, InterpreterMetrics, Limit, LimitOverrides};
+pub use analysis::{count_function_calls, detect_unguarded_recursion, find_warnings, is_boolean_result, is_pure_function, undefined_variables, validate, Warning};
+pub use error_types::{ParseError, EvalError, MathError, SymbolError, ControlFlowError, ExecutionError, InterpreterError};
 
 /// Type alias for calculator errors.
 pub type CalcError = InterpreterError; 
@@ -0,0 +1,277 @@
+//! Counters describing how the interpreter has been used.
+//!
+//! [`measure`] runs one execution and sums it up in an [`ExecutionReport`]:
+//! how long it took, how many calls the result caches answered, and which
+//! limit stopped it, if any. [`InterpreterMetrics`] adds reports up over
+//! time. Nothing here knows where the executions come from.
+//!
+//! [`with_limits`] runs executions with some limits changed, as a server
+//! may ask for with [`LimitOverrides`].
+
+use std::ops::RangeInclusive;
+use std::time::{Duration, Instant};
+
+use crate::core::ast_statement::{MAX_CALL_DEPTH, MAX_EXECUTION_MILLIS, MAX_LOOP_ITERATIONS};
+use crate::core::error_types::{ControlFlowError, EvalError, InterpreterError, ParseError};
+use crate::core::execution_state::{with_cache_stats, with_limit_overrides, CacheStats};
+use crate::core::lexical_analyzer::DEFAULT_MAX_DEPTH;
+use crate::core::snippet_registry::MAX_IMPORT_DEPTH;
+
+/// A limit that stops an execution before it finishes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Limit {
+    /// How deeply expressions, blocks and branches may nest.
+    NestingDepth,
+
+    /// How deeply snippets may import one another.
+    ImportDepth,
+
+    /// How many loop iterations, fold steps and tail calls one execution may run.
+    LoopIterations,
+
+    /// How deeply user function and procedure calls may nest.
+    CallDepth,
+
+    /// How many milliseconds one execution may run.
+    TimeLimit,
+}
+
+impl Limit {
+    /// All limits, in the order they are presented.
+    pub const ALL: [Limit; 5] = [Limit::NestingDepth, Limit::ImportDepth, Limit::LoopIterations, Limit::CallDepth, Limit::TimeLimit];
+
+    /// Returns a human-readable name for the limit.
+    pub fn name(self) -> &'static str {
+        match self {
+            Limit::NestingDepth => "Nesting depth",
+            Limit::ImportDepth => "Import depth",
+            Limit::LoopIterations => "Loop iterations",
+            Limit::CallDepth => "Call depth",
+            Limit::TimeLimit => "Time limit (ms)",
+        }
+    }
+
+    /// Returns the value the limit has unless it is overridden.
+    pub fn value(self) -> usize {
+        match self {
+            Limit::NestingDepth => DEFAULT_MAX_DEPTH,
+            Limit::ImportDepth => MAX_IMPORT_DEPTH,
+            Limit::LoopIterations => MAX_LOOP_ITERATIONS,
+            Limit::CallDepth => MAX_CALL_DEPTH,
+            Limit::TimeLimit => MAX_EXECUTION_MILLIS,
+        }
+    }
+
+    /// Returns the values the limit may be overridden with, `None` if it is fixed.
+    ///
+    /// Loops may run longer than by default, up to a point, but nesting and
+    /// calls may only get shallower since going deeper could overflow the
+    /// stack, and executions may only get shorter since a long one holds up
+    /// everything waiting on the session.
+    pub fn adjustable_range(self) -> Option<RangeInclusive<usize>> {
+        match self {
+            Limit::NestingDepth => Some(16..=DEFAULT_MAX_DEPTH),
+            Limit::ImportDepth => None,
+            Limit::LoopIterations => Some(1..=MAX_LOOP_ITERATIONS * 5),
+            Limit::CallDepth => Some(1..=MAX_CALL_DEPTH),
+            Limit::TimeLimit => Some(100..=MAX_EXECUTION_MILLIS),
+        }
+    }
+
+    /// Returns the limit an error reports reaching, if any.
+    pub fn reached_by(error: &InterpreterError) -> Option<Limit> {
+        match error {
+            InterpreterError::Parse(ParseError::TooDeeplyNested(_)) => Some(Limit::NestingDepth),
+            InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::ImportTooDeep { .. })) => Some(Limit::ImportDepth),
+            InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::TooManyIterations { .. })) => Some(Limit::LoopIterations),
+            InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::CallTooDeep { .. })) => Some(Limit::CallDepth),
+            InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::TimedOut { .. })) => Some(Limit::TimeLimit),
+            _ => None,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Values that replace the defaults of some limits, such as those a server chose.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LimitOverrides {
+    /// Deepest nesting, `None` for the default.
+    pub nesting_depth: Option<usize>,
+
+    /// Most loop iterations of one execution, `None` for the default.
+    pub loop_iterations: Option<usize>,
+
+    /// Deepest nesting of calls, `None` for the default.
+    pub call_depth: Option<usize>,
+
+    /// Longest execution in milliseconds, `None` for the default.
+    pub time_limit: Option<usize>,
+}
+
+impl LimitOverrides {
+    /// Returns the override of a limit, if it has one.
+    pub fn get(&self, limit: Limit) -> Option<usize> {
+        match limit {
+            Limit::NestingDepth => self.nesting_depth,
+            Limit::ImportDepth => None,
+            Limit::LoopIterations => self.loop_iterations,
+            Limit::CallDepth => self.call_depth,
+            Limit::TimeLimit => self.time_limit,
+        }
+    }
+
+    /// Overrides a limit, or goes back to its default with `None`.
+    ///
+    /// Values outside [`Limit::adjustable_range`] are refused and leave the
+    /// overrides unchanged.
+    pub fn set(&mut self, limit: Limit, value: Option<usize>) -> Result<(), RangeInclusive<usize>> {
+        let range = limit.adjustable_range().unwrap_or(limit.value()..=limit.value());
+        if value.is_some_and(|value| !range.contains(&value)) {
+            return Err(range);
+        }
+        match limit {
+            Limit::NestingDepth => self.nesting_depth = value,
+            Limit::ImportDepth => return Err(range),
+            Limit::LoopIterations => self.loop_iterations = value,
+            Limit::CallDepth => self.call_depth = value,
+            Limit::TimeLimit => self.time_limit = value,
+        }
+        Ok(())
+    }
+
+    /// Returns the value a limit has: its override if it has one, else its default.
+    pub fn value(&self, limit: Limit) -> usize {
+        self.get(limit).unwrap_or(limit.value())
+    }
+
+    /// Returns whether no limit is overridden.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Runs `f` with the given limits in place of the defaults.
+///
+/// The limits apply to everything `f` parses and runs on this thread, and
+/// the previous ones are back once it returns.
+pub fn with_limits<R>(limits: LimitOverrides, f: impl FnOnce() -> R) -> R {
+    let previous = with_limit_overrides(|current| std::mem::replace(current, limits));
+    let result = f();
+    with_limit_overrides(|current| *current = previous);
+    result
+}
+
+/// Returns the value a limit has for the executing script.
+pub(crate) fn current_limit(limit: Limit) -> usize {
+    with_limit_overrides(|overrides| overrides.value(limit))
+}
+
+/// What happened during one execution.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ExecutionReport {
+    /// How long the execution took.
+    pub duration: Duration,
+
+    /// Calls answered from a result cache.
+    pub cache_hits: u64,
+
+    /// Calls that could have been cached but had to run.
+    pub cache_misses: u64,
+
+    /// The limit that stopped the execution, if one did.
+    pub stopped_by: Option<Limit>,
+}
+
+/// Runs `f` and reports how it went.
+///
+/// Cache counts include every call made while `f` runs on this thread.
+pub fn measure<T>(f: impl FnOnce() -> Result<T, InterpreterError>) -> (Result<T, InterpreterError>, ExecutionReport) {
+    with_cache_stats(|stats| *stats = CacheStats::default());
+    let start = Instant::now();
+    let result = f();
+    let duration = start.elapsed();
+    let stats = with_cache_stats(std::mem::take);
+
+    let report = ExecutionReport {
+        duration,
+        cache_hits: stats.hits,
+        cache_misses: stats.misses,
+        stopped_by: result.as_ref().err().and_then(Limit::reached_by),
+    };
+    (result, report)
+}
+
+/// Counts a call that a result cache answered, or that it could have.
+pub(crate) fn record_cache_lookup(hit: bool) {
+    with_cache_stats(|stats| {
+        if hit {
+            stats.hits += 1;
+        } else {
+            stats.misses += 1;
+        }
+    });
+}
+
+/// Totals of the reports of many executions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct InterpreterMetrics {
+    executions: u64,
+    total_time: Duration,
+    cache_hits: u64,
+    cache_misses: u64,
+    stopped: [u64; Limit::ALL.len()],
+}
+
+impl InterpreterMetrics {
+    /// Creates metrics with nothing recorded.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the report of one execution to the totals.
+    pub fn record(&mut self, report: &ExecutionReport) {
+        self.executions = self.executions.saturating_add(1);
+        self.total_time = self.total_time.saturating_add(report.duration);
+        self.cache_hits = self.cache_hits.saturating_add(report.cache_hits);
+        self.cache_misses = self.cache_misses.saturating_add(report.cache_misses);
+        if let Some(limit) = report.stopped_by {
+            self.stopped[limit.index()] = self.stopped[limit.index()].saturating_add(1);
+        }
+    }
+
+    /// Returns the number of executions recorded.
+    pub fn executions(&self) -> u64 {
+        self.executions
+    }
+
+    /// Returns the time spent in all recorded executions.
+    pub fn total_time(&self) -> Duration {
+        self.total_time
+    }
+
+    /// Returns the calls answered from a result cache.
+    pub fn cache_hits(&self) -> u64 {
+        self.cache_hits
+    }
+
+    /// Returns the calls that could have been cached but had to run.
+    pub fn cache_misses(&self) -> u64 {
+        self.cache_misses
+    }
+
+    /// Returns the share of cacheable calls answered from a cache, from 0 to 1.
+    ///
+    /// `None` until a cacheable call has been made.
+    pub fn hit_rate(&self) -> Option<f64> {
+        let lookups = self.cache_hits.saturating_add(self.cache_misses);
+        (lookups > 0).then(|| self.cache_hits as f64 / lookups as f64)
+    }
+
+    /// Returns how many executions the limit stopped.
+    pub fn stopped_by(&self, limit: Limit) -> u64 {
+        self.stopped[limit.index()]
+    }
+}
@@ -0,0 +1,156 @@
+//! Operators of the expression language.
+//!
+//! The tokenizer reads each operator as an [`Operator`], which the parser
+//! carries into syntax trees, so evaluation, error messages and exports all
+//! work with the spelling users wrote. The binding power tables that drive
+//! Pratt parsing live here too.
+
+use std::fmt;
+
+/// A prefix, infix or assignment operator.
+///
+/// Unary minus and plus share [`Operator::Subtract`] and [`Operator::Add`]
+/// with their binary forms; an operation with one operand is unary.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Operator {
+    /// `+`
+    Add,
+    /// `-`
+    Subtract,
+    /// `*`
+    Multiply,
+    /// `/`
+    Divide,
+    /// `%`, the remainder
+    Modulo,
+    /// `^`
+    Power,
+    /// `√`, square root as a prefix, `n √ x` as the n-th root
+    Root,
+    /// `&&`
+    And,
+    /// `||`
+    Or,
+    /// `^^`
+    Xor,
+    /// `!^`
+    Xnor,
+    /// `!&`
+    Nand,
+    /// `!|`
+    Nor,
+    /// `!` as a prefix
+    Not,
+    /// `>`
+    Greater,
+    /// `<`
+    Less,
+    /// `>=`
+    GreaterEqual,
+    /// `<=`
+    LessEqual,
+    /// `==`
+    Equal,
+    /// `!=`
+    NotEqual,
+    /// `.`
+    Dot,
+    /// `=`
+    Assign,
+}
+
+impl Operator {
+    /// Returns whether the operator gives a truth value, 1 for true and 0 for
+    /// false, as comparisons and logical operators do.
+    pub fn is_boolean(self) -> bool {
+        matches!(
+            self,
+            Operator::And | Operator::Or | Operator::Xor | Operator::Xnor | Operator::Nand | Operator::Nor | Operator::Not
+                | Operator::Greater | Operator::Less | Operator::GreaterEqual | Operator::LessEqual | Operator::Equal | Operator::NotEqual
+        )
+    }
+
+    /// Returns the operator as it is written in source, e.g. `>=`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Modulo => "%",
+            Operator::Power => "^",
+            Operator::Root => "√",
+            Operator::And => "&&",
+            Operator::Or => "||",
+            Operator::Xor => "^^",
+            Operator::Xnor => "!^",
+            Operator::Nand => "!&",
+            Operator::Nor => "!|",
+            Operator::Not => "!",
+            Operator::Greater => ">",
+            Operator::Less => "<",
+            Operator::GreaterEqual => ">=",
+            Operator::LessEqual => "<=",
+            Operator::Equal => "==",
+            Operator::NotEqual => "!=",
+            Operator::Dot => ".",
+            Operator::Assign => "=",
+        }
+    }
+
+    /// Defines precedence and associativity.
+    ///
+    /// Returns a tuple of (left_binding_power, right_binding_power, is_left_associative).
+    ///
+    /// Higher binding power means higher precedence.
+    pub fn infix_binding_power(self) -> Option<(f32, f32, bool)> {
+        // For left associative operators, left_bp < right_bp
+        // For right associative operators, left_bp > right_bp
+        match self {
+            Operator::Assign => Some((0.2, 0.1, false)), // right-associative
+            Operator::And | Operator::Or | Operator::Xor | Operator::Xnor | Operator::Nand | Operator::Nor => {
+                Some((0.3, 0.4, true)) // logical operators
+            }
+            Operator::Less | Operator::Greater | Operator::GreaterEqual | Operator::LessEqual | Operator::Equal | Operator::NotEqual => {
+                Some((0.5, 0.6, true)) // comparison operators
+            }
+            Operator::Add | Operator::Subtract => Some((1.0, 1.1, true)), // left-associative
+            Operator::Multiply | Operator::Divide | Operator::Modulo => Some((2.0, 2.1, true)),
+            Operator::Dot => Some((5.0, 5.1, true)), // dot has higher precedence now
+            Operator::Power | Operator::Root => Some((4.0, 3.9, false)), // power remains the same
+            Operator::Not => None,
+        }
+    }
+
+    /// Determines how tightly unary ops bind.
+    ///
+    /// Returns the binding power for prefix operators.
+    ///
+    /// Higher values mean the operator binds tighter to its operand.
+    pub fn prefix_binding_power(self) -> Option<f32> {
+        match self {
+            Operator::Subtract | Operator::Add => Some(20.0),
+            Operator::Not => Some(20.0), // logical NOT
+            // root is a unary prefix
+            Operator::Root => Some(20.0),
+            _ => None,
+        }
+    }
+
+    /// Determines how tightly postfix ops bind.
+    ///
+    /// A `!` after an operand is factorial rather than NOT, and it binds
+    /// tighter than the prefix operators, so `-3!` is `-(3!)`.
+    pub fn postfix_binding_power(self) -> Option<f32> {
+        match self {
+            Operator::Not => Some(30.0), // factorial
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.symbol())
+    }
+}
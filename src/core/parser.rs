@@ -24,12 +24,13 @@ impl Parser {
     
     /// Parses the input as an expression.
     pub fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.tokenizer.check_unrecognized()?;
         let expr = Expression::parse(&mut self.tokenizer, 0.0)?;
         
         // Ensure we've consumed all tokens
         if self.tokenizer.peek_token() != &Token::EndOfInput {
             return Err(ParseError::UnexpectedToken(
-                format!("Expected end of input, found {:?}", self.tokenizer.peek_token())
+                format!("Expected end of input, found {}", self.tokenizer.peek_token())
             ));
         }
         
@@ -38,16 +39,18 @@ impl Parser {
     
     /// Parses the input as a statement or sequence of statements.
     pub fn parse_statements(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.tokenizer.check_unrecognized()?;
         let mut statements = Vec::new();
         
         while self.tokenizer.peek_token() != &Token::EndOfInput {
-            let statement = Statement::parse(&mut self.tokenizer)?;
-            statements.push(statement);
-            
-            // Skip any semicolons between statements
-            while self.tokenizer.peek_token() == &Token::Operator(';') {
+            // Skip empty statements (lone semicolons)
+            if self.tokenizer.peek_token() == &Token::Punctuation(';') {
                 self.tokenizer.next_token();
+                continue;
             }
+
+            let statement = Statement::parse(&mut self.tokenizer)?;
+            statements.push(statement);
         }
         
         Ok(statements)
@@ -55,21 +58,23 @@ impl Parser {
     
     /// Tries to parse a single statement.
     pub fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        self.tokenizer.check_unrecognized()?;
         Statement::parse(&mut self.tokenizer)
     }
     
     /// Tries to parse the input first as statements, then as an expression.
     ///
     /// This method attempts to parse the input as a sequence of statements. If that
-    /// fails, it falls back to parsing it as a single expression.
+    /// fails, it falls back to parsing it as a single expression. Blank input and
+    /// lone semicolons give an empty program.
     pub fn parse_program(&mut self) -> Result<ParsedProgram, ParseError> {
         // First try parsing as statements
         let mut tmp_parser = self.clone();
         match tmp_parser.parse_statements() {
-            Ok(statements) if !statements.is_empty() => {
+            Ok(statements) => {
                 // If successful, update our state and return the statements
                 *self = tmp_parser;
-                return Ok(ParsedProgram::Statements(statements));
+                Ok(ParsedProgram::Statements(statements))
             }
             _ => {
                 // Reset the tokenizer and try parsing as expression
@@ -0,0 +1,87 @@
+//! Random arithmetic problems for practice.
+//!
+//! Problems are built as syntax trees, printed with [`Expression::to_source`]
+//! and answered with [`Expression::evaluate`], so the text always parses back
+//! to the reported answer. Answers are whole numbers: divisions are only
+//! generated when they come out even.
+
+use rand::Rng;
+
+use crate::core::ast_expression::Expression;
+use crate::core::operator::Operator;
+use crate::core::symbol_manager::SymbolTable;
+
+/// Operators used when none are chosen.
+pub const DEFAULT_OPERATORS: [char; 4] = ['+', '-', '*', '/'];
+
+/// Largest product kept in a problem, so answers stay easy to work out.
+const MAX_PRODUCT: f32 = 1000.0;
+
+/// Generates a random problem with all four basic operators.
+///
+/// Returns the problem text and its answer. `depth` is the number of
+/// operator levels, so depth 1 gives problems like `7 * 3`.
+pub fn generate_expression<R: Rng + ?Sized>(depth: usize, rng: &mut R) -> (String, f32) {
+    generate_expression_with(depth, &DEFAULT_OPERATORS, rng)
+}
+
+/// Generates a random problem using only the given operators.
+///
+/// Characters other than `+`, `-`, `*` and `/` are ignored; without any
+/// usable operator the problem is a single number. A product that would
+/// exceed 1000 becomes a sum instead.
+pub fn generate_expression_with<R: Rng + ?Sized>(depth: usize, operators: &[char], rng: &mut R) -> (String, f32) {
+    let operators = operators
+        .iter()
+        .filter_map(|operator| match operator {
+            '+' => Some(Operator::Add),
+            '-' => Some(Operator::Subtract),
+            '*' => Some(Operator::Multiply),
+            '/' => Some(Operator::Divide),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    let expression = generate_node(depth, &operators, rng);
+    let answer = evaluate_node(&expression);
+    (expression.to_source(), answer)
+}
+
+fn generate_node<R: Rng + ?Sized>(depth: usize, operators: &[Operator], rng: &mut R) -> Expression {
+    if depth == 0 || operators.is_empty() {
+        return number(rng.gen_range(1..=9));
+    }
+
+    let operator = operators[rng.gen_range(0..operators.len())];
+    let mut left = generate_node(depth - 1, operators, rng);
+    let mut right = generate_node(depth - 1, operators, rng);
+
+    match operator {
+        // Divide by a positive whole number, making the dividend a multiple of it
+        Operator::Divide => {
+            let mut divisor = evaluate_node(&right);
+            if divisor < 1.0 {
+                divisor = rng.gen_range(1..=9) as f32;
+                right = number(divisor as u32);
+            }
+            if evaluate_node(&left) % divisor != 0.0 {
+                left = number(divisor as u32 * rng.gen_range(1..=9));
+            }
+        }
+        Operator::Multiply if (evaluate_node(&left) * evaluate_node(&right)).abs() > MAX_PRODUCT => {
+            return Expression::Operation(Operator::Add, vec![left, right]);
+        }
+        _ => {}
+    }
+
+    Expression::Operation(operator, vec![left, right])
+}
+
+fn number(value: u32) -> Expression {
+    Expression::Literal(value.to_string())
+}
+
+/// Evaluates a generated node, which has no variables and never divides by zero.
+fn evaluate_node(expression: &Expression) -> f32 {
+    expression.evaluate(&SymbolTable::new()).unwrap_or(0.0)
+}
@@ -0,0 +1,383 @@
+//! Exact rational evaluation for the opt-in exact mode.
+//!
+//! In exact mode numbers are fractions of two `i64`s, so `1/3 + 1/6` is
+//! exactly `1/2`. Operations without an exact answer, such as `sqrt(2)` or
+//! `sin(1)`, fall back to floating point, or fail when the mode is strict.
+//! A fraction that no longer fits in `i64` falls back the same way.
+
+use std::cmp::Ordering;
+
+use crate::core::ast_expression::{apply_operator, call_function, name_divisor, Expression};
+use crate::core::bignum::F32_EXACT_LIMIT;
+use crate::core::complex::Value;
+use crate::core::error_types::{EvalError, MathError};
+use crate::core::operator::Operator;
+use crate::core::symbol_manager::SymbolTable;
+
+/// A fraction in lowest terms with a positive denominator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Rational {
+    num: i64,
+    den: i64,
+}
+
+impl Rational {
+    /// Reduces a fraction, or returns `None` when it does not fit in `i64`.
+    /// The denominator must not be zero.
+    fn new(num: i128, den: i128) -> Option<Self> {
+        let divisor = gcd(num, den);
+        let sign = if den < 0 { -1 } else { 1 };
+        Some(Self {
+            num: i64::try_from(sign * num / divisor).ok()?,
+            den: i64::try_from(sign * den / divisor).ok()?,
+        })
+    }
+
+    fn integer(value: i64) -> Self {
+        Self { num: value, den: 1 }
+    }
+
+    /// Reads a plain decimal literal such as `12` or `0.25` exactly.
+    fn from_decimal(text: &str) -> Option<Self> {
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if whole.is_empty() && fraction.is_empty() {
+            return None;
+        }
+        if !whole.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let digits = format!("{}{}", whole, fraction);
+        let num = digits.parse::<i128>().ok()?;
+        let den = 10_i128.checked_pow(u32::try_from(fraction.len()).ok()?)?;
+        Self::new(num, den)
+    }
+
+    /// Converts an integral float that `f32` holds exactly.
+    fn from_float(value: f32) -> Option<Self> {
+        if value.fract() == 0.0 && value.abs() <= F32_EXACT_LIMIT {
+            Some(Self::integer(value as i64))
+        } else {
+            None
+        }
+    }
+
+    fn to_f32(self) -> f32 {
+        (self.num as f64 / self.den as f64) as f32
+    }
+
+    fn is_integer(self) -> bool {
+        self.den == 1
+    }
+
+    fn is_zero(self) -> bool {
+        self.num == 0
+    }
+
+    fn checked_add(self, other: Self) -> Option<Self> {
+        Self::new(
+            self.num as i128 * other.den as i128 + other.num as i128 * self.den as i128,
+            self.den as i128 * other.den as i128,
+        )
+    }
+
+    fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(Self { num: other.num.checked_neg()?, den: other.den })
+    }
+
+    fn checked_mul(self, other: Self) -> Option<Self> {
+        Self::new(self.num as i128 * other.num as i128, self.den as i128 * other.den as i128)
+    }
+
+    /// Divides by a non-zero fraction.
+    fn checked_div(self, other: Self) -> Option<Self> {
+        Self::new(self.num as i128 * other.den as i128, self.den as i128 * other.num as i128)
+    }
+
+    /// Remainder with the sign of the dividend, like `%` in real mode.
+    fn checked_rem(self, other: Self) -> Option<Self> {
+        let quotient = self.checked_div(other)?;
+        let truncated = Self::integer(quotient.num / quotient.den);
+        self.checked_sub(other.checked_mul(truncated)?)
+    }
+
+    /// Raises to an integer power. A zero base needs a non-negative exponent.
+    fn checked_pow(self, exponent: i64) -> Option<Self> {
+        let mut base = if exponent < 0 { Self::integer(1).checked_div(self)? } else { self };
+        let mut remaining = exponent.unsigned_abs();
+        let mut result = Self::integer(1);
+
+        while remaining > 0 {
+            if remaining & 1 == 1 {
+                result = result.checked_mul(base)?;
+            }
+            remaining >>= 1;
+            if remaining > 0 {
+                base = base.checked_mul(base)?;
+            }
+        }
+        Some(result)
+    }
+
+    fn floor(self) -> Self {
+        Self::integer(self.num.div_euclid(self.den))
+    }
+
+    fn ceil(self) -> Self {
+        Self::integer(-(-self.num).div_euclid(self.den))
+    }
+
+    /// Rounds to the nearest integer, breaking ties with `tie` given the floor.
+    fn round_with(self, tie: impl FnOnce(i64) -> bool) -> Self {
+        let floor = self.num.div_euclid(self.den);
+        let twice_remainder = 2 * self.num.rem_euclid(self.den) as i128;
+        let up = match twice_remainder.cmp(&(self.den as i128)) {
+            Ordering::Less => false,
+            Ordering::Greater => true,
+            Ordering::Equal => tie(floor),
+        };
+        // A remainder means den > 1, so the floor is below i64::MAX
+        Self::integer(if up { floor + 1 } else { floor })
+    }
+
+    /// Rounds halves away from zero, like `f32::round`.
+    fn round(self) -> Self {
+        let negative = self.num < 0;
+        self.round_with(|_| !negative)
+    }
+
+    /// Rounds halves to the even neighbour.
+    fn round_even(self) -> Self {
+        self.round_with(|floor| floor % 2 != 0)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num as i128 * other.den as i128).cmp(&(other.num as i128 * self.den as i128))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn gcd(mut a: i128, mut b: i128) -> i128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.abs().max(1)
+}
+
+/// A number during exact evaluation: a fraction, or a float once exactness is lost.
+#[derive(Clone, Copy, Debug)]
+enum Number {
+    Exact(Rational),
+    Approx(f32),
+}
+
+impl Number {
+    fn to_f32(self) -> f32 {
+        match self {
+            Number::Exact(value) => value.to_f32(),
+            Number::Approx(value) => value,
+        }
+    }
+
+    fn as_exact(self) -> Option<Rational> {
+        match self {
+            Number::Exact(value) => Some(value),
+            Number::Approx(_) => None,
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Exact(value) => Value::Rational(value.num, value.den),
+            Number::Approx(value) => Value::Real(value),
+        }
+    }
+}
+
+/// The outcome of trying an operation on fractions.
+enum Exactly {
+    /// The exact result.
+    Done(Rational),
+    /// The result does not fit in `i64`.
+    Overflowed,
+    /// The operation has no exact implementation.
+    Unsupported,
+}
+
+impl From<Option<Rational>> for Exactly {
+    fn from(result: Option<Rational>) -> Self {
+        result.map_or(Exactly::Overflowed, Exactly::Done)
+    }
+}
+
+impl Expression {
+    /// Evaluates the expression with exact fractions.
+    ///
+    /// The result is a [`Value::Rational`] when it is exact. Otherwise it is
+    /// a [`Value::Real`] approximation, unless `strict` is set, in which case
+    /// inexact operations fail with [`MathError::InexactResult`] and
+    /// overflowing fractions with [`MathError::Overflow`].
+    pub fn evaluate_exact(&self, context: &SymbolTable<f32>, strict: bool) -> Result<Value, EvalError> {
+        ExactEvaluator { context, strict }.evaluate(self).map(Value::from)
+    }
+}
+
+/// Walks an expression with exact fractions.
+struct ExactEvaluator<'a> {
+    context: &'a SymbolTable<f32>,
+    strict: bool,
+}
+
+impl ExactEvaluator<'_> {
+    fn evaluate(&self, expression: &Expression) -> Result<Number, EvalError> {
+        match expression {
+            Expression::Literal(text) => {
+                if let Some(value) = Rational::from_decimal(text) {
+                    return Ok(Number::Exact(value));
+                }
+                // Variables, constants and hex literals are read as floats
+                let value = expression.evaluate(self.context)?;
+                self.float_result(expression, value, true)
+            }
+
+            // Assignments store floats, so they are done in real mode
+            Expression::Operation(Operator::Assign, _) => {
+                let value = expression.evaluate(self.context)?;
+                self.float_result(expression, value, true)
+            }
+
+            Expression::Operation(operator, operands) => {
+                let values = operands
+                    .iter()
+                    .map(|operand| self.evaluate(operand))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.apply_operator(expression, *operator, &values)
+                    .map_err(|error| name_divisor(error, *operator, operands))
+            }
+
+            // Unit names, the loop of fold() and the name in typeof() are not values, so they stay real
+            Expression::FunctionCall(name, _) if matches!(name.as_str(), "convert" | "fold" | "typeof") => {
+                let value = expression.evaluate(self.context)?;
+                self.float_result(expression, value, true)
+            }
+
+            Expression::FunctionCall(name, args) => {
+                let values = args
+                    .iter()
+                    .map(|arg| self.evaluate(arg))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.call_function(expression, name, &values)
+            }
+        }
+    }
+
+    fn apply_operator(&self, expression: &Expression, operator: Operator, values: &[Number]) -> Result<Number, EvalError> {
+        let unary = values.len() == 1;
+        let left = values[0];
+        let right = values.get(1).copied().unwrap_or(Number::Exact(Rational::integer(0)));
+
+        let outcome = match (left.as_exact(), right.as_exact()) {
+            (Some(l), Some(r)) => exact_operator(operator, unary, l, r)?,
+            _ => Exactly::Unsupported,
+        };
+
+        match outcome {
+            Exactly::Done(value) => Ok(Number::Exact(value)),
+            Exactly::Overflowed => self.overflowed(apply_operator(operator, unary, left.to_f32(), right.to_f32())?),
+            Exactly::Unsupported => {
+                let value = apply_operator(operator, unary, left.to_f32(), right.to_f32())?;
+                self.float_result(expression, value, all_integers(values))
+            }
+        }
+    }
+
+    fn call_function(&self, expression: &Expression, name: &str, values: &[Number]) -> Result<Number, EvalError> {
+        let exact = values.iter().map(|value| value.as_exact()).collect::<Option<Vec<_>>>();
+
+        // Functions that keep fractions exact
+        let result = match (name, exact.as_deref()) {
+            ("abs", Some(&[x])) => Some(x.num.checked_abs().map(|num| Rational { num, den: x.den })),
+            ("floor", Some(&[x])) => Some(Some(x.floor())),
+            ("ceil", Some(&[x])) => Some(Some(x.ceil())),
+            ("round", Some(&[x])) => Some(Some(x.round())),
+            ("round_even", Some(&[x])) => Some(Some(x.round_even())),
+            ("min", Some(&[x, y])) => Some(Some(x.min(y))),
+            ("max", Some(&[x, y])) => Some(Some(x.max(y))),
+            _ => None,
+        };
+
+        let floats = values.iter().map(|value| value.to_f32()).collect::<Vec<_>>();
+        match result {
+            Some(Some(value)) => Ok(Number::Exact(value)),
+            Some(None) => self.overflowed(call_function(name, floats, self.context)?),
+            None => {
+                let value = call_function(name, floats, self.context)?;
+                self.float_result(expression, value, all_integers(values))
+            }
+        }
+    }
+
+    /// Keeps a float computed from exact inputs exact when it is an integer.
+    ///
+    /// Only integer inputs are trusted, since a float computed from fractions
+    /// may have rounded onto an integer.
+    fn float_result(&self, expression: &Expression, value: f32, trusted: bool) -> Result<Number, EvalError> {
+        match Rational::from_float(value) {
+            Some(exact) if trusted => Ok(Number::Exact(exact)),
+            _ if self.strict => Err(MathError::InexactResult(expression.to_source()).into()),
+            _ => Ok(Number::Approx(value)),
+        }
+    }
+
+    /// Falls back to the float result of an operation whose fraction overflowed.
+    fn overflowed(&self, value: f32) -> Result<Number, EvalError> {
+        if self.strict {
+            Err(MathError::Overflow.into())
+        } else {
+            Ok(Number::Approx(value))
+        }
+    }
+}
+
+/// Applies an operator to fractions, if it has an exact result.
+fn exact_operator(operator: Operator, unary: bool, left: Rational, right: Rational) -> Result<Exactly, EvalError> {
+    let truth = |condition: bool| Exactly::Done(Rational::integer(i64::from(condition)));
+
+    Ok(match operator {
+        Operator::Add if unary => Exactly::Done(left),
+        Operator::Subtract if unary => left.num.checked_neg().map(|num| Rational { num, den: left.den }).into(),
+        Operator::Add => left.checked_add(right).into(),
+        Operator::Subtract => left.checked_sub(right).into(),
+        Operator::Multiply => left.checked_mul(right).into(),
+        Operator::Divide if right.is_zero() => return Err(MathError::DivisionByZero(None).into()),
+        Operator::Divide => left.checked_div(right).into(),
+        Operator::Modulo if right.is_zero() => return Err(MathError::ModuloByZero(None).into()),
+        Operator::Modulo => left.checked_rem(right).into(),
+        Operator::Power if right.is_integer() => {
+            if left.is_zero() && right.num < 0 {
+                return Err(MathError::DivisionByZero(None).into());
+            }
+            left.checked_pow(right.num).into()
+        }
+        Operator::Less => truth(left < right),
+        Operator::Greater => truth(left > right),
+        Operator::LessEqual => truth(left <= right),
+        Operator::GreaterEqual => truth(left >= right),
+        Operator::Equal => truth(left == right),
+        Operator::NotEqual => truth(left != right),
+        _ => Exactly::Unsupported,
+    })
+}
+
+fn all_integers(values: &[Number]) -> bool {
+    values.iter().all(|value| value.as_exact().is_some_and(Rational::is_integer))
+}
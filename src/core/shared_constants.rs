@@ -0,0 +1,131 @@
+//! Constants shared by every session.
+//!
+//! `const global NAME = value` publishes a constant that all users can read.
+//! Only the user who published a name can declare it, and once published it
+//! never changes. Names are looked up after the session's own variables and
+//! before the built-in constants like `PI`. Which users share a table is up
+//! to the caller.
+
+use std::collections::HashMap;
+
+use crate::core::error_types::SymbolError;
+use crate::core::execution_state::{with_shared_constant_state, SharedConstantState};
+use crate::core::symbol_manager::global_constants;
+
+/// A published constant and who published it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SharedConstant {
+    /// The value of the constant.
+    pub value: f32,
+
+    /// The ID of the user who published the constant.
+    pub author: u64,
+}
+
+/// Most constants one user may publish in a table.
+pub const MAX_SHARED_CONSTANTS_PER_AUTHOR: usize = 50;
+
+/// Stores the constants published with `const global`, by name.
+#[derive(Clone, Debug, Default)]
+pub struct SharedConstants {
+    constants: HashMap<String, SharedConstant>,
+}
+
+impl SharedConstants {
+    /// Creates a new, empty table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Publishes a constant under the given name.
+    ///
+    /// Built-in constants and names that are already published cannot be
+    /// declared again, and a name published by another user says so. The
+    /// author declaring their constant again with the same value changes
+    /// nothing, so replaying a script that published it still works. Each
+    /// author may publish up to [`MAX_SHARED_CONSTANTS_PER_AUTHOR`] names.
+    pub fn publish(&mut self, name: String, value: f32, author: u64) -> Result<(), SymbolError> {
+        if global_constants().contains(&name) {
+            return Err(SymbolError::ImmutableConstant(name));
+        }
+
+        match self.constants.get(&name) {
+            Some(existing) if existing.author != author => Err(SymbolError::SharedConstantOwnedByAnotherUser(name)),
+            Some(existing) if existing.value.to_bits() == value.to_bits() => Ok(()),
+            Some(_) => Err(SymbolError::ImmutableConstant(name)),
+            None if self.count_by(author) >= MAX_SHARED_CONSTANTS_PER_AUTHOR => {
+                Err(SymbolError::TooManySharedConstants { limit: MAX_SHARED_CONSTANTS_PER_AUTHOR })
+            }
+            None => {
+                self.constants.insert(name, SharedConstant { value, author });
+                Ok(())
+            }
+        }
+    }
+
+    /// Returns how many constants a user has published.
+    pub fn count_by(&self, author: u64) -> usize {
+        self.constants.values().filter(|constant| constant.author == author).count()
+    }
+
+    /// Gets a published constant by name.
+    pub fn get(&self, name: &str) -> Option<&SharedConstant> {
+        self.constants.get(name)
+    }
+
+    /// Iterates over the published constants sorted by name.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&str, &SharedConstant)> {
+        let mut entries: Vec<_> = self.constants.iter().map(|(name, constant)| (name.as_str(), constant)).collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter()
+    }
+
+    /// Returns the number of published constants.
+    pub fn len(&self) -> usize {
+        self.constants.len()
+    }
+
+    /// Returns true if no constants have been published.
+    pub fn is_empty(&self) -> bool {
+        self.constants.is_empty()
+    }
+}
+
+/// Runs `f` with the shared constants readable and `author` able to publish new ones.
+///
+/// Constants published while `f` runs are kept in `constants`, even when
+/// the script that published them fails later on.
+pub fn with_shared_constants<R>(constants: &mut SharedConstants, author: u64, f: impl FnOnce() -> R) -> R {
+    install(constants.clone(), Some(author));
+    let result = f();
+    *constants = with_shared_constant_state(|state| std::mem::take(state).constants);
+    result
+}
+
+/// Runs `f` with the shared constants readable but not writable.
+///
+/// Meant for evaluations, where `const global` would outlive the copy of the
+/// variables they run on.
+pub fn reading_shared_constants<R>(constants: &SharedConstants, f: impl FnOnce() -> R) -> R {
+    install(constants.clone(), None);
+    let result = f();
+    with_shared_constant_state(|state| *state = SharedConstantState::default());
+    result
+}
+
+fn install(constants: SharedConstants, author: Option<u64>) {
+    with_shared_constant_state(|state| *state = SharedConstantState { constants, author });
+}
+
+/// Looks up a shared constant for the executing script.
+pub(crate) fn shared_constant(name: &str) -> Option<f32> {
+    with_shared_constant_state(|state| state.constants.get(name).map(|constant| constant.value))
+}
+
+/// Publishes a constant declared with `const global` by the executing script.
+pub(crate) fn publish_shared_constant(name: &str, value: f32) -> Result<(), SymbolError> {
+    with_shared_constant_state(|state| match state.author {
+        Some(author) => state.constants.publish(name.to_string(), value, author),
+        None => Err(SymbolError::GlobalConstantNotAllowed(name.to_string())),
+    })
+}
@@ -0,0 +1,185 @@
+//! Registry of published snippets that scripts can import.
+//!
+//! A snippet is a named piece of source code holding function, procedure and
+//! constant definitions, which any script can pull into its scope with `import "name"`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::core::lexical_analyzer::Tokenizer;
+use crate::core::ast_statement::Statement;
+use crate::core::parser::Parser;
+use crate::core::symbol_manager::SymbolTable;
+use crate::core::error_types::{EvalError, SymbolError, ControlFlowError, InterpreterError};
+use crate::core::execution_state::with_import_state;
+use crate::core::interpreter::execute_with_imports;
+
+/// Maximum number of snippets that may be imported inside one another.
+pub(crate) const MAX_IMPORT_DEPTH: usize = 8;
+
+/// Most snippets one user may publish.
+pub const MAX_SNIPPETS_PER_AUTHOR: usize = 25;
+
+/// Longest snippet name, in characters.
+pub const MAX_SNIPPET_NAME_LENGTH: usize = 32;
+
+/// A published piece of source code.
+#[derive(Clone, Debug)]
+pub struct Snippet {
+    /// The source code of the snippet.
+    pub source: String,
+
+    /// The ID of the user who published the snippet.
+    pub author: u64,
+}
+
+/// Stores published snippets by name.
+///
+/// The table is shared behind an `Arc`, so handing the registry to an
+/// execution only bumps a reference count. Publishing copies the table of
+/// pointers when an execution still holds the old one, never the sources.
+#[derive(Clone, Debug, Default)]
+pub struct SnippetRegistry {
+    snippets: Arc<HashMap<String, Arc<Snippet>>>,
+}
+
+impl SnippetRegistry {
+    /// Creates a new, empty registry.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores a snippet under the given name, returning the snippet it replaced.
+    pub fn insert(&mut self, name: String, source: String, author: u64) -> Option<Snippet> {
+        Arc::make_mut(&mut self.snippets)
+            .insert(name, Arc::new(Snippet { source, author }))
+            .map(Arc::unwrap_or_clone)
+    }
+
+    /// Publishes a snippet under the given name after validating it.
+    ///
+    /// The snippet must import cleanly into an empty scope, and a name that
+    /// belongs to another user cannot be overwritten. Names are at most
+    /// [`MAX_SNIPPET_NAME_LENGTH`] characters and each user may publish up to
+    /// [`MAX_SNIPPETS_PER_AUTHOR`] of them. Returns the snippet it replaced
+    /// when the author republishes their own name.
+    pub fn publish(&mut self, name: String, source: String, author: u64) -> Result<Option<Snippet>, InterpreterError> {
+        // Names are written between double quotes in import statements
+        if name.is_empty() || name.chars().any(|ch| ch == '"' || ch.is_whitespace()) {
+            return Err(EvalError::from(SymbolError::InvalidIdentifier(name)).into());
+        }
+        if name.chars().count() > MAX_SNIPPET_NAME_LENGTH {
+            return Err(EvalError::from(SymbolError::SnippetNameTooLong { limit: MAX_SNIPPET_NAME_LENGTH }).into());
+        }
+
+        match self.snippets.get(&name) {
+            Some(existing) if existing.author != author => {
+                return Err(EvalError::from(SymbolError::SnippetOwnedByAnotherUser(name)).into());
+            }
+            None if self.count_by(author) >= MAX_SNIPPETS_PER_AUTHOR => {
+                return Err(EvalError::from(SymbolError::TooManySnippets { limit: MAX_SNIPPETS_PER_AUTHOR }).into());
+            }
+            _ => {}
+        }
+
+        // Validate by importing the candidate the same way scripts will,
+        // and keep it once it imports
+        let mut candidate = self.clone();
+        let replaced = candidate.insert(name.clone(), source, author);
+        execute_with_imports(&format!("import \"{}\"", name), &mut SymbolTable::new(), &candidate)?;
+
+        *self = candidate;
+        Ok(replaced)
+    }
+
+    /// Returns how many snippets a user has published.
+    pub fn count_by(&self, author: u64) -> usize {
+        self.snippets.values().filter(|snippet| snippet.author == author).count()
+    }
+
+    /// Gets a snippet by name.
+    pub fn get(&self, name: &str) -> Option<&Snippet> {
+        self.snippets.get(name).map(Arc::as_ref)
+    }
+
+    /// Checks if a snippet with the given name exists.
+    pub fn contains(&self, name: &str) -> bool {
+        self.snippets.contains_key(name)
+    }
+
+    /// Returns the number of published snippets.
+    pub fn len(&self) -> usize {
+        self.snippets.len()
+    }
+
+    /// Returns true if no snippets have been published.
+    pub fn is_empty(&self) -> bool {
+        self.snippets.is_empty()
+    }
+}
+
+/// Imports a snippet from the registry of the current execution into the context.
+///
+/// Snippets already imported during this execution are skipped, so two snippets
+/// may share a dependency without redefining its functions.
+pub(crate) fn import_snippet(name: &str, context: &mut SymbolTable<f32>) -> Result<(), EvalError> {
+    let snippet = with_import_state(|state| -> Result<Option<Arc<Snippet>>, EvalError> {
+        if state.stack.iter().any(|importing| importing == name) {
+            return Err(ControlFlowError::RecursiveImport { name: name.to_string() }.into());
+        }
+        if state.stack.len() >= MAX_IMPORT_DEPTH {
+            return Err(ControlFlowError::ImportTooDeep { name: name.to_string(), limit: MAX_IMPORT_DEPTH }.into());
+        }
+        if state.imported.contains(name) {
+            return Ok(None);
+        }
+
+        let snippet = state.registry.snippets.get(name)
+            .ok_or_else(|| SymbolError::SnippetNotFound(name.to_string()))?;
+        state.stack.push(name.to_string());
+        Ok(Some(Arc::clone(snippet)))
+    })?;
+
+    let Some(snippet) = snippet else {
+        return Ok(());
+    };
+
+    let result = define_snippet(name, &snippet.source, context);
+
+    with_import_state(|state| {
+        state.stack.pop();
+        if result.is_ok() {
+            state.imported.insert(name.to_string());
+        }
+    });
+
+    result
+}
+
+/// Parses a snippet and evaluates its definitions into the context.
+fn define_snippet(name: &str, source: &str, context: &mut SymbolTable<f32>) -> Result<(), EvalError> {
+    let statements = Parser::new(Tokenizer::from_input(source))
+        .parse_statements()
+        .map_err(|error| ControlFlowError::ImportFailed {
+            name: name.to_string(),
+            reason: error.to_string(),
+        })?;
+
+    // Only definitions are allowed, check them all before defining anything
+    let only_definitions = statements.iter().all(|statement| matches!(
+        statement,
+        Statement::Function { .. } | Statement::Procedure { .. } | Statement::Const { .. } | Statement::Import(_)
+    ));
+    if !only_definitions {
+        return Err(ControlFlowError::ImportFailed {
+            name: name.to_string(),
+            reason: "snippets may only contain function, procedure, constant and import declarations.".to_string(),
+        }.into());
+    }
+
+    for statement in &statements {
+        statement.evaluate(context)?;
+    }
+
+    Ok(())
+}
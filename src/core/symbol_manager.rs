@@ -1,7 +1,30 @@
 use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
 use lazy_static::lazy_static;
 use crate::core::error_types::{EvalError, SymbolError, ControlFlowError};
 use crate::core::ast_statement::Statement;
+use crate::core::ast_expression::Expression;
+use crate::core::builtins::list_builtins;
+use crate::core::lexical_analyzer::{is_identifier, KEYWORDS};
+
+/// Most syntax nodes one function or procedure body may have.
+pub const MAX_CALLABLE_NODES: usize = 10_000;
+
+/// Most syntax nodes the functions and procedures of one table may have together.
+///
+/// Sessions keep their definitions for as long as the bot runs, so this
+/// bounds the memory a session can hold in code.
+pub const MAX_TOTAL_CALLABLE_NODES: usize = 100_000;
+
+/// Most results kept for one `memo fn` function.
+pub const MAX_MEMO_RESULTS_PER_FUNCTION: usize = 10_000;
+
+/// Most results kept for all `memo fn` functions of one table together.
+///
+/// Like the code itself, cached results last for the whole session.
+pub const MAX_MEMO_RESULTS: usize = 50_000;
 
 /// Stores global constants that are always available to expressions.
 ///
@@ -29,7 +52,7 @@ impl GlobalConstants {
         values.insert("TAU".to_string(), std::f32::consts::PI * 2.0);
         values.insert("E".to_string(), std::f32::consts::E);
          // The golden ratio number
-        values.insert("PHI".to_string(), 1.618033988749895);
+        values.insert("PHI".to_string(), 1.618_034);
         values.insert("SQRT2".to_string(), std::f32::consts::SQRT_2);
         values.insert("INFINITY".to_string(), f32::INFINITY);
         
@@ -56,6 +79,11 @@ impl GlobalConstants {
 // throughout the program's lifetime without being recreated
 lazy_static! {
     static ref GLOBAL_CONSTANTS: GlobalConstants = GlobalConstants::new();
+    static ref RESERVED_NAMES: HashSet<&'static str> = KEYWORDS
+        .iter()
+        .copied()
+        .chain(list_builtins().iter().map(|spec| spec.name))
+        .collect();
 }
 
 /// Gets a reference to the global constants.
@@ -67,6 +95,45 @@ pub fn global_constants() -> &'static GlobalConstants {
     &GLOBAL_CONSTANTS
 }
 
+/// Gets the names that variables and constants cannot take: the keywords and
+/// the names of built-in functions.
+///
+/// A variable named `sin` would be confused with the built-in whenever it
+/// is followed by parentheses, and one named after a keyword could never be
+/// read back.
+pub fn reserved_names() -> &'static HashSet<&'static str> {
+    &RESERVED_NAMES
+}
+
+/// Checks whether a name refers to the result of an earlier top-level
+/// statement of a script: `_` for the latest one, `_1`, `_2`, … by position.
+///
+/// These names are bound while a script runs and cannot be declared or assigned.
+pub fn is_result_name(name: &str) -> bool {
+    name.strip_prefix('_')
+        .is_some_and(|position| position.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// Checks whether a name is reserved or refers to an earlier result.
+fn is_reserved(name: &str) -> bool {
+    reserved_names().contains(name) || is_result_name(name)
+}
+
+/// Checks that a name can be given to a variable or constant.
+///
+/// The name must be read as a single identifier and must not be reserved,
+/// see [`reserved_names`] and [`is_result_name`], nor the name of a built-in
+/// constant like `PI`.
+pub fn validate_identifier(name: &str) -> Result<(), SymbolError> {
+    if !is_identifier(name) || is_reserved(name) {
+        return Err(SymbolError::InvalidIdentifier(name.to_string()));
+    }
+    if global_constants().contains(name) {
+        return Err(SymbolError::ImmutableConstant(name.to_string()));
+    }
+    Ok(())
+}
+
 /// Stores variables and their values during evaluation.
 /// 
 /// Also tracks which variables are constants that cannot be modified.
@@ -74,35 +141,92 @@ pub fn global_constants() -> &'static GlobalConstants {
 /// Provides safe access and modification methods for variables.
 ///
 /// A symbol table for storing variables and constants.
+///
+/// The table is `Send` and `Sync` whenever `T` is. Evaluation only needs a
+/// shared reference, which lets several threads evaluate against the same
+/// table; see [`SymbolTable::freeze`].
+///
+/// The maps are shared behind `Arc`s and copied on their first change, so
+/// nested scopes and snapshots share them with the table they came from
+/// until one of them writes.
 #[derive(Clone, Default)]
 pub struct SymbolTable<T: Clone + PartialEq> {
     /// The values of variables and constants.
-    pub values: HashMap<String, T>,
+    pub values: Arc<HashMap<String, T>>,
     
     /// Names of symbols that are constants and cannot be modified.
-    pub constants: HashSet<String>,
+    pub constants: Arc<HashSet<String>>,
 
     /// Functions defined in this scope.
-    pub functions: HashMap<String, (Vec<String>, Statement)>,
+    pub functions: Arc<HashMap<String, (Vec<String>, Statement)>>,
     
     /// Procedures defined in this scope.
-    pub procedures: HashMap<String, (Vec<String>, Statement)>,
+    pub procedures: Arc<HashMap<String, (Vec<String>, Statement)>>,
+
+    /// Formulas (`let name := expr`) that are re-evaluated on every read.
+    pub formulas: Arc<HashMap<String, Expression>>,
+
+    /// Cached results of functions declared with `memo fn`.
+    pub memo_cache: MemoCache,
+
+    /// Whether this scope belongs to a function or procedure call.
+    ///
+    /// Only [`SymbolTable::call_scope`] sets it, on a scope that is dropped
+    /// when the call ends. Blocks inside the call inherit it, while the
+    /// session's own table and its top-level blocks never have it.
+    in_callable: bool,
+
+    /// Names declared in this scope, with the kind of item they declare.
+    ///
+    /// Inner scopes start empty, so a block may shadow outer names.
+    scope_names: HashMap<String, &'static str>,
+
+    /// When each variable, constant and formula was last set, once
+    /// [`SymbolTable::track_modifications`] turns this on.
+    ///
+    /// Nested scopes never track, so evaluation pays nothing for it; their
+    /// changes are stamped when copied back to the tracking table.
+    modified: Option<HashMap<String, SystemTime>>,
 }
 
 impl<T: Clone + PartialEq> SymbolTable<T> {
     /// Creates a new, empty symbol table.
     pub fn new() -> Self {
         Self {
-            values: HashMap::new(),
-            constants: HashSet::new(),
-            functions: HashMap::new(),
-            procedures: HashMap::new(),
+            values: Arc::default(),
+            constants: Arc::default(),
+            functions: Arc::default(),
+            procedures: Arc::default(),
+            formulas: Arc::default(),
+            memo_cache: MemoCache::default(),
+            in_callable: false,
+            scope_names: HashMap::new(),
+            modified: None,
         }
     }
     
-    /// Checks if a symbol is defined (either as a variable or constant).
+    /// Starts recording when each symbol is set, see [`SymbolTable::modified_at`].
+    ///
+    /// Symbols set before this call have no time.
+    pub fn track_modifications(&mut self) {
+        self.modified.get_or_insert_with(HashMap::new);
+    }
+
+    /// Returns when a variable, constant or formula was last set, if recorded.
+    pub fn modified_at(&self, name: &str) -> Option<SystemTime> {
+        self.modified.as_ref()?.get(name).copied()
+    }
+
+    /// Records that a symbol was just set, when tracking is on.
+    fn touch(&mut self, name: &str) {
+        if let Some(modified) = &mut self.modified {
+            modified.insert(name.to_string(), SystemTime::now());
+        }
+    }
+
+    /// Checks if a symbol is defined (as a variable, constant or formula).
     pub fn contains(&self, name: &str) -> bool {
-        self.values.contains_key(name)
+        self.values.contains_key(name) || self.formulas.contains_key(name)
     }
     
     /// Gets the value of a symbol.
@@ -124,6 +248,9 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
         if global_constants().contains(&name) {
             return Err(SymbolError::ImmutableConstant(name).into());
         }
+        if is_reserved(&name) {
+            return Err(SymbolError::InvalidIdentifier(name).into());
+        }
         
         // Then check if it's a local constant
         if self.is_constant(&name) {
@@ -136,7 +263,12 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
             }
             return Err(SymbolError::ImmutableConstant(name).into());
         }
-        self.values.insert(name, value);
+        // Assigning a value turns a formula back into a plain variable
+        if self.formulas.contains_key(&name) {
+            Arc::make_mut(&mut self.formulas).remove(&name);
+        }
+        self.touch(&name);
+        Arc::make_mut(&mut self.values).insert(name, value);
         Ok(())
     }
     
@@ -150,40 +282,215 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
         if global_constants().contains(&name) {
             return Err(SymbolError::ImmutableConstant(name).into());
         }
+        if is_reserved(&name) {
+            return Err(SymbolError::InvalidIdentifier(name).into());
+        }
         
         // Then check if it exists locally
-        if self.values.contains_key(&name) {
+        if self.contains(&name) {
+            return Err(SymbolError::ImmutableConstant(name).into());
+        }
+        self.touch(&name);
+        Arc::make_mut(&mut self.values).insert(name.clone(), value);
+        Arc::make_mut(&mut self.constants).insert(name);
+        Ok(())
+    }
+
+    /// Declares a formula that is re-evaluated every time the name is read.
+    ///
+    /// Replaces any variable or formula of the same name. Constants cannot be
+    /// redefined, and a formula that depends on itself, directly or through
+    /// other formulas, is rejected.
+    pub fn declare_formula(&mut self, name: String, expression: Expression) -> Result<(), EvalError> {
+        if global_constants().contains(&name) || self.is_constant(&name) {
             return Err(SymbolError::ImmutableConstant(name).into());
         }
-        self.values.insert(name.clone(), value);
-        self.constants.insert(name);
+        if is_reserved(&name) {
+            return Err(SymbolError::InvalidIdentifier(name).into());
+        }
+
+        if self.formula_reads(&expression, &name, &mut HashSet::new()) {
+            return Err(SymbolError::CyclicFormula(name).into());
+        }
+
+        if self.values.contains_key(&name) {
+            Arc::make_mut(&mut self.values).remove(&name);
+        }
+        self.touch(&name);
+        Arc::make_mut(&mut self.formulas).insert(name, expression);
         Ok(())
     }
+
+    /// Gets the expression of a formula by name.
+    pub fn get_formula(&self, name: &str) -> Option<&Expression> {
+        self.formulas.get(name)
+    }
+
+    /// Checks whether an expression reads `target`, following formulas transitively.
+    fn formula_reads<'a>(&'a self, expression: &'a Expression, target: &str, visited: &mut HashSet<&'a str>) -> bool {
+        match expression {
+            Expression::Literal(name) => {
+                if name == target {
+                    return true;
+                }
+                match self.formulas.get(name) {
+                    Some(formula) if visited.insert(name) => self.formula_reads(formula, target, visited),
+                    _ => false,
+                }
+            }
+            Expression::Operation(_, operands) | Expression::FunctionCall(_, operands) => {
+                operands.iter().any(|operand| self.formula_reads(operand, target, visited))
+            }
+        }
+    }
     
+    /// Records that `let`, `const`, `fn` or `proc` declares a name in this scope.
+    ///
+    /// A name holds one kind of item per scope: a function or procedure
+    /// cannot share its name with another function, procedure or variable
+    /// declared in the same scope. Variables may be declared again, and an
+    /// inner scope may shadow any outer name.
+    pub(crate) fn declare_name(&mut self, name: &str, kind: &'static str) -> Result<(), EvalError> {
+        let is_callable = |kind: &str| kind == "Function" || kind == "Procedure";
+        match self.scope_names.get(name) {
+            Some(&existing) if is_callable(existing) || is_callable(kind) => {
+                Err(ControlFlowError::FunctionOrProcedureAlreadyDefined {
+                    name: name.to_string(),
+                    kind: existing.to_string(),
+                }.into())
+            }
+            _ => {
+                self.scope_names.insert(name.to_string(), kind);
+                Ok(())
+            }
+        }
+    }
+
     /// Declares a new function with the given name, parameters, and body.
+    ///
+    /// Fails if the name is already declared in this scope; see [`SymbolTable::declare_name`].
     pub fn declare_function(&mut self, name: String, params: Vec<String>, body: Statement) -> Result<(), EvalError> {
-        if self.functions.contains_key(&name) {
-            return Err(ControlFlowError::FunctionOrProcedureAlreadyDefined {
-                name,
-                kind: "Function".to_string(),
-            }.into());
+        self.check_callable_size(&name, "function", &body)?;
+        self.declare_name(&name, "Function")?;
+        // A procedure of the same name from an outer scope is shadowed
+        if self.procedures.contains_key(&name) {
+            Arc::make_mut(&mut self.procedures).remove(&name);
         }
-        self.functions.insert(name, (params, body));
+        Arc::make_mut(&mut self.functions).insert(name, (params, body));
         Ok(())
     }
     
     /// Declares a new procedure with the given name, parameters, and body.
+    ///
+    /// Fails if the name is already declared in this scope; see [`SymbolTable::declare_name`].
     pub fn declare_procedure(&mut self, name: String, params: Vec<String>, body: Statement) -> Result<(), EvalError> {
-        if self.procedures.contains_key(&name) {
-            return Err(ControlFlowError::FunctionOrProcedureAlreadyDefined {
-                name,
-                kind: "Procedure".to_string(),
-            }.into());
+        self.check_callable_size(&name, "procedure", &body)?;
+        self.declare_name(&name, "Procedure")?;
+        // A function of the same name from an outer scope is shadowed
+        if self.functions.contains_key(&name) {
+            Arc::make_mut(&mut self.functions).remove(&name);
         }
-        self.procedures.insert(name, (params, body));
+        Arc::make_mut(&mut self.procedures).insert(name, (params, body));
         Ok(())
     }
     
+    /// Returns the syntax nodes of all function and procedure bodies together.
+    pub fn callable_nodes(&self) -> usize {
+        self.functions
+            .values()
+            .chain(self.procedures.values())
+            .map(|(_, body)| body.node_count())
+            .sum()
+    }
+
+    /// Checks that a body fits within [`MAX_CALLABLE_NODES`], and that the
+    /// table stays within [`MAX_TOTAL_CALLABLE_NODES`] once it replaces any
+    /// function or procedure of the same name.
+    fn check_callable_size(&self, name: &str, kind: &str, body: &Statement) -> Result<(), EvalError> {
+        let limit_exceeded = |nodes, limit, session_total| SymbolError::LimitExceeded {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            nodes,
+            limit,
+            session_total,
+        };
+
+        let nodes = body.node_count();
+        if nodes > MAX_CALLABLE_NODES {
+            return Err(limit_exceeded(nodes, MAX_CALLABLE_NODES, false).into());
+        }
+
+        let replaced = self.functions.get(name)
+            .or_else(|| self.procedures.get(name))
+            .map_or(0, |(_, body)| body.node_count());
+        let total = self.callable_nodes() - replaced + nodes;
+        if total > MAX_TOTAL_CALLABLE_NODES {
+            return Err(limit_exceeded(total, MAX_TOTAL_CALLABLE_NODES, true).into());
+        }
+        Ok(())
+    }
+
+    /// Removes a variable or formula from this scope.
+    ///
+    /// Constants cannot be removed, and removing a name that holds no
+    /// variable is an error.
+    pub fn remove_variable(&mut self, name: &str) -> Result<(), EvalError> {
+        if global_constants().contains(name) || self.is_constant(name) {
+            return Err(SymbolError::ImmutableConstant(name.to_string()).into());
+        }
+        if self.values.contains_key(name) {
+            Arc::make_mut(&mut self.values).remove(name);
+        } else if self.formulas.contains_key(name) {
+            Arc::make_mut(&mut self.formulas).remove(name);
+        } else {
+            return Err(SymbolError::VariableNotFound(name.to_string()).into());
+        }
+        if let Some(modified) = &mut self.modified {
+            modified.remove(name);
+        }
+        self.scope_names.remove(name);
+        Ok(())
+    }
+
+    /// Removes a function from this scope, along with its memoized results.
+    pub fn remove_function(&mut self, name: &str) -> Result<(), EvalError> {
+        if !self.functions.contains_key(name) {
+            return Err(ControlFlowError::FunctionOrProcedureNotFound { name: name.to_string() }.into());
+        }
+        Arc::make_mut(&mut self.functions).remove(name);
+        self.memo_cache.unregister(name);
+        self.scope_names.remove(name);
+        Ok(())
+    }
+
+    /// Removes a procedure from this scope.
+    pub fn remove_procedure(&mut self, name: &str) -> Result<(), EvalError> {
+        if !self.procedures.contains_key(name) {
+            return Err(ControlFlowError::FunctionOrProcedureNotFound { name: name.to_string() }.into());
+        }
+        Arc::make_mut(&mut self.procedures).remove(name);
+        self.scope_names.remove(name);
+        Ok(())
+    }
+
+    /// Describes what a name is bound to in this table.
+    ///
+    /// Built-in and shared constants are not part of the table, so they are
+    /// not described here.
+    pub fn describe(&self, name: &str) -> Option<BindingInfo<T>> {
+        if let Some(value) = self.values.get(name) {
+            let value = value.clone();
+            return Some(if self.is_constant(name) { BindingInfo::Constant(value) } else { BindingInfo::Variable(value) });
+        }
+        if let Some(expression) = self.formulas.get(name) {
+            return Some(BindingInfo::Formula(expression.to_source()));
+        }
+        if let Some((params, _)) = self.functions.get(name) {
+            return Some(BindingInfo::Function { params: params.clone(), memo: self.memo_cache.is_memoized(name) });
+        }
+        self.procedures.get(name).map(|(params, _)| BindingInfo::Procedure { params: params.clone() })
+    }
+
     /// Gets a function by name.
     pub fn get_function(&self, name: &str) -> Option<(Vec<String>, Statement)> {
         self.functions.get(name).cloned()
@@ -197,15 +504,54 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
     /// Creates a new symbol table with the same constants but independent variables.
     ///
     /// Used for creating nested scopes in blocks like if/while statements.
+    /// The scope shares the maps of this table until either of them writes.
     pub fn new_scope(&self) -> Self {
         Self {
-            values: self.values.clone(),
-            constants: self.constants.clone(),
-            functions: self.functions.clone(),
-            procedures: self.procedures.clone(),
+            values: Arc::clone(&self.values),
+            constants: Arc::clone(&self.constants),
+            functions: Arc::clone(&self.functions),
+            procedures: Arc::clone(&self.procedures),
+            formulas: Arc::clone(&self.formulas),
+            memo_cache: self.memo_cache.share(),
+            in_callable: self.in_callable,
+            scope_names: HashMap::new(),
+            modified: None,
         }
     }
     
+    /// Creates a scope like [`SymbolTable::new_scope`] with a memo cache of
+    /// its own, starting with no results, so nothing it caches reaches this table.
+    pub(crate) fn detached_scope(&self) -> Self {
+        Self {
+            memo_cache: self.memo_cache.without_results(),
+            ..self.new_scope()
+        }
+    }
+
+    /// Creates a scope with some variables bound on top of this one.
+    ///
+    /// The bindings shadow variables of the same name and are gone once the
+    /// scope is dropped, so this table is never changed. Binding a constant
+    /// fails.
+    pub fn overlay(&self, bindings: &[(String, T)]) -> Result<Self, EvalError> {
+        let mut scope = self.new_scope();
+        for (name, value) in bindings {
+            scope.set_variable(name.clone(), value.clone())?;
+        }
+        Ok(scope)
+    }
+
+    /// Creates the scope a function or procedure call runs in.
+    ///
+    /// Like [`SymbolTable::new_scope`], but `return` is allowed inside it and
+    /// inside every block nested in it.
+    pub(crate) fn call_scope(&self) -> Self {
+        Self {
+            in_callable: true,
+            ..self.new_scope()
+        }
+    }
+
     /// Merges variables from another scope back into this one.
     ///
     /// Only updates variables that already exist in the outer scope.
@@ -236,21 +582,242 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
         Ok(())
     }
 
-    /// Returns the number of variables and constants in the symbol table.
+    /// Iterates over variables and constants sorted by name.
+    ///
+    /// The values live in a hash map, so plain iteration order changes from
+    /// run to run. Use this wherever the order is shown to users.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = (&str, &T)> {
+        let mut entries: Vec<_> = self.values.iter().map(|(name, value)| (name.as_str(), value)).collect();
+        entries.sort_unstable_by(|a, b| a.0.cmp(b.0));
+        entries.into_iter()
+    }
+
+    /// Returns the number of variables, constants and formulas in the symbol table.
     pub fn len(&self) -> usize {
-        self.values.len()
+        self.values.len() + self.formulas.len()
     }
     
     /// Returns true if the symbol table is empty.
     pub fn is_empty(&self) -> bool {
-        self.values.is_empty()
+        self.values.is_empty() && self.formulas.is_empty()
+    }
+
+    /// Takes a read-only snapshot of the table that is cheap to clone and share.
+    ///
+    /// The snapshot shares the maps of this table rather than copying them,
+    /// and starts with no memoized results. It dereferences to a
+    /// `SymbolTable`, so it can be passed to `evaluate` directly from any
+    /// number of threads.
+    pub fn freeze(&self) -> FrozenSymbols<T> {
+        FrozenSymbols {
+            table: Arc::new(self.detached_scope()),
+        }
+    }
+
+    /// Captures the whole state of the table so it can be put back with
+    /// [`SymbolTable::restore`].
+    ///
+    /// The snapshot shares the maps of this table and copies the memoized
+    /// results and modification times. It is cheap to clone, so several
+    /// features can hold on to the same one.
+    pub fn snapshot(&self) -> SymbolTableSnapshot<T> {
+        SymbolTableSnapshot {
+            table: Arc::new(self.clone()),
+        }
+    }
+
+    /// Puts the table back in the state a snapshot captured.
+    ///
+    /// Variables, constants, formulas, functions, procedures, memoized
+    /// results and modification times all return to what they were. The
+    /// table is only copied if the snapshot is still shared.
+    pub fn restore(&mut self, snapshot: SymbolTableSnapshot<T>) {
+        *self = Arc::try_unwrap(snapshot.table).unwrap_or_else(|shared| (*shared).clone());
     }
 
     /// Returns true if we're inside a function or procedure context.
     pub fn is_in_callable(&self) -> bool {
-        // This is a simple placeholder implementation
-        // In a real implementation, you would track the current execution context
-        false
+        self.in_callable
+    }
+}
+
+/// The kinds of things a name can be bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BindingKind {
+    Variable,
+    Constant,
+    Formula,
+    Function,
+    Procedure,
+}
+
+impl BindingKind {
+    /// Returns the name of the kind, such as "variable".
+    pub fn name(self) -> &'static str {
+        match self {
+            BindingKind::Variable => "variable",
+            BindingKind::Constant => "constant",
+            BindingKind::Formula => "formula",
+            BindingKind::Function => "function",
+            BindingKind::Procedure => "procedure",
+        }
+    }
+
+    /// Returns the code `typeof` gives for a name of this kind.
+    ///
+    /// Codes start at 1, leaving 0 for names that are not bound at all.
+    pub fn code(self) -> f32 {
+        match self {
+            BindingKind::Variable => 1.0,
+            BindingKind::Constant => 2.0,
+            BindingKind::Formula => 3.0,
+            BindingKind::Function => 4.0,
+            BindingKind::Procedure => 5.0,
+        }
+    }
+}
+
+/// What a name is bound to, as returned by [`SymbolTable::describe`].
+#[derive(Clone, Debug, PartialEq)]
+pub enum BindingInfo<T> {
+    /// A variable and its value.
+    Variable(T),
+    /// A constant and its value.
+    Constant(T),
+    /// A formula and the source of its expression, evaluated on every read.
+    Formula(String),
+    /// A function and its parameters, with whether it was declared with `memo fn`.
+    Function { params: Vec<String>, memo: bool },
+    /// A procedure and its parameters.
+    Procedure { params: Vec<String> },
+}
+
+impl<T> BindingInfo<T> {
+    /// Returns the kind of the binding.
+    pub fn kind(&self) -> BindingKind {
+        match self {
+            BindingInfo::Variable(_) => BindingKind::Variable,
+            BindingInfo::Constant(_) => BindingKind::Constant,
+            BindingInfo::Formula(_) => BindingKind::Formula,
+            BindingInfo::Function { .. } => BindingKind::Function,
+            BindingInfo::Procedure { .. } => BindingKind::Procedure,
+        }
+    }
+}
+
+/// Results of `memo fn` functions, keyed by function name and argument bits.
+///
+/// Evaluation only has a shared reference to the table, so the cache is
+/// behind a lock. Nested scopes share their parent's cache through
+/// [`MemoCache::share`], so results found during a call outlive it, while
+/// cloning a table copies the cache and the copies fill independently.
+#[derive(Default)]
+pub struct MemoCache {
+    entries: Arc<Mutex<MemoResults>>,
+}
+
+/// Cached results per function name, keyed by the bits of the arguments.
+type MemoResults = HashMap<String, HashMap<Vec<u32>, f32>>;
+
+impl MemoCache {
+    /// Returns a handle to the same cache.
+    pub fn share(&self) -> Self {
+        Self { entries: Arc::clone(&self.entries) }
+    }
+
+    /// Returns a separate cache for the same memoized functions, with no results yet.
+    pub fn without_results(&self) -> Self {
+        let names = self.lock().keys().map(|name| (name.clone(), HashMap::new())).collect();
+        Self { entries: Arc::new(Mutex::new(names)) }
+    }
+
+    /// Marks a function as memoized, dropping results of an earlier function with this name.
+    pub fn register(&self, name: &str) {
+        self.lock().insert(name.to_string(), HashMap::new());
+    }
+
+    /// Forgets a function and its results.
+    pub fn unregister(&self, name: &str) {
+        self.lock().remove(name);
+    }
+
+    /// Checks if a function was declared with `memo fn`.
+    pub fn is_memoized(&self, name: &str) -> bool {
+        self.lock().contains_key(name)
+    }
+
+    /// Gets the cached result of a call.
+    pub fn get(&self, name: &str, args: &[f32]) -> Option<f32> {
+        self.lock().get(name)?.get(&Self::key(args)).copied()
+    }
+
+    /// Stores the result of a call to a memoized function.
+    ///
+    /// Once the function has [`MAX_MEMO_RESULTS_PER_FUNCTION`] results, or
+    /// all functions have [`MAX_MEMO_RESULTS`], further results are not kept.
+    pub fn insert(&self, name: &str, args: &[f32], value: f32) {
+        let mut entries = self.lock();
+        let total: usize = entries.values().map(HashMap::len).sum();
+        if let Some(results) = entries.get_mut(name) {
+            if results.len() < MAX_MEMO_RESULTS_PER_FUNCTION && total < MAX_MEMO_RESULTS {
+                results.insert(Self::key(args), value);
+            }
+        }
+    }
+
+    /// Returns the number of cached results of a function.
+    pub fn len(&self, name: &str) -> usize {
+        self.lock().get(name).map_or(0, HashMap::len)
+    }
+
+    fn key(args: &[f32]) -> Vec<u32> {
+        args.iter().map(|arg| arg.to_bits()).collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MemoResults> {
+        // The cache holds plain data, so a poisoned lock is still usable
+        self.entries.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Clone for MemoCache {
+    fn clone(&self) -> Self {
+        Self { entries: Arc::new(Mutex::new(self.lock().clone())) }
+    }
+}
+
+/// A read-only snapshot of a symbol table, shared through an `Arc`.
+///
+/// Cloning a snapshot only bumps a reference count, so it can be handed to
+/// worker threads without copying the variables and function bodies.
+#[derive(Clone, Default)]
+pub struct FrozenSymbols<T: Clone + PartialEq = f32> {
+    table: Arc<SymbolTable<T>>,
+}
+
+impl<T: Clone + PartialEq> Deref for FrozenSymbols<T> {
+    type Target = SymbolTable<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.table
+    }
+}
+
+/// The state of a symbol table saved by [`SymbolTable::snapshot`].
+///
+/// Like [`FrozenSymbols`], cloning a snapshot only bumps a reference count,
+/// and it dereferences to the saved table so it can be read or evaluated
+/// against without restoring it.
+#[derive(Clone, Default)]
+pub struct SymbolTableSnapshot<T: Clone + PartialEq = f32> {
+    table: Arc<SymbolTable<T>>,
+}
+
+impl<T: Clone + PartialEq> Deref for SymbolTableSnapshot<T> {
+    type Target = SymbolTable<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.table
     }
 }
 
@@ -259,7 +826,7 @@ impl<T: Clone + PartialEq> IntoIterator for SymbolTable<T> {
     type IntoIter = std::collections::hash_map::IntoIter<String, T>;
 
     fn into_iter(self) -> Self::IntoIter {
-        self.values.into_iter()
+        Arc::unwrap_or_clone(self.values).into_iter()
     }
 }
 
@@ -267,6 +834,6 @@ impl<T: Clone + PartialEq> SymbolTable<T> {
     /// Checks if a variable has the same value.
     #[allow(dead_code)]
     pub fn value_equals(&self, name: &str, value: T) -> bool {
-        self.get(name).map_or(false, |v| v == &value)
+        self.get(name) == Some(&value)
     }
 } 
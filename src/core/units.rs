@@ -0,0 +1,131 @@
+//! Unit table and conversions for `convert(value, from_unit, to_unit)`.
+//!
+//! Every unit is described by how to reach the base unit of its dimension:
+//! `base = (value + offset) * factor`. Only temperatures use an offset.
+
+use std::fmt;
+
+use crate::core::error_types::MathError;
+
+/// The physical quantity a unit measures. Only units of the same dimension convert.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dimension {
+    Length,
+    Mass,
+    Time,
+    Temperature,
+    Data,
+}
+
+impl Dimension {
+    /// Returns a human-readable name for the dimension.
+    pub fn title(&self) -> &'static str {
+        match self {
+            Dimension::Length => "length",
+            Dimension::Mass => "mass",
+            Dimension::Time => "time",
+            Dimension::Temperature => "temperature",
+            Dimension::Data => "data size",
+        }
+    }
+}
+
+impl fmt::Display for Dimension {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}", self.title())
+    }
+}
+
+/// Describes a single unit.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    /// The name the unit is written as, e.g. `km`.
+    pub name: &'static str,
+
+    /// The quantity the unit measures.
+    pub dimension: Dimension,
+
+    /// How many base units one unit is, after adding the offset.
+    pub factor: f64,
+
+    /// Added to a value before scaling it to the base unit.
+    pub offset: f64,
+}
+
+/// Shorthand for units without an offset.
+const fn unit(name: &'static str, dimension: Dimension, factor: f64) -> Unit {
+    Unit { name, dimension, factor, offset: 0.0 }
+}
+
+/// The table of all units. Base units are metres, kilograms, seconds, kelvin and bytes.
+static UNITS: [Unit; 35] = [
+    // Length
+    unit("m", Dimension::Length, 1.0),
+    unit("km", Dimension::Length, 1000.0),
+    unit("cm", Dimension::Length, 0.01),
+    unit("mm", Dimension::Length, 0.001),
+    unit("mi", Dimension::Length, 1609.344),
+    unit("yd", Dimension::Length, 0.9144),
+    unit("ft", Dimension::Length, 0.3048),
+    unit("inch", Dimension::Length, 0.0254),
+    unit("nmi", Dimension::Length, 1852.0),
+    // Mass
+    unit("kg", Dimension::Mass, 1.0),
+    unit("g", Dimension::Mass, 0.001),
+    unit("mg", Dimension::Mass, 0.000_001),
+    unit("t", Dimension::Mass, 1000.0),
+    unit("lb", Dimension::Mass, 0.453_592_37),
+    unit("oz", Dimension::Mass, 0.028_349_523_125),
+    // Time
+    unit("s", Dimension::Time, 1.0),
+    unit("ms", Dimension::Time, 0.001),
+    unit("min", Dimension::Time, 60.0),
+    unit("h", Dimension::Time, 3600.0),
+    unit("day", Dimension::Time, 86_400.0),
+    unit("week", Dimension::Time, 604_800.0),
+    unit("year", Dimension::Time, 31_557_600.0), // Julian year
+    // Temperature
+    Unit { name: "K", dimension: Dimension::Temperature, factor: 1.0, offset: 0.0 },
+    Unit { name: "C", dimension: Dimension::Temperature, factor: 1.0, offset: 273.15 },
+    Unit { name: "F", dimension: Dimension::Temperature, factor: 5.0 / 9.0, offset: 459.67 },
+    // Data sizes, decimal and binary prefixes
+    unit("bit", Dimension::Data, 0.125),
+    unit("B", Dimension::Data, 1.0),
+    unit("KB", Dimension::Data, 1e3),
+    unit("MB", Dimension::Data, 1e6),
+    unit("GB", Dimension::Data, 1e9),
+    unit("TB", Dimension::Data, 1e12),
+    unit("KiB", Dimension::Data, 1024.0),
+    unit("MiB", Dimension::Data, 1_048_576.0),
+    unit("GiB", Dimension::Data, 1_073_741_824.0),
+    unit("TiB", Dimension::Data, 1_099_511_627_776.0),
+];
+
+/// Returns all known units.
+pub fn list_units() -> &'static [Unit] {
+    &UNITS
+}
+
+/// Finds a unit by its exact name. Names are case-sensitive, so `MB` and `mB` differ.
+pub fn find_unit(name: &str) -> Option<&'static Unit> {
+    UNITS.iter().find(|unit| unit.name == name)
+}
+
+/// Converts a value between two units of the same dimension.
+///
+/// Fails for unknown unit names and for units of different dimensions,
+/// e.g. `km` to `kg`.
+pub fn convert_units(value: f32, from: &str, to: &str) -> Result<f32, MathError> {
+    let from_unit = find_unit(from).ok_or_else(|| MathError::UnknownUnit(from.to_string()))?;
+    let to_unit = find_unit(to).ok_or_else(|| MathError::UnknownUnit(to.to_string()))?;
+
+    if from_unit.dimension != to_unit.dimension {
+        return Err(MathError::IncompatibleUnits {
+            from: format!("{} ({})", from_unit.name, from_unit.dimension),
+            to: format!("{} ({})", to_unit.name, to_unit.dimension),
+        });
+    }
+
+    let base = (value as f64 + from_unit.offset) * from_unit.factor;
+    Ok((base / to_unit.factor - to_unit.offset) as f32)
+}
@@ -2,9 +2,12 @@ use log::{info, error};
 use serenity::all::*;
 use std::collections::HashMap;
 
+use crate::core::MAX_SNIPPET_NAME_LENGTH;
 use crate::utils::extract_code_from_message;
-use crate::discord::models::{Bot, UserSession, CommandMetadata, CommandMetadataContainer};
+use crate::discord::models::{Bot, UserSession, CommandMetadata, CommandMetadataContainer, AdminConfig, AdminConfigContainer};
 use crate::discord::commands;
+use crate::discord::retry::{defer_component_with_retry, defer_with_retry, edit_component_with_retry, edit_with_retry, respond_with_retry, respond_to_component_with_retry};
+use crate::i18n;
 
 #[async_trait]
 impl EventHandler for Bot {
@@ -14,49 +17,166 @@ impl EventHandler for Bot {
             // Both slash commands and context menu commands now come through as Interaction::Command
             Interaction::Command(interaction) => {
                 let user_id = interaction.user.id.get();
-                let mut state_guard = self.state.lock().await;
-                
-                // Create a session with predefined constants if it doesn't exist
-                let session = state_guard.sessions
-                    .entry(user_id)
-                    .or_insert_with(UserSession::new);
-
-                // Handle slash command
-                match interaction.data.name.as_str() {
-                    "execute" => commands::handle_execute(&context, &interaction, session).await,
-                    "evaluate" => commands::handle_evaluate(&context, &interaction, session).await,
-                    "vars" => commands::handle_vars(&context, &interaction, session).await,
-                    "clear" => commands::handle_clear(&context, &interaction, session).await,
-                    "statistics" => commands::handle_statistics(&context, &interaction).await,
-                    "help" => commands::handle_help(&context, &interaction).await,
-                    "Execute Code" => {
-                        // Handle message context menu command
-                        if let Some(message) = interaction.data.resolved.messages.values().next() {
-                            // Extract code from code blocks
-                            if let Some(code) = extract_code_from_message(&message.content) {
-                                // Use the existing session for evaluation
-                                commands::handle_execute_code(&context, &interaction, session, &code).await;
-                            } else {
-                                // No code block found
-                                interaction.create_response(&context.http, CreateInteractionResponse::Message(
-                                    CreateInteractionResponseMessage::new()
-                                        .content("No code block found in the selected message.")
-                                        .ephemeral(false)
-                                )).await.ok();
-                            }
+
+                // Commands that run code may take longer than Discord waits
+                // for an answer, so they are deferred before anything else
+                // and answered by editing once they are done
+                if runs_interpreter(&interaction.data.name) {
+                    let ephemeral = match interaction.data.name.as_str() {
+                        "history" => true,
+                        "evaluate" => commands::has_blank_expression(&interaction),
+                        _ => false,
+                    };
+                    if let Err(error) = defer_with_retry(&context, &interaction, ephemeral).await {
+                        error!("Failed to defer {} command: {:?}", interaction.data.name, error);
+                        return;
+                    }
+                }
+
+                // Code files are downloaded before taking the lock, so a slow
+                // download does not hold up everyone else's commands
+                let execute_source = match interaction.data.name.as_str() {
+                    "execute" => Some(commands::read_execute_source(&interaction).await),
+                    _ => None,
+                };
+
+                // Commands that use the bot's state build their reply while
+                // it is locked. The reply is sent once the lock is released,
+                // so retrying a slow response does not hold up other commands
+                let (reply, edit, metrics, theme, locale) = {
+                    let mut state_guard = self.state.lock().await;
+                    let state = &mut *state_guard;
+                    state.record_command(&interaction.data.name);
+                    let guild_id = interaction.guild_id.map(|id| id.get());
+                    let theme = state.theme_for(guild_id);
+                    let limits = state.limits_for(guild_id);
+                    
+                    // Create a session with predefined constants if it doesn't exist
+                    let session = state.sessions
+                        .entry(user_id)
+                        .or_insert_with(UserSession::new);
+                    let locale = session.locale_for(&interaction.locale);
+                    let constants = state.constants.table(guild_id, user_id);
+
+                    // Handle deferred slash commands, which run code
+                    let edit = match interaction.data.name.as_str() {
+                        "execute" => execute_source.map(|source| {
+                            commands::handle_execute(&interaction, session, source, &state.snippets, constants, &mut state.metrics, limits, theme)
+                        }),
+                        "evaluate" => Some(commands::handle_evaluate(&interaction, session, constants, &mut state.metrics, limits, theme)),
+                        "history" => commands::handle_history(&interaction, session, &state.snippets, constants, limits),
+                        "graph-compare" => Some(commands::handle_graph_compare(&interaction, session, constants, limits, theme)),
+                        "verify-deterministic" => Some(commands::handle_verify_deterministic(&interaction, session, &state.snippets, constants, limits, theme)),
+                        "maxexpr" => Some(commands::handle_maxexpr(&interaction, session, constants, limits, theme)),
+                        "minexpr" => Some(commands::handle_minexpr(&interaction, session, constants, limits, theme)),
+                        "Execute Code" => {
+                            // Handle message context menu command
+                            interaction.data.resolved.messages.values().next().map(|message| {
+                                // Extract code from code blocks
+                                match extract_code_from_message(&message.content) {
+                                    // Use the existing session for evaluation
+                                    Some(code) => commands::handle_execute_code(&interaction, session, &state.snippets, constants, &mut state.metrics, &code, limits, theme),
+                                    // No code block found
+                                    None => EditInteractionResponse::new()
+                                        .content("No code block found in the selected message."),
+                                }
+                            })
                         }
+                        _ => None,
+                    };
+
+                    // Handle the other slash commands
+                    let reply = match interaction.data.name.as_str() {
+                        "vars" => Some(commands::handle_vars(&interaction, session, theme)),
+                        "vars-export" => Some(commands::handle_vars_export(session)),
+                        "profile" => Some(commands::handle_profile(session, theme)),
+                        "clear" => Some(commands::handle_clear(session, theme)),
+                        "remove" => Some(commands::handle_remove(&interaction, session, locale, theme)),
+                        "publish" => Some(commands::handle_publish(&interaction, &mut state.snippets, locale, theme)),
+                        "language" => Some(commands::handle_language(&interaction, session, theme)),
+                        "complex-mode" => Some(commands::handle_complex_mode(&interaction, session, theme)),
+                        "validate-identifier" => Some(commands::handle_validate_identifier(&interaction, session, locale, theme)),
+                        "set-limit" => Some(commands::handle_set_limit(&interaction, state, locale)),
+                        "admin" => commands::handle_admin(&context, &interaction, state, locale).await,
+                        _ => None,
+                    };
+
+                    // Statistics are collected after unlocking, from a copy of the counters
+                    let metrics = (interaction.data.name == "statistics").then(|| state.metrics.clone());
+                    (reply, edit, metrics, theme, locale)
+                };
+
+                // Commands that do not use the bot's state
+                let reply = match interaction.data.name.as_str() {
+                    "statistics" => {
+                        if let Some(metrics) = metrics {
+                            commands::handle_statistics(&context, &interaction, &metrics, theme).await;
+                        }
+                        None
+                    }
+                    "help" => Some(commands::handle_help(&context, &interaction, locale, theme).await),
+                    "decompose" => Some(commands::handle_decompose(&interaction, locale, theme)),
+                    "syntax-highlight" => Some(commands::handle_format(&interaction, locale)),
+                    "convert-base" => Some(commands::handle_convert_base(&interaction, locale, theme)),
+                    "convert" => Some(commands::handle_convert(&interaction, locale, theme)),
+                    "random-expr" => Some(commands::handle_random_expr(&interaction, theme)),
+                    _ => reply,
+                };
+
+                if let Some(reply) = reply {
+                    if let Err(error) = respond_with_retry(&context, &interaction, reply).await {
+                        error!("Failed to respond to {} command: {:?}", interaction.data.name, error);
+                    }
+                }
+                if let Some(edit) = edit {
+                    if let Err(error) = edit_with_retry(&context, &interaction, edit).await {
+                        error!("Failed to answer {} command: {:?}", interaction.data.name, error);
                     }
-                    _ => {}
                 }
             }
             // Handle component interactions (dropdown selections, buttons)
             Interaction::Component(interaction) => {
-                // Try to handle help command dropdown interactions
-                if commands::help::handle_help_component_interaction(&context, &interaction).await {
-                    return;
+                // Buttons under /evaluate results run code, so like the
+                // commands that do, their answer is deferred
+                let evaluate_button = commands::evaluate_buttons::decode_custom_id(&interaction.data.custom_id).is_some();
+                if evaluate_button {
+                    if let Err(error) = defer_component_with_retry(&context, &interaction).await {
+                        error!("Failed to defer component interaction: {:?}", error);
+                        return;
+                    }
+                }
+
+                let (response, edit) = {
+                    let mut state_guard = self.state.lock().await;
+                    let state = &mut *state_guard;
+                    let theme = state.theme_for(interaction.guild_id.map(|id| id.get()));
+
+                    if evaluate_button {
+                        // Buttons under /evaluate results work on the clicking user's session
+                        let guild_id = interaction.guild_id.map(|id| id.get());
+                        let limits = state.limits_for(guild_id);
+                        let session = state.sessions
+                            .entry(interaction.user.id.get())
+                            .or_insert_with(UserSession::new);
+                        let constants = state.constants.table(guild_id, interaction.user.id.get());
+                        (None, commands::evaluate_buttons::handle_evaluate_component_interaction(&interaction, session, constants, limits, theme))
+                    } else {
+                        // Try to handle help command dropdown interactions, other component
+                        // handlers can be chained here if needed
+                        (commands::help::handle_help_component_interaction(&context, &interaction, theme).await, None)
+                    }
+                };
+
+                if let Some(response) = response {
+                    if let Err(error) = respond_to_component_with_retry(&context, &interaction, response).await {
+                        error!("Failed to respond to component interaction: {:?}", error);
+                    }
+                }
+                if let Some(edit) = edit {
+                    if let Err(error) = edit_component_with_retry(&context, &interaction, edit).await {
+                        error!("Failed to answer component interaction: {:?}", error);
+                    }
                 }
-                
-                // Add other component handlers here if needed
             }
             _ => {}
         }
@@ -68,8 +188,7 @@ impl EventHandler for Bot {
 
         // Define slash commands
         let slash_commands = vec![
-            CreateCommand::new("evaluate")
-                .description("Computes a mathematical expression")
+            localized_command("evaluate")
                 .add_option(
                     CreateCommandOption::new(
                         CommandOptionType::String,
@@ -77,25 +196,361 @@ impl EventHandler for Bot {
                         "The mathematical expression to evaluate",
                     )
                     .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "show_ast",
+                        "Also show the syntax tree of the expression",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "complex",
+                        "Use complex numbers for this call (overrides /complex-mode)",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "exact",
+                        "Use exact fractions, so 1/3 + 1/6 gives 1/2",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "with",
+                        "One-off variable values that are not kept, e.g. x=3, y=4",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "normalize",
+                        "Also show the input with its grouping in parentheses",
+                    )
+                    .required(false),
                 ),
-            CreateCommand::new("execute")
-                .description("Executes calculator code")
+            localized_command("execute")
                 .add_option(
                     CreateCommandOption::new(
                         CommandOptionType::String, 
                         "code",
                         "The code to execute",
                     )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Attachment,
+                        "file",
+                        "A text file with the code to execute, for programs too long to type",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "trace",
+                        "Also list every statement that ran with its result",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "normalize",
+                        "Also show the code with its grouping in parentheses",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "strict",
+                        "Refuse to run code with warnings, such as unused variables",
+                    )
+                    .required(false),
+                ),
+            localized_command("publish")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "The name to import the definitions by",
+                    )
+                    .max_length(MAX_SNIPPET_NAME_LENGTH as u16)
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "code",
+                        "The fn, proc and const definitions to publish",
+                    )
+                    .required(true),
+                ),
+            localized_command("language")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "language",
+                        "The language to answer in",
+                    )
+                    .required(true)
+                    .add_string_choice("Automatic (Discord language)", "auto")
+                    .add_string_choice("English", "en")
+                    .add_string_choice("Español", "es"),
+                ),
+            localized_command("complex-mode")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Boolean,
+                        "enabled",
+                        "Whether /evaluate uses complex numbers, so sqrt(-4) gives 2i",
+                    )
                     .required(true),
                 ),
-            CreateCommand::new("vars")
-                .description("Shows your stored variables"),
-            CreateCommand::new("clear")
-                .description("Removes all your variables and history"),
-            CreateCommand::new("statistics")
-                .description("Shows detailed system statistics information"),
-            CreateCommand::new("help")
-                .description("Shows detailed help for the calculator")
+            localized_command("convert-base")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "value",
+                        "The integer to convert, e.g. ff",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "from",
+                        "The base the value is written in",
+                    )
+                    .required(true)
+                    .min_int_value(2)
+                    .max_int_value(36),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "to",
+                        "The base to convert to",
+                    )
+                    .required(true)
+                    .min_int_value(2)
+                    .max_int_value(36),
+                ),
+            localized_command("convert")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "value",
+                        "The value to convert",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "from",
+                        "The unit of the value, e.g. km, lb, F or MiB",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "to",
+                        "The unit to convert to, e.g. mi, kg, C or MB",
+                    )
+                    .required(true),
+                ),
+            localized_command("random-expr")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "depth",
+                        "How many levels of operators the problem has",
+                    )
+                    .required(false)
+                    .min_int_value(1)
+                    .max_int_value(4),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "operators",
+                        "The operators to use, e.g. +- or */ (default +-*/)",
+                    )
+                    .required(false),
+                ),
+            localized_command("decompose")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "number",
+                        "The whole number to factor",
+                    )
+                    .required(true)
+                    .min_number_value(0.0),
+                ),
+            localized_command("graph-compare")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "first",
+                        "The first expression in x, e.g. sin(x)",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "second",
+                        "The second expression in x, e.g. x - x^3 / 6",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "from",
+                        "Start of the x range (default -10)",
+                    )
+                    .required(false),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Number,
+                        "to",
+                        "End of the x range (default 10)",
+                    )
+                    .required(false),
+                ),
+            localized_command("verify-deterministic")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "code",
+                        "The code to run twice",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "seed",
+                        "Seed for rand() (random if omitted)",
+                    )
+                    .required(false)
+                    .min_int_value(0),
+                ),
+            localized_command("maxexpr")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "first",
+                        "The first expression, e.g. 1.05^10",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "second",
+                        "The second expression, e.g. 1 + 10 * 0.05",
+                    )
+                    .required(true),
+                ),
+            localized_command("minexpr")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "first",
+                        "The first expression, e.g. 1.05^10",
+                    )
+                    .required(true),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "second",
+                        "The second expression, e.g. 1 + 10 * 0.05",
+                    )
+                    .required(true),
+                ),
+            localized_command("syntax-highlight")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "code",
+                        "The program to format",
+                    )
+                    .required(true),
+                ),
+            localized_command("validate-identifier")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "The name to check",
+                    )
+                    .required(true),
+                ),
+            localized_command("vars")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "sort",
+                        "The order to list variables in",
+                    )
+                    .required(false)
+                    .add_string_choice("Name", "name")
+                    .add_string_choice("Recently updated", "recent"),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "Show only the variable, constant, function or procedure with this name",
+                    )
+                    .required(false),
+                ),
+            localized_command("vars-export"),
+            localized_command("history")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "export",
+                        "Downloads your inputs as a script that rebuilds your session",
+                    ),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "replay",
+                        "Runs your inputs again in fresh variables to rebuild your session",
+                    ),
+                ),
+            localized_command("profile"),
+            localized_command("clear"),
+            localized_command("remove")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "name",
+                        "The variable, function or procedure to remove",
+                    )
+                    .required(true),
+                ),
+            localized_command("statistics"),
+            localized_command("help")
                 .add_option(
                     CreateCommandOption::new(
                         CommandOptionType::String,
@@ -109,27 +564,161 @@ impl EventHandler for Bot {
                     .add_string_choice("Variables", "3")
                     .add_string_choice("Control Flow", "4")
                     .add_string_choice("Functions", "5")
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "function",
+                        "Name of a built-in function, e.g. atan2"
+                    )
+                    .required(false)
+                ),
+            localized_command("set-limit")
+                .default_member_permissions(Permissions::MANAGE_GUILD)
+                .contexts(vec![InteractionContext::Guild])
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::String,
+                        "limit",
+                        "The limit to change",
+                    )
+                    .required(true)
+                    .add_string_choice("Loop iterations", "loop_iterations")
+                    .add_string_choice("Nesting depth", "nesting_depth")
+                    .add_string_choice("Call depth", "call_depth")
+                    .add_string_choice("Time limit (ms)", "time_limit"),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::Integer,
+                        "value",
+                        "The new limit, or leave empty for the default",
+                    )
+                    .required(false)
+                    .min_int_value(1),
+                ),
+            localized_command("admin")
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "reset_sessions",
+                        "Clears every user's session",
+                    ),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "reset_user",
+                        "Clears a single user's session",
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::User,
+                            "user",
+                            "The user whose session to clear",
+                        )
+                        .required(true),
+                    ),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "stats",
+                        "Shows internal counters",
+                    ),
+                )
+                .add_option(
+                    CreateCommandOption::new(
+                        CommandOptionType::SubCommand,
+                        "theme",
+                        "Sets this server's embed accent colour",
+                    )
+                    .add_sub_option(
+                        CreateCommandOption::new(
+                            CommandOptionType::String,
+                            "colour",
+                            "A colour like #5865F2, or leave empty for the default colours",
+                        )
+                        .required(false),
+                    ),
                 ),
         ];
         
+        // Resolve who may use the admin commands
+        let owner_id = match context.http.get_current_application_info().await {
+            Ok(info) => info.owner.map(|owner| owner.id.get()),
+            Err(error) => {
+                error!("Failed to fetch application info: {:?}", error);
+                None
+            }
+        };
+        context.data.write().await.insert::<AdminConfigContainer>(AdminConfig::new(owner_id, self.config.admin_ids.iter().copied()));
+
         // Initialize command metadata
         let command_metadata = initialize_command_metadata();
         context.data.write().await.insert::<CommandMetadataContainer>(command_metadata);
 
-        // Register each slash command globally
-        for command in slash_commands {
-            if let Err(error) = Command::create_global_command(&context.http, command).await {
-                error!("Failed to register slash command: {:?}", error);
+        // Register a message context menu command alongside the slash commands
+        let context_menu = available_everywhere(CreateCommand::new("Execute Code"))
+            .kind(CommandType::Message);
+        let commands = slash_commands.into_iter().chain(std::iter::once(context_menu));
+
+        // Commands registered in a test server update at once, global ones can take an hour
+        for command in commands {
+            let registered = match self.config.test_guild_id {
+                Some(guild_id) => GuildId::new(guild_id).create_command(&context.http, command).await,
+                None => Command::create_global_command(&context.http, command).await,
+            };
+            if let Err(error) = registered {
+                error!("Failed to register command: {:?}", error);
             }
         }
+    }
+}
 
-        // Register a message context menu command
-        let context_menu = CreateCommand::new("Execute Code")
-            .kind(CommandType::Message);
-        if let Err(error) = Command::create_global_command(&context.http, context_menu).await {
-            error!("Failed to register context command: {:?}", error);
+/// Returns whether a command runs code, so its answer is deferred.
+///
+/// Code may run for as long as the server's time limit allows, which can be
+/// longer than the three seconds Discord waits for an answer.
+fn runs_interpreter(command: &str) -> bool {
+    matches!(
+        command,
+        "execute" | "evaluate" | "Execute Code" | "history" | "graph-compare" | "verify-deterministic" | "maxexpr" | "minexpr"
+    )
+}
+
+/// Makes a command available in servers, DMs and group DMs, for both server
+/// and user installs of the app.
+fn available_everywhere(command: CreateCommand) -> CreateCommand {
+    command
+        .integration_types(vec![InstallationContext::Guild, InstallationContext::User])
+        .contexts(vec![
+            InteractionContext::Guild,
+            InteractionContext::BotDm,
+            InteractionContext::PrivateChannel,
+        ])
+}
+
+/// Creates a slash command with its name and description in every supported locale.
+///
+/// The English strings are the defaults, other locales are added as localizations.
+fn localized_command(name: &str) -> CreateCommand {
+    let description_key = format!("command.{}.description", name);
+    let mut command = available_everywhere(CreateCommand::new(name))
+        .description(i18n::translate(i18n::DEFAULT_LOCALE, &description_key));
+
+    for locale in i18n::SUPPORTED_LOCALES.iter().filter(|locale| **locale != i18n::DEFAULT_LOCALE) {
+        for discord_locale in i18n::discord_locales(locale) {
+            if let Some(localized_name) = i18n::lookup(locale, &format!("command.{}.name", name)) {
+                command = command.name_localized(*discord_locale, localized_name);
+            }
+            if let Some(description) = i18n::lookup(locale, &description_key) {
+                command = command.description_localized(*discord_locale, description);
+            }
         }
     }
+
+    command
 }
 
 /// Initialize metadata for all available bot commands.
@@ -147,12 +736,17 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
         CommandMetadata {
             name: "evaluate".to_string(),
             description: "Evaluates a mathematical expression".to_string(),
-            usage: "/evaluate <expression>".to_string(),
+            usage: "/evaluate <expression> [show_ast] [complex] [exact] [with] [normalize]".to_string(),
             examples: vec![
                 "/evaluate 2 + 2 * 3".to_string(),
                 "/evaluate 6 * 2".to_string(),
+                "/evaluate 2 + 3 * 4 show_ast:True".to_string(),
+                "/evaluate sqrt(-4) complex:True".to_string(),
+                "/evaluate 1/3 + 1/6 exact:True".to_string(),
+                "/evaluate x^2 + y with:x=3, y=4".to_string(),
+                "/evaluate 2 + 3 * 4 normalize:True".to_string(),
             ],
-            callback_signature: "handle_evaluate(context, interaction, session)".to_string(),
+            callback_signature: "handle_evaluate(interaction, session, constants)".to_string(),
         }
     );
     
@@ -162,12 +756,13 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
         CommandMetadata {
             name: "execute".to_string(),
             description: "Executes calculator code".to_string(),
-            usage: "/execute <code>".to_string(),
+            usage: "/execute <code | file> [trace] [normalize] [strict]".to_string(),
             examples: vec![
                 "/execute let x = 10; x * 2".to_string(),
+                "/execute code: let n = 0; foreach i in 1 .. 3 { n += i } trace: true".to_string(),
                 "/execute { let sum = 0; let i = 1; while i <= 10 { sum += i; i += 1 }; sum }".to_string(),
             ],
-            callback_signature: "handle_execute(context, interaction, session)".to_string(),
+            callback_signature: "handle_execute(interaction, session, source, snippets, constants)".to_string(),
         }
     );
     
@@ -181,7 +776,7 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
             examples: vec![
                 "Right-click on message containing `2 + 2` > Apps > Execute Code".to_string(),
             ],
-            callback_signature: "handle_execute_code(context, interaction, session, code)".to_string(),
+            callback_signature: "handle_execute_code(interaction, session, snippets, constants, code)".to_string(),
         }
     );
     
@@ -191,11 +786,56 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
         CommandMetadata {
             name: "vars".to_string(),
             description: "Shows your stored variables".to_string(),
-            usage: "/vars".to_string(),
+            usage: "/vars [sort] [name]".to_string(),
             examples: vec![
                 "/vars".to_string(),
+                "/vars sort:recent".to_string(),
+                "/vars name:total".to_string(),
+            ],
+            callback_signature: "handle_vars(interaction, session)".to_string(),
+        }
+    );
+    
+    // Add vars-export command metadata
+    commands.insert(
+        "vars-export".to_string(),
+        CommandMetadata {
+            name: "vars-export".to_string(),
+            description: "Downloads your stored variables as a CSV file".to_string(),
+            usage: "/vars-export".to_string(),
+            examples: vec![
+                "/vars-export".to_string(),
+            ],
+            callback_signature: "handle_vars_export(session)".to_string(),
+        }
+    );
+    
+    // Add history command metadata
+    commands.insert(
+        "history".to_string(),
+        CommandMetadata {
+            name: "history".to_string(),
+            description: "Works with your input history".to_string(),
+            usage: "/history <export | replay>".to_string(),
+            examples: vec![
+                "/history export".to_string(),
+                "/history replay".to_string(),
+            ],
+            callback_signature: "handle_history(interaction, session, snippets, constants)".to_string(),
+        }
+    );
+    
+    // Add profile command metadata
+    commands.insert(
+        "profile".to_string(),
+        CommandMetadata {
+            name: "profile".to_string(),
+            description: "Shows your session statistics and favorite functions".to_string(),
+            usage: "/profile".to_string(),
+            examples: vec![
+                "/profile".to_string(),
             ],
-            callback_signature: "handle_vars(context, interaction, session)".to_string(),
+            callback_signature: "handle_profile(session)".to_string(),
         }
     );
     
@@ -204,12 +844,27 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
         "clear".to_string(),
         CommandMetadata {
             name: "clear".to_string(),
-            description: "Removes all your variables and history".to_string(),
+            description: "Removes all your variables, keeping your history".to_string(),
             usage: "/clear".to_string(),
             examples: vec![
                 "/clear".to_string(),
             ],
-            callback_signature: "handle_clear(context, interaction, session)".to_string(),
+            callback_signature: "handle_clear(session)".to_string(),
+        }
+    );
+    
+    // Add remove command metadata
+    commands.insert(
+        "remove".to_string(),
+        CommandMetadata {
+            name: "remove".to_string(),
+            description: "Removes one of your variables, functions or procedures".to_string(),
+            usage: "/remove <name>".to_string(),
+            examples: vec![
+                "/remove x".to_string(),
+                "/remove area".to_string(),
+            ],
+            callback_signature: "handle_remove(interaction, session, locale)".to_string(),
         }
     );
     
@@ -223,7 +878,233 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
             examples: vec![
                 "/statistics".to_string(),
             ],
-            callback_signature: "handle_statistics(context, interaction)".to_string(),
+            callback_signature: "handle_statistics(context, interaction, metrics)".to_string(),
+        }
+    );
+    
+    // Add publish command metadata
+    commands.insert(
+        "publish".to_string(),
+        CommandMetadata {
+            name: "publish".to_string(),
+            description: "Shares reusable definitions that anyone can import".to_string(),
+            usage: "/publish <name> <code>".to_string(),
+            examples: vec![
+                "/publish geometry fn area(w, h) { w * h }; const HALF = 0.5".to_string(),
+                "/execute import \"geometry\"; area(3, 4) * HALF".to_string(),
+            ],
+            callback_signature: "handle_publish(interaction, snippets)".to_string(),
+        }
+    );
+    
+    // Add complex-mode command metadata
+    commands.insert(
+        "complex-mode".to_string(),
+        CommandMetadata {
+            name: "complex-mode".to_string(),
+            description: "Turns complex numbers on or off for /evaluate".to_string(),
+            usage: "/complex-mode <enabled>".to_string(),
+            examples: vec![
+                "/complex-mode enabled:true".to_string(),
+                "/complex-mode enabled:false".to_string(),
+            ],
+            callback_signature: "handle_complex_mode(interaction, session)".to_string(),
+        }
+    );
+    
+    // Add convert-base command metadata
+    commands.insert(
+        "convert-base".to_string(),
+        CommandMetadata {
+            name: "convert-base".to_string(),
+            description: "Converts an integer between bases 2 to 36".to_string(),
+            usage: "/convert-base <value> <from> <to>".to_string(),
+            examples: vec![
+                "/convert-base value:255 from:10 to:16".to_string(),
+                "/convert-base value:1010 from:2 to:10".to_string(),
+            ],
+            callback_signature: "handle_convert_base(interaction)".to_string(),
+        }
+    );
+    
+    // Add convert command metadata
+    commands.insert(
+        "convert".to_string(),
+        CommandMetadata {
+            name: "convert".to_string(),
+            description: "Converts a value between units of the same quantity".to_string(),
+            usage: "/convert <value> <from> <to>".to_string(),
+            examples: vec![
+                "/convert value:5 from:km to:mi".to_string(),
+                "/convert value:100 from:F to:C".to_string(),
+            ],
+            callback_signature: "handle_convert(interaction)".to_string(),
+        }
+    );
+    
+    // Add random-expr command metadata
+    commands.insert(
+        "random-expr".to_string(),
+        CommandMetadata {
+            name: "random-expr".to_string(),
+            description: "Generates a practice problem with a hidden answer".to_string(),
+            usage: "/random-expr [depth] [operators]".to_string(),
+            examples: vec![
+                "/random-expr".to_string(),
+                "/random-expr depth:3 operators:+-".to_string(),
+            ],
+            callback_signature: "handle_random_expr(interaction)".to_string(),
+        }
+    );
+    
+    // Add decompose command metadata
+    commands.insert(
+        "decompose".to_string(),
+        CommandMetadata {
+            name: "decompose".to_string(),
+            description: "Shows the prime factorization of a whole number".to_string(),
+            usage: "/decompose <number>".to_string(),
+            examples: vec![
+                "/decompose 360".to_string(),
+                "/decompose 97".to_string(),
+            ],
+            callback_signature: "handle_decompose(interaction)".to_string(),
+        }
+    );
+    
+    // Add graph-compare command metadata
+    commands.insert(
+        "graph-compare".to_string(),
+        CommandMetadata {
+            name: "graph-compare".to_string(),
+            description: "Plots two expressions in x on the same axes".to_string(),
+            usage: "/graph-compare <first> <second> [from] [to]".to_string(),
+            examples: vec![
+                "/graph-compare sin(x) x - x^3 / 6 -3 3".to_string(),
+                "/graph-compare x^2 2^x 0 5".to_string(),
+            ],
+            callback_signature: "handle_graph_compare(interaction, session, constants)".to_string(),
+        }
+    );
+    
+    // Add verify-deterministic command metadata
+    commands.insert(
+        "verify-deterministic".to_string(),
+        CommandMetadata {
+            name: "verify-deterministic".to_string(),
+            description: "Runs code twice with the same random seed and compares the results".to_string(),
+            usage: "/verify-deterministic <code> [seed]".to_string(),
+            examples: vec![
+                "/verify-deterministic rand(1, 6) 42".to_string(),
+                "/verify-deterministic let total = 0; foreach i in 0..10 { total += rand() }; total".to_string(),
+            ],
+            callback_signature: "handle_verify_deterministic(interaction, session, snippets, constants)".to_string(),
+        }
+    );
+    
+    // Add maxexpr command metadata
+    commands.insert(
+        "maxexpr".to_string(),
+        CommandMetadata {
+            name: "maxexpr".to_string(),
+            description: "Evaluates two expressions and reports which is larger, and by how much".to_string(),
+            usage: "/maxexpr <first> <second>".to_string(),
+            examples: vec![
+                "/maxexpr 1.05^10 1 + 10 * 0.05".to_string(),
+                "/maxexpr x^2 2^x".to_string(),
+            ],
+            callback_signature: "handle_maxexpr(interaction, session, constants)".to_string(),
+        }
+    );
+    
+    // Add minexpr command metadata
+    commands.insert(
+        "minexpr".to_string(),
+        CommandMetadata {
+            name: "minexpr".to_string(),
+            description: "Evaluates two expressions and reports which is smaller, and by how much".to_string(),
+            usage: "/minexpr <first> <second>".to_string(),
+            examples: vec![
+                "/minexpr sqrt(2) 1.414".to_string(),
+            ],
+            callback_signature: "handle_minexpr(interaction, session, constants)".to_string(),
+        }
+    );
+    
+    // Add syntax-highlight command metadata
+    commands.insert(
+        "syntax-highlight".to_string(),
+        CommandMetadata {
+            name: "syntax-highlight".to_string(),
+            description: "Reformats a program with consistent indentation in a highlighted code block".to_string(),
+            usage: "/syntax-highlight <code>".to_string(),
+            examples: vec![
+                "/syntax-highlight fn f(n) { if n < 2 { n } else { f(n - 1) + f(n - 2) } }".to_string(),
+            ],
+            callback_signature: "handle_format(interaction, locale)".to_string(),
+        }
+    );
+    
+    // Add language command metadata
+    commands.insert(
+        "language".to_string(),
+        CommandMetadata {
+            name: "language".to_string(),
+            description: "Sets the language the bot answers you in".to_string(),
+            usage: "/language <language>".to_string(),
+            examples: vec![
+                "/language es".to_string(),
+                "/language auto".to_string(),
+            ],
+            callback_signature: "handle_language(interaction, session)".to_string(),
+        }
+    );
+    
+    // Add validate-identifier command metadata
+    commands.insert(
+        "validate-identifier".to_string(),
+        CommandMetadata {
+            name: "validate-identifier".to_string(),
+            description: "Checks whether a name can be used for a variable or constant".to_string(),
+            usage: "/validate-identifier <name>".to_string(),
+            examples: vec![
+                "/validate-identifier name:radius".to_string(),
+                "/validate-identifier name:sin".to_string(),
+            ],
+            callback_signature: "handle_validate_identifier(interaction, session, locale)".to_string(),
+        }
+    );
+    
+    // Add set-limit command metadata
+    commands.insert(
+        "set-limit".to_string(),
+        CommandMetadata {
+            name: "set-limit".to_string(),
+            description: "Makes this server's execution limits stricter or looser".to_string(),
+            usage: "/set-limit <limit> [value]".to_string(),
+            examples: vec![
+                "/set-limit limit:Loop iterations value:5000".to_string(),
+                "/set-limit limit:Nesting depth".to_string(),
+                "/set-limit limit:Call depth value:50".to_string(),
+            ],
+            callback_signature: "handle_set_limit(interaction, state, locale)".to_string(),
+        }
+    );
+    
+    // Add admin command metadata
+    commands.insert(
+        "admin".to_string(),
+        CommandMetadata {
+            name: "admin".to_string(),
+            description: "Owner-only maintenance commands".to_string(),
+            usage: "/admin <reset_sessions|reset_user|stats|theme>".to_string(),
+            examples: vec![
+                "/admin reset_sessions".to_string(),
+                "/admin reset_user user:@someone".to_string(),
+                "/admin stats".to_string(),
+                "/admin theme colour:#5865F2".to_string(),
+            ],
+            callback_signature: "handle_admin(context, interaction, state)".to_string(),
         }
     );
     
@@ -233,11 +1114,12 @@ pub fn initialize_command_metadata() -> HashMap<String, CommandMetadata> {
         CommandMetadata {
             name: "help".to_string(),
             description: "Shows help for the calculator".to_string(),
-            usage: "/help <topic>".to_string(),
+            usage: "/help [topic] [function]".to_string(),
             examples: vec![
                 "/help".to_string(),
                 "/help syntax".to_string(),
                 "/help variables".to_string(),
+                "/help function:atan2".to_string(),
             ],
             callback_signature: "handle_help(context, interaction)".to_string(),
         }
@@ -0,0 +1,139 @@
+use log::warn;
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::theme::{format_hex_colour, parse_hex_colour, Theme, Tone};
+use crate::discord::{AdminConfigContainer, SharedState};
+use crate::i18n;
+
+/// Handles the owner-only `/admin` command group.
+///
+/// Only the application owner and users listed in `ADMIN_IDS` may use it,
+/// everyone else gets an ephemeral refusal. Members who can manage a server
+/// may also set its `theme`. Destructive subcommands are logged at warn
+/// level together with the invoking admin's id.
+pub async fn handle_admin(
+    context: &Context,
+    interaction: &CommandInteraction,
+    state: &mut SharedState,
+    locale: &str,
+) -> Option<CreateInteractionResponseMessage> {
+    let admin_id = interaction.user.id.get();
+
+    // Check the invoker against the configured admins
+    let is_admin = context.data.read().await
+        .get::<AdminConfigContainer>()
+        .is_some_and(|config| config.is_admin(admin_id));
+
+    // Subcommands arrive as the only top-level option
+    let subcommand = interaction.data.options.first()?;
+
+    // A server's colours are up to the people who manage it
+    let manages_guild = interaction.member.as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild());
+    let guild_id = interaction.guild_id.map(|id| id.get());
+
+    let allowed = is_admin || (subcommand.name == "theme" && manages_guild);
+    if !allowed {
+        warn!("User {} tried to use /admin without permission", admin_id);
+        let colour = state.theme_for(guild_id).colour(Tone::Error);
+        return Some(respond(i18n::translate(locale, "message.admin_refused"), colour));
+    }
+    let arguments = match &subcommand.value {
+        CommandDataOptionValue::SubCommand(arguments) => arguments.as_slice(),
+        _ => &[],
+    };
+
+    let mut tone = Tone::Info;
+    let message = match subcommand.name.as_str() {
+        "reset_sessions" => {
+            let count = state.reset_sessions();
+            warn!("Admin {} reset all {} sessions", admin_id, count);
+            i18n::translate_with(locale, "message.admin_sessions_reset", &[("count", count.to_string())])
+        }
+        "reset_user" => {
+            let user_id = arguments.first().and_then(|opt| opt.value.as_user_id())?;
+            let key = if state.reset_user(user_id.get()) {
+                warn!("Admin {} reset the session of user {}", admin_id, user_id);
+                "message.admin_user_reset"
+            } else {
+                "message.admin_user_not_found"
+            };
+            i18n::translate_with(locale, key, &[("user", user_id.mention().to_string())])
+        }
+        "stats" => format_internal_stats(state),
+        "theme" => {
+            let Some(guild_id) = guild_id else {
+                return Some(respond(i18n::translate(locale, "message.admin_theme_guild_only"), Tone::Error.default_colour()));
+            };
+            match arguments.first().and_then(|opt| opt.value.as_str()).map(parse_hex_colour) {
+                Some(Ok(colour)) => {
+                    state.guild_themes.insert(guild_id, Theme { accent: Some(colour) });
+                    i18n::translate_with(locale, "message.admin_theme_set", &[("colour", format_hex_colour(colour))])
+                }
+                Some(Err(error)) => {
+                    tone = Tone::Error;
+                    error.to_string()
+                }
+                None => {
+                    state.guild_themes.remove(&guild_id);
+                    i18n::translate(locale, "message.admin_theme_reset")
+                }
+            }
+        }
+        _ => return None,
+    };
+
+    Some(respond(message, state.theme_for(guild_id).colour(tone)))
+}
+
+/// Formats the internal counters that `/statistics` does not show.
+fn format_internal_stats(state: &SharedState) -> String {
+    let variables: usize = state.sessions.values().map(|session| session.variables.len()).sum();
+    let functions: usize = state.sessions.values().map(|session| session.variables.functions.len()).sum();
+    let procedures: usize = state.sessions.values().map(|session| session.variables.procedures.len()).sum();
+    let callable_nodes: usize = state.sessions.values().map(|session| session.variables.callable_nodes()).sum();
+    let history: usize = state.sessions.values().map(|session| session.history.len()).sum();
+
+    let mut commands: Vec<_> = state.command_counts.iter().collect();
+    commands.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    let command_list = if commands.is_empty() {
+        "_None yet_".to_string()
+    } else {
+        commands.iter()
+            .map(|(name, count)| format!("`{}`: {}", name, count))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    format!(
+        "**Sessions:** {}\n\
+        **Variables:** {}\n\
+        **Functions:** {}\n\
+        **Procedures:** {}\n\
+        **Syntax nodes in functions and procedures:** {}\n\
+        **History entries:** {}\n\
+        **Published snippets:** {}\n\n\
+        **Command uses:**\n{}",
+        state.sessions.len(),
+        variables,
+        functions,
+        procedures,
+        callable_nodes,
+        history,
+        state.snippets.len(),
+        command_list
+    )
+}
+
+/// Builds an ephemeral reply so admin output stays private.
+fn respond(message: String, colour: Colour) -> CreateInteractionResponseMessage {
+    let embed = CreateEmbed::new()
+        .description(message)
+        .colour(colour);
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .ephemeral(true)
+}
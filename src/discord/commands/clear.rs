@@ -1,39 +1,27 @@
-use log::error;
 use serenity::all::*;
 use serenity::builder::CreateEmbed;
 
 use crate::discord::UserSession;
-use crate::core::SymbolTable;
+use crate::discord::theme::{Theme, Tone};
 
 /// Handles the `/clear` slash command to reset user session.
 /// 
-/// Removes all variables and expression history for the user's session.
-pub async fn handle_clear(
-    context: &Context,
-    interaction: &CommandInteraction,
+/// Removes all variables from the user's session. The history is kept, so
+/// `/history replay` can rebuild them.
+pub fn handle_clear(
     session: &mut UserSession,
-) {
-    // Reset the session
-    session.variables = SymbolTable::<f32>::new();
-    session.history.clear();
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    // Reset the variables
+    session.clear_variables();
 
     // Create response embed
     let embed = CreateEmbed::new()
         .title("Session Cleared")
-        .description("Your variables and command history have been reset.")
-        .colour(Colour::RED);
+        .description("Your variables have been reset. Your history is kept, so `/history replay` can rebuild them.")
+        .colour(theme.colour(Tone::Warning));
 
-    // Send confirmation
-    if let Err(error) = interaction
-        .create_response(
-            &context.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .embed(embed)
-            ),
-        )
-        .await
-    {
-        error!("Failed to respond to clear command: {:?}", error);
-    }
+    // Build confirmation
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
 } 
@@ -0,0 +1,35 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/complex-mode` slash command for switching complex numbers on or off.
+///
+/// The setting is stored in the user's session and applies to `/evaluate`,
+/// which can still override it for a single call.
+pub fn handle_complex_mode(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    session.complex_mode = interaction
+        .data
+        .options
+        .first()
+        .and_then(|opt| opt.value.as_bool())
+        .unwrap_or(false);
+
+    let locale = session.locale_for(&interaction.locale);
+    let key = if session.complex_mode { "message.complex_mode_on" } else { "message.complex_mode_off" };
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .description(i18n::translate(locale, key))
+        .colour(theme.colour(Tone::Info));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .ephemeral(true)
+}
@@ -0,0 +1,50 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::{convert_units, find_unit, EvalError, InterpreterError};
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/convert` slash command for converting a value between units.
+///
+/// Uses the same unit table as the `convert(value, from, to)` built-in, so
+/// both refuse conversions between different quantities like km to kg.
+pub fn handle_convert(
+    interaction: &CommandInteraction,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let value = option("value").and_then(|value| value.as_f64()).unwrap_or(0.0) as f32;
+    let from = option("from").and_then(|value| value.as_str()).unwrap_or("").trim();
+    let to = option("to").and_then(|value| value.as_str()).unwrap_or("").trim();
+
+    let converted = match convert_units(value, from, to) {
+        Ok(converted) => converted,
+        Err(error) => {
+            let error = InterpreterError::Eval(EvalError::from(error));
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_response(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
+    let dimension = find_unit(from).map_or("", |unit| unit.dimension.title());
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Unit Conversion")
+        .description(format!("```rs\n{} {} = {} {}\n```", value, from, converted, to))
+        .field("Quantity", dimension, true)
+        .colour(theme.colour(Tone::Success));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -0,0 +1,54 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/convert-base` slash command for converting integers between bases.
+///
+/// Parses the value in the source base and renders it in the target base,
+/// both of which must be between 2 and 36.
+pub fn handle_convert_base(
+    interaction: &CommandInteraction,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let value = option("value").and_then(|value| value.as_str()).unwrap_or("").trim();
+    let from = option("from").and_then(|value| value.as_i64()).unwrap_or(10);
+    let to = option("to").and_then(|value| value.as_i64()).unwrap_or(10);
+
+    // Out of range bases are reported by the conversion itself
+    let base = |base: i64| u32::try_from(base).unwrap_or(0);
+
+    let converted = match utils::convert_base(value, base(from), base(to)) {
+        Ok(converted) => converted,
+        Err(error) => {
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&error.to_string()));
+            return error_response(&error_message, Some(ErrorCategory::Syntax), locale);
+        }
+    };
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Base Conversion")
+        .description(format!(
+            "**Base {}:**\n```rs\n{}\n```\n\
+            **Base {}:**\n```rs\n{}\n```",
+            from,
+            value,
+            to,
+            converted
+        ))
+        .colour(theme.colour(Tone::Success));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -0,0 +1,47 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// The largest integer a number option represents exactly.
+const MAX_EXACT_INTEGER: f64 = 9_007_199_254_740_992.0;
+
+/// Handles the `/decompose` slash command showing a prime factorization.
+///
+/// The number must be a non-negative integer small enough to be exact.
+pub fn handle_decompose(
+    interaction: &CommandInteraction,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let number = interaction
+        .data
+        .options
+        .first()
+        .and_then(|opt| match opt.value {
+            CommandDataOptionValue::Number(number) => Some(number),
+            CommandDataOptionValue::Integer(number) => Some(number as f64),
+            _ => None,
+        })
+        .unwrap_or(0.0);
+
+    if number.fract() != 0.0 || !(0.0..=MAX_EXACT_INTEGER).contains(&number) {
+        let error_message = format!(
+            "```fix\nCannot decompose {}. Only whole numbers from 0 to {} can be factored.\n```",
+            number,
+            MAX_EXACT_INTEGER
+        );
+        return error_response(&error_message, Some(ErrorCategory::Runtime), locale);
+    }
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Prime Factorization")
+        .description(format!("```rs\n{}\n```", utils::format_factorization(number as u64)))
+        .colour(theme.colour(Tone::Success));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -1,83 +1,205 @@
-use log::error;
 use serenity::all::*;
 use serenity::builder::{CreateEmbed, CreateEmbedFooter};
 
-use crate::discord::error_handler::send_error;
-use crate::discord::UserSession;
+use crate::discord::commands::evaluate_buttons;
+use crate::discord::error_handler::{error_edit, ErrorCategory};
+use crate::i18n;
+use crate::utils;
+use crate::discord::{HistoryKind, UserSession};
+use crate::core::{is_blank_input, is_boolean_result, measure, normalize_source, reading_shared_constants, with_limits, EvalOptions, InterpreterError, InterpreterMetrics, LimitOverrides, Parser, SharedConstants, Tokenizer, Value, F32_EXACT_LIMIT};
+use crate::discord::theme::{Theme, Tone};
+
+/// Largest syntax tree, in nodes, drawn inline instead of attached as JSON.
+const AST_MAX_INLINE_NODES: usize = 40;
+
+/// Longest text tree that still fits in an embed field.
+const AST_MAX_INLINE_LENGTH: usize = 1000;
+
+/// Depth and children per node shown in an inline syntax tree.
+pub(crate) const AST_MAX_DEPTH: usize = 8;
+pub(crate) const AST_MAX_CHILDREN: usize = 6;
+
+/// Longest big integer shown in full; longer ones are shortened and attached.
+const BIG_INLINE_DIGITS: usize = 1000;
+
+/// Digits kept from each end of a shortened big integer.
+const BIG_EDGE_DIGITS: usize = 100;
+
+/// Returns whether the expression given to `/evaluate` is blank or only a
+/// comment, so its answer is deferred as ephemeral.
+pub fn has_blank_expression(interaction: &CommandInteraction) -> bool {
+    let expression = interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == "expression")
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("");
+    is_blank_input(utils::normalize_input(expression).trim())
+}
 
 /// Handles the `/evaluate` slash command for mathematical expressions.
 /// 
-/// Supports variable assignments, control flow structures, and complex calculations
-/// with detailed error reporting.
-pub async fn handle_evaluate(
-    context: &Context,
+/// Input runs through the same pipeline as `/execute`, so statements, errors
+/// and limits behave the same, the server's `limits` included, but on a copy
+/// of the session's variables: only the value is shown and nothing is kept.
+pub fn handle_evaluate(
     interaction: &CommandInteraction,
     session: &mut UserSession,
-) {
-    // Extract and clean input
-    let input = interaction
+    constants: &SharedConstants,
+    metrics: &mut InterpreterMetrics,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
+    let option = |name: &str| interaction
         .data
         .options
-        .first()
-        .and_then(|opt| opt.value.as_str())
-        .unwrap_or("")
-        .trim();
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    // Extract and clean input, folding exotic Unicode from mobile keyboards
+    let input = utils::normalize_input(option("expression").and_then(|value| value.as_str()).unwrap_or(""));
+    let input = input.trim();
+    let show_ast = option("show_ast").and_then(|value| value.as_bool()).unwrap_or(false);
+    let complex = option("complex").and_then(|value| value.as_bool()).unwrap_or(session.complex_mode);
+    let exact = option("exact").and_then(|value| value.as_bool()).unwrap_or(false);
+    let with = option("with").and_then(|value| value.as_str()).unwrap_or("");
+    let normalize = option("normalize").and_then(|value| value.as_bool()).unwrap_or(false);
 
-    if input.is_empty() {
-        send_error(context, interaction, "Please provide an expression to evaluate.", None).await;
-        return;
+    // Blank or comment-only input is not a mistake worth an error embed,
+    // and is answered privately, see `has_blank_expression`
+    if is_blank_input(input) {
+        let embed = CreateEmbed::new()
+            .description(i18n::translate(locale, "message.nothing_to_evaluate"))
+            .colour(theme.colour(Tone::Info));
+        return EditInteractionResponse::new().embed(embed);
     }
 
+    // One-off values from `with` are bound over the session for this call only
+    let bindings = match utils::parse_bindings(with) {
+        Ok(bindings) => bindings,
+        Err(error) => {
+            return error_edit(&format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&error.to_string())), Some(ErrorCategory::Variable), locale);
+        }
+    };
+    let overlay = match session.variables.overlay(&bindings) {
+        Ok(overlay) => overlay,
+        Err(error) => {
+            let error = InterpreterError::from(error);
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
     // Use the evaluate function from core to evaluate the input
-    let result = match crate::core::evaluate(input, &mut session.variables) {
+    let options = EvalOptions { complex, exact, ..EvalOptions::default() };
+    let (result, report) = reading_shared_constants(constants, || measure(|| with_limits(limits, || crate::core::evaluate_with_options(input, &overlay, options))));
+    metrics.record(&report);
+    session.record_evaluation(input, result.is_ok());
+
+    let result = match result {
         Ok(value) => value,
         Err(error) => {
-            // Format error messages differently based on type
-            let error_message = match &error {
-                &crate::core::CalcError::Parse(ref parse_err) => {
-                    format!("```fix\n{}\n```", parse_err)
-                },
-                &crate::core::CalcError::Eval(ref eval_err) => {
-                    format!("```fix\n{}\n```", eval_err)
-                },
-                &crate::core::CalcError::Exec(ref exec_err) => {
-                    format!("```fix\n{}\n```", exec_err)
-                },
-            };
-            
-            send_error(context, interaction, &error_message, None).await;
-            return;
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
         }
     };
     
-    // Save to history
-    session.history.push(input.to_string());
+    // Save to history, unless the result depends on values the session doesn't have
+    if bindings.is_empty() {
+        session.record_history(input, Some(result.to_string()), HistoryKind::Evaluated);
+    }
     
-    // Create description with the result (evaluate always returns a value)
+    // Huge integers are shortened inline and attached in full, and truth
+    // values from comparisons read as true or false
+    let mut message = EditInteractionResponse::new();
+    let mut shown = result.format_with_hint(is_boolean_result(input));
+    if let Value::BigInt(_) = result {
+        let digits = shown.trim_start_matches('-').len();
+        if digits > BIG_INLINE_DIGITS {
+            message = message.new_attachment(CreateAttachment::bytes(shown.clone().into_bytes(), "result.txt"));
+            shown = format!(
+                "{}…{}\n({} digits, full value attached)",
+                &shown[..BIG_EDGE_DIGITS],
+                &shown[shown.len() - BIG_EDGE_DIGITS..],
+                digits,
+            );
+        }
+    }
+
+    // Create description with the result (evaluate always returns a value),
+    // showing how the input was grouped if asked
+    let source = normalize.then(|| normalize_source(input).ok()).flatten();
+    let (echo, parsed) = utils::fit_code_echoes(input, source.as_deref());
+    let parsed = match parsed {
+        Some(source) => format!("**Parsed as:**\n```rs\n{}\n```\n", source),
+        None => String::new(),
+    };
     let description = format!(
-        "**Code:**\n```rs\n{}\n```\n\
+        "**Code:**\n```rs\n{}\n```\n{}\
         **Result:**\n```rs\n{}\n```",
-        input.trim(),
-        result
+        echo,
+        parsed,
+        shown
     );
+    let description = if bindings.is_empty() {
+        description
+    } else {
+        let shown_bindings: Vec<String> = bindings.iter().map(|(name, value)| format!("{} = {}", name, value)).collect();
+        format!("{}\n**With:** `{}`", description, shown_bindings.join(", "))
+    };
+
+    // Exact mode falls back to floats for results like sqrt(2), and integers
+    // too large for a float only stay exact with +, -, *, ^ and fact
+    let notice = match result {
+        Value::Real(_) if exact => Some("message.exact_fallback"),
+        Value::Real(value) if !complex && value.abs() > F32_EXACT_LIMIT => Some("message.big_demoted"),
+        _ => None,
+    };
+    let description = match notice {
+        Some(key) => format!("{}\n{}", description, i18n::translate(locale, key)),
+        None => description,
+    };
+
+    // Statements run like /execute, but their variables are not kept
+    let mut footer = format!(
+        "Session contains {} variables and {} history entries!",
+        session.variables.len(),
+        session.history.len()
+    );
+    if crate::core::is_statement_input(input) {
+        footer = format!("{} {}", footer, i18n::translate(locale, "message.evaluate_ran_statements"));
+    }
 
     // Create response embed
-    let embed = CreateEmbed::new()
+    let mut embed = CreateEmbed::new()
         .title("Expression Evaluation Successful")
         .description(description)
-        .colour(Colour::DARK_GREEN)
-        .footer(CreateEmbedFooter::new(format!(
-            "Session contains {} variables and {} history entries!",
-            session.variables.len(),
-            session.history.len()
-        )));
-
-    let response = CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(embed)
-    );
+        .colour(theme.colour(Tone::Success))
+        .footer(CreateEmbedFooter::new(footer));
 
-    if let Err(error) = interaction.create_response(&context.http, response).await {
-        error!("Failed to respond to evaluate command: {:?}", error);
+    // Small trees are drawn inline, larger ones are attached as JSON
+    if show_ast {
+        if let Ok(expr) = Parser::new(Tokenizer::from_input(input)).parse_expression() {
+            let tree = expr.to_text_tree(AST_MAX_DEPTH, AST_MAX_CHILDREN);
+            if expr.node_count() <= AST_MAX_INLINE_NODES && tree.len() <= AST_MAX_INLINE_LENGTH {
+                embed = embed.field("Syntax Tree", format!("```\n{}```", tree), false);
+            } else {
+                let json = serde_json::to_string_pretty(&expr.to_json()).unwrap_or_default();
+                message = message.new_attachment(CreateAttachment::bytes(json.into_bytes(), "ast.json"));
+            }
+        }
     }
+
+    // Buttons act on the expression alone, so they are left out for one-off `with` values
+    if bindings.is_empty() {
+        if let Some(row) = evaluate_buttons::create_action_row(input) {
+            message = message.components(vec![row]);
+        }
+    }
+
+    message.embed(embed)
 } 
@@ -0,0 +1,187 @@
+// Buttons under `/evaluate` results that re-run or transform the expression
+
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::{reading_shared_constants, with_limits, EvalOptions, LimitOverrides, Parser, SharedConstants, Tokenizer};
+use crate::discord::commands::evaluate::{AST_MAX_CHILDREN, AST_MAX_DEPTH};
+use crate::discord::commands::graph_compare::{PLOT_HEIGHT, SAMPLE_COUNT};
+use crate::discord::commands::plot;
+use crate::discord::theme::{Theme, Tone};
+use crate::discord::{HistoryKind, UserSession};
+use crate::i18n;
+use crate::utils;
+
+/// The most characters Discord accepts in a component's custom id.
+pub const CUSTOM_ID_LIMIT: usize = 100;
+
+/// Prefix of the custom ids of `/evaluate` buttons.
+const CUSTOM_ID_PREFIX: &str = "evaluate";
+
+/// What a button under an `/evaluate` result does with the expression.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EvaluateAction {
+    /// Evaluates the expression again with the current variables.
+    RunAgain,
+    /// Shows the syntax tree of the expression.
+    ShowAst,
+    /// Plots the expression in `x` from -10 to 10.
+    Graph,
+}
+
+impl EvaluateAction {
+    /// Every action, in the order the buttons are shown.
+    pub const ALL: [EvaluateAction; 3] = [EvaluateAction::RunAgain, EvaluateAction::ShowAst, EvaluateAction::Graph];
+
+    /// Returns the short tag that names the action in a custom id.
+    fn tag(self) -> &'static str {
+        match self {
+            EvaluateAction::RunAgain => "run",
+            EvaluateAction::ShowAst => "ast",
+            EvaluateAction::Graph => "graph",
+        }
+    }
+
+    /// Returns the button label.
+    fn label(self) -> &'static str {
+        match self {
+            EvaluateAction::RunAgain => "Run again",
+            EvaluateAction::ShowAst => "Show AST",
+            EvaluateAction::Graph => "Graph",
+        }
+    }
+}
+
+/// Encodes an action and the expression it applies to as a custom id.
+///
+/// The expression is kept verbatim after the action tag, so any characters,
+/// including `:`, survive the round trip. Returns `None` when the id would
+/// exceed [`CUSTOM_ID_LIMIT`] characters.
+pub fn encode_custom_id(action: EvaluateAction, expression: &str) -> Option<String> {
+    let custom_id = format!("{}:{}:{}", CUSTOM_ID_PREFIX, action.tag(), expression);
+    (custom_id.chars().count() <= CUSTOM_ID_LIMIT).then_some(custom_id)
+}
+
+/// Decodes a custom id made by [`encode_custom_id`].
+///
+/// Returns `None` for ids of other components.
+pub fn decode_custom_id(custom_id: &str) -> Option<(EvaluateAction, &str)> {
+    let rest = custom_id.strip_prefix(CUSTOM_ID_PREFIX)?.strip_prefix(':')?;
+    let (tag, expression) = rest.split_once(':')?;
+    let action = EvaluateAction::ALL.into_iter().find(|action| action.tag() == tag)?;
+    Some((action, expression))
+}
+
+/// Creates the row of buttons for an evaluated expression.
+///
+/// Returns `None` when the expression is too long to fit in a custom id.
+pub fn create_action_row(expression: &str) -> Option<CreateActionRow> {
+    let buttons = EvaluateAction::ALL
+        .into_iter()
+        .map(|action| {
+            encode_custom_id(action, expression).map(|custom_id| {
+                CreateButton::new(custom_id)
+                    .label(action.label())
+                    .style(ButtonStyle::Secondary)
+            })
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(CreateActionRow::Buttons(buttons))
+}
+
+/// Handles clicks on the buttons under an `/evaluate` result.
+///
+/// Each click answers with a new message, so the original result stays as it
+/// was. The answer is deferred, since running the expression can take as
+/// long as the server's `limits` allow. Returns `None` for components that
+/// are not `/evaluate` buttons.
+pub fn handle_evaluate_component_interaction(
+    interaction: &ComponentInteraction,
+    session: &mut UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> Option<EditInteractionResponse> {
+    let (action, expression) = decode_custom_id(&interaction.data.custom_id)?;
+    let locale = session.locale_for(&interaction.locale);
+
+    let (embed, components) = match action {
+        EvaluateAction::RunAgain => run_again(expression, session, constants, limits, theme, locale),
+        EvaluateAction::ShowAst => (show_ast(expression, theme, locale), Vec::new()),
+        EvaluateAction::Graph => (graph(expression, session, constants, limits, theme, locale), Vec::new()),
+    };
+
+    Some(EditInteractionResponse::new().embed(embed).components(components))
+}
+
+/// Evaluates the expression again, offering the same buttons under the result.
+fn run_again(
+    expression: &str,
+    session: &mut UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+    locale: &str,
+) -> (CreateEmbed, Vec<CreateActionRow>) {
+    let options = EvalOptions { complex: session.complex_mode, ..EvalOptions::default() };
+    let result = reading_shared_constants(constants, || with_limits(limits, || crate::core::evaluate_with_options(expression, &session.variables, options)));
+    session.record_evaluation(expression, result.is_ok());
+
+    let result = match result {
+        Ok(value) => value,
+        Err(error) => return (error_embed(&i18n::localize_error(locale, &error), theme), Vec::new()),
+    };
+    session.record_history(expression, Some(result.to_string()), HistoryKind::Evaluated);
+
+    let embed = CreateEmbed::new()
+        .title("Expression Evaluation Successful")
+        .description(format!(
+            "**Code:**\n```rs\n{}\n```\n\
+            **Result:**\n```rs\n{}\n```",
+            expression,
+            utils::truncate_for_discord(&result.to_string(), utils::CODE_ECHO_LIMIT)
+        ))
+        .colour(theme.colour(Tone::Success));
+
+    (embed, create_action_row(expression).into_iter().collect())
+}
+
+/// Draws the syntax tree of the expression.
+fn show_ast(expression: &str, theme: Theme, locale: &str) -> CreateEmbed {
+    match Parser::new(Tokenizer::from_input(expression)).parse_expression() {
+        Ok(expr) => CreateEmbed::new()
+            .title("Syntax Tree")
+            .description(format!(
+                "```\n{}```",
+                utils::truncate_for_discord(&expr.to_text_tree(AST_MAX_DEPTH, AST_MAX_CHILDREN), utils::CODE_ECHO_LIMIT)
+            ))
+            .colour(theme.colour(Tone::Info)),
+        Err(error) => error_embed(&i18n::localize_error(locale, &error.into()), theme),
+    }
+}
+
+/// Plots the expression in `x` from -10 to 10.
+fn graph(expression: &str, session: &UserSession, constants: &SharedConstants, limits: LimitOverrides, theme: Theme, locale: &str) -> CreateEmbed {
+    let sampled = reading_shared_constants(constants, || with_limits(limits, || plot::sample_series(&[expression], &session.variables, -10.0, 10.0, SAMPLE_COUNT)));
+    let series = match sampled {
+        Ok(series) => series,
+        Err(error) => return error_embed(&i18n::localize_error(locale, &error), theme),
+    };
+
+    match plot::render_plot(&series, PLOT_HEIGHT) {
+        Some(chart) => CreateEmbed::new()
+            .title("Graph")
+            .description(format!("**x from -10 to 10:**\n```\n{}```", chart))
+            .colour(theme.colour(Tone::Success)),
+        None => error_embed("The expression is not defined between -10 and 10.", theme),
+    }
+}
+
+/// Creates the embed shown when a button's action fails.
+fn error_embed(message: &str, theme: Theme) -> CreateEmbed {
+    CreateEmbed::new()
+        .title("Error")
+        .description(format!("```fix\n{}\n```", utils::sanitize_for_codeblock(message)))
+        .colour(theme.colour(Tone::Error))
+}
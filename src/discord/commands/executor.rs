@@ -1,144 +1,324 @@
-use log::error;
+use std::fmt;
+use std::time::Duration;
+
 use serenity::all::*;
 use serenity::builder::{CreateEmbed, CreateEmbedFooter};
 
-use crate::discord::error_handler::send_error;
-use crate::discord::UserSession;
+use crate::discord::error_handler::{error_edit, ErrorCategory};
+use crate::i18n;
+use crate::utils;
+use crate::core::{detect_unguarded_recursion, is_boolean_result, normalize_source, trace_execution, undefined_variables, validate, with_limits, with_shared_constants, measure, ExecOptions, InterpreterMetrics, LimitOverrides, SharedConstants, SnippetRegistry, Trace, Value};
+use crate::discord::{HistoryKind, UserSession};
+use crate::discord::theme::{Theme, Tone};
+
+/// Longest trace shown in an embed field; longer ones are attached.
+const TRACE_MAX_INLINE_LENGTH: usize = 1000;
+
+/// Largest code file `/execute` accepts, in bytes.
+pub const MAX_CODE_FILE_SIZE: usize = 64 * 1024;
+
+/// Longest wait for a code file to download.
+///
+/// The command is deferred by then, but a stalled download would still keep
+/// its answer waiting.
+const CODE_FILE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Why a code file attached to `/execute` cannot be run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CodeFileError {
+    /// The file is larger than [`MAX_CODE_FILE_SIZE`].
+    TooLarge { size: usize },
+    /// The file is not UTF-8 text.
+    NotText,
+    /// The file could not be downloaded.
+    Download(String),
+    /// Both the `code` and `file` options were given.
+    Ambiguous,
+}
+
+impl fmt::Display for CodeFileError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodeFileError::TooLarge { size } => write!(
+                formatter,
+                "The file is {} bytes, but code files can be at most {} bytes",
+                size, MAX_CODE_FILE_SIZE
+            ),
+            CodeFileError::NotText => write!(formatter, "The file is not UTF-8 text"),
+            CodeFileError::Download(reason) => write!(formatter, "Could not download the file: {}", reason),
+            CodeFileError::Ambiguous => write!(formatter, "Give either code or a file, not both"),
+        }
+    }
+}
+
+impl std::error::Error for CodeFileError {}
+
+/// Checks a code file's size against [`MAX_CODE_FILE_SIZE`].
+///
+/// Called with the size Discord reports before downloading, and again with
+/// the size of the downloaded bytes.
+pub fn check_code_file_size(size: usize) -> Result<(), CodeFileError> {
+    if size > MAX_CODE_FILE_SIZE {
+        Err(CodeFileError::TooLarge { size })
+    } else {
+        Ok(())
+    }
+}
+
+/// Turns the downloaded bytes of a code file into source text.
+pub fn decode_code_file(bytes: Vec<u8>) -> Result<String, CodeFileError> {
+    check_code_file_size(bytes.len())?;
+    String::from_utf8(bytes).map_err(|_| CodeFileError::NotText)
+}
+
+/// Downloads the code file attached to a command, if it is small enough.
+async fn read_code_file(attachment: &Attachment) -> Result<String, CodeFileError> {
+    check_code_file_size(attachment.size as usize)?;
+
+    let bytes = match tokio::time::timeout(CODE_FILE_TIMEOUT, attachment.download()).await {
+        Ok(Ok(bytes)) => bytes,
+        Ok(Err(error)) => return Err(CodeFileError::Download(error.to_string())),
+        Err(_) => return Err(CodeFileError::Download("it took too long".to_string())),
+    };
+    decode_code_file(bytes)
+}
+
+/// Renders a trace with one statement per line, indented by nesting.
+fn trace_text(trace: &Trace) -> String {
+    let mut text = String::new();
+    for entry in &trace.entries {
+        text.push_str(&format!("{}\n", entry));
+    }
+    if trace.omitted > 0 {
+        text.push_str(&format!("… {} more statements\n", trace.omitted));
+    }
+    text
+}
+
+/// Reads the code to run for `/execute`, from the `code` option or an attached file.
+///
+/// An attached file is downloaded here, so this is called before the bot's
+/// state is locked and a slow download does not hold up other commands.
+pub async fn read_execute_source(interaction: &CommandInteraction) -> Result<String, CodeFileError> {
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    // Code comes from the option or an attached file, for programs too long to type
+    let attachment = match option("file") {
+        Some(CommandDataOptionValue::Attachment(id)) => interaction.data.resolved.attachments.get(id),
+        _ => None,
+    };
+    let typed = option("code").and_then(|value| value.as_str()).unwrap_or("");
+    match attachment {
+        Some(_) if !typed.trim().is_empty() => Err(CodeFileError::Ambiguous),
+        Some(attachment) => read_code_file(attachment).await,
+        None => Ok(typed.to_string()),
+    }
+}
 
 /// Handles the `/execute` slash command for executing calculator code.
 /// 
 /// Similar to evaluate but emphasizes code execution with support for
-/// multiline code blocks and complex logic.
-pub async fn handle_execute(
-    context: &Context,
+/// multiline code blocks and complex logic. The code runs with the server's
+/// `limits`, see `/set-limit`. `source` is the code as read by
+/// [`read_execute_source`].
+#[allow(clippy::too_many_arguments)]
+pub fn handle_execute(
     interaction: &CommandInteraction,
     session: &mut UserSession,
-) {
-    // Extract and clean input
-    let code = interaction
+    source: Result<String, CodeFileError>,
+    snippets: &SnippetRegistry,
+    constants: &mut SharedConstants,
+    metrics: &mut InterpreterMetrics,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
+    let option = |name: &str| interaction
         .data
         .options
-        .first()
-        .and_then(|opt| opt.value.as_str())
-        .unwrap_or("")
-        .trim();
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let source = match source {
+        Ok(source) => source,
+        Err(error) => {
+            return error_edit(&format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&error.to_string())), Some(ErrorCategory::General), locale);
+        }
+    };
+
+    // Clean input, folding exotic Unicode from mobile keyboards
+    let code = utils::normalize_input(&source);
+    let code = code.trim();
+    let trace = option("trace").and_then(|value| value.as_bool()).unwrap_or(false);
+    let normalize = option("normalize").and_then(|value| value.as_bool()).unwrap_or(false);
+    let options = ExecOptions { strict: option("strict").and_then(|value| value.as_bool()).unwrap_or(false) };
 
     if code.is_empty() {
-        send_error(context, interaction, "Please provide code to execute.", None).await;
-        return;
+        return error_edit(&i18n::translate(locale, "message.missing_code"), None, locale);
     }
 
-    // Use the execute function from core to evaluate the input
-    let result = match crate::core::execute(code, &mut session.variables) {
+    // Mistakes found without running, such as a procedure used as a value,
+    // and warnings in strict mode are reported before the code can change
+    // any variables
+    if let Err(error) = validate(code, &session.variables).and_then(|()| options.check(code)) {
+        session.record_evaluation(code, false);
+        let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+        return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+    }
+
+    let author = interaction.user.id.get();
+    let unguarded_before = detect_unguarded_recursion(&session.variables.functions);
+
+    // Variables that are read but never defined are found before running.
+    // Then use the execute function from core to evaluate the input,
+    // recording every statement only when a trace was asked for
+    let (undefined, (result, report), trace) = with_shared_constants(constants, author, || {
+        let undefined = undefined_variables(code, &session.variables);
+        let mut run = || measure(|| with_limits(limits, || crate::core::execute_with_imports(code, &mut session.variables, snippets)));
+        if trace {
+            let (outcome, trace) = trace_execution(run);
+            (undefined, outcome, Some(trace))
+        } else {
+            (undefined, run(), None)
+        }
+    });
+    metrics.record(&report);
+    session.record_evaluation(code, result.is_ok());
+
+    let result = match result {
         Ok(value) => value,
         Err(error) => {
-            // Format error messages differently based on type
-            let error_message = match &error {
-                &crate::core::CalcError::Parse(ref parse_err) => {
-                    format!("```fix\n{}\n```", parse_err)
-                },
-                &crate::core::CalcError::Eval(ref eval_err) => {
-                    format!("```fix\n{}\n```", eval_err)
-                },
-                &crate::core::CalcError::Exec(ref exec_err) => {
-                    format!("```fix\n{}\n```", exec_err)
-                },
-            };
-            
-            send_error(context, interaction, &error_message, None).await;
-            return;
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
         }
     };
     
     // Save to history
-    session.history.push(code.to_string());
+    session.record_history(code, result.map(|value| value.to_string()), HistoryKind::Executed);
+
+    // Functions this code made recurse without a base case
+    let unguarded = detect_unguarded_recursion(&session.variables.functions)
+        .into_iter()
+        .filter(|name| !unguarded_before.contains(name));
     
-    // Create description based on result
-    let description = match result {
+    // Create description based on result, keeping long code within the embed limit
+    // and showing how the code was grouped if asked
+    let source = normalize.then(|| normalize_source(code).ok()).flatten();
+    let (echo, parsed) = utils::fit_code_echoes(code.trim(), source.as_deref());
+    let parsed = match parsed {
+        Some(source) => format!("**Parsed as:**\n```rs\n{}\n```\n", source),
+        None => String::new(),
+    };
+    let mut description = match result {
         Some(val) => format!(
-            "**Code:**\n```rs\n{}\n```\n\
+            "**Code:**\n```rs\n{}\n```\n{}\
             **Result:**\n```rs\n{}\n```",
-            code.trim(),
-            val
+            echo,
+            parsed,
+            Value::Real(val).format_with_hint(is_boolean_result(code))
         ),
         None => format!(
-            "**Code:**\n```rs\n{}\n```\n",
-            code.trim()
+            "**Code:**\n```rs\n{}\n```\n{}",
+            echo,
+            parsed
         )
     };
 
+    // Reads in branches that did not run can still be undefined
+    for name in undefined {
+        description.push('\n');
+        description.push_str(&i18n::translate_with(locale, "message.undefined_variable_warning", &[("name", name)]));
+    }
+    for name in unguarded {
+        description.push('\n');
+        description.push_str(&i18n::translate_with(locale, "message.unguarded_recursion_warning", &[("name", name)]));
+    }
+
     // Create response embed
-    let embed = CreateEmbed::new()
+    let mut embed = CreateEmbed::new()
         .title("Code Execution Successful")
         .description(description)
-        .colour(Colour::DARK_GREEN)
+        .colour(theme.colour(Tone::Success))
         .footer(CreateEmbedFooter::new(format!(
             "Session contains {} variables and {} history entries!",
             session.variables.len(),
             session.history.len()
         )));
+    let mut message = EditInteractionResponse::new();
 
-    let response = CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(embed)
-    );
-
-    if let Err(error) = interaction.create_response(&context.http, response).await {
-        error!("Failed to respond to execute command: {:?}", error);
+    // Short traces are shown inline, longer ones are attached in full
+    if let Some(trace) = trace {
+        let text = trace_text(&trace);
+        if text.len() <= TRACE_MAX_INLINE_LENGTH {
+            embed = embed.field("Trace", format!("```\n{}```", text), false);
+        } else {
+            embed = embed.field("Trace", i18n::translate(locale, "message.trace_attached"), false);
+            message = message.new_attachment(CreateAttachment::bytes(text.into_bytes(), "trace.txt"));
+        }
     }
+
+    message.embed(embed)
 }
 
 /// Handles the context menu command for executing code from messages.
 /// 
 /// Maintains the original formatting and executes the code while showing
 /// both input and output.
-pub async fn handle_execute_code(
-    context: &Context,
+// Each argument is a separate part of the shared state, borrowed alongside the session
+#[allow(clippy::too_many_arguments)]
+pub fn handle_execute_code(
     interaction: &CommandInteraction,
     session: &mut UserSession,
+    snippets: &SnippetRegistry,
+    constants: &mut SharedConstants,
+    metrics: &mut InterpreterMetrics,
     code: &str,
-) {
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
     if code.is_empty() {
-        send_error(context, interaction, "The extracted code is empty.", None).await;
-        return;
+        return error_edit(&i18n::translate(locale, "message.empty_extracted_code"), None, locale);
     }
 
     // Use the execute function from core to evaluate the input
-    let result = match crate::core::execute(code, &mut session.variables) {
+    let (result, report) = with_shared_constants(constants, interaction.user.id.get(), || {
+        measure(|| with_limits(limits, || crate::core::execute_with_imports(code, &mut session.variables, snippets)))
+    });
+    metrics.record(&report);
+    session.record_evaluation(code, result.is_ok());
+
+    let result = match result {
         Ok(value) => value,
         Err(error) => {
-            // Format error messages differently based on type
-            let error_message = match &error {
-                &crate::core::CalcError::Parse(ref parse_err) => {
-                    format!("```fix\n{}\n```", parse_err)
-                },
-                &crate::core::CalcError::Eval(ref eval_err) => {
-                    format!("```fix\n{}\n```", eval_err)
-                },
-                &crate::core::CalcError::Exec(ref exec_err) => {
-                    format!("```fix\n{}\n```", exec_err)
-                },
-            };
-            
-            send_error(context, interaction, &error_message, None).await;
-            return;
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
         }
     };
     
     // Save to history
-    session.history.push(code.to_string());
+    session.record_history(code, result.map(|value| value.to_string()), HistoryKind::Executed);
     
-    // Create description based on result
+    // Create description based on result, keeping long code within the embed limit
+    let echo = utils::truncate_for_discord(&utils::sanitize_for_codeblock(code.trim()), utils::CODE_ECHO_LIMIT);
     let description = match result {
         Some(val) => format!(
             "**Input:**\n```rs\n{}\n```\n\
             **Result:**\n```rs\n{}\n```",
-            code.trim(),
-            val
+            echo,
+            Value::Real(val).format_with_hint(is_boolean_result(code))
         ),
         None => format!(
             "**Input:**\n```rs\n{}\n```\n",
-            code.trim()
+            echo
         )
     };
 
@@ -146,19 +326,12 @@ pub async fn handle_execute_code(
     let embed = CreateEmbed::new()
         .title("Code Execution Successful")
         .description(description)
-        .colour(Colour::DARK_GREEN)
+        .colour(theme.colour(Tone::Success))
         .footer(CreateEmbedFooter::new(format!(
             "Session contains {} variables and {} history entries!",
             session.variables.len(),
             session.history.len()
         )));
 
-    let response = CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(embed)
-    );
-
-    if let Err(error) = interaction.create_response(&context.http, response).await {
-        error!("Failed to respond to execute code command: {:?}", error);
-    }
+    EditInteractionResponse::new().embed(embed)
 } 
\ No newline at end of file
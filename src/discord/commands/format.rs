@@ -0,0 +1,49 @@
+use serenity::all::*;
+
+use crate::core::{format_source, InterpreterError};
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::i18n;
+use crate::utils;
+
+/// Handles the `/syntax-highlight` slash command.
+///
+/// Parses the program and sends it back with consistent indentation in a
+/// ```` ```rust ```` block, whose highlighting suits the language well. The
+/// program is not run. A result too long for a message is attached instead.
+pub fn handle_format(
+    interaction: &CommandInteraction,
+    locale: &str,
+) -> CreateInteractionResponseMessage {
+    let code = interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == "code")
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("");
+    let code = utils::normalize_input(code);
+
+    if code.trim().is_empty() {
+        return error_response(&i18n::translate(locale, "message.missing_format_code"), None, locale);
+    }
+
+    let formatted = match format_source(&code) {
+        Ok(formatted) => formatted,
+        Err(error) => {
+            let error = InterpreterError::from(error);
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_response(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
+    let block = format!("```rust\n{}\n```", utils::sanitize_for_codeblock(&formatted));
+    let message = if block.chars().count() <= utils::MESSAGE_CONTENT_LIMIT {
+        CreateInteractionResponseMessage::new().content(block)
+    } else {
+        CreateInteractionResponseMessage::new()
+            .content(i18n::translate(locale, "message.format_attached"))
+            .add_file(CreateAttachment::bytes(formatted.into_bytes(), "program.rs"))
+    };
+
+    message.allowed_mentions(CreateAllowedMentions::new())
+}
@@ -0,0 +1,76 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::{reading_shared_constants, with_limits, LimitOverrides, SharedConstants};
+use crate::discord::commands::plot;
+use crate::discord::error_handler::{error_edit, ErrorCategory};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Number of samples taken of each expression, one per chart column.
+pub(crate) const SAMPLE_COUNT: usize = 61;
+
+/// Number of chart rows.
+pub(crate) const PLOT_HEIGHT: usize = 15;
+
+/// Handles the `/graph-compare` slash command plotting two expressions in `x`.
+///
+/// Both expressions are sampled over the same range and drawn on shared axes
+/// with a legend. Each may be undefined on different parts of the range.
+/// Sampling runs with the server's `limits`, see `/set-limit`, and can read
+/// the server's global constants.
+pub fn handle_graph_compare(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let first = utils::normalize_input(option("first").and_then(|value| value.as_str()).unwrap_or(""));
+    let second = utils::normalize_input(option("second").and_then(|value| value.as_str()).unwrap_or(""));
+    let (first, second) = (first.trim(), second.trim());
+    let from = option("from").and_then(|value| value.as_f64()).unwrap_or(-10.0) as f32;
+    let to = option("to").and_then(|value| value.as_f64()).unwrap_or(10.0) as f32;
+
+    if first.is_empty() || second.is_empty() {
+        return error_edit(&i18n::translate(locale, "message.missing_expression"), None, locale);
+    }
+
+    if !from.is_finite() || !to.is_finite() || from >= to {
+        let error_message = format!("```fix\nInvalid range {} to {}. The start must be below the end.\n```", from, to);
+        return error_edit(&error_message, Some(ErrorCategory::Runtime), locale);
+    }
+
+    let series = match reading_shared_constants(constants, || with_limits(limits, || plot::sample_series(&[first, second], &session.variables, from, to, SAMPLE_COUNT))) {
+        Ok(series) => series,
+        Err(error) => {
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
+    let Some(chart) = plot::render_plot(&series, PLOT_HEIGHT) else {
+        let error_message = format!("```fix\nNeither expression is defined between {} and {}.\n```", from, to);
+        return error_edit(&error_message, Some(ErrorCategory::Runtime), locale);
+    };
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Graph Comparison")
+        .description(format!("**x from {} to {}:**\n```\n{}```", from, to, chart))
+        .colour(theme.colour(Tone::Success));
+
+    EditInteractionResponse::new()
+        .embed(embed)
+}
@@ -1,4 +1,4 @@
-use log::{info, error};
+use log::info;
 use serenity::all::*;
 use serenity::builder::{
     CreateEmbed, 
@@ -11,7 +11,150 @@ use serenity::builder::{
 };
 use std::collections::HashMap;
 
+use crate::core::{list_builtins, find_builtin, BuiltinSpec, BuiltinCategory};
+use crate::i18n;
+use crate::utils;
 use crate::discord::models::{HelpEmbedsContainer, CommandMetadataContainer};
+use crate::discord::theme::{Theme, Tone};
+
+/// A runnable code example shown in a help embed field.
+///
+/// Every example must run without errors in a fresh session, so the help
+/// never shows code the calculator rejects. The test suite executes them all.
+pub struct HelpExample {
+    /// Key the embed builders use to place the example.
+    pub key: &'static str,
+    /// Text shown above the code block.
+    pub intro: &'static str,
+    /// The example code.
+    pub code: &'static str,
+    /// Text shown below the code block.
+    pub note: &'static str,
+}
+
+/// All code examples shown in the help embeds.
+static HELP_EXAMPLES: [HelpExample; 16] = [
+    HelpExample {
+        key: "overview.examples",
+        intro: "",
+        code: "2 + 2 * 3;\n(10 - 5) / 2;\nlet x = 5;\n\n// Define a function\nfn square(x) {\n    x * x\n}\nlet y = square(x);",
+        note: "",
+    },
+    HelpExample {
+        key: "basics.simple",
+        intro: "Examples of basic calculations:",
+        code: "2 + 2 * 3;\n(10 - 3) / 2",
+        note: "",
+    },
+    HelpExample {
+        key: "basics.assignment",
+        intro: "",
+        code: "let x = 5       // Declare x with value 5\nx = 10          // Change x to 10\nx += 2          // Add 2 to x (now 12)",
+        note: "",
+    },
+    HelpExample {
+        key: "basics.constants",
+        intro: "",
+        code: "const RATE = 0.25\n// Constants cannot be changed",
+        note: "",
+    },
+    HelpExample {
+        key: "variables.declaring",
+        intro: "Use `let` to declare variables:",
+        code: "let x = 42;\nlet result = x * 2;",
+        note: "",
+    },
+    HelpExample {
+        key: "variables.constants",
+        intro: "Use `const` for immutable values:",
+        code: "const MY_CONSTANT = 3.14159;\nconst GRAVITY = 9.81;",
+        note: "Constants cannot be modified after declaration.",
+    },
+    HelpExample {
+        key: "variables.assignment",
+        intro: "Update existing variables:",
+        code: "let x = 1;\nlet y = 3;\nx = x + 1;\nx += 5;\ny *= 2;\nx = y = 0;  // Chained assignment\nlet z = (x = 2) * 10;  // Sets x to 2 and z to 20",
+        note: "Note: Variables must be declared with `let` first. An assignment is also a value, so it works inside expressions and calls, except in `/evaluate`, which never changes variables.",
+    },
+    HelpExample {
+        key: "variables.formulas",
+        intro: "Use `:=` for values that are recomputed on every read:",
+        code: "let r = 1;\nlet area := PI * r * r;\nr = 2;\narea  // uses r = 2",
+        note: "Assigning a value with `=` turns a formula back into a variable.",
+    },
+    HelpExample {
+        key: "control_flow.if",
+        intro: "",
+        code: "let x = 7\nif x > 5 {\n  x = x * 2\n} else {\n  x = x + 1\n}\n// x now equals 14",
+        note: "",
+    },
+    HelpExample {
+        key: "control_flow.while",
+        intro: "",
+        code: "let i = 1\nlet factorial = 1\nwhile i <= 5 {\n  factorial *= i\n  i += 1\n}\n// factorial now equals 120",
+        note: "",
+    },
+    HelpExample {
+        key: "control_flow.foreach",
+        intro: "",
+        code: "let sum = 0\nforeach i in 1..6 {\n  sum += i\n}\n// sum now equals 15 (the end is excluded)",
+        note: "",
+    },
+    HelpExample {
+        key: "control_flow.blocks",
+        intro: "",
+        code: "let x = 3\nlet y = 0\n// Blocks create temporary scopes\n{\n  let temp = x * 2\n  y = temp + 1\n}\n// temp is no longer accessible, y equals 7",
+        note: "",
+    },
+    HelpExample {
+        key: "functions.usage",
+        intro: "",
+        code: "let a = sin(PI / 2);\nlet b = atan2(1, -1);\nlet c = sqrt(25) + abs(-10);",
+        note: "Use `/help function:<name>` for details on a single function.",
+    },
+    HelpExample {
+        key: "functions.user",
+        intro: "Define your own reusable functions that return values:",
+        code: "fn square(x) {\n    x * x\n}\n\nfn hypotenuse(a, b) {\n    sqrt(a * a + b * b)\n}\n\n// Use your functions\nlet area = square(5);  // 25\nlet c = hypotenuse(3, 4);  // 5",
+        note: "A function returns the value of the last statement in its body.",
+    },
+    HelpExample {
+        key: "functions.procedures",
+        intro: "Define procedures that perform operations without returning values:",
+        code: "let total = 0;\n\nproc add_values(a, b) {\n    total = total + a + b;\n}\n\n// Use your procedure\nadd_values(5, 10);  // total is now 15",
+        note: "",
+    },
+    HelpExample {
+        key: "functions.end",
+        intro: "Terminate program execution and return a value:",
+        code: "let threshold = 10;\nlet x = sqrt(400);\nif x > threshold {\n    end x;  // Exits with value x\n}\n// Code continues if x <= threshold\nx / 2",
+        note: "",
+    },
+];
+
+/// Returns the code examples shown in the help embeds.
+pub fn help_examples() -> &'static [HelpExample] {
+    &HELP_EXAMPLES
+}
+
+/// Formats the help example with the given key as an embed field value.
+fn example_field(key: &str) -> String {
+    let example = HELP_EXAMPLES.iter()
+        .find(|example| example.key == key)
+        .expect("Help examples are looked up by their known keys");
+
+    let mut value = String::new();
+    if !example.intro.is_empty() {
+        value.push_str(example.intro);
+        value.push('\n');
+    }
+    value.push_str(&format!("```rust\n{}\n```", example.code));
+    if !example.note.is_empty() {
+        value.push('\n');
+        value.push_str(example.note);
+    }
+    value
+}
 
 /// Handles the `/help` command with detailed information about calculator usage.
 ///
@@ -19,12 +162,33 @@ use crate::discord::models::{HelpEmbedsContainer, CommandMetadataContainer};
 pub async fn handle_help(
     context: &Context,
     interaction: &CommandInteraction,
-) {
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    // Answer for a single builtin function if one was requested
+    let function = interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == "function")
+        .and_then(|opt| opt.value.as_str());
+
+    if let Some(function) = function {
+        return match find_builtin(function.trim()) {
+            Some(spec) => CreateInteractionResponseMessage::new().embed(create_builtin_help_embed(spec, theme)),
+            None => CreateInteractionResponseMessage::new()
+                .content(format!("There is no built-in function named {}.", utils::escape_markdown(function.trim())))
+                .allowed_mentions(CreateAllowedMentions::new())
+                .ephemeral(true),
+        };
+    }
+
     // Check if a specific topic was requested
     let topic = interaction
         .data
         .options
-        .first()
+        .iter()
+        .find(|opt| opt.name == "topic")
         .and_then(|opt| opt.value.as_str())
         .unwrap_or("overview")
         .to_lowercase();
@@ -36,57 +200,42 @@ pub async fn handle_help(
     let command_metadata = data_read.get::<CommandMetadataContainer>();
     if let Some(metadata_map) = command_metadata {
         if let Some(cmd_metadata) = metadata_map.get(&topic) {
-            // Create and send a command-specific help embed
-            let embed = create_command_help_embed(cmd_metadata);
+            // Create a command-specific help embed
+            let embed = create_command_help_embed(cmd_metadata, theme);
             
-            let response = CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .embed(embed)
-            );
-
-            if let Err(error) = interaction.create_response(&context.http, response).await {
-                error!("Failed to send help response: {:?}", error);
-            }
-            return;
+            return CreateInteractionResponseMessage::new()
+                .embed(embed);
         }
     }
     
     // If not a command, use the standard help topic embeds
-    let help_embeds = data_read.get::<HelpEmbedsContainer>()
+    let help_embeds_by_locale = data_read.get::<HelpEmbedsContainer>()
         .expect("Expected HelpEmbedsContainer in TypeMap");
+    let help_embeds = help_embeds_by_locale.get(locale)
+        .or_else(|| help_embeds_by_locale.get(i18n::DEFAULT_LOCALE))
+        .expect("Expected help embeds for the default locale");
     
     // Get the requested embed or fall back to overview if not found
+    // The cached embeds have the default colour, the server's accent replaces it
     let embed = help_embeds.get(&topic)
         .unwrap_or_else(|| help_embeds.get("0").unwrap())
-        .clone();
+        .clone()
+        .colour(theme.colour(Tone::Help));
 
     // If showing the main overview, add a dropdown for commands
     if topic == "overview" || topic == "0" || topic == "main" {
         if let Some(metadata_map) = command_metadata {
             let command_dropdown = create_command_dropdown(metadata_map);
             
-            let response = CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .embed(embed)
-                    .components(vec![command_dropdown])
-            );
-
-            if let Err(error) = interaction.create_response(&context.http, response).await {
-                error!("Failed to send help response: {:?}", error);
-            }
-            return;
+            return CreateInteractionResponseMessage::new()
+                .embed(embed)
+                .components(vec![command_dropdown]);
         }
     }
     
     // For other topics or if no command metadata available
-    let response = CreateInteractionResponse::Message(
-        CreateInteractionResponseMessage::new()
-            .embed(embed)
-    );
-
-    if let Err(error) = interaction.create_response(&context.http, response).await {
-        error!("Failed to send help response: {:?}", error);
-    }
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
 }
 
 /// Creates a dropdown menu component listing all available commands.
@@ -119,12 +268,14 @@ fn create_command_dropdown(command_metadata: &HashMap<String, crate::discord::mo
 
 /// Handles interactions with the help command's dropdown menu.
 ///
-/// When a user selects a command from the dropdown, this displays detailed help
-/// for that specific command by updating the original message.
+/// When a user selects a command from the dropdown, this builds detailed help
+/// for that specific command to update the original message with. Returns
+/// `None` for interactions that are not from the dropdown.
 pub async fn handle_help_component_interaction(
     context: &Context,
     interaction: &ComponentInteraction,
-) -> bool {
+    theme: Theme,
+) -> Option<CreateInteractionResponse> {
     // Check if this is our help command select menu
     if interaction.data.custom_id == "help_command_select" {
         // Get the selected value from the interaction data
@@ -149,39 +300,33 @@ pub async fn handle_help_component_interaction(
                     // Get the command at the selected index
                     if let Some(cmd_metadata) = commands.get(index) {
                         // Create the command help embed
-                        let embed = create_command_help_embed(cmd_metadata);
+                        let embed = create_command_help_embed(cmd_metadata, theme);
                         
                         // Update the original message with the command help
-                        let response = CreateInteractionResponse::UpdateMessage(
+                        return Some(CreateInteractionResponse::UpdateMessage(
                             CreateInteractionResponseMessage::new()
                                 .embed(embed)
                                 .components(vec![create_command_dropdown(metadata_map)]) // Keep the dropdown
-                        );
-                        
-                        if let Err(error) = interaction.create_response(&context.http, response).await {
-                            error!("Failed to update help response: {:?}", error);
-                        }
-                        
-                        return true;
+                        ));
                     }
                 }
             }
         }
     }
     
-    false
+    None
 }
 
 /// Create a help embed for a specific command showing usage and examples.
 /// 
 /// Builds a detailed help message that includes command description, proper usage syntax,
 /// and a list of practical examples.
-fn create_command_help_embed(metadata: &crate::discord::models::CommandMetadata) -> CreateEmbed {
+fn create_command_help_embed(metadata: &crate::discord::models::CommandMetadata, theme: Theme) -> CreateEmbed {
     let mut embed = CreateEmbed::new()
         .title("Command Help")
         .description(&metadata.description)
         .field("Usage", format!("`{}`", metadata.usage), false)
-        .colour(Colour::BLUE);
+        .colour(theme.colour(Tone::Help));
 
     // Add examples if available
     if !metadata.examples.is_empty() {
@@ -197,19 +342,30 @@ fn create_command_help_embed(metadata: &crate::discord::models::CommandMetadata)
     embed
 }
 
+/// Create a help embed for a single built-in function.
+fn create_builtin_help_embed(spec: &BuiltinSpec, theme: Theme) -> CreateEmbed {
+    CreateEmbed::new()
+        .title(format!("Function: {}", spec.name))
+        .description(spec.summary)
+        .field("Usage", format!("`{}`", spec.signature), true)
+        .field("Category", spec.category.title(), true)
+        .field("Example", format!("```rust\n{}\n```", spec.example), false)
+        .colour(theme.colour(Tone::Help))
+}
+
 /// Initialize and configure pre-cached help embeds for different topics.
 /// 
 /// Creates a collection of embeds for each help topic to avoid rebuilding them on every request.
 ///
 /// This improves response time and reduces code duplication.
-pub fn initialize_help_embeds(command_metadata: Option<&HashMap<String, crate::discord::models::CommandMetadata>>) -> HashMap<String, CreateEmbed> {
-    info!("Initializing help embeds cache");
+pub fn initialize_help_embeds(command_metadata: Option<&HashMap<String, crate::discord::models::CommandMetadata>>, locale: &str) -> HashMap<String, CreateEmbed> {
+    info!("Initializing help embeds cache for locale {}", locale);
     
     let mut embeds = HashMap::new();
     
     // Add the main help embed
     if let Some(metadata) = command_metadata {
-        let overview = create_overview_help(metadata);
+        let overview = create_overview_help(metadata, locale);
         embeds.insert("0".to_string(), overview.clone());
     }
 
@@ -217,74 +373,78 @@ pub fn initialize_help_embeds(command_metadata: Option<&HashMap<String, crate::d
     embeds.insert(
         "1".to_string(),
         CreateEmbed::new()
-            .title("Calculator Basics")
-            .description("The calculator supports standard arithmetic operations and follows order of operations.")
+            .title(i18n::translate(locale, "help.basics.title"))
+            .description(i18n::translate(locale, "help.basics.description"))
             .field(
                 "Simple Calculations",
-                "Examples of basic calculations:\n\
-                ```rust\n2 + 2 * 3\n(10 - 3) / 2\n```",
+                example_field("basics.simple"),
                 false
             )
             .field(
                 "Assignment",
-                "```\nlet x = 5       // Declare x with value 5\nx = 10          // Change x to 10\nx += 2          // Add 2 to x (now 12)\n```",
+                example_field("basics.assignment"),
                 false
             )
             .field(
                 "Constants",
-                "```\nconst PI = 3.14159\n// Constants cannot be changed\n```",
+                example_field("basics.constants"),
                 false
             )
-            .colour(Colour::from_rgb(50, 168, 82))
+            .colour(Tone::Help.default_colour())
     );
     
     // Add the syntax help embed
     embeds.insert(
         "2".to_string(),
-        create_syntax_help()
+        create_syntax_help(locale)
     );
     
     // Add the variables help embed
     embeds.insert(
         "3".to_string(),
-        create_variables_help()
+        create_variables_help(locale)
     );
     
     // Add the control flow help embed
     embeds.insert(
         "4".to_string(),
         CreateEmbed::new()
-            .title("Control Flow")
-            .description("Control flow statements let you make decisions and repeat calculations.")
+            .title(i18n::translate(locale, "help.control_flow.title"))
+            .description(i18n::translate(locale, "help.control_flow.description"))
             .field(
                 "If Statements",
-                "```rust\nif x > 5 {\n  x = x * 2\n} else {\n  x = x + 1\n}\n```",
+                example_field("control_flow.if"),
                 false
             )
             .field(
                 "While Loops",
-                "```rust\nlet i = 1\nlet factorial = 1\nwhile i <= 5 {\n  factorial *= i\n  i += 1\n}\n// factorial now equals 120\n```",
+                example_field("control_flow.while"),
+                false
+            )
+            .field(
+                "Foreach Loops",
+                example_field("control_flow.foreach"),
                 false
             )
             .field(
                 "Block Statements",
-                "```rust\n// Blocks create temporary scopes\n{\n  let temp = x * 2\n  y = temp + 1\n}\n// temp is no longer accessible\n```",
+                example_field("control_flow.blocks"),
                 false
             )
-            .colour(Colour::from_rgb(194, 124, 14))
+            .colour(Tone::Help.default_colour())
     );
     
     // Add the functions help embed
     embeds.insert(
         "5".to_string(),
-        create_functions_help()
+        create_functions_help(locale)
     );
     
     embeds
 }
 
 /// Creates the overview help embed with general information.
-fn create_overview_help(command_metadata: &HashMap<String, crate::discord::models::CommandMetadata>) -> CreateEmbed {
+fn create_overview_help(command_metadata: &HashMap<String, crate::discord::models::CommandMetadata>, locale: &str) -> CreateEmbed {
     // Generate command list from metadata; filter out "Execute Code" context command
     let commands_list = command_metadata.values()
         .filter(|cmd| cmd.name != "Execute Code") // Filter out the context menu command
@@ -293,8 +453,8 @@ fn create_overview_help(command_metadata: &HashMap<String, crate::discord::model
         .join("\n");
     
     CreateEmbed::new()
-        .title("Calculator Help")
-        .description("This calculator bot allows you to evaluate mathematical expressions, store variables, use control flow structures, and define custom functions and procedures.")
+        .title(i18n::translate(locale, "help.overview.title"))
+        .description(i18n::translate(locale, "help.overview.description"))
         .field("Available Commands", commands_list, false)
         .field("Help Topics", 
                "`basics` - Basic usage and expressions\n\
@@ -303,10 +463,8 @@ fn create_overview_help(command_metadata: &HashMap<String, crate::discord::model
                 `control` - Control flow structures\n\
                 `functions & procedures` - Built-in and user-defined functions/procedures", 
                 false)
-        .field("Examples", 
-               "```rust\n2 + 2 * 3;\n(10 - 5) / 2;\nlet x = 5;\n\n// Define a function\nfn square(x) {\n    return x * x\n}\n```", 
-                false)
-        .colour(Colour::BLUE)
+        .field("Examples", example_field("overview.examples"), false)
+        .colour(Tone::Help.default_colour())
 }
 
 /// Creates the basics help embed with fundamental information.
@@ -327,57 +485,47 @@ fn create_basics_help() -> CreateEmbed {
                "Your calculations persist across interactions.\n\
                 Clear your session with `/clear`.", 
                false)
-        .colour(Colour::from_rgb(65, 105, 225))
+        .colour(Tone::Help.default_colour())
 }
 
 /// Creates the syntax help embed with detailed operator information.
-fn create_syntax_help() -> CreateEmbed {
+fn create_syntax_help(locale: &str) -> CreateEmbed {
     CreateEmbed::new()
-        .title("Syntax Help")
-        .description("Learn about the basic syntax elements and operators of the calculator.")
+        .title(i18n::translate(locale, "help.syntax.title"))
+        .description(i18n::translate(locale, "help.syntax.description"))
         .field("Literals", 
                "```\nNumbers: 123, 3.14, 0xFF (hex), 0b1010 (binary)\nVariables: x, counter, result\nKeywords: true (1), false (0)\n```", 
                false)
         .field("Arithmetic", 
-               "```\nAddition: a + b\nSubtraction: a - b\nMultiplication: a * b\nDivision: a / b\nModulo: a % b\nPower: a ^ b\nRoot: b √ a (b'th root of a)\n```", 
+               "```\nAddition: a + b\nSubtraction: a - b\nMultiplication: a * b\nDivision: a / b\nModulo: a % b (remainder, 7 % 3 = 1)\nPercent of: percent(p, whole) (percent(50, 200) = 100)\nPower: a ^ b\nRoot: b √ a or root(b, a) (3 √ -8 = -2)\n```", 
                false)
         .field("Comparison", 
-               "```\nEqual: a == b\nNot equal: a != b\nGreater: a > b\nLess: a < b\nGreater or equal: a >= b\nLess or equal: a <= b\n```", 
+               "```\nEqual: a == b\nNot equal: a != b\nGreater: a > b\nLess: a < b\nGreater or equal: a >= b\nLess or equal: a <= b\nResults show as true or false and count as 1 and 0\n```", 
                false)
         .field("Logical", 
-               "```\nAND: a && b (1 if both a and b are non-zero)\nOR: a || b (1 if either a or b is non-zero)\nXOR: a ^^ b (1 if exactly one of a or b is non-zero)\nNOT: !a (1 if a is zero, 0 otherwise)\n```", 
+               "```\nAND: a && b (1 if both a and b are non-zero)\nOR: a || b (1 if either a or b is non-zero)\nXOR: a ^^ b (1 if exactly one of a or b is non-zero)\nNOT: !a (1 if a is zero, 0 otherwise)\nTo truth value: bool(a) (true if a is non-zero)\nZero test: iszero(a)\n```", 
                false)
         .field("Advanced Logical", 
                "```\nNAND: a !& b (NOT of AND)\nNOR: a !| b (NOT of OR)\nXNOR: a !^ b (NOT of XOR)\n```", 
                false)
-        .colour(Colour::from_rgb(100, 149, 237))
+        .colour(Tone::Help.default_colour())
 }
 
 /// Creates the variables help embed with variable usage information.
-fn create_variables_help() -> CreateEmbed {
+fn create_variables_help(locale: &str) -> CreateEmbed {
     CreateEmbed::new()
-        .title("Working with Variables")
-        .description("Variables let you store values for later use.")
-        .field("Declaring Variables", 
-               "Use `let` to declare variables:\n\
-                ```rust\nlet x = 42;\nlet result = x * 2;\n```", 
-               false)
-        .field("Constants", 
-               "Use `const` for immutable values:\n\
-                ```rust\nconst MY_CONSTANT = 3.14159;\nconst GRAVITY = 9.81;\n```\n\
-                Constants cannot be modified after declaration.", 
-               false)
+        .title(i18n::translate(locale, "help.variables.title"))
+        .description(i18n::translate(locale, "help.variables.description"))
+        .field("Declaring Variables", example_field("variables.declaring"), false)
+        .field("Constants", example_field("variables.constants"), false)
         .field("Predefined Constants", 
                "The calculator comes with built-in mathematical constants:\n\
                 ```\n• π (3.14159...)\n• τ (2π, 6.28318...)\n• Euler's number (2.71828...)\n• Golden ratio (1.61803...)\n• Square root of 2 (1.41421...)\n• Positive infinity\n```\n\
                 Access these via their reserved names (PI, TAU, E, PHI, SQRT2, INFINITY).", 
                false)
-        .field("Assignment", 
-               "Update existing variables:\n\
-                ```rust\nx = x + 1;\nx += 5;\ny *= 2;\n```\n\
-                Note: Variables must be declared with `let` first.", 
-               false)
-        .colour(Colour::from_rgb(70, 130, 180))
+        .field("Assignment", example_field("variables.assignment"), false)
+        .field("Formulas", example_field("variables.formulas"), false)
+        .colour(Tone::Help.default_colour())
 }
 
 /// Creates the control flow help embed with information about conditionals and loops.
@@ -401,47 +549,45 @@ fn create_control_flow_help() -> CreateEmbed {
                 ```rust\nwhile x < 100 { x += 10; if x > 50 { break; } }\n```\n\
                 ```rust\nwhile x < 10 { x += 1; if x % 2 == 0 { continue; } }\n```", 
                false)
+        .field("Try and Catch", 
+               "Recover from errors such as division by zero:\n\
+                ```rust\ntry { ratio = a / b; } catch { ratio = 0; }\n```", 
+               false)
         .field("Code Blocks", 
                "Group multiple statements with curly braces `{ }`\n\
                 Separate statements with semicolons `;`", 
                false)
-        .colour(Colour::from_rgb(75, 0, 130))
+        .colour(Tone::Help.default_colour())
 }
 
 /// Creates the functions help embed with information about built-in functions.
-fn create_functions_help() -> CreateEmbed {
-    CreateEmbed::new()
-        .title("Calculator Functions")
-        .description("The calculator supports built-in mathematical functions and user-defined functions & procedures.")
-        .field("Basic Trigonometric", 
-               "```rust\nsin(x) - Sine of x (radians)\ncos(x) - Cosine of x (radians)\ntan(x) - Tangent of x (radians)\n```", 
-               false)
-        .field("Additional Trigonometric", 
-               "```rust\ncot(x) - Cotangent of x (radians)\nsec(x) - Secant of x (radians)\ncsc(x) - Cosecant of x (radians)\n```", 
-               false)
-        .field("Inverse Trigonometric", 
-               "```rust\nasin(x) - Arc sine (inverse sine)\nacos(x) - Arc cosine (inverse cosine)\natan(x) - Arc tangent (inverse tangent)\natan2(y, x) - Arc tangent of y/x with quadrant\n```", 
-               false)
-        .field("Math Functions", 
-               "```rust\nlog(x) - Natural logarithm of x\nsqrt(x) - Square root of x\nabs(x) - Absolute value of x\n```", 
-               false)
-        .field("Min/Max Functions", 
-               "```rust\nmin(x, y) - Minimum of x and y\nmax(x, y) - Maximum of x and y\n```", 
-               false)
-        .field("Random Number Generator", 
-               "```rust\nrand() - Random number between 0 and 1\nrand(max) - Random number between 0 and max\nrand(min, max) - Random number between min and max\n```", 
-               false)
-        .field("Function Usage", 
-               "```rust\nsin(PI / 2);\natan2(1, -1);\nsqrt(25) + abs(-10);\n```", 
-               false)
-        .field("User-Defined Functions",
-               "Define your own reusable functions that return values:\n```rust\nfn square(x) {\n    return x * x\n}\n\nfn hypotenuse(a, b) {\n    return sqrt(a * a + b * b)\n}\n\n// Use your functions\nlet area = square(5);  // 25\nlet c = hypotenuse(3, 4);  // 5\n```",
-               false)
-        .field("User-Defined Procedures",
-               "Define procedures that perform operations without returning values:\n```rust\nlet total = 0;\n\nproc add_values(a, b) {\n    total = total + a + b;\n}\n\n// Use your procedure\nadd_values(5, 10);  // total is now 15\n```",
-               false)
-        .field("The End Keyword",
-               "Terminate program execution and return a value:\n```rust\nlet x = calculate_value();\nif x > threshold {\n    end x;  // Exits with value x\n}\n// Code continues if x <= threshold\n```",
+///
+/// The built-in function fields are generated from the core builtin table.
+fn create_functions_help(locale: &str) -> CreateEmbed {
+    let mut embed = CreateEmbed::new()
+        .title(i18n::translate(locale, "help.functions.title"))
+        .description(i18n::translate(locale, "help.functions.description"));
+
+    // One field per builtin category
+    for category in BuiltinCategory::ALL {
+        let lines = list_builtins().iter()
+            .filter(|spec| spec.category == category)
+            .map(|spec| format!("{} - {}", spec.signature, spec.summary))
+            .collect::<Vec<_>>();
+
+        if !lines.is_empty() {
+            embed = embed.field(category.title(), format!("```rust\n{}\n```", lines.join("\n")), false);
+        }
+    }
+
+    embed
+        .field("Function Usage", example_field("functions.usage"), false)
+        .field("User-Defined Functions", example_field("functions.user"), false)
+        .field("User-Defined Procedures", example_field("functions.procedures"), false)
+        .field("The End Keyword", example_field("functions.end"), false)
+        .field("Names",
+               "A function or procedure cannot share its name with another function, procedure or variable declared in the same block, and defining it twice fails too.\n\
+                An inner block `{ }` may reuse an outer name, which is shadowed until the block ends.",
                false)
-        .colour(Colour::from_rgb(138, 43, 226))
+        .colour(Tone::Help.default_colour())
 } 
@@ -0,0 +1,63 @@
+use serenity::all::*;
+
+use crate::core::{with_shared_constants, LimitOverrides, SharedConstants, SnippetRegistry};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils;
+
+/// Handles the `/history` command group.
+///
+/// `export` sends the session's executed inputs as a `.txt` script, with
+/// each result as a comment, so running it with `/execute` rebuilds the same
+/// variables. `replay` runs those inputs again in fresh variables and keeps
+/// what they build, all within one time limit from the server's `limits`,
+/// listing any input that fails this time. Replayed inputs read and publish
+/// the server's global constants as they did when first run. Evaluated
+/// inputs changed no variables, so both leave them out. The answer is
+/// deferred as ephemeral, so only its owner sees it.
+pub fn handle_history(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    snippets: &SnippetRegistry,
+    constants: &mut SharedConstants,
+    limits: LimitOverrides,
+) -> Option<EditInteractionResponse> {
+    let locale = session.locale_for(&interaction.locale);
+
+    // Subcommands arrive as the only top-level option
+    let subcommand = interaction.data.options.first()?;
+
+    let executed = session.executed_history().count();
+    let message = match subcommand.name.as_str() {
+        "export" | "replay" if executed == 0 => EditInteractionResponse::new()
+            .content(i18n::translate(locale, "message.history_empty")),
+        "export" => EditInteractionResponse::new()
+            .content(i18n::translate_with(locale, "message.history_exported", &[("count", executed.to_string())]))
+            .new_attachment(CreateAttachment::bytes(session.history_script().into_bytes(), "transcript.txt")),
+        "replay" => {
+            let replay = with_shared_constants(constants, interaction.user.id.get(), || session.replay_history(snippets, limits));
+            session.variables = replay.variables;
+
+            let mut content = i18n::translate_with(
+                locale,
+                "message.history_replayed",
+                &[("count", replay.succeeded.to_string()), ("variables", session.variables.len().to_string())],
+            );
+            if !replay.failures.is_empty() {
+                content.push_str("\n\n");
+                content.push_str(&i18n::translate_with(locale, "message.history_replay_failed", &[("count", replay.failures.len().to_string())]));
+                for (input, error) in &replay.failures {
+                    content.push_str(&format!("\n{}: {}", utils::escape_markdown(input), utils::escape_markdown(error)));
+                }
+            }
+
+            // The inputs are echoed, so nothing in them may ping anyone
+            EditInteractionResponse::new()
+                .content(utils::truncate_for_discord(&content, utils::MESSAGE_CONTENT_LIMIT))
+                .allowed_mentions(CreateAllowedMentions::new())
+        }
+        _ => return None,
+    };
+
+    Some(message)
+}
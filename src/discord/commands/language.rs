@@ -0,0 +1,46 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/language` slash command for choosing the reply language.
+///
+/// Stores the chosen locale in the user's session, or clears it with `auto`
+/// so that replies follow the Discord client's language again.
+pub fn handle_language(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let choice = interaction
+        .data
+        .options
+        .first()
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("auto");
+
+    session.locale = if choice == "auto" {
+        None
+    } else {
+        Some(i18n::resolve_locale(choice).to_string())
+    };
+
+    let locale = session.locale_for(&interaction.locale);
+    let message = match &session.locale {
+        Some(_) => i18n::translate_with(locale, "message.language_set", &[
+            ("language", i18n::translate(locale, "language.name")),
+        ]),
+        None => i18n::translate(locale, "message.language_reset"),
+    };
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .description(message)
+        .colour(theme.colour(Tone::Info));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .ephemeral(true)
+}
@@ -1,18 +1,57 @@
 // Commands module for Discord bot
 
 mod evaluate;
-mod executor;
-mod vars;
+pub mod executor;
+pub mod vars;
+mod vars_export;
+mod history;
 mod clear;
+mod remove;
 mod statistics;
+pub mod publish;
+mod language;
+mod admin;
+mod complex_mode;
+mod convert;
+mod convert_base;
+mod decompose;
+mod graph_compare;
+mod verify_deterministic;
+mod format;
+mod select_expr;
+mod random_expr;
+mod validate_identifier;
+pub mod set_limit;
 pub mod help;
+pub mod evaluate_buttons;
+pub mod profile;
+pub mod plot;
 
 // Re-export command handlers
-pub use evaluate::handle_evaluate;
+pub use evaluate::{handle_evaluate, has_blank_expression};
 pub use executor::handle_execute;
+pub use executor::read_execute_source;
 pub use executor::handle_execute_code;
 pub use vars::handle_vars;
+pub use vars_export::handle_vars_export;
+pub use history::handle_history;
 pub use clear::handle_clear;
+pub use remove::handle_remove;
 pub use statistics::handle_statistics;
+pub use publish::handle_publish;
+pub use language::handle_language;
+pub use admin::handle_admin;
+pub use complex_mode::handle_complex_mode;
+pub use convert::handle_convert;
+pub use convert_base::handle_convert_base;
+pub use decompose::handle_decompose;
+pub use graph_compare::handle_graph_compare;
+pub use verify_deterministic::handle_verify_deterministic;
+pub use format::handle_format;
+pub use select_expr::{handle_maxexpr, handle_minexpr};
+pub use random_expr::handle_random_expr;
+pub use validate_identifier::handle_validate_identifier;
+pub use set_limit::handle_set_limit;
+pub use profile::handle_profile;
 pub use help::handle_help;
 pub use help::handle_help_component_interaction; 
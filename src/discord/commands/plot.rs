@@ -0,0 +1,144 @@
+// Sampling and text rendering of functions in `x`
+
+use crate::core::{evaluate_batch, InterpreterError, Parser, SymbolTable, Tokenizer};
+
+/// Markers for the series of a plot, in legend order.
+const MARKERS: [char; 4] = ['*', 'o', '@', '%'];
+
+/// Marker for cells where several series meet.
+const OVERLAP_MARKER: char = '#';
+
+/// A sampled expression with the label shown in the legend.
+///
+/// Samples where the expression is undefined (errors, NaN or infinities)
+/// are `None`, so every series can have its own gaps.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Series {
+    pub label: String,
+    pub points: Vec<(f32, Option<f32>)>,
+}
+
+impl Series {
+    /// Checks whether the series has no defined sample at all.
+    pub fn is_undefined(&self) -> bool {
+        self.points.iter().all(|(_, y)| y.is_none())
+    }
+}
+
+/// Samples an expression in `x` at `count` evenly spaced points of `[start, end]`.
+///
+/// The expression is parsed once and evaluated with `evaluate_batch`. The
+/// user's variables and functions are visible to it. Parse errors are
+/// returned, while errors at a single point only leave a gap.
+pub fn sample_expression(
+    expression: &str,
+    variables: &SymbolTable<f32>,
+    start: f32,
+    end: f32,
+    count: usize,
+) -> Result<Vec<(f32, Option<f32>)>, InterpreterError> {
+    let expression = Parser::new(Tokenizer::from_input(expression)).parse_expression()?;
+    let step = if count > 1 { (end - start) / (count - 1) as f32 } else { 0.0 };
+    let xs: Vec<f32> = (0..count).map(|index| start + step * index as f32).collect();
+
+    let points = evaluate_batch(&expression, "x", &xs, variables)
+        .into_iter()
+        .zip(&xs)
+        .map(|(y, &x)| (x, y.ok().filter(|y| y.is_finite())))
+        .collect();
+
+    Ok(points)
+}
+
+/// Samples several expressions over the same range, labelled by their source.
+pub fn sample_series(
+    expressions: &[&str],
+    variables: &SymbolTable<f32>,
+    start: f32,
+    end: f32,
+    count: usize,
+) -> Result<Vec<Series>, InterpreterError> {
+    expressions
+        .iter()
+        .map(|expression| {
+            Ok(Series {
+                label: expression.to_string(),
+                points: sample_expression(expression, variables, start, end, count)?,
+            })
+        })
+        .collect()
+}
+
+/// Finds the smallest and largest defined value across all series.
+pub fn value_range(series: &[Series]) -> Option<(f32, f32)> {
+    series
+        .iter()
+        .flat_map(|series| series.points.iter().filter_map(|(_, y)| *y))
+        .fold(None, |range, y| match range {
+            None => Some((y, y)),
+            Some((low, high)) => Some((low.min(y), high.max(y))),
+        })
+}
+
+/// Renders series sampled over the same x values as a text chart with a legend.
+///
+/// Each series gets its own marker, one column per sample. Returns `None`
+/// when no series has a defined value to plot.
+pub fn render_plot(series: &[Series], height: usize) -> Option<String> {
+    let (mut low, mut high) = value_range(series)?;
+    if low == high {
+        // Give flat lines some room
+        low -= 1.0;
+        high += 1.0;
+    }
+
+    let width = series.iter().map(|series| series.points.len()).max().unwrap_or(0);
+    let height = height.max(2);
+    let row_of = |y: f32| ((high - y) / (high - low) * (height - 1) as f32).round() as usize;
+    let mut grid = vec![vec![' '; width]; height];
+
+    // Axes where zero is within range
+    if (low..=high).contains(&0.0) {
+        grid[row_of(0.0)].fill('-');
+    }
+    if let Some(points) = series.first().map(|series| &series.points) {
+        if let Some(column) = points.iter().position(|(x, _)| *x >= 0.0) {
+            if points[0].0 <= 0.0 {
+                for row in grid.iter_mut() {
+                    row[column] = if row[column] == '-' { '+' } else { '|' };
+                }
+            }
+        }
+    }
+
+    // Plot every series with its marker, marking shared cells
+    let mut owners = vec![vec![None; width]; height];
+    for (index, series) in series.iter().enumerate() {
+        let marker = MARKERS[index % MARKERS.len()];
+        for (column, (_, y)) in series.points.iter().enumerate() {
+            let Some(y) = y else { continue };
+            let row = row_of(*y);
+            grid[row][column] = match owners[row][column] {
+                Some(owner) if owner != index => OVERLAP_MARKER,
+                _ => marker,
+            };
+            owners[row][column] = Some(index);
+        }
+    }
+
+    let mut output = format!("{:>10.3} ┐\n", high);
+    for row in grid {
+        output.push_str("           │");
+        output.extend(row);
+        output.push('\n');
+    }
+    output.push_str(&format!("{:>10.3} ┘\n\n", low));
+
+    // Legend
+    for (index, series) in series.iter().enumerate() {
+        let note = if series.is_undefined() { " (undefined on this range)" } else { "" };
+        output.push_str(&format!("{} {}{}\n", MARKERS[index % MARKERS.len()], series.label, note));
+    }
+
+    Some(output)
+}
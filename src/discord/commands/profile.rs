@@ -0,0 +1,68 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::discord::UserSession;
+use crate::utils;
+use crate::utils::TimeFormatOptions;
+use crate::discord::theme::{Theme, Tone};
+
+/// How many favorite functions the profile lists.
+const FAVORITE_FUNCTION_LIMIT: usize = 5;
+
+/// Handles the `/profile` slash command showing the user's session statistics.
+pub fn handle_profile(
+    session: &UserSession,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Your Profile")
+        .field("Session Age", format_session_age(session), true)
+        .field("Evaluations", format!("`{}`", session.evaluations), true)
+        .field("Errors", format_error_rate(session), true)
+        .field("Favorite Functions", format_favorite_functions(session), false)
+        .colour(theme.colour(Tone::Info));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
+
+/// Formats how long ago the session was created.
+pub fn format_session_age(session: &UserSession) -> String {
+    let options = TimeFormatOptions {
+        max_units: 2,
+        ..Default::default()
+    };
+
+    utils::format_uptime(session.created_at.elapsed().as_secs(), Some(options))
+}
+
+/// Formats the error count with its share of all evaluations.
+pub fn format_error_rate(session: &UserSession) -> String {
+    if session.evaluations == 0 {
+        return "`0`".to_string();
+    }
+
+    let percentage = session.errors as f64 / session.evaluations as f64 * 100.0;
+    format!("`{}` ({:.1}%)", session.errors, percentage)
+}
+
+/// Formats the most used built-in functions as a ranked list.
+pub fn format_favorite_functions(session: &UserSession) -> String {
+    let favorites = session.favorite_functions(FAVORITE_FUNCTION_LIMIT);
+    if favorites.is_empty() {
+        return "_No built-in functions used yet._".to_string();
+    }
+
+    favorites.iter()
+        .enumerate()
+        .map(|(rank, (name, count))| format!(
+            "{}. `{}` - {} {}",
+            rank + 1,
+            name,
+            count,
+            if *count == 1 { "call" } else { "calls" }
+        ))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
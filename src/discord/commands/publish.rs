@@ -0,0 +1,81 @@
+use serenity::all::*;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+
+use crate::core::SnippetRegistry;
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/publish` slash command for sharing reusable definitions.
+///
+/// Stores a program of `fn`/`proc`/`const` definitions under a name so that
+/// anyone can pull it into their session with `import "name"`.
+pub fn handle_publish(
+    interaction: &CommandInteraction,
+    snippets: &mut SnippetRegistry,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    // Extract the snippet name and code
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("")
+        .trim();
+
+    let name = option("name");
+    let code = option("code");
+
+    if name.is_empty() || code.is_empty() {
+        return error_response(&i18n::translate(locale, "message.missing_publish_input"), None, locale);
+    }
+
+    // Validate and store the snippet
+    let replaced = match snippets.publish(name.to_string(), code.to_string(), interaction.user.id.get()) {
+        Ok(replaced) => replaced,
+        Err(error) => {
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_response(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
+    let title = if replaced.is_some() {
+        "Snippet Updated"
+    } else {
+        "Snippet Published"
+    };
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title(title)
+        .description(format_publish_description(name, code))
+        .colour(theme.colour(Tone::Success))
+        .footer(CreateEmbedFooter::new(format!(
+            "{} snippets published!",
+            snippets.len()
+        )));
+
+    // The code and name are echoed, so nothing in them may ping anyone
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .allowed_mentions(CreateAllowedMentions::new())
+}
+
+/// Formats the confirmation for a published snippet.
+///
+/// The code is echoed like `/execute` does, shortened to fit the embed, and
+/// the name is escaped so it shows as typed.
+pub fn format_publish_description(name: &str, code: &str) -> String {
+    let echo = utils::truncate_for_discord(&utils::sanitize_for_codeblock(code), utils::CODE_ECHO_LIMIT);
+    let description = format!(
+        "**Code:**\n```rs\n{}\n```\n\
+        Use it with import \"{}\" in `/execute`.",
+        echo,
+        utils::escape_markdown(name)
+    );
+    utils::truncate_for_discord(&description, utils::EMBED_DESCRIPTION_LIMIT)
+}
@@ -0,0 +1,43 @@
+use serenity::all::*;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+
+use crate::core::{generate_expression_with, DEFAULT_OPERATORS};
+use crate::discord::theme::{Theme, Tone};
+
+/// Levels of operators used when no depth option is given.
+const DEFAULT_DEPTH: usize = 2;
+
+/// Handles the `/random-expr` slash command for practice problems.
+///
+/// Posts a random arithmetic problem with its answer hidden behind a spoiler.
+/// The depth and the allowed operators can be chosen.
+pub fn handle_random_expr(
+    interaction: &CommandInteraction,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let depth = option("depth")
+        .and_then(|value| value.as_i64())
+        .map_or(DEFAULT_DEPTH, |depth| depth.clamp(1, 4) as usize);
+    let operators = option("operators")
+        .and_then(|value| value.as_str())
+        .map_or_else(|| DEFAULT_OPERATORS.to_vec(), |text| text.chars().collect());
+
+    let (problem, answer) = generate_expression_with(depth, &operators, &mut rand::thread_rng());
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Practice Problem")
+        .description(format!("```rs\n{}\n```\n**Answer:** ||{}||", problem, answer))
+        .colour(theme.colour(Tone::Info))
+        .footer(CreateEmbedFooter::new(format!("Depth: {}", depth)));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
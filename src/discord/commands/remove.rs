@@ -0,0 +1,53 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::InterpreterError;
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/remove` slash command to delete a single definition.
+///
+/// Removes the variable, function or procedure with the given name from the
+/// user's session, leaving everything else in place. Constants stay defined.
+pub fn handle_remove(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let name = interaction
+        .data
+        .options
+        .first()
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("")
+        .trim();
+
+    // Functions and procedures never share a name with a variable in the session scope
+    let variables = &mut session.variables;
+    let (kind, result) = if variables.functions.contains_key(name) {
+        ("Function", variables.remove_function(name))
+    } else if variables.procedures.contains_key(name) {
+        ("Procedure", variables.remove_procedure(name))
+    } else {
+        ("Variable", variables.remove_variable(name))
+    };
+
+    if let Err(error) = result {
+        let error = InterpreterError::from(error);
+        let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+        return error_response(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+    }
+
+    // Create response embed
+    let embed = CreateEmbed::new()
+        .title("Definition Removed")
+        .description(format!("{} `{}` has been removed.", kind, name))
+        .colour(theme.colour(Tone::Warning));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -0,0 +1,120 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::{evaluate, reading_shared_constants, with_limits, InterpreterError, LimitOverrides, SharedConstants};
+use crate::discord::error_handler::{error_edit, ErrorCategory};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils::{self, Extreme, Pick};
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/maxexpr` slash command, reporting which of two expressions is larger.
+pub fn handle_maxexpr(
+    interaction: &CommandInteraction,
+    session: &UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    select(interaction, session, constants, limits, Extreme::Max, theme)
+}
+
+/// Handles the `/minexpr` slash command, reporting which of two expressions is smaller.
+pub fn handle_minexpr(
+    interaction: &CommandInteraction,
+    session: &UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    select(interaction, session, constants, limits, Extreme::Min, theme)
+}
+
+/// Evaluates both expressions against the session and reports the winner
+/// and by how much it won.
+///
+/// An expression that fails loses by default, with its error shown next to
+/// it. Only when both fail is the command answered with an error. Both run
+/// with the server's `limits`, see `/set-limit`.
+fn select(
+    interaction: &CommandInteraction,
+    session: &UserSession,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    extreme: Extreme,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .and_then(|opt| opt.value.as_str())
+        .map(utils::normalize_input)
+        .unwrap_or_default();
+
+    let (first, second) = (option("first"), option("second"));
+    let (first, second) = (first.trim(), second.trim());
+
+    if first.is_empty() || second.is_empty() {
+        return error_edit(&i18n::translate(locale, "message.missing_expression"), None, locale);
+    }
+
+    let (first_result, second_result) = reading_shared_constants(constants, || with_limits(limits, || {
+        (evaluate(first, &session.variables), evaluate(second, &session.variables))
+    }));
+
+    let selection = utils::select_expression(first_result.as_ref().ok().copied(), second_result.as_ref().ok().copied(), extreme);
+    let Some(selection) = selection else {
+        // Neither has a value, so the first error is the one to fix
+        let (message, category) = match first_result.as_ref().err().or(second_result.as_ref().err()) {
+            Some(error) => (i18n::localize_error(locale, error), ErrorCategory::from_error(error)),
+            None => ("Neither expression has a value.".to_string(), ErrorCategory::Runtime),
+        };
+        let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&message));
+        return error_edit(&error_message, Some(category), locale);
+    };
+
+    let describe = |expression: &str, result: &Result<f32, InterpreterError>| {
+        let outcome = match result {
+            Ok(value) => value.to_string(),
+            Err(error) => format!("error: {}", i18n::localize_error(locale, error)),
+        };
+        format!("```rs\n{}\n= {}\n```", utils::sanitize_for_codeblock(expression), utils::sanitize_for_codeblock(&outcome))
+    };
+
+    let (title, comparison) = match extreme {
+        Extreme::Max => ("Larger Expression", "larger"),
+        Extreme::Min => ("Smaller Expression", "smaller"),
+    };
+    let verdict = match (selection.pick, selection.margin) {
+        (Pick::Tie, _) => format!("Both expressions equal `{}`.", selection.value),
+        (pick, Some(margin)) => format!("**{}** is {} by `{}`.", side_name(pick), comparison, margin),
+        (pick, None) => format!(
+            "**{}** wins by default with `{}`: the other expression has no value.",
+            side_name(pick),
+            selection.value
+        ),
+    };
+    let tone = if selection.margin.is_some() { Tone::Success } else { Tone::Warning };
+
+    let embed = CreateEmbed::new()
+        .title(title)
+        .description(verdict)
+        .field("First", describe(first, &first_result), false)
+        .field("Second", describe(second, &second_result), false)
+        .colour(theme.colour(tone));
+
+    EditInteractionResponse::new()
+        .embed(embed)
+}
+
+fn side_name(pick: Pick) -> &'static str {
+    match pick {
+        Pick::First => "First",
+        Pick::Second => "Second",
+        Pick::Tie => "Neither",
+    }
+}
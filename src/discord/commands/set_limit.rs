@@ -0,0 +1,104 @@
+use log::warn;
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::Limit;
+use crate::discord::SharedState;
+use crate::discord::theme::Tone;
+use crate::i18n;
+
+/// Reads the `limit` option of `/set-limit`, `None` for limits that cannot be set.
+pub fn parse_limit(name: &str) -> Option<Limit> {
+    match name {
+        "loop_iterations" => Some(Limit::LoopIterations),
+        "nesting_depth" => Some(Limit::NestingDepth),
+        "call_depth" => Some(Limit::CallDepth),
+        "time_limit" => Some(Limit::TimeLimit),
+        _ => None,
+    }
+}
+
+/// Handles the `/set-limit` slash command.
+///
+/// Members who can manage a server may make its execution limits stricter or
+/// looser, within [`Limit::adjustable_range`], and leaving out the value
+/// brings a limit back to its default. Everyone else gets an ephemeral
+/// refusal. The limits apply to `/execute` and `/evaluate` in that server.
+pub fn handle_set_limit(
+    interaction: &CommandInteraction,
+    state: &mut SharedState,
+    locale: &str,
+) -> CreateInteractionResponseMessage {
+    let theme = state.theme_for(interaction.guild_id.map(|id| id.get()));
+    let Some(guild_id) = interaction.guild_id.map(|id| id.get()) else {
+        return respond(i18n::translate(locale, "message.limit_guild_only"), theme.colour(Tone::Error));
+    };
+
+    // A server's limits are up to the people who manage it
+    let manages_guild = interaction.member.as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_guild());
+    if !manages_guild {
+        warn!("User {} tried to use /set-limit without permission", interaction.user.id);
+        return respond(i18n::translate(locale, "message.limit_refused"), theme.colour(Tone::Error));
+    }
+
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+    let Some(limit) = option("limit").and_then(|value| value.as_str()).and_then(parse_limit) else {
+        let limits = Limit::ALL
+            .iter()
+            .filter(|limit| limit.adjustable_range().is_some())
+            .map(|limit| limit.name())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let message = i18n::translate_with(locale, "message.limit_unknown", &[("limits", limits)]);
+        return respond(message, theme.colour(Tone::Error));
+    };
+    // Negative values are out of range like any other
+    let value = option("value")
+        .and_then(|value| value.as_i64())
+        .map(|value| usize::try_from(value).unwrap_or(0));
+
+    let mut limits = state.limits_for(Some(guild_id));
+    let (message, tone) = match limits.set(limit, value) {
+        Ok(()) => {
+            if limits.is_empty() {
+                state.guild_limits.remove(&guild_id);
+            } else {
+                state.guild_limits.insert(guild_id, limits);
+            }
+            let (key, value) = match value {
+                Some(value) => ("message.limit_set", value),
+                None => ("message.limit_reset", limit.value()),
+            };
+            let message = i18n::translate_with(locale, key, &[("limit", limit.name().to_string()), ("value", value.to_string())]);
+            (message, Tone::Info)
+        }
+        Err(range) => {
+            let message = i18n::translate_with(locale, "message.limit_out_of_range", &[
+                ("limit", limit.name().to_string()),
+                ("min", range.start().to_string()),
+                ("max", range.end().to_string()),
+            ]);
+            (message, Tone::Error)
+        }
+    };
+
+    respond(message, theme.colour(tone))
+}
+
+/// Builds an ephemeral reply, since limits are a matter for the server's managers.
+fn respond(message: String, colour: Colour) -> CreateInteractionResponseMessage {
+    let embed = CreateEmbed::new()
+        .description(message)
+        .colour(colour);
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+        .ephemeral(true)
+}
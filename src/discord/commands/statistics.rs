@@ -8,21 +8,25 @@ use sysinfo::{
     CpuExt
 };
 
-use crate::discord::ShardManagerContainer;
+use crate::core::{InterpreterMetrics, Limit};
+use crate::discord::{InvocationScope, ShardManagerContainer};
+use crate::discord::retry::{defer_with_retry, edit_with_retry};
 use crate::utils;
 use crate::utils::{TimeFormatOptions, TemperatureOptions, ProgressBarOptions};
+use crate::discord::theme::{Theme, Tone};
 
 /// Enhanced statistics command with comprehensive metrics and bot statistics.
+///
+/// Collecting system data takes a while, so this runs after the bot's state
+/// is unlocked, on a copy of the interpreter `metrics`.
 pub async fn handle_statistics(
     context: &Context,
     interaction: &CommandInteraction,
+    metrics: &InterpreterMetrics,
+    theme: Theme,
 ) {
     // Acknowledge interaction immediately to prevent timeouts during data collection
-    let thinking_response = CreateInteractionResponse::Defer(
-        CreateInteractionResponseMessage::new().ephemeral(false)
-    );
-    
-    if let Err(error) = interaction.create_response(&context.http, thinking_response).await {
+    if let Err(error) = defer_with_retry(context, interaction, false).await {
         error!("Failed to create deferring response: {:?}", error);
         return;
     }
@@ -40,12 +44,12 @@ pub async fn handle_statistics(
     // Look up the single shard's runner info (shard ID 0)
     let runners = &shard_manager_lock.runners;
     let runners_guard = runners.lock().await;
-    let runner_info = runners_guard
+    let latency = runners_guard
         .get(&ShardId(0))
-        .expect("Shard 0 runner not found");
+        .and_then(|runner_info| runner_info.latency);
 
     // Retrieve the WebSocket latency
-    let latency_display = match runner_info.latency {
+    let latency_display = match latency {
         Some(duration) => utils::format_duration(duration.as_millis(), None, None),
         None => "`N/A`".to_string(),
     };
@@ -141,10 +145,38 @@ pub async fn handle_statistics(
         system.host_name().unwrap_or_else(|| "Unknown".to_string())
     );
 
+    // Describe where the command was used, server details need the bot installed there
+    let scope = InvocationScope::from_interaction(interaction);
+    let invoked_from = match scope {
+        InvocationScope::Guild(id) => format!("Server `{}`", id),
+        InvocationScope::UserInstalledGuild(_) => "Server (user install)".to_string(),
+        InvocationScope::Private => "Direct messages".to_string(),
+    };
+
+    // Interpreter totals since startup, with the limits that stop executions
+    let hit_rate = match metrics.hit_rate() {
+        Some(rate) => format!("`{:.1}%` of `{}` cacheable calls", rate * 100.0, metrics.cache_hits() + metrics.cache_misses()),
+        None => "`N/A`".to_string(),
+    };
+    let limits = Limit::ALL
+        .iter()
+        .map(|limit| format!("{}: `{}`, stopped `{}` runs", limit.name(), limit.value(), metrics.stopped_by(*limit)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let interpreter_info = format!(
+        "Executions: `{}` in `{}`\n\
+         Cache hit rate: {}\n\
+         {}",
+        metrics.executions(),
+        utils::format_duration(metrics.total_time().as_millis(), None, None),
+        hit_rate,
+        limits
+    );
+
     // Format system information
     let embed = CreateEmbed::new()
         .title("Statistics")
-        .colour(Colour::DARK_GREEN)
+        .colour(theme.colour(Tone::Info))
         .field("Bot Status", format!(
             "Discord WebSocket latency: `{}`\n\
              Computer uptime: `{}`",
@@ -155,10 +187,7 @@ pub async fn handle_statistics(
             system.cpus().len(),
             system.physical_core_count().unwrap_or(0)
         ), true)
-        .field("CPU Usage", format!(
-            "{}",
-            cpu_bar
-        ), false)
+        .field("CPU Usage", cpu_bar.to_string(), false)
         .field("Memory Usage", memory_bar, false)
         .field("Processes", format!(
             "`{}` processes", 
@@ -167,12 +196,11 @@ pub async fn handle_statistics(
         .field("Temperature", utils::format_temperature(&mut system, Some(temp_options)), true)
         .field("Storage", disk_info, false)
         .field("Metrics Collection Time", format!("`{}`", collection_time), true)
+        .field("Invoked From", invoked_from, true)
+        .field("Interpreter", interpreter_info, false)
         .field("System Information", system_info, false);
 
-    // Fix the edit_response call with the correct type
-    if let Err(error) = interaction.edit_response(&context.http, 
-        EditInteractionResponse::new().embed(embed)
-    ).await {
+    if let Err(error) = edit_with_retry(context, interaction, EditInteractionResponse::new().embed(embed)).await {
         error!("Failed to send status response: {:?}", error);
     }
 }
@@ -0,0 +1,53 @@
+use serenity::all::*;
+use serenity::builder::CreateEmbed;
+
+use crate::core::{validate_identifier, EvalError, InterpreterError};
+use crate::discord::error_handler::{error_response, ErrorCategory};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/validate-identifier` slash command.
+///
+/// Checks whether a name can be given to a variable or constant, so that
+/// keywords and the names of built-in functions are caught before a script
+/// trips over them. A name the session already uses for a function or
+/// procedure is pointed out as well.
+pub fn handle_validate_identifier(
+    interaction: &CommandInteraction,
+    session: &UserSession,
+    locale: &str,
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let name = interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == "name")
+        .and_then(|opt| opt.value.as_str())
+        .unwrap_or("")
+        .trim();
+
+    if let Err(error) = validate_identifier(name) {
+        let error = InterpreterError::Eval(EvalError::from(error));
+        let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+        return error_response(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+    }
+
+    let (description, tone) = if session.variables.get_function(name).is_some() {
+        (format!("`{}` is a valid name, but it already names one of your functions.", name), Tone::Warning)
+    } else if session.variables.get_procedure(name).is_some() {
+        (format!("`{}` is a valid name, but it already names one of your procedures.", name), Tone::Warning)
+    } else {
+        (format!("`{}` can be used as a variable or constant name.", name), Tone::Success)
+    };
+
+    let embed = CreateEmbed::new()
+        .title("Identifier Check")
+        .description(description)
+        .colour(theme.colour(tone));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -1,53 +1,199 @@
-use log::error;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::SystemTime;
+
 use serenity::all::*;
 use serenity::builder::CreateEmbed;
 
+use crate::core::{BindingInfo, BindingKind, Statement, SymbolTable, Value};
 use crate::discord::UserSession;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// A group of entries listed by `/vars` under a heading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarsSection {
+    /// The heading, such as "Constants".
+    pub title: &'static str,
+    /// The name and formatted line of each entry, sorted by name.
+    pub entries: Vec<(String, String)>,
+}
+
+/// Formats a value the same way `/evaluate` shows its result.
+pub fn format_result(value: f32) -> String {
+    Value::Real(value).to_string()
+}
+
+/// Sorts the symbols of a table into constants, variables, functions and
+/// procedures, leaving out the groups that are empty.
+///
+/// Formulas are listed with the variables, showing their source and the
+/// value they have right now.
+pub fn group_symbols(table: &SymbolTable<f32>) -> Vec<VarsSection> {
+    let mut constants = Vec::new();
+    let mut variables = Vec::new();
+
+    for (name, value) in table.iter_sorted() {
+        if table.is_constant(name) {
+            constants.push((name.to_string(), format!("**const** {} = {}", utils::escape_markdown(name), format_result(*value))));
+        } else {
+            variables.push((name.to_string(), format!("**let** {} = {}", utils::escape_markdown(name), format_result(*value))));
+        }
+    }
+
+    for (name, formula) in table.formulas.iter() {
+        let value = formula
+            .evaluate(table)
+            .map_or_else(|_| "?".to_string(), format_result);
+        variables.push((name.clone(), format!(
+            "**let** {} := {} → {}",
+            utils::escape_markdown(name),
+            utils::escape_markdown(&formula.to_source()),
+            value
+        )));
+    }
+    variables.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    [
+        ("Constants", constants),
+        ("Variables", variables),
+        ("Functions", signatures("fn", &table.functions)),
+        ("Procedures", signatures("proc", &table.procedures)),
+    ]
+    .into_iter()
+    .filter(|(_, entries)| !entries.is_empty())
+    .map(|(title, entries)| VarsSection { title, entries })
+    .collect()
+}
+
+/// Describes a single binding for `/vars name:<x>`, `None` if nothing has that name.
+///
+/// The first line is the binding as `/vars` lists it, with its value or
+/// signature, and the second its kind. A formula is shown with its source
+/// and the value it has right now.
+pub fn describe_binding(table: &SymbolTable<f32>, name: &str) -> Option<String> {
+    let binding = table.describe(name)?;
+    let escaped = utils::escape_markdown(name);
+
+    let line = match &binding {
+        BindingInfo::Variable(value) => format!("**let** {} = {}", escaped, format_result(*value)),
+        BindingInfo::Constant(value) => format!("**const** {} = {}", escaped, format_result(*value)),
+        BindingInfo::Formula(source) => {
+            let value = table
+                .get_formula(name)
+                .and_then(|formula| formula.evaluate(table).ok())
+                .map_or_else(|| "?".to_string(), format_result);
+            format!("**let** {} := {} → {}", escaped, utils::escape_markdown(source), value)
+        }
+        BindingInfo::Function { params, memo } => format!(
+            "**{}fn** {}({})",
+            if *memo { "memo " } else { "" },
+            escaped,
+            utils::escape_markdown(&params.join(", "))
+        ),
+        BindingInfo::Procedure { params } => {
+            format!("**proc** {}({})", escaped, utils::escape_markdown(&params.join(", ")))
+        }
+    };
+
+    let kind = match binding.kind() {
+        BindingKind::Formula => "formula, re-evaluated on every read".to_string(),
+        kind => kind.name().to_string(),
+    };
+    Some(format!("{}\n_{}_", line, kind))
+}
+
+/// Lists functions or procedures by their signature, sorted by name.
+fn signatures(keyword: &str, callables: &HashMap<String, (Vec<String>, Statement)>) -> Vec<(String, String)> {
+    let mut entries: Vec<(String, String)> = callables
+        .iter()
+        .map(|(name, (params, _))| (name.clone(), format!(
+            "**{}** {}({})",
+            keyword,
+            utils::escape_markdown(name),
+            utils::escape_markdown(&params.join(", "))
+        )))
+        .collect();
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+    entries
+}
 
 /// Handles the `/vars` slash command to display current session variables.
-/// 
-/// Shows a table of defined variables with their values and special styling
-/// for neat presentation.
-pub async fn handle_vars(
-    context: &Context,
+///
+/// Shows the session's constants, variables, functions and procedures under
+/// separate headings, with when each value was last set. Entries are sorted
+/// by name or, with `sort:recent`, newest first within each heading. With
+/// `name`, only that binding is shown; see [`describe_binding`].
+pub fn handle_vars(
     interaction: &CommandInteraction,
     session: &UserSession,
-) {
-    // Format variables into a neat table
-    let mut vars_list = String::new();
-    let mut has_vars = false;
-    
-    // Use IntoIterator to iterate through the SymbolTable
-    for (name, value) in session.variables.clone() {
-        has_vars = true;
-        let is_const = session.variables.is_constant(&name);
-        let var_type = if is_const { "const" } else { "let" };
-        vars_list.push_str(&format!("**{}** {} = {}\n", var_type, name, value));
+    theme: Theme,
+) -> CreateInteractionResponseMessage {
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .and_then(|opt| opt.value.as_str());
+    let recent_first = option("sort") == Some("recent");
+
+    if let Some(name) = option("name") {
+        let description = match describe_binding(&session.variables, name) {
+            Some(description) => match session.variables.modified_at(name) {
+                Some(time) => {
+                    let elapsed = SystemTime::now().duration_since(time).unwrap_or_default();
+                    format!("{} · _updated {}_", description, utils::format_relative_time(elapsed))
+                }
+                None => description,
+            },
+            None => format!("_Nothing is named `{}`. Use /vars to list your variables._", name.replace('`', "")),
+        };
+        return vars_response(description, theme);
     }
-    
-    let vars = if !has_vars {
+
+    let mut sections = group_symbols(&session.variables);
+
+    // Newest first with entries of unknown age last
+    if recent_first {
+        for section in &mut sections {
+            section.entries.sort_by_key(|(name, _)| Reverse(session.variables.modified_at(name)));
+        }
+    }
+
+    let vars = if sections.is_empty() {
         "_No variables set. Use expressions with '=' to define variables._".to_string()
     } else {
-        vars_list
+        sections
+            .iter()
+            .map(|section| {
+                let lines: String = section
+                    .entries
+                    .iter()
+                    .map(|(name, line)| match session.variables.modified_at(name) {
+                        Some(time) => {
+                            let elapsed = SystemTime::now().duration_since(time).unwrap_or_default();
+                            format!("{} · _updated {}_\n", line, utils::format_relative_time(elapsed))
+                        }
+                        None => format!("{}\n", line),
+                    })
+                    .collect();
+                format!("__{}__\n{}", section.title, lines)
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
     };
 
+    vars_response(vars, theme)
+}
+
+/// Builds the `/vars` embed with the given description.
+fn vars_response(description: String, theme: Theme) -> CreateInteractionResponseMessage {
     // Create response embed with formatting
     let embed = CreateEmbed::new()
         .title("Your Variables")
-        .description(vars)
-        .colour(Colour::GOLD);
-
-    // Send the formatted response
-    if let Err(error) = interaction
-        .create_response(
-            &context.http,
-            CreateInteractionResponse::Message(
-                CreateInteractionResponseMessage::new()
-                    .embed(embed)
-            ),
-        )
-        .await
-    {
-        error!("Failed to send vars command response: {:?}", error);
-    }
-} 
+        .description(utils::truncate_for_discord(&description, utils::EMBED_DESCRIPTION_LIMIT))
+        .colour(theme.colour(Tone::Info));
+
+    CreateInteractionResponseMessage::new()
+        .embed(embed)
+}
@@ -0,0 +1,35 @@
+use serenity::all::*;
+
+use crate::discord::UserSession;
+use crate::utils;
+
+/// Handles the `/vars-export` slash command to download session variables.
+///
+/// Sends variables, constants and formulas as a CSV attachment with one
+/// `name,value,kind` row each. Formulas are exported with their current value.
+pub fn handle_vars_export(session: &UserSession) -> CreateInteractionResponseMessage {
+    let mut rows: Vec<(&str, String, &str)> = session
+        .variables
+        .iter_sorted()
+        .map(|(name, value)| {
+            let kind = if session.variables.is_constant(name) { "const" } else { "let" };
+            (name, value.to_string(), kind)
+        })
+        .collect();
+
+    for (name, formula) in session.variables.formulas.iter() {
+        let value = formula
+            .evaluate(&session.variables)
+            .map_or_else(|_| String::new(), |value| value.to_string());
+        rows.push((name, value, "formula"));
+    }
+    rows.sort_unstable_by(|a, b| a.0.cmp(b.0));
+
+    let entries: Vec<(&str, &str, &str)> = rows.iter().map(|(name, value, kind)| (*name, value.as_str(), *kind)).collect();
+    let csv = utils::to_csv(&entries);
+
+    CreateInteractionResponseMessage::new()
+        .content(format!("Exported {} variables.", entries.len()))
+        .add_file(CreateAttachment::bytes(csv.into_bytes(), "variables.csv"))
+        .ephemeral(true)
+}
@@ -0,0 +1,72 @@
+use serenity::all::*;
+use serenity::builder::{CreateEmbed, CreateEmbedFooter};
+
+use crate::core::{reading_shared_constants, verify_deterministic, with_limits, LimitOverrides, SharedConstants, SnippetRegistry};
+use crate::discord::error_handler::{error_edit, ErrorCategory};
+use crate::discord::UserSession;
+use crate::i18n;
+use crate::utils;
+use crate::discord::theme::{Theme, Tone};
+
+/// Handles the `/verify-deterministic` slash command.
+///
+/// Runs the code twice with the same seed for `rand()`, each time on a copy
+/// of the session variables, and reports whether both runs agree. The
+/// session itself is left unchanged. Without a seed option a random seed is
+/// picked and shown, so the check can be repeated. Both runs have the
+/// server's `limits`, see `/set-limit`, and can read but not publish its
+/// global constants.
+pub fn handle_verify_deterministic(
+    interaction: &CommandInteraction,
+    session: &mut UserSession,
+    snippets: &SnippetRegistry,
+    constants: &SharedConstants,
+    limits: LimitOverrides,
+    theme: Theme,
+) -> EditInteractionResponse {
+    let locale = session.locale_for(&interaction.locale);
+
+    let option = |name: &str| interaction
+        .data
+        .options
+        .iter()
+        .find(|opt| opt.name == name)
+        .map(|opt| &opt.value);
+
+    let code = utils::normalize_input(option("code").and_then(|value| value.as_str()).unwrap_or(""));
+    let code = code.trim();
+    let seed = option("seed")
+        .and_then(|value| value.as_i64())
+        .map_or_else(|| u64::from(rand::random::<u32>()), |seed| seed as u64);
+
+    if code.is_empty() {
+        return error_edit(&i18n::translate(locale, "message.missing_code"), None, locale);
+    }
+
+    let check = match reading_shared_constants(constants, || with_limits(limits, || verify_deterministic(code, &session.variables, snippets, seed))) {
+        Ok(check) => check,
+        Err(error) => {
+            let error_message = format!("```fix\n{}\n```", utils::sanitize_for_codeblock(&i18n::localize_error(locale, &error)));
+            return error_edit(&error_message, Some(ErrorCategory::from_error(&error)), locale);
+        }
+    };
+
+    let show = |result: Option<f32>| result.map_or_else(|| "(no value)".to_string(), |value| value.to_string());
+    let (verdict, colour) = if check.deterministic {
+        ("✅ Both runs gave the same result and variables.", theme.colour(Tone::Success))
+    } else {
+        ("❌ The runs differ, so this program is not deterministic for this seed.", theme.colour(Tone::Warning))
+    };
+
+    let echo = utils::truncate_for_discord(&utils::sanitize_for_codeblock(code), utils::CODE_ECHO_LIMIT);
+    let embed = CreateEmbed::new()
+        .title("Determinism Check")
+        .description(format!("**Code:**\n```rs\n{}\n```\n{}", echo, verdict))
+        .field("First run", format!("```rs\n{}\n```", show(check.first)), true)
+        .field("Second run", format!("```rs\n{}\n```", show(check.second)), true)
+        .colour(colour)
+        .footer(CreateEmbedFooter::new(format!("Seed: {}", check.seed)));
+
+    EditInteractionResponse::new()
+        .embed(embed)
+}
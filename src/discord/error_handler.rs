@@ -2,6 +2,11 @@ use log::warn;
 use serenity::all::*;
 use serenity::builder::{CreateEmbed};
 
+use crate::core::{InterpreterError, EvalError};
+use crate::discord::retry::respond_with_retry;
+use crate::i18n;
+use crate::utils;
+
 /// Enum representing different error categories for better organization and clearer user feedback.
 #[derive(Debug, Clone, Copy)]
 pub enum ErrorCategory {
@@ -22,16 +27,21 @@ pub enum ErrorCategory {
 }
 
 impl ErrorCategory {
-    /// Get the title string for this error category.
-    pub fn title(&self) -> &'static str {
+    /// Get the translation key for this error category.
+    fn key(&self) -> &'static str {
         match self {
-            ErrorCategory::Syntax => "Syntax Error",
-            ErrorCategory::Runtime => "Calculation Error",
-            ErrorCategory::Variable => "Variable Error",
-            ErrorCategory::System => "System Error",
-            ErrorCategory::General => "Error",
+            ErrorCategory::Syntax => "syntax",
+            ErrorCategory::Runtime => "runtime",
+            ErrorCategory::Variable => "variable",
+            ErrorCategory::System => "system",
+            ErrorCategory::General => "general",
         }
     }
+
+    /// Get the title string for this error category.
+    pub fn title(&self, locale: &str) -> String {
+        i18n::translate(locale, &format!("error.category.{}.title", self.key()))
+    }
     
     /// Get the color for this error category.
     pub fn color(&self) -> Colour {
@@ -45,13 +55,18 @@ impl ErrorCategory {
     }
     
     /// Get a suggestion based on the error category.
-    pub fn suggestion(&self) -> &'static str {
-        match self {
-            ErrorCategory::Syntax => "Check your expression for typos or missing parentheses.",
-            ErrorCategory::Runtime => "Verify your calculation doesn't involve division by zero or other invalid operations.",
-            ErrorCategory::Variable => "Make sure variables are declared before use and constants aren't being modified.",
-            ErrorCategory::System => "Try again later or contact the bot administrator.",
-            ErrorCategory::General => "Try simplifying your input or check the help command.",
+    pub fn suggestion(&self, locale: &str) -> String {
+        i18n::translate(locale, &format!("error.category.{}.suggestion", self.key()))
+    }
+    
+    /// Determine the error category from the kind of interpreter error.
+    pub fn from_error(error: &InterpreterError) -> Self {
+        match error {
+            InterpreterError::Parse(_) => ErrorCategory::Syntax,
+            InterpreterError::Eval(EvalError::MathError(_)) => ErrorCategory::Runtime,
+            InterpreterError::Eval(EvalError::SymbolError(_)) => ErrorCategory::Variable,
+            InterpreterError::Eval(EvalError::ControlFlowError(_)) => ErrorCategory::General,
+            InterpreterError::Exec(_) => ErrorCategory::Runtime,
         }
     }
     
@@ -69,36 +84,59 @@ impl ErrorCategory {
     }
 }
 
-/// Enhanced error handling utility that provides rich, categorized error information.
+/// Builds a rich, categorized error message.
 /// 
-/// Displays errors with appropriate formatting, color coding, and helpful suggestions
-/// based on error category. Also logs detailed information for debugging.
-pub async fn send_error(
-    context: &Context,
-    interaction: &CommandInteraction,
+/// Formats the error with appropriate color coding and a helpful suggestion
+/// based on its category. Also logs detailed information for debugging.
+pub fn error_response(
     message: &str,
     category: Option<ErrorCategory>,
-) {
+    locale: &str,
+) -> CreateInteractionResponseMessage {
+    // Error messages echo user input, so they never ping anyone
+    CreateInteractionResponseMessage::new()
+        .embed(error_embed(message, category, locale))
+        .allowed_mentions(CreateAllowedMentions::new())
+}
+
+/// Builds the same error message as [`error_response`], for a command whose
+/// answer was deferred.
+pub fn error_edit(
+    message: &str,
+    category: Option<ErrorCategory>,
+    locale: &str,
+) -> EditInteractionResponse {
+    EditInteractionResponse::new()
+        .embed(error_embed(message, category, locale))
+        .allowed_mentions(CreateAllowedMentions::new())
+}
+
+/// Creates the embed of an error message and logs the error.
+fn error_embed(message: &str, category: Option<ErrorCategory>, locale: &str) -> CreateEmbed {
     // Determine error category based on message content if not provided
     let category = category.unwrap_or_else(|| ErrorCategory::from_message(message));
     
     // Log the error with category for debugging
-    warn!("{} - {}", category.title(), message);
+    warn!("{} - {}", category.title(i18n::DEFAULT_LOCALE), message);
     
     // Create an enhanced embed with appropriate styling and suggestions
-    let embed = CreateEmbed::new()
-        .title(category.title())
-        .description(message)
-        .field("Suggestion", category.suggestion(), false)
-        .colour(category.color());
-    
-    // Attempt to send the response
-    if let Err(error) = interaction.create_response(
-        &context.http,
-        CreateInteractionResponse::Message(
-            CreateInteractionResponseMessage::new().embed(embed)
-        )
-    ).await {
+    CreateEmbed::new()
+        .title(category.title(locale))
+        .description(utils::truncate_for_discord(message, utils::EMBED_DESCRIPTION_LIMIT))
+        .field(i18n::translate(locale, "message.suggestion"), category.suggestion(locale), false)
+        .colour(category.color())
+}
+
+/// Enhanced error handling utility that sends an [`error_response`] as the answer to a command.
+pub async fn send_error(
+    context: &Context,
+    interaction: &CommandInteraction,
+    message: &str,
+    category: Option<ErrorCategory>,
+    locale: &str,
+) {
+    let response = error_response(message, category, locale);
+    if let Err(error) = respond_with_retry(context, interaction, response).await {
         log::error!("Failed to send error message: {}", error);
     }
-} 
\ No newline at end of file
+}
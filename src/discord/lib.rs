@@ -3,8 +3,11 @@
 pub mod commands;
 mod error_handler;
 mod models;
+pub mod retry;
+pub mod theme;
 pub mod bot_handler;
 
 // Re-export for easier access
-pub use models::{Bot, UserSession, SharedState, ShardManagerContainer, HelpEmbedsContainer, CommandMetadataContainer};
-pub use error_handler::send_error; 
\ No newline at end of file
+pub use models::{Bot, UserSession, GlobalConstants, HistoryEntry, HistoryKind, Replay, SharedState, ShardManagerContainer, HelpEmbedsContainer, CommandMetadataContainer, AdminConfig, AdminConfigContainer, InvocationScope};
+pub use error_handler::{send_error, error_response};
+pub use retry::{respond_with_retry, respond_to_component_with_retry, defer_with_retry, edit_with_retry, send_with_retry};
\ No newline at end of file
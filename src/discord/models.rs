@@ -1,12 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use serenity::all::*;
 use serenity::gateway::ShardManager;
 use serenity::prelude::*;
 use tokio::sync::Mutex;
 
-use crate::core::SymbolTable;
+use crate::core::{SymbolTable, ControlFlowError, EvalError, InterpreterError, InterpreterMetrics, Limit, LimitOverrides, SharedConstants, SnippetRegistry, Parser, Tokenizer, count_function_calls, execute_with_imports, find_builtin, with_limits};
+use crate::config::Config;
+use crate::discord::theme::Theme;
+use crate::i18n;
+use crate::utils;
 
 /// This implementation tells the TypeMap that `ShardManagerContainer` is the key, and its
 /// associated value is an `Arc<ShardManager>` object.
@@ -18,10 +23,12 @@ impl TypeMapKey for ShardManagerContainer {
 }
 
 /// Stores pre-created help embeds to avoid creating them on each command use.
+///
+/// Embeds are keyed by locale, then by help topic.
 pub struct HelpEmbedsContainer;
 
 impl TypeMapKey for HelpEmbedsContainer {
-    type Value = HashMap<String, CreateEmbed>;
+    type Value = HashMap<String, HashMap<String, CreateEmbed>>;
 }
 
 /// Stores metadata about available commands for help and documentation.
@@ -31,6 +38,83 @@ impl TypeMapKey for CommandMetadataContainer {
     type Value = HashMap<String, CommandMetadata>;
 }
 
+/// Stores who may use the owner-only `/admin` commands.
+pub struct AdminConfigContainer;
+
+impl TypeMapKey for AdminConfigContainer {
+    type Value = AdminConfig;
+}
+
+/// The application owner and the extra admins allowed to use `/admin`.
+#[derive(Clone, Debug, Default)]
+pub struct AdminConfig {
+    owner_id: Option<u64>,
+    admin_ids: HashSet<u64>,
+}
+
+impl AdminConfig {
+    /// Creates a configuration from the application owner and a list of admins.
+    pub fn new(owner_id: Option<u64>, admin_ids: impl IntoIterator<Item = u64>) -> Self {
+        Self {
+            owner_id,
+            admin_ids: admin_ids.into_iter().collect(),
+        }
+    }
+
+    /// Parses a comma or whitespace separated list of user ids.
+    ///
+    /// Entries that are not valid ids are skipped.
+    pub fn parse_admin_ids(value: &str) -> HashSet<u64> {
+        value
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter_map(|id| id.trim().parse().ok())
+            .collect()
+    }
+
+    /// Returns whether the user is the owner or one of the configured admins.
+    pub fn is_admin(&self, user_id: u64) -> bool {
+        self.owner_id == Some(user_id) || self.admin_ids.contains(&user_id)
+    }
+}
+
+/// Where a command was invoked from and whether the bot is installed there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvocationScope {
+    /// A server the bot is installed in.
+    Guild(u64),
+    /// A server where only the invoking user installed the app.
+    UserInstalledGuild(u64),
+    /// A DM with the bot or another private channel.
+    Private,
+}
+
+impl InvocationScope {
+    /// Determines the scope from the interaction's guild and whether the app
+    /// is installed to that guild.
+    pub fn detect(guild_id: Option<u64>, guild_installed: bool) -> Self {
+        match guild_id {
+            Some(id) if guild_installed => InvocationScope::Guild(id),
+            Some(id) => InvocationScope::UserInstalledGuild(id),
+            None => InvocationScope::Private,
+        }
+    }
+
+    /// Determines the scope of a command interaction.
+    pub fn from_interaction(interaction: &CommandInteraction) -> Self {
+        let guild_installed = interaction.authorizing_integration_owners.0.iter()
+            .any(|owner| matches!(owner, AuthorizingIntegrationOwner::GuildInstall(Some(_))));
+        Self::detect(interaction.guild_id.map(|id| id.get()), guild_installed)
+    }
+
+    /// Returns whether guild-scope features can be offered.
+    ///
+    /// They need the bot to be a member of the guild, so user installs and
+    /// private channels only get the user-scoped features.
+    pub fn offers_guild_features(&self) -> bool {
+        matches!(self, InvocationScope::Guild(_))
+    }
+}
+
 /// Metadata for a single command including its usage and examples.
 #[derive(Clone)]
 pub struct CommandMetadata {
@@ -41,19 +125,200 @@ pub struct CommandMetadata {
     pub callback_signature: String,
 }
 
-/// Holds each user's variables and input history.
-#[derive(Default)]
+/// A successful input and the result it showed, if any.
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub input: String,
+    pub result: Option<String>,
+    pub kind: HistoryKind,
+}
+
+/// How an input in the history was run.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HistoryKind {
+    /// Run with `/execute` or from a message, so its variables were kept.
+    Executed,
+    /// Run with `/evaluate` or its buttons, on a copy of the variables.
+    Evaluated,
+}
+
+/// The outcome of replaying a session's history into fresh variables.
+pub struct Replay {
+    /// The variables the history builds.
+    pub variables: SymbolTable<f32>,
+    /// How many inputs ran without an error.
+    pub succeeded: usize,
+    /// Inputs that failed this time, with their error messages.
+    pub failures: Vec<(String, String)>,
+}
+
+/// Holds each user's variables, input history, preferences and usage counters.
 pub struct UserSession {
     pub variables: SymbolTable<f32>,
-    pub history: Vec<String>,
+    pub history: Vec<HistoryEntry>,
+    /// The preferred locale, or `None` to follow the Discord client's locale.
+    pub locale: Option<String>,
+    /// When the session was created.
+    pub created_at: Instant,
+    /// How many inputs were evaluated or executed, including failed ones.
+    pub evaluations: u64,
+    /// How many inputs failed with an error.
+    pub errors: u64,
+    /// How often each built-in function was called.
+    pub builtin_usage: HashMap<String, u32>,
+    /// Whether `/evaluate` uses complex numbers by default.
+    pub complex_mode: bool,
+}
+
+impl Default for UserSession {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl UserSession {
     /// Creates a new user session with predefined mathematical constants.
     pub fn new() -> Self {
+        // /vars shows how long ago each variable was set
+        let mut variables = SymbolTable::new();
+        variables.track_modifications();
+
         Self {
-            variables: SymbolTable::new(),
+            variables,
             history: Vec::new(),
+            locale: None,
+            created_at: Instant::now(),
+            evaluations: 0,
+            errors: 0,
+            builtin_usage: HashMap::new(),
+            complex_mode: false,
+        }
+    }
+
+    /// Updates the usage counters after evaluating or executing some code.
+    ///
+    /// Built-in function calls are counted from the parsed code, so calls in
+    /// branches that did not run are counted too.
+    pub fn record_evaluation(&mut self, code: &str, succeeded: bool) {
+        self.evaluations += 1;
+        if !succeeded {
+            self.errors += 1;
+        }
+
+        let Ok(statements) = Parser::new(Tokenizer::from_input(code)).parse_statements() else {
+            return;
+        };
+        for statement in &statements {
+            for (name, count) in count_function_calls(statement) {
+                if find_builtin(&name).is_some() {
+                    *self.builtin_usage.entry(name).or_insert(0) += count;
+                }
+            }
+        }
+    }
+
+    /// Adds a successful input and its result to the history.
+    pub fn record_history(&mut self, input: &str, result: Option<String>, kind: HistoryKind) {
+        self.history.push(HistoryEntry { input: input.to_string(), result, kind });
+    }
+
+    /// Returns the inputs whose variables were kept, in order.
+    ///
+    /// Evaluated inputs ran on a copy of the variables, so running them again
+    /// as statements could change variables they never changed.
+    pub fn executed_history(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.history.iter().filter(|entry| entry.kind == HistoryKind::Executed)
+    }
+
+    /// Removes every variable, keeping the history so it can be replayed.
+    pub fn clear_variables(&mut self) {
+        self.variables = SymbolTable::new();
+        self.variables.track_modifications();
+    }
+
+    /// Runs every executed input in the history, in order, in fresh variables.
+    ///
+    /// This is what running the exported script does, so a failure means the
+    /// history does not reproduce the session it was recorded in. The
+    /// session itself is left as it is. Evaluated inputs are skipped, see
+    /// [`UserSession::executed_history`].
+    ///
+    /// All inputs share one time limit from `limits`, rather than each
+    /// getting its own, and inputs left once it runs out fail without running.
+    pub fn replay_history(&self, snippets: &SnippetRegistry, limits: LimitOverrides) -> Replay {
+        let mut variables = SymbolTable::new();
+        variables.track_modifications();
+
+        let budget_ms = limits.value(Limit::TimeLimit);
+        let budget = Duration::from_millis(budget_ms as u64);
+        let timed_out = || InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::TimedOut { limit_ms: budget_ms }));
+        let started = Instant::now();
+
+        let mut succeeded = 0;
+        let mut failures = Vec::new();
+        for entry in self.executed_history() {
+            let remaining = budget.saturating_sub(started.elapsed());
+            if remaining.is_zero() {
+                failures.push((entry.input.clone(), timed_out().to_string()));
+                continue;
+            }
+
+            // Each input may only use what the earlier ones left of the time
+            let entry_limits = LimitOverrides { time_limit: Some(remaining.as_millis() as usize), ..limits };
+            match with_limits(entry_limits, || execute_with_imports(&entry.input, &mut variables, snippets)) {
+                Ok(_) => succeeded += 1,
+                Err(error) if Limit::reached_by(&error) == Some(Limit::TimeLimit) => {
+                    failures.push((entry.input.clone(), timed_out().to_string()));
+                }
+                Err(error) => failures.push((entry.input.clone(), error.to_string())),
+            }
+        }
+
+        Replay { variables, succeeded, failures }
+    }
+
+    /// Returns the executed inputs as a script that reproduces the session when run.
+    pub fn history_script(&self) -> String {
+        let entries: Vec<(&str, Option<&str>)> = self
+            .executed_history()
+            .map(|entry| (entry.input.as_str(), entry.result.as_deref()))
+            .collect();
+        utils::transcript_to_script(&entries)
+    }
+
+    /// Returns the most used built-in functions, most used first.
+    pub fn favorite_functions(&self, limit: usize) -> Vec<(&str, u32)> {
+        let mut favorites: Vec<_> = self.builtin_usage.iter()
+            .map(|(name, count)| (name.as_str(), *count))
+            .collect();
+        favorites.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        favorites.truncate(limit);
+        favorites
+    }
+
+    /// Returns the locale to answer in, falling back to the interaction's locale.
+    pub fn locale_for(&self, interaction_locale: &str) -> &'static str {
+        i18n::resolve_locale(self.locale.as_deref().unwrap_or(interaction_locale))
+    }
+}
+
+/// Constants published with `const global`, one table per server.
+///
+/// Users share the table of the server they run code in. Outside servers
+/// each user has a table of their own.
+#[derive(Default)]
+pub struct GlobalConstants {
+    guilds: HashMap<u64, SharedConstants>,
+    users: HashMap<u64, SharedConstants>,
+}
+
+impl GlobalConstants {
+    /// Returns the table a user reads and publishes to in a server, or in
+    /// direct messages when `guild_id` is `None`.
+    pub fn table(&mut self, guild_id: Option<u64>, user_id: u64) -> &mut SharedConstants {
+        match guild_id {
+            Some(guild_id) => self.guilds.entry(guild_id).or_default(),
+            None => self.users.entry(user_id).or_default(),
         }
     }
 }
@@ -62,9 +327,58 @@ impl UserSession {
 #[derive(Default)]
 pub struct SharedState {
     pub sessions: HashMap<u64, UserSession>,
+    pub snippets: SnippetRegistry,
+    /// Constants published with `const global`, by server.
+    pub constants: GlobalConstants,
+    /// How many times each command was invoked since startup.
+    pub command_counts: HashMap<String, u64>,
+    /// Embed colours set with `/admin theme`, by server id.
+    pub guild_themes: HashMap<u64, Theme>,
+    /// Execution limits set with `/set-limit`, by server id.
+    pub guild_limits: HashMap<u64, LimitOverrides>,
+    /// Totals of the executions and evaluations run since startup.
+    pub metrics: InterpreterMetrics,
+}
+
+impl SharedState {
+    /// Counts an invocation of the named command.
+    pub fn record_command(&mut self, name: &str) {
+        *self.command_counts.entry(name.to_string()).or_insert(0) += 1;
+    }
+
+    /// Returns the embed colours of a server, the defaults outside servers
+    /// and in servers that have not set any.
+    pub fn theme_for(&self, guild_id: Option<u64>) -> Theme {
+        guild_id
+            .and_then(|id| self.guild_themes.get(&id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the execution limits of a server: the limits it overrides and
+    /// the defaults for the rest, and only the defaults outside servers.
+    pub fn limits_for(&self, guild_id: Option<u64>) -> LimitOverrides {
+        guild_id
+            .and_then(|id| self.guild_limits.get(&id))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Removes every user session, returning how many were removed.
+    pub fn reset_sessions(&mut self) -> usize {
+        let count = self.sessions.len();
+        self.sessions.clear();
+        count
+    }
+
+    /// Removes a single user's session, returning whether it existed.
+    pub fn reset_user(&mut self, user_id: u64) -> bool {
+        self.sessions.remove(&user_id).is_some()
+    }
 }
 
 /// Main bot structure with shared state.
 pub struct Bot {
     pub state: Arc<Mutex<SharedState>>,
+    pub config: Arc<Config>,
 } 
@@ -0,0 +1,257 @@
+// Retrying interaction responses that fail for transient reasons
+
+use std::future::Future;
+use std::time::Duration;
+
+use log::warn;
+use rand::Rng;
+use serenity::all::*;
+
+/// Attempts made to send a response, the first one included.
+pub const RESPONSE_ATTEMPTS: u32 = 3;
+
+/// Delay before the first retry; each further retry waits twice as long.
+///
+/// Discord drops an interaction that is not answered within three seconds,
+/// so retries stay well inside that window.
+pub const RESPONSE_BACKOFF: Duration = Duration::from_millis(250);
+
+/// Longest delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Discord's JSON error code for an interaction that was already answered.
+const ALREADY_ACKNOWLEDGED: isize = 40060;
+
+/// What went wrong with a request, as far as retrying is concerned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ApiFailure {
+    /// HTTP status of the reply, `None` when no reply arrived.
+    pub status: Option<u16>,
+    /// Discord's JSON error code, if the reply carried one.
+    pub code: Option<isize>,
+    /// Whether the request never reached Discord or timed out.
+    pub network: bool,
+}
+
+impl ApiFailure {
+    /// Extracts the parts of a Serenity error that decide whether to retry.
+    pub fn from_error(error: &Error) -> Self {
+        match error {
+            Error::Http(HttpError::UnsuccessfulRequest(response)) => Self {
+                status: Some(response.status_code.as_u16()),
+                code: Some(response.error.code),
+                network: false,
+            },
+            Error::Http(HttpError::Request(_)) => Self { status: None, code: None, network: true },
+            _ => Self { status: None, code: None, network: false },
+        }
+    }
+}
+
+/// What to do after a failed attempt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Wait and send the same request again.
+    Retry,
+    /// The interaction was answered elsewhere, so send a follow-up message instead.
+    FollowUp,
+    /// An earlier attempt went through after all, so there is nothing left to do.
+    Delivered,
+    /// The failure is permanent or the attempts ran out.
+    GiveUp,
+}
+
+/// Decides what to do after attempt `attempt` (counted from 0) failed.
+///
+/// Rate limits, server errors and network errors are retried. Discord
+/// answers a retry with "already acknowledged" when an attempt that seemed
+/// to fail was in fact received, which counts as delivered; on the first
+/// attempt it means the interaction was answered elsewhere.
+pub fn retry_decision(failure: &ApiFailure, attempt: u32, attempts: u32) -> RetryDecision {
+    if failure.code == Some(ALREADY_ACKNOWLEDGED) {
+        return if attempt == 0 { RetryDecision::FollowUp } else { RetryDecision::Delivered };
+    }
+
+    let transient = failure.network || matches!(failure.status, Some(429) | Some(500..=599));
+    if transient && attempt + 1 < attempts {
+        RetryDecision::Retry
+    } else {
+        RetryDecision::GiveUp
+    }
+}
+
+/// Returns how long to wait before retry `retry` (counted from 0).
+///
+/// The delay doubles with each retry up to a cap. `jitter`, between 0 and 1,
+/// picks a point in the upper half of that delay, so clients that failed
+/// together do not retry together.
+pub fn backoff_delay(backoff: Duration, retry: u32, jitter: f64) -> Duration {
+    let full = backoff.saturating_mul(2u32.saturating_pow(retry)).min(MAX_BACKOFF);
+    full.mul_f64(0.5 + 0.5 * jitter.clamp(0.0, 1.0))
+}
+
+/// Why a request still failed after retrying.
+#[derive(Debug)]
+pub enum SendFailure {
+    /// The interaction was already answered, see [`RetryDecision::FollowUp`].
+    AlreadyAcknowledged(Error),
+    /// The request failed for good.
+    Failed(Error),
+}
+
+/// Sends a request made by `send`, retrying transient failures.
+///
+/// `send` is called once per attempt, so it must build an identical,
+/// idempotent request each time. Waits between attempts follow
+/// [`backoff_delay`] with random jitter.
+pub async fn send_with_retry<F, Fut>(mut send: F, attempts: u32, backoff: Duration) -> Result<(), SendFailure>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), Error>>,
+{
+    let mut attempt = 0;
+    loop {
+        let error = match send().await {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+
+        match retry_decision(&ApiFailure::from_error(&error), attempt, attempts) {
+            RetryDecision::Retry => {
+                warn!("Discord request failed, retrying: {}", error);
+                let jitter = rand::thread_rng().gen::<f64>();
+                tokio::time::sleep(backoff_delay(backoff, attempt, jitter)).await;
+                attempt += 1;
+            }
+            RetryDecision::Delivered => return Ok(()),
+            RetryDecision::FollowUp => return Err(SendFailure::AlreadyAcknowledged(error)),
+            RetryDecision::GiveUp => return Err(SendFailure::Failed(error)),
+        }
+    }
+}
+
+/// Answers a command with a message, retrying transient failures.
+///
+/// If the interaction was already answered, the message is sent as a
+/// follow-up instead. Files added to the message are only sent with a
+/// response, not with a follow-up.
+pub async fn respond_with_retry(
+    context: &Context,
+    interaction: &CommandInteraction,
+    message: CreateInteractionResponseMessage,
+) -> Result<(), Error> {
+    let sent = send_with_retry(
+        || interaction.create_response(&context.http, CreateInteractionResponse::Message(message.clone())),
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) => Ok(()),
+        Err(SendFailure::AlreadyAcknowledged(_)) => context
+            .http
+            .create_followup_message(&interaction.token, &message, Vec::new())
+            .await
+            .map(|_| ()),
+        Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
+
+/// Answers a button or menu interaction, retrying transient failures.
+///
+/// Unlike commands, a component that was already answered gets no
+/// follow-up, since the message it belongs to has been updated already.
+pub async fn respond_to_component_with_retry(
+    context: &Context,
+    interaction: &ComponentInteraction,
+    response: CreateInteractionResponse,
+) -> Result<(), Error> {
+    let sent = send_with_retry(
+        || interaction.create_response(&context.http, response.clone()),
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) => Ok(()),
+        Err(SendFailure::AlreadyAcknowledged(error)) | Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
+
+/// Tells Discord a command's answer is on its way, retrying transient failures.
+///
+/// This buys time past the three second window; the answer is then sent
+/// with [`edit_with_retry`]. Whether the answer is `ephemeral` is decided
+/// here, since an edit cannot change it. An interaction that was already
+/// answered needs no deferring, so that counts as success.
+pub async fn defer_with_retry(context: &Context, interaction: &CommandInteraction, ephemeral: bool) -> Result<(), Error> {
+    let deferred = CreateInteractionResponseMessage::new().ephemeral(ephemeral);
+    let sent = send_with_retry(
+        || interaction.create_response(&context.http, CreateInteractionResponse::Defer(deferred.clone())),
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) | Err(SendFailure::AlreadyAcknowledged(_)) => Ok(()),
+        Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
+
+/// Replaces the answer to a command, retrying transient failures.
+pub async fn edit_with_retry(
+    context: &Context,
+    interaction: &CommandInteraction,
+    edit: EditInteractionResponse,
+) -> Result<(), Error> {
+    let sent = send_with_retry(
+        || async { interaction.edit_response(&context.http, edit.clone()).await.map(|_| ()) },
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) => Ok(()),
+        Err(SendFailure::AlreadyAcknowledged(error)) | Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
+
+/// Tells Discord a button's answer is on its way, like [`defer_with_retry`].
+///
+/// The answer is a new message, sent with [`edit_component_with_retry`].
+pub async fn defer_component_with_retry(context: &Context, interaction: &ComponentInteraction) -> Result<(), Error> {
+    let sent = send_with_retry(
+        || interaction.create_response(&context.http, CreateInteractionResponse::Defer(CreateInteractionResponseMessage::new())),
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) | Err(SendFailure::AlreadyAcknowledged(_)) => Ok(()),
+        Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
+
+/// Replaces the deferred answer to a button, retrying transient failures.
+pub async fn edit_component_with_retry(
+    context: &Context,
+    interaction: &ComponentInteraction,
+    edit: EditInteractionResponse,
+) -> Result<(), Error> {
+    let sent = send_with_retry(
+        || async { interaction.edit_response(&context.http, edit.clone()).await.map(|_| ()) },
+        RESPONSE_ATTEMPTS,
+        RESPONSE_BACKOFF,
+    )
+    .await;
+
+    match sent {
+        Ok(()) => Ok(()),
+        Err(SendFailure::AlreadyAcknowledged(error)) | Err(SendFailure::Failed(error)) => Err(error),
+    }
+}
@@ -0,0 +1,83 @@
+// Embed colours by meaning, with an accent colour servers can override
+
+use std::fmt;
+
+use serenity::all::Colour;
+
+/// What an embed tells the user, which decides its colour.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tone {
+    /// A result or a completed action.
+    Success,
+    /// Something went wrong.
+    Error,
+    /// An action that removed something, or a result to double-check.
+    Warning,
+    /// Neutral information, such as settings or listings.
+    Info,
+    /// Help pages and examples.
+    Help,
+}
+
+impl Tone {
+    /// Returns the colour used when a server has not set its own.
+    pub fn default_colour(self) -> Colour {
+        match self {
+            Tone::Success => Colour::DARK_GREEN,
+            Tone::Error => Colour::RED,
+            Tone::Warning => Colour::GOLD,
+            Tone::Info => Colour::BLUE,
+            Tone::Help => Colour::from_rgb(138, 43, 226),
+        }
+    }
+}
+
+/// The embed colours of a server.
+///
+/// The accent replaces the neutral colours, info and help. Success, error
+/// and warning keep their colours everywhere so their meaning stays clear.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Theme {
+    /// The server's accent colour, `None` for the default colours.
+    pub accent: Option<Colour>,
+}
+
+impl Theme {
+    /// Returns the colour for embeds of the given tone.
+    pub fn colour(&self, tone: Tone) -> Colour {
+        match (tone, self.accent) {
+            (Tone::Info | Tone::Help, Some(accent)) => accent,
+            _ => tone.default_colour(),
+        }
+    }
+}
+
+/// Why a colour could not be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidColour(pub String);
+
+impl fmt::Display for InvalidColour {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(formatter, "'{}' is not a colour. Use six hexadecimal digits, like #5865F2.", self.0)
+    }
+}
+
+impl std::error::Error for InvalidColour {}
+
+/// Reads a colour written as `#RRGGBB`, with or without the `#`.
+pub fn parse_hex_colour(text: &str) -> Result<Colour, InvalidColour> {
+    let trimmed = text.trim();
+    let digits = trimmed.strip_prefix('#').unwrap_or(trimmed);
+
+    if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(InvalidColour(trimmed.to_string()));
+    }
+    u32::from_str_radix(digits, 16)
+        .map(Colour::new)
+        .map_err(|_| InvalidColour(trimmed.to_string()))
+}
+
+/// Writes a colour as `#RRGGBB`.
+pub fn format_hex_colour(colour: Colour) -> String {
+    format!("#{:06X}", colour.0)
+}
@@ -0,0 +1,168 @@
+//! Lightweight localization of user-facing text.
+//!
+//! Translations are simple key to string TOML files from `locales/`, compiled
+//! into the binary. Nested tables are flattened into dotted keys, so
+//! `[command.evaluate] description = "..."` is looked up as
+//! `command.evaluate.description`. Keys missing from a locale fall back to
+//! English, and interpreter errors fall back to their `Display` text.
+
+use std::collections::HashMap;
+
+use lazy_static::lazy_static;
+
+use crate::core::{is_result_name, InterpreterError, ParseError, EvalError, MathError, SymbolError, ControlFlowError};
+
+/// The locale used when no translation is available.
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Locales with a translation file, the default first.
+pub const SUPPORTED_LOCALES: [&str; 2] = ["en", "es"];
+
+lazy_static! {
+    static ref CATALOGS: HashMap<&'static str, HashMap<String, String>> = {
+        let mut catalogs = HashMap::new();
+        catalogs.insert("en", parse_catalog(include_str!("../locales/en.toml")));
+        catalogs.insert("es", parse_catalog(include_str!("../locales/es.toml")));
+        catalogs
+    };
+}
+
+/// Parses a locale file into a flat key to string map.
+fn parse_catalog(source: &str) -> HashMap<String, String> {
+    let table: toml::Table = source.parse().expect("Locale files must be valid TOML");
+    let mut catalog = HashMap::new();
+    flatten_table("", &table, &mut catalog);
+    catalog
+}
+
+/// Flattens nested tables into dotted keys.
+fn flatten_table(prefix: &str, table: &toml::Table, catalog: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let full_key = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+        match value {
+            toml::Value::String(text) => { catalog.insert(full_key, text.clone()); },
+            toml::Value::Table(nested) => flatten_table(&full_key, nested, catalog),
+            _ => {}
+        }
+    }
+}
+
+/// Maps a Discord locale code (e.g. `es-ES`) to a supported locale.
+///
+/// Unknown locales resolve to the default locale.
+pub fn resolve_locale(code: &str) -> &'static str {
+    let language = code.split(['-', '_']).next().unwrap_or(code).to_lowercase();
+    SUPPORTED_LOCALES.iter()
+        .find(|locale| **locale == language)
+        .copied()
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Returns the Discord locale codes served by a supported locale.
+pub fn discord_locales(locale: &str) -> &'static [&'static str] {
+    match locale {
+        "es" => &["es-ES", "es-419"],
+        _ => &["en-US", "en-GB"],
+    }
+}
+
+/// Looks up a key in a single locale, without falling back.
+pub fn lookup(locale: &str, key: &str) -> Option<&'static str> {
+    CATALOGS.get(resolve_locale(locale))
+        .and_then(|catalog| catalog.get(key))
+        .map(String::as_str)
+}
+
+/// Translates a key, falling back to English and then to the key itself.
+pub fn translate(locale: &str, key: &str) -> String {
+    lookup(locale, key)
+        .or_else(|| lookup(DEFAULT_LOCALE, key))
+        .unwrap_or(key)
+        .to_string()
+}
+
+/// Translates a key and replaces `{name}` placeholders with the given values.
+pub fn translate_with(locale: &str, key: &str, args: &[(&str, String)]) -> String {
+    let mut text = translate(locale, key);
+    for (name, value) in args {
+        text = text.replace(&format!("{{{}}}", name), value);
+    }
+    text
+}
+
+/// Formats an interpreter error in the given locale.
+///
+/// Errors without a translation use their English `Display` text.
+pub fn localize_error(locale: &str, error: &InterpreterError) -> String {
+    let english = match error {
+        InterpreterError::Parse(error) => error.to_string(),
+        InterpreterError::Eval(error) => error.to_string(),
+        InterpreterError::Exec(error) => error.to_string(),
+    };
+
+    match error_key(error) {
+        Some((key, args)) => match lookup(locale, &format!("error.{}", key)) {
+            Some(_) => translate_with(locale, &format!("error.{}", key), &args),
+            None => english,
+        },
+        None => english,
+    }
+}
+
+/// Returns the translation key and placeholder values for an error.
+fn error_key(error: &InterpreterError) -> Option<(&'static str, Vec<(&'static str, String)>)> {
+    let key = match error {
+        InterpreterError::Parse(ParseError::EmptyInput) => ("empty_input", vec![]),
+        InterpreterError::Parse(ParseError::UnexpectedToken(token)) => ("unexpected_token", vec![("token", token.clone())]),
+        InterpreterError::Parse(ParseError::UnmatchedParenthesis) => ("unmatched_parenthesis", vec![]),
+        InterpreterError::Parse(ParseError::TooDeeplyNested(limit)) => ("too_deeply_nested", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::MathError(MathError::DivisionByZero(None))) => ("division_by_zero", vec![]),
+        InterpreterError::Eval(EvalError::MathError(MathError::DivisionByZero(Some(divisor)))) => ("division_by_zero_divisor", vec![("divisor", divisor.clone())]),
+        InterpreterError::Eval(EvalError::MathError(MathError::ModuloByZero(None))) => ("modulo_by_zero", vec![]),
+        InterpreterError::Eval(EvalError::MathError(MathError::ModuloByZero(Some(divisor)))) => ("modulo_by_zero_divisor", vec![("divisor", divisor.clone())]),
+        InterpreterError::Eval(EvalError::MathError(MathError::UnknownUnit(unit))) => ("unknown_unit", vec![("unit", unit.clone())]),
+        InterpreterError::Eval(EvalError::MathError(MathError::IncompatibleUnits { from, to })) => (
+            "incompatible_units",
+            vec![("from", from.clone()), ("to", to.clone())],
+        ),
+        InterpreterError::Eval(EvalError::MathError(MathError::InexactResult(expression))) => ("inexact_result", vec![("expression", expression.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::VariableNotFound(name))) if is_result_name(name) => ("result_not_found", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::VariableNotFound(name))) => ("variable_not_found", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::UndeclaredVariable(name))) => ("undeclared_variable", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::ImmutableConstant(name))) => ("immutable_constant", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::SnippetNotFound(name))) => ("snippet_not_found", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::SnippetNameTooLong { limit })) => ("snippet_name_too_long", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::TooManySnippets { limit })) => ("too_many_snippets", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::SharedConstantOwnedByAnotherUser(name))) => ("shared_constant_taken", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::GlobalConstantNotAllowed(name))) => ("global_constant_not_allowed", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::TooManySharedConstants { limit })) => ("too_many_shared_constants", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::CyclicFormula(name))) => ("cyclic_formula", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::ImpureMemoFunction(name))) => ("impure_memo_function", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::NotCallable(name))) => ("not_callable", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::CallableUsedAsVariable { name, kind })) => (
+            match kind.as_str() {
+                "procedure" => "procedure_used_as_variable",
+                _ => "function_used_as_variable",
+            },
+            vec![("name", name.clone())],
+        ),
+        InterpreterError::Eval(EvalError::SymbolError(SymbolError::LimitExceeded { name, nodes, limit, session_total, .. })) => (
+            if *session_total { "callables_too_large" } else { "callable_too_large" },
+            vec![("name", name.clone()), ("nodes", nodes.to_string()), ("limit", limit.to_string())],
+        ),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::BreakOutsideLoop)) => ("break_outside_loop", vec![]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::ContinueOutsideLoop)) => ("continue_outside_loop", vec![]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::TooManyIterations { limit })) => ("too_many_iterations", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::CallTooDeep { limit })) => ("call_too_deep", vec![("limit", limit.to_string())]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::TimedOut { limit_ms })) => ("timed_out", vec![("limit", limit_ms.to_string())]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::FunctionOrProcedureNotFound { name })) => ("callable_not_found", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::ProcedureUsedAsValue { name })) => ("procedure_used_as_value", vec![("name", name.clone())]),
+        InterpreterError::Eval(EvalError::ControlFlowError(ControlFlowError::WrongArgumentCount { name, expected, got })) => (
+            "wrong_argument_count",
+            vec![("name", name.clone()), ("expected", expected.to_string()), ("got", got.to_string())],
+        ),
+        _ => return None,
+    };
+
+    Some(key)
+}
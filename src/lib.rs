@@ -11,5 +11,11 @@ pub mod discord;
 // Utils
 pub mod utils;
 
+// Startup settings
+pub mod config;
+
 // Logging
-pub mod logging; 
+pub mod logging;
+
+// Localization
+pub mod i18n; 
@@ -1,13 +1,11 @@
-use std::env;
 use std::io::Write;
 use chrono::Local;
 use env_logger::Builder;
 use log::{LevelFilter};
 
 /// Sets up an enhanced logger with custom formatting and error logs directed to stderr
-pub fn setup_logger() {
-    // Create a custom builder from environment
-    let mut builder = Builder::from_env(env_logger::Env::default());
+pub fn setup_logger(log_level: LevelFilter) {
+    let mut builder = Builder::new();
     
     // Direct log output based on level
     builder.format(|_buf, record| {
@@ -45,19 +43,6 @@ pub fn setup_logger() {
         }
     });
     
-    // Set the default log level from env or fallback to info
-    let log_level = match env::var("RUST_LOG") {
-        Ok(level) => match level.to_lowercase().as_str() {
-            "trace" => LevelFilter::Trace,
-            "debug" => LevelFilter::Debug,
-            "info" => LevelFilter::Info,
-            "warn" => LevelFilter::Warn,
-            "error" => LevelFilter::Error,
-            _ => LevelFilter::Info
-        },
-        Err(_) => LevelFilter::Info
-    };
-    
     builder.filter_level(log_level);
     
     // Initialize the logger
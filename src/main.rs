@@ -1,5 +1,5 @@
 // Standard library imports
-use std::env;
+use std::process;
 use std::sync::Arc;
 
 // Async and synchronization
@@ -19,37 +19,44 @@ use dotenv::dotenv;
 use ppaaeedb::discord::{Bot, SharedState, ShardManagerContainer, HelpEmbedsContainer, CommandMetadataContainer};
 use ppaaeedb::discord::commands::help::initialize_help_embeds;
 use ppaaeedb::discord::bot_handler::initialize_command_metadata;
+use ppaaeedb::config::Config;
 use ppaaeedb::logging;
+use ppaaeedb::i18n;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env::set_var("RUST_BACKTRACE", "0");
-
     // Load environment variables from a .env file, ignoring errors if the file is missing
     dotenv().ok();
 
-    // Set RUST_LOG if not already set
-    if env::var("RUST_LOG").is_err() {
-        env::set_var("RUST_LOG", "error");
-    }
+    // Read and validate every setting before connecting, so mistakes surface at once
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(error) => {
+            eprintln!("Cannot start the bot: {}", error);
+            process::exit(1);
+        }
+    };
 
     // Initialize enhanced logger with custom configuration
-    logging::setup_logger();
-
-    // Retrieve the Discord bot token from environment variables
-    let token: String = std::env::var("DISCORD_TOKEN")
-        .expect("Cannot start bot because authentication credentials not found");
+    logging::setup_logger(config.log_level);
 
     // Specify only the gateway intents required for slash commands
     let intents: GatewayIntents = GatewayIntents::GUILD_INTEGRATIONS | GatewayIntents::GUILDS;
 
     // Build the Discord client with the token, intents, and an event handler
-    let mut client: Client = Client::builder(&token, intents)
+    let mut client: Client = match Client::builder(&config.discord_token, intents)
         .event_handler(Bot {
             state: Arc::new(Mutex::new(SharedState::default())),
+            config: config.clone(),
         })
         .await
-        .expect("Error creating Discord client");
+    {
+        Ok(client) => client,
+        Err(error) => {
+            error!("Cannot create the Discord client, check that {} is a valid bot token: {}", ppaaeedb::config::TOKEN_VAR, error);
+            process::exit(1);
+        }
+    };
 
     // This is necessary to access shard-specific information (like latency) from commands
     // Even with a single shard (default), Serenity uses a shard manager internally
@@ -61,8 +68,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         let command_metadata = initialize_command_metadata();
         data.insert::<CommandMetadataContainer>(command_metadata.clone());
         
-        // Initialize and store help embeds
-        let help_embeds = initialize_help_embeds(Some(&command_metadata));
+        // Initialize and store help embeds for every supported locale
+        let help_embeds = i18n::SUPPORTED_LOCALES.iter()
+            .map(|locale| (locale.to_string(), initialize_help_embeds(Some(&command_metadata), locale)))
+            .collect();
         data.insert::<HelpEmbedsContainer>(help_embeds);
         
         info!("Initialized help embeds");
@@ -1,3 +1,6 @@
+use std::cmp::Ordering;
+use std::time::Duration;
+
 use sysinfo::{ComponentExt, SystemExt};
 
 /// Configuration options for formatting time units
@@ -317,9 +320,64 @@ pub fn format_duration(ms: u128, format: Option<DurationFormat>, precision: Opti
     }
 }
 
+/// Formats how long ago something happened, e.g. `5m ago` or `2h ago`.
+///
+/// Only the largest unit is shown, which is enough to tell fresh values
+/// from stale ones.
+pub fn format_relative_time(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds {
+        0..=59 => "just now".to_string(),
+        60..=3599 => format!("{}m ago", seconds / 60),
+        3600..=86399 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+/// Folds exotic Unicode from mobile keyboards and copy-paste into plain ASCII.
+///
+/// Fullwidth forms become their ASCII counterparts, Unicode minus signs and
+/// dashes become `-`, `×` and `÷` become `*` and `/`, smart quotes become
+/// straight quotes, non-breaking and other special spaces become a regular
+/// space, and zero-width characters are removed. Everything else, including
+/// operators like `√`, is kept as is.
+pub fn normalize_input(input: &str) -> String {
+    input
+        .chars()
+        .filter_map(|c| match c {
+            // Zero-width spaces, joiners and byte order marks
+            '\u{200B}' | '\u{200C}' | '\u{200D}' | '\u{2060}' | '\u{FEFF}' => None,
+
+            // Fullwidth ASCII variants (e.g. digits from CJK keyboards)
+            '\u{FF01}'..='\u{FF5E}' => char::from_u32(c as u32 - 0xFEE0),
+
+            // Non-breaking, ideographic and typographic spaces
+            '\u{00A0}' | '\u{2000}'..='\u{200A}' | '\u{202F}' | '\u{205F}' | '\u{3000}' => Some(' '),
+
+            // Minus signs and dashes
+            '\u{2212}' | '\u{2010}'..='\u{2013}' | '\u{FE63}' => Some('-'),
+
+            // Multiplication and division signs
+            '×' | '⋅' | '∙' => Some('*'),
+            '÷' | '∕' => Some('/'),
+
+            // Smart quotes
+            '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' => Some('"'),
+            '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => Some('\''),
+
+            _ => Some(c),
+        })
+        .collect()
+}
+
 /// Extracts code from code blocks in a message.
 /// Supports both ```code``` and `code` formats.
+///
+/// The message is passed through [`normalize_input`] first.
 pub fn extract_code_from_message(content: &str) -> Option<String> {
+    let content = normalize_input(content);
+    let content = content.as_str();
+
     // Check for triple backtick code blocks
     if let Some(start) = content.find("```") {
         let after_start = &content[start + 3..];
@@ -347,3 +405,394 @@ pub fn extract_code_from_message(content: &str) -> Option<String> {
     
     None
 } 
+
+/// Errors from converting a number between bases
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BaseConversionError {
+    /// No digits were given
+    Empty,
+    /// A base outside of 2 to 36
+    InvalidBase(u32),
+    /// A digit that is not valid in the source base
+    InvalidDigit { digit: char, base: u32 },
+    /// The value does not fit in 128 bits
+    Overflow,
+}
+
+impl std::fmt::Display for BaseConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BaseConversionError::Empty => write!(f, "No digits to convert."),
+            BaseConversionError::InvalidBase(base) => write!(f, "Invalid base {}. Bases must be between 2 and 36.", base),
+            BaseConversionError::InvalidDigit { digit, base } => write!(f, "Invalid digit '{}' for base {}.", digit, base),
+            BaseConversionError::Overflow => write!(f, "The value is too large to convert."),
+        }
+    }
+}
+
+impl std::error::Error for BaseConversionError {}
+
+/// Converts an integer written in one base to another base (2 to 36).
+///
+/// Digits above 9 are letters and are accepted in either case, the output
+/// uses uppercase. A leading `-` is kept, and `_` can be used as a separator.
+pub fn convert_base(digits: &str, from: u32, to: u32) -> Result<String, BaseConversionError> {
+    for base in [from, to] {
+        if !(2..=36).contains(&base) {
+            return Err(BaseConversionError::InvalidBase(base));
+        }
+    }
+
+    let digits = digits.trim();
+    let (negative, digits) = match digits.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, digits),
+    };
+
+    // Parse the digits in the source base
+    let mut value: u128 = 0;
+    let mut has_digits = false;
+    for digit in digits.chars().filter(|c| *c != '_') {
+        let digit_value = digit.to_digit(from)
+            .ok_or(BaseConversionError::InvalidDigit { digit, base: from })?;
+        value = value.checked_mul(from as u128)
+            .and_then(|v| v.checked_add(digit_value as u128))
+            .ok_or(BaseConversionError::Overflow)?;
+        has_digits = true;
+    }
+
+    if !has_digits {
+        return Err(BaseConversionError::Empty);
+    }
+
+    // Render the value in the target base, least significant digit first
+    let mut rendered = Vec::new();
+    loop {
+        let digit = std::char::from_digit((value % to as u128) as u32, to)
+            .expect("Remainder is always a valid digit")
+            .to_ascii_uppercase();
+        rendered.push(digit);
+        value /= to as u128;
+        if value == 0 {
+            break;
+        }
+    }
+
+    if negative && rendered != ['0'] {
+        rendered.push('-');
+    }
+
+    Ok(rendered.iter().rev().collect())
+}
+
+/// Errors from parsing `name=value` bindings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindingParseError {
+    /// A binding without `=`, e.g. `x 3`
+    MissingEquals(String),
+    /// A name that is not a valid identifier
+    InvalidName(String),
+    /// A value that is not a number
+    InvalidValue { name: String, value: String },
+    /// The same name bound twice
+    Duplicate(String),
+}
+
+impl std::fmt::Display for BindingParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            BindingParseError::MissingEquals(binding) => write!(f, "Expected 'name=value', but found '{}'.", binding),
+            BindingParseError::InvalidName(name) => write!(f, "'{}' is not a valid variable name.", name),
+            BindingParseError::InvalidValue { name, value } => write!(f, "The value '{}' for '{}' is not a number.", value, name),
+            BindingParseError::Duplicate(name) => write!(f, "'{}' is given more than once.", name),
+        }
+    }
+}
+
+impl std::error::Error for BindingParseError {}
+
+/// Parses comma-separated `name=value` pairs, e.g. `x=3, y=-4.5`.
+///
+/// Spaces around names and values are ignored, and an empty string gives no
+/// bindings. Values must be plain numbers, and each name may appear once.
+pub fn parse_bindings(text: &str) -> Result<Vec<(String, f32)>, BindingParseError> {
+    let mut bindings: Vec<(String, f32)> = Vec::new();
+
+    for binding in text.split(',').map(str::trim).filter(|binding| !binding.is_empty()) {
+        let (name, value) = binding
+            .split_once('=')
+            .ok_or_else(|| BindingParseError::MissingEquals(binding.to_string()))?;
+        let (name, value) = (name.trim(), value.trim());
+
+        let valid_name = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !valid_name {
+            return Err(BindingParseError::InvalidName(name.to_string()));
+        }
+
+        let value = value.parse::<f32>().ok().filter(|value| value.is_finite()).ok_or_else(|| {
+            BindingParseError::InvalidValue { name: name.to_string(), value: value.to_string() }
+        })?;
+
+        if bindings.iter().any(|(bound, _)| bound == name) {
+            return Err(BindingParseError::Duplicate(name.to_string()));
+        }
+        bindings.push((name.to_string(), value));
+    }
+
+    Ok(bindings)
+}
+
+/// Returns the prime factorization of `n` as `(prime, exponent)` pairs in ascending order.
+///
+/// `0` and `1` have no prime factors, so the result is empty for them.
+pub fn prime_factors(mut n: u64) -> Vec<(u64, u32)> {
+    let mut factors = Vec::new();
+    if n < 2 {
+        return factors;
+    }
+
+    // Trial division by 2, then by odd candidates up to the square root
+    let mut candidate = 2;
+    while candidate <= n / candidate {
+        let mut exponent = 0;
+        while n.is_multiple_of(candidate) {
+            n /= candidate;
+            exponent += 1;
+        }
+        if exponent > 0 {
+            factors.push((candidate, exponent));
+        }
+        candidate += if candidate == 2 { 1 } else { 2 };
+    }
+
+    // Whatever remains is itself prime
+    if n > 1 {
+        factors.push((n, 1));
+    }
+
+    factors
+}
+
+/// Formats a prime factorization like `360 = 2^3 * 3^2 * 5`.
+pub fn format_factorization(n: u64) -> String {
+    match n {
+        0 => "0 has no prime factorization".to_string(),
+        1 => "1 = 1".to_string(),
+        _ => {
+            let factors = prime_factors(n)
+                .iter()
+                .map(|(prime, exponent)| match exponent {
+                    1 => prime.to_string(),
+                    _ => format!("{}^{}", prime, exponent),
+                })
+                .collect::<Vec<_>>()
+                .join(" * ");
+            format!("{} = {}", n, factors)
+        }
+    }
+}
+
+/// Serializes variables as CSV with a `name,value,kind` header row.
+///
+/// Fields containing commas, quotes or line breaks are quoted, with inner
+/// quotes doubled, so any spreadsheet reads them back unchanged.
+pub fn to_csv(entries: &[(&str, &str, &str)]) -> String {
+    let escape = |field: &str| {
+        if field.contains([',', '"', '\n', '\r']) {
+            format!("\"{}\"", field.replace('"', "\"\""))
+        } else {
+            field.to_string()
+        }
+    };
+
+    let mut csv = String::from("name,value,kind\n");
+    for (name, value, kind) in entries {
+        csv.push_str(&format!("{},{},{}\n", escape(name), escape(value), escape(kind)));
+    }
+    csv
+}
+
+/// Writes a session's inputs as a script that can be run again with `/execute`.
+///
+/// Each entry is an input and, if it produced one, its result. Inputs are
+/// terminated with `;` so they stay separate statements, and results follow as
+/// `// = result` comments: on the same line for one-line inputs, otherwise on
+/// the next line.
+pub fn transcript_to_script(entries: &[(&str, Option<&str>)]) -> String {
+    let mut script = format!("// PrattCalc session transcript ({} inputs)\n", entries.len());
+
+    for (input, result) in entries {
+        let input = input.trim();
+        let last_line = input.lines().last().unwrap_or("");
+
+        // A trailing line comment would swallow the terminator, so it goes on its own line
+        let statement = if input.ends_with(';') || input.ends_with('}') {
+            input.to_string()
+        } else if last_line.contains("//") {
+            format!("{}\n;", input)
+        } else {
+            format!("{};", input)
+        };
+        script.push_str(&statement);
+
+        if let Some(result) = result {
+            let separator = if statement.contains('\n') || statement.contains("//") { "\n" } else { " " };
+            script.push_str(&format!("{}// = {}", separator, result.replace(['\n', '\r'], " ")));
+        }
+        script.push('\n');
+    }
+
+    script
+}
+
+/// The most characters Discord accepts in an embed description.
+pub const EMBED_DESCRIPTION_LIMIT: usize = 4096;
+
+/// The most characters Discord accepts in the content of a message.
+pub const MESSAGE_CONTENT_LIMIT: usize = 2000;
+
+/// The most characters of code echoed back in a reply, leaving room for the result.
+pub const CODE_ECHO_LIMIT: usize = EMBED_DESCRIPTION_LIMIT - 500;
+
+/// The notice appended to text cut by [`truncate_for_discord`].
+pub const TRUNCATION_NOTICE: &str = "\n… (output truncated)";
+
+/// Shortens text to at most `limit` characters so Discord accepts it.
+///
+/// Text within the limit is returned unchanged. Longer text is cut on a
+/// character boundary and ends with [`TRUNCATION_NOTICE`]. A code block left
+/// open by the cut is closed first, so the rest of the message renders normally.
+pub fn truncate_for_discord(text: &str, limit: usize) -> String {
+    if text.chars().count() <= limit {
+        return text.to_string();
+    }
+
+    // Leave room for the notice and a closing code fence
+    let closing_fence = "\n```";
+    let reserved = TRUNCATION_NOTICE.chars().count() + closing_fence.len();
+    if limit <= reserved {
+        return text.chars().take(limit).collect();
+    }
+
+    let mut truncated: String = text.chars().take(limit - reserved).collect();
+    if truncated.matches("```").count() % 2 == 1 {
+        truncated.push_str(closing_fence);
+    }
+    truncated.push_str(TRUNCATION_NOTICE);
+    truncated
+}
+
+/// The fewest characters worth showing of a "Parsed as" echo; with less room it is left out.
+const MIN_PARSED_ECHO: usize = 200;
+
+/// Shortens the echo of some code and of how it was parsed to share [`CODE_ECHO_LIMIT`].
+///
+/// The code is sanitized for a code block and comes first. The parsed source
+/// gets whatever room is left, and is left out when that is less than a
+/// useful amount, so the two echoes together never crowd out the result.
+pub fn fit_code_echoes(code: &str, parsed: Option<&str>) -> (String, Option<String>) {
+    let echo = truncate_for_discord(&sanitize_for_codeblock(code), CODE_ECHO_LIMIT);
+    let room = CODE_ECHO_LIMIT.saturating_sub(echo.chars().count());
+    let parsed = parsed
+        .filter(|_| room >= MIN_PARSED_ECHO)
+        .map(|source| truncate_for_discord(source, room));
+    (echo, parsed)
+}
+
+/// A zero-width space, invisible but enough to break up Discord markup.
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// Escapes user text for Discord markdown outside of code blocks.
+///
+/// Formatting characters are preceded by a backslash so they show as typed,
+/// and a zero-width space after each `@` keeps `@everyone`, `@here` and
+/// `<@id>` from being read as mentions.
+pub fn escape_markdown(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+        if c == '@' {
+            escaped.push(ZERO_WIDTH_SPACE);
+        }
+    }
+    escaped
+}
+
+/// Makes user text safe to place inside a fenced code block.
+///
+/// Markdown has no escapes inside code blocks, so a zero-width space is put
+/// between consecutive backticks. The text can then never close the fence
+/// it is shown in, while single backticks still read as typed.
+pub fn sanitize_for_codeblock(text: &str) -> String {
+    let mut sanitized = String::with_capacity(text.len());
+    let mut previous = None;
+    for c in text.chars() {
+        if c == '`' && previous == Some('`') {
+            sanitized.push(ZERO_WIDTH_SPACE);
+        }
+        sanitized.push(c);
+        previous = Some(c);
+    }
+    sanitized
+}
+
+/// Whether [`select_expression`] looks for the larger or the smaller value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extreme {
+    Max,
+    Min,
+}
+
+/// Which of two expressions [`select_expression`] picked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pick {
+    /// The first expression.
+    First,
+    /// The second expression.
+    Second,
+    /// Both have the same value.
+    Tie,
+}
+
+/// The outcome of comparing the values of two expressions.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    /// The expression whose value was picked.
+    pub pick: Pick,
+    /// The picked value.
+    pub value: f32,
+    /// How far apart the two values are, `None` when the pick won by
+    /// default because the other expression has no value.
+    pub margin: Option<f32>,
+}
+
+/// Picks the expression with the larger or smaller value.
+///
+/// `None` stands for an expression that failed, and `NaN` counts as no value
+/// either, so the other expression wins by default. When neither has a
+/// value there is nothing to pick.
+pub fn select_expression(first: Option<f32>, second: Option<f32>, extreme: Extreme) -> Option<Selection> {
+    let first = first.filter(|value| !value.is_nan());
+    let second = second.filter(|value| !value.is_nan());
+
+    match (first, second) {
+        (Some(a), Some(b)) => {
+            let pick = match (a.partial_cmp(&b)?, extreme) {
+                (Ordering::Equal, _) => Pick::Tie,
+                (Ordering::Greater, Extreme::Max) | (Ordering::Less, Extreme::Min) => Pick::First,
+                _ => Pick::Second,
+            };
+            let value = if pick == Pick::Second { b } else { a };
+            // Equal infinities are a tie, not an undefined distance
+            let margin = if pick == Pick::Tie { 0.0 } else { (a - b).abs() };
+            Some(Selection { pick, value, margin: Some(margin) })
+        }
+        (Some(a), None) => Some(Selection { pick: Pick::First, value: a, margin: None }),
+        (None, Some(b)) => Some(Selection { pick: Pick::Second, value: b, margin: None }),
+        (None, None) => None,
+    }
+}
@@ -0,0 +1,76 @@
+use ppaaeedb::discord::{AdminConfig, SharedState, UserSession};
+use std::error::Error;
+
+//----------------------------------------------------------------------
+// Admin Gating Tests
+//----------------------------------------------------------------------
+
+/// Tests that the application owner is an admin.
+#[test]
+fn test_owner_is_admin() -> Result<(), Box<dyn Error>> {
+    let config = AdminConfig::new(Some(42), []);
+
+    assert!(config.is_admin(42));
+    assert!(!config.is_admin(7));
+
+    Ok(())
+}
+
+/// Tests parsing the `ADMIN_IDS` list.
+#[test]
+fn test_parse_admin_ids() -> Result<(), Box<dyn Error>> {
+    let ids = AdminConfig::parse_admin_ids("1, 2 3,,not-an-id");
+    assert_eq!(ids.len(), 3);
+
+    let config = AdminConfig::new(None, ids);
+    assert!(config.is_admin(1));
+    assert!(config.is_admin(3));
+    assert!(!config.is_admin(4));
+
+    Ok(())
+}
+
+/// Tests that nobody is an admin without an owner or admin list.
+#[test]
+fn test_unconfigured_admins_refuse_everyone() -> Result<(), Box<dyn Error>> {
+    let config = AdminConfig::new(None, AdminConfig::parse_admin_ids(""));
+
+    assert!(!config.is_admin(0));
+    assert!(!config.is_admin(42));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Session Reset Tests
+//----------------------------------------------------------------------
+
+/// Tests clearing all sessions and single sessions.
+#[test]
+fn test_reset_sessions() -> Result<(), Box<dyn Error>> {
+    let mut state = SharedState::default();
+    state.sessions.insert(1, UserSession::new());
+    state.sessions.insert(2, UserSession::new());
+    state.sessions.insert(3, UserSession::new());
+
+    assert!(state.reset_user(2));
+    assert!(!state.reset_user(2));
+    assert_eq!(state.reset_sessions(), 2);
+    assert!(state.sessions.is_empty());
+
+    Ok(())
+}
+
+/// Tests counting command invocations.
+#[test]
+fn test_record_command() -> Result<(), Box<dyn Error>> {
+    let mut state = SharedState::default();
+    state.record_command("evaluate");
+    state.record_command("evaluate");
+    state.record_command("help");
+
+    assert_eq!(state.command_counts["evaluate"], 2);
+    assert_eq!(state.command_counts["help"], 1);
+
+    Ok(())
+}
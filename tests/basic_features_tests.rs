@@ -1,4 +1,8 @@
-use ppaaeedb::core::{evaluate, execute, SymbolTable};
+// The tests feed literal approximations of PI through the calculator on purpose
+#![allow(clippy::approx_constant)]
+
+use ppaaeedb::core::{is_blank_input, list_builtins, reserved_names, validate_identifier, evaluate, evaluate_pure, normalize_math_symbols, execute, execute_detailed, is_statement_input, ExecutionResult, EvalError, ExecutionError, InterpreterError, MathError, Operator, Parser, SymbolError, SymbolTable, Token, Tokenizer};
+use std::borrow::Cow;
 use std::error::Error;
 
 //----------------------------------------------------------------------
@@ -8,121 +12,181 @@ use std::error::Error;
 /// Tests evaluation of numeric literals.
 #[test]
 fn test_eval_numeric_literal() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("42", &mut context)?, 42.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("42", &context)?, 42.0);
     Ok(())
 }
 
 /// Tests evaluation of decimal numbers.
 #[test]
 fn test_eval_decimal_number() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("3.14159", &mut context)?, 3.14159);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("3.14159", &context)?, 3.14159);
     Ok(())
 }
 
 /// Tests evaluation of hexadecimal numbers.
 #[test]
 fn test_eval_hex_number() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("0xFF", &mut context)?, 255.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("0xFF", &context)?, 255.0);
     Ok(())
 }
 
 /// Tests evaluation of binary numbers.
 #[test]
 fn test_eval_binary_number() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("0b1010", &mut context)?, 10.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("0b1010", &context)?, 10.0);
+    Ok(())
+}
+
+/// Tests that malformed hex and binary literals are reported as number format errors.
+#[test]
+fn test_eval_malformed_radix_literals() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+
+    let error = evaluate("0x", &context).unwrap_err().to_string();
+    assert!(error.contains("Invalid number format: '0x' has no digits"), "{}", error);
+
+    let error = evaluate("0xGG + 1", &context).unwrap_err().to_string();
+    assert!(error.contains("'0xGG' contains 'G', which is not a hexadecimal digit"), "{}", error);
+
+    let error = evaluate("0b12", &context).unwrap_err().to_string();
+    assert!(error.contains("'0b12' contains '2', which is not a binary digit"), "{}", error);
+
+    // 64-bit values are accepted, anything wider is out of range
+    assert_eq!(evaluate("0xFFFFFFFFFF", &context)?, 1_099_511_627_775.0);
+    let error = evaluate("0x1FFFFFFFFFFFFFFFF", &context).unwrap_err().to_string();
+    assert!(error.contains("out of range"), "{}", error);
+
     Ok(())
 }
 
 /// Tests basic arithmetic operations.
 #[test]
 fn test_eval_basic_arithmetic() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("2 + 3", &mut context)?, 5.0);
-    assert_eq!(evaluate("5 - 2", &mut context)?, 3.0);
-    assert_eq!(evaluate("3 * 4", &mut context)?, 12.0);
-    assert_eq!(evaluate("10 / 2", &mut context)?, 5.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("2 + 3", &context)?, 5.0);
+    assert_eq!(evaluate("5 - 2", &context)?, 3.0);
+    assert_eq!(evaluate("3 * 4", &context)?, 12.0);
+    assert_eq!(evaluate("10 / 2", &context)?, 5.0);
     Ok(())
 }
 
 /// Tests operator precedence.
 #[test]
 fn test_eval_operator_precedence() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("2 + 3 * 4", &mut context)?, 14.0); // 3 * 4 = 12, then 2 + 12 = 14
-    assert_eq!(evaluate("2 * 3 + 4", &mut context)?, 10.0); // 2 * 3 = 6, then 6 + 4 = 10
-    assert_eq!(evaluate("(2 + 3) * 4", &mut context)?, 20.0); // 2 + 3 = 5, then 5 * 4 = 20
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("2 + 3 * 4", &context)?, 14.0); // 3 * 4 = 12, then 2 + 12 = 14
+    assert_eq!(evaluate("2 * 3 + 4", &context)?, 10.0); // 2 * 3 = 6, then 6 + 4 = 10
+    assert_eq!(evaluate("(2 + 3) * 4", &context)?, 20.0); // 2 + 3 = 5, then 5 * 4 = 20
     Ok(())
 }
 
 /// Tests nested expressions with parentheses.
 #[test]
 fn test_eval_nested_expressions() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("(2 + 3) * (4 - 1)", &mut context)?, 15.0);
-    assert_eq!(evaluate("2 * (3 + (4 * 5))", &mut context)?, 46.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("(2 + 3) * (4 - 1)", &context)?, 15.0);
+    assert_eq!(evaluate("2 * (3 + (4 * 5))", &context)?, 46.0);
     Ok(())
 }
 
 /// Tests complex expressions with multiple operators.
 #[test]
 fn test_eval_complex_expressions() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("2 + 3 * 4 - 5 / 5", &mut context)?, 13.0);
-    assert_eq!(evaluate("10 - 2 * 3 + 5 / 5", &mut context)?, 5.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("2 + 3 * 4 - 5 / 5", &context)?, 13.0);
+    assert_eq!(evaluate("10 - 2 * 3 + 5 / 5", &context)?, 5.0);
     Ok(())
 }
 
 /// Tests unary minus operator.
 #[test]
 fn test_eval_unary_minus() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("-5", &mut context)?, -5.0);
-    assert_eq!(evaluate("--5", &mut context)?, 5.0); // Double negative
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("-5", &context)?, -5.0);
+    assert_eq!(evaluate("--5", &context)?, 5.0); // Double negative
     Ok(())
 }
 
 /// Tests modulo operator.
 #[test]
 fn test_eval_modulo() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("10 % 3", &mut context)?, 1.0);
-    assert_eq!(evaluate("17 % 5", &mut context)?, 2.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("10 % 3", &context)?, 1.0);
+    assert_eq!(evaluate("17 % 5", &context)?, 2.0);
     Ok(())
 }
 
 /// Tests power operator.
 #[test]
 fn test_eval_power() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("2 ^ 3", &mut context)?, 8.0);
-    assert_eq!(evaluate("3 ^ 2", &mut context)?, 9.0);
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("2 ^ 3", &context)?, 8.0);
+    assert_eq!(evaluate("3 ^ 2", &context)?, 9.0);
     Ok(())
 }
 
 /// Tests root operator.
 #[test]
 fn test_eval_root() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     // Using sqrt function instead of word root
-    assert_eq!(evaluate("sqrt(9)", &mut context)?, 3.0);
-    assert_eq!(evaluate("sqrt(16)", &mut context)?, 4.0);
+    assert_eq!(evaluate("sqrt(9)", &context)?, 3.0);
+    assert_eq!(evaluate("sqrt(16)", &context)?, 4.0);
+    Ok(())
+}
+
+/// Tests that `root(n, x)` and `n √ x` agree, including odd roots of negative numbers.
+#[test]
+fn test_root_function() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("root(3, 27)", &context)?, 3.0);
+    assert_eq!(evaluate("root(3, -8)", &context)?, -2.0);
+    assert_eq!(evaluate("root(2, 16)", &context)?, 4.0);
+    assert_eq!(evaluate("3 √ -8", &context)?, evaluate("root(3, -8)", &context)?);
+    assert_eq!(evaluate("5 √ -32", &context)?, -2.0);
+
+    assert!(matches!(evaluate("root(2, -4)", &context), Err(InterpreterError::Eval(EvalError::MathError(MathError::NegativeRoot)))));
+    assert!(matches!(evaluate("2 √ -4", &context), Err(InterpreterError::Eval(EvalError::MathError(MathError::NegativeRoot)))));
+    assert!(matches!(evaluate("root(1.5, -8)", &context), Err(InterpreterError::Eval(EvalError::MathError(MathError::NegativeRoot)))));
+    assert!(matches!(evaluate("root(0, 5)", &context), Err(InterpreterError::Eval(EvalError::MathError(MathError::ZerothRoot)))));
+    assert!(evaluate("root(27)", &context).is_err());
+    assert!(evaluate("root(3, 27, 1)", &context).is_err());
     Ok(())
 }
 
 /// Tests comparison operators.
 #[test]
 fn test_eval_comparisons() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    assert_eq!(evaluate("5 > 3", &context)?, 1.0); // true
+    assert_eq!(evaluate("5 < 3", &context)?, 0.0); // false
+    assert_eq!(evaluate("5 == 5", &context)?, 1.0); // true
+    assert_eq!(evaluate("5 != 5", &context)?, 0.0); // false
+    assert_eq!(evaluate("5 >= 5", &context)?, 1.0); // true
+    assert_eq!(evaluate("5 <= 3", &context)?, 0.0); // false
+    Ok(())
+}
+
+/// Tests that comparisons ending in `=` never assign in statement position.
+#[test]
+fn test_comparisons_do_not_assign() -> Result<(), Box<dyn Error>> {
     let mut context = SymbolTable::<f32>::new();
-    assert_eq!(evaluate("5 > 3", &mut context)?, 1.0); // true
-    assert_eq!(evaluate("5 < 3", &mut context)?, 0.0); // false
-    assert_eq!(evaluate("5 == 5", &mut context)?, 1.0); // true
-    assert_eq!(evaluate("5 != 5", &mut context)?, 0.0); // false
-    assert_eq!(evaluate("5 >= 5", &mut context)?, 1.0); // true
-    assert_eq!(evaluate("5 <= 3", &mut context)?, 0.0); // false
+    execute("let x = 3", &mut context)?;
+
+    assert_eq!(execute("x >= 3", &mut context)?, Some(1.0));
+    assert_eq!(execute("x <= 2", &mut context)?, Some(0.0));
+    assert_eq!(execute("x == 3", &mut context)?, Some(1.0));
+    assert_eq!(execute("x != 3", &mut context)?, Some(0.0));
+    assert_eq!(execute("x == 4; x != 4", &mut context)?, Some(1.0));
+    assert_eq!(context.get("x"), Some(&3.0));
+
+    // Augmented assignment still updates the variable
+    assert_eq!(execute("x += 3; x >= 6", &mut context)?, Some(1.0));
+    assert_eq!(context.get("x"), Some(&6.0));
     Ok(())
 }
 
@@ -131,7 +195,7 @@ fn test_eval_comparisons() -> Result<(), Box<dyn Error>> {
 fn test_eval_variable() -> Result<(), Box<dyn Error>> {
     let mut context = SymbolTable::<f32>::new();
     context.set_variable("x".to_string(), 42.0)?;
-    assert_eq!(evaluate("x", &mut context)?, 42.0);
+    assert_eq!(evaluate("x", &context)?, 42.0);
     Ok(())
 }
 
@@ -141,10 +205,107 @@ fn test_eval_expressions_with_variables() -> Result<(), Box<dyn Error>> {
     let mut context = SymbolTable::<f32>::new();
     context.set_variable("x".to_string(), 5.0)?;
     context.set_variable("y".to_string(), 3.0)?;
-    assert_eq!(evaluate("x + y", &mut context)?, 8.0);
-    assert_eq!(evaluate("x * y", &mut context)?, 15.0);
-    assert_eq!(evaluate("x - y", &mut context)?, 2.0);
-    assert_eq!(evaluate("x / y", &mut context)?, 5.0 / 3.0);
+    assert_eq!(evaluate("x + y", &context)?, 8.0);
+    assert_eq!(evaluate("x * y", &context)?, 15.0);
+    assert_eq!(evaluate("x - y", &context)?, 2.0);
+    assert_eq!(evaluate("x / y", &context)?, 5.0 / 3.0);
+    Ok(())
+}
+
+/// Tests that characters the lexer does not know are reported instead of dropped.
+#[test]
+fn test_unrecognized_characters() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+
+    let error = evaluate("2 @ 3", &context).unwrap_err().to_string();
+    assert!(error.contains("Syntax error: unrecognized character '@' at position 3"), "{}", error);
+
+    let error = evaluate("$price * 2", &context).unwrap_err().to_string();
+    assert!(error.contains("unrecognized character '$' at position 1"), "{}", error);
+
+    let error = execute("let x = 1; # note\nx + 1", &mut context).unwrap_err().to_string();
+    assert!(error.contains("unrecognized character '#' at position 12"), "{}", error);
+
+    // A colon is only an operator as part of :=
+    let error = evaluate("2 : 3", &context).unwrap_err().to_string();
+    assert!(error.contains("unrecognized character ':' at position 3"), "{}", error);
+
+    let error = execute("let x = 1: x", &mut context).unwrap_err().to_string();
+    assert!(error.contains("unrecognized character ':' at position 10"), "{}", error);
+    assert_eq!(execute("let z := 2; z", &mut context)?, Some(2.0));
+
+    // Comments may still contain anything
+    assert_eq!(execute("let y = 1; // costs $5 @ #1\ny + 1", &mut context)?, Some(2.0));
+
+    // The old behaviour is available on request
+    let tokenizer = Tokenizer::from_input("2 + @3").skipping_unrecognized();
+    assert!(Parser::new(tokenizer).parse_expression().is_ok());
+
+    Ok(())
+}
+
+/// Tests that the tokenizer reads operators as operators, whatever their length.
+#[test]
+fn test_operator_tokens() -> Result<(), Box<dyn Error>> {
+    let mut tokenizer = Tokenizer::from_input("a >= b ^^ c !& d & e = f; (1..2) := x");
+    let mut tokens = Vec::new();
+    while tokenizer.peek_token() != &Token::EndOfInput {
+        tokens.push(tokenizer.next_token());
+    }
+
+    let name = |text: &str| Token::Literal(text.to_string());
+    assert_eq!(tokens, vec![
+        name("a"), Token::Operator(Operator::GreaterEqual),
+        name("b"), Token::Operator(Operator::Xor),
+        name("c"), Token::Operator(Operator::Nand),
+        name("d"), Token::Operator(Operator::And),
+        name("e"), Token::Operator(Operator::Assign),
+        name("f"), Token::Punctuation(';'),
+        Token::Punctuation('('), name("1"), Token::Range, name("2"), Token::Punctuation(')'),
+        Token::FormulaAssign, name("x"),
+    ]);
+
+    // Tokens are shown as they are written
+    let shown: Vec<String> = tokens.iter().map(Token::to_string).collect();
+    assert_eq!(shown.join(" "), "a >= b ^^ c !& d && e = f ; ( 1 .. 2 ) := x");
+
+    Ok(())
+}
+
+/// Tests that division and modulo by zero name the divisor.
+#[test]
+fn test_division_by_zero_names_divisor() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+
+    let error = evaluate("1 / 0", &context).unwrap_err().to_string();
+    assert!(error.contains("Division by zero error: divisor `0` evaluated to 0."), "{}", error);
+
+    let error = execute("let count = 0; let total = 10; total / count", &mut context).unwrap_err().to_string();
+    assert!(error.contains("divisor `count` evaluated to 0"), "{}", error);
+
+    let error = execute("let offset = 0; 7 % (count - offset)", &mut context).unwrap_err().to_string();
+    assert!(error.contains("Modulo by zero error: divisor `count - offset` evaluated to 0."), "{}", error);
+
+    Ok(())
+}
+
+/// Tests that Unicode math symbols pasted from elsewhere work like their ASCII forms.
+#[test]
+fn test_unicode_math_symbols() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let x = 4", &mut context)?;
+
+    assert_eq!(evaluate("2 × 3 ÷ 2", &context)?, 3.0);
+    assert_eq!(evaluate("2²", &context)?, 4.0);
+    assert_eq!(evaluate("2³ − 1", &context)?, 7.0);
+    assert_eq!(evaluate("x⁻¹", &context)?, 0.25);
+    assert_eq!(evaluate("10¹⁰ ÷ 10⁹", &context)?, 10.0);
+    assert_eq!(evaluate("3 ≥ 2 && 2 ≤ 3 && 1 ≠ 2", &context)?, 1.0);
+    assert_eq!(evaluate("3 ⋅ 2 ∙ 2", &context)?, 12.0);
+
+    assert_eq!(normalize_math_symbols("x² + 1"), "x^2 + 1");
+    assert!(matches!(normalize_math_symbols("2 * 3"), Cow::Borrowed(_)));
+
     Ok(())
 }
 
@@ -290,6 +451,52 @@ fn test_constants_in_blocks() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests that keywords and built-in function names cannot name variables or constants.
+#[test]
+fn test_reserved_identifiers() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+
+    let error = execute("let sin = 5", &mut context).unwrap_err();
+    assert!(matches!(&error, InterpreterError::Eval(EvalError::SymbolError(SymbolError::InvalidIdentifier(name))) if name == "sin"));
+    assert!(error.to_string().contains("built-in function"));
+    assert!(execute("const max = 1", &mut context).is_err());
+    assert!(execute("let round := 2", &mut context).is_err());
+    assert!(execute("let if = 1", &mut context).is_err());
+    assert!(context.is_empty());
+
+    // The table refuses them directly too
+    assert!(matches!(context.set_variable("log".to_string(), 1.0), Err(EvalError::SymbolError(SymbolError::InvalidIdentifier(_)))));
+    assert!(context.declare_constant("while".to_string(), 1.0).is_err());
+
+    // Names that only contain a reserved word are fine
+    execute("let sine = 1; let if_x = 2; const maximum = 3", &mut context)?;
+    assert_eq!(evaluate("sine + if_x + maximum", &context)?, 6.0);
+
+    Ok(())
+}
+
+/// Tests the single list of reserved names and checking names against it.
+#[test]
+fn test_validate_identifier() -> Result<(), Box<dyn Error>> {
+    for spec in list_builtins() {
+        assert!(reserved_names().contains(spec.name), "{} should be reserved", spec.name);
+    }
+    for keyword in ["if", "while", "fn", "true", "defer"] {
+        assert!(reserved_names().contains(keyword));
+    }
+
+    validate_identifier("radius")?;
+    validate_identifier("_x2")?;
+    assert!(matches!(validate_identifier("cos"), Err(SymbolError::InvalidIdentifier(_))));
+    assert!(matches!(validate_identifier("foreach"), Err(SymbolError::InvalidIdentifier(_))));
+    assert!(matches!(validate_identifier("2x"), Err(SymbolError::InvalidIdentifier(_))));
+    assert!(matches!(validate_identifier("a b"), Err(SymbolError::InvalidIdentifier(_))));
+    assert!(matches!(validate_identifier(""), Err(SymbolError::InvalidIdentifier(_))));
+    assert!(matches!(validate_identifier("PI"), Err(SymbolError::ImmutableConstant(_))));
+
+    Ok(())
+}
+
 //----------------------------------------------------------------------
 // Variable and Assignment Tests
 //----------------------------------------------------------------------
@@ -335,6 +542,21 @@ fn test_simple_assignment() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests right-associative chained assignment.
+#[test]
+fn test_chained_assignment() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let x; let y", &mut context)?;
+    let result = execute("x = y = 5", &mut context)?;
+    assert_eq!(context.get("x"), Some(&5.0));
+    assert_eq!(context.get("y"), Some(&5.0));
+    assert_eq!(result, Some(5.0));
+
+    // Every target in the chain must be declared
+    assert!(execute("x = z = 1", &mut context).is_err());
+    Ok(())
+}
+
 /// Tests compound assignment operators.
 #[test]
 fn test_augmented_assignment() -> Result<(), Box<dyn Error>> {
@@ -359,6 +581,92 @@ fn test_augmented_assignment() -> Result<(), Box<dyn Error>> {
     Ok(())
 } 
 
+/// Tests that variables are listed by name regardless of insertion order.
+#[test]
+fn test_iter_sorted() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let zeta = 1; const alpha = 2; let mid = 3; let Beta = 4; let a2 = 5", &mut context)?;
+    
+    let names: Vec<&str> = context.iter_sorted().map(|(name, _)| name).collect();
+    assert_eq!(names, vec!["Beta", "a2", "alpha", "mid", "zeta"]);
+    
+    let values: Vec<f32> = context.iter_sorted().map(|(_, value)| *value).collect();
+    assert_eq!(values, vec![4.0, 5.0, 2.0, 3.0, 1.0]);
+    
+    Ok(())
+}
+
+/// Tests that assignments nested inside expressions write to the context
+#[test]
+fn test_assignment_in_expressions() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let a = 0; let b = 0; const LIMIT = 10", &mut context)?;
+
+    // Chained assignment sets every target
+    assert_eq!(execute("a = b = 5", &mut context)?, Some(5.0));
+    assert_eq!(context.get("a"), Some(&5.0));
+    assert_eq!(context.get("b"), Some(&5.0));
+
+    // Nested in an operation or a call argument
+    assert_eq!(execute("a = (b = 3) + 1", &mut context)?, Some(4.0));
+    assert_eq!(context.get("b"), Some(&3.0));
+    assert_eq!(execute("max(b = 7, 1)", &mut context)?, Some(7.0));
+    assert_eq!(context.get("b"), Some(&7.0));
+    execute("if (a = 2) > 1 { b = 0 }", &mut context)?;
+    assert_eq!(context.get("a"), Some(&2.0));
+    assert_eq!(context.get("b"), Some(&0.0));
+
+    // Constants and undeclared variables are still refused
+    assert!(execute("a = (LIMIT = 3)", &mut context).is_err());
+    assert!(execute("a = max(PI = 3, 1)", &mut context).is_err());
+    assert!(execute("a = (missing = 1) + 1", &mut context).is_err());
+    assert_eq!(context.get("LIMIT"), Some(&10.0));
+    assert_eq!(context.get("missing"), None);
+
+    // Evaluation never writes
+    assert_eq!(evaluate("(b = 9) + 1", &context)?, 10.0);
+    assert_eq!(context.get("b"), Some(&0.0));
+
+    Ok(())
+}
+
+/// Tests that augmented assignment refuses undeclared variables and constants
+#[test]
+fn test_augmented_assignment_targets() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("const LIMIT = 10", &mut context)?;
+
+    for operator in ["+=", "-=", "*=", "/=", "%=", "^="] {
+        // An undeclared target is reported as such, and never created
+        let code = format!("ghost {} 2", operator);
+        match execute(&code, &mut context) {
+            Err(InterpreterError::Eval(EvalError::SymbolError(SymbolError::UndeclaredVariable(name)))) => assert_eq!(name, "ghost"),
+            other => panic!("{} should report an undeclared variable, got {:?}", code, other),
+        }
+        match evaluate(&code, &context) {
+            Err(InterpreterError::Eval(EvalError::SymbolError(SymbolError::UndeclaredVariable(_)))) => {}
+            other => panic!("evaluating {} should report an undeclared variable, got {:?}", code, other),
+        }
+        assert_eq!(context.get("ghost"), None);
+
+        // Local and global constants cannot be updated
+        for constant in ["LIMIT", "PI"] {
+            let code = format!("{} {} 2", constant, operator);
+            match execute(&code, &mut context) {
+                Err(InterpreterError::Eval(EvalError::SymbolError(SymbolError::ImmutableConstant(name)))) => assert_eq!(name, constant),
+                other => panic!("{} should report a constant, got {:?}", code, other),
+            }
+        }
+    }
+    assert_eq!(context.get("LIMIT"), Some(&10.0));
+
+    // Only a variable can be the target
+    assert!(execute("3 += 1", &mut context).is_err());
+    assert!(execute("(LIMIT + 1) += 1", &mut context).is_err());
+
+    Ok(())
+}
+
 //----------------------------------------------------------------------
 // Logical Operators Tests
 //----------------------------------------------------------------------
@@ -366,19 +674,19 @@ fn test_augmented_assignment() -> Result<(), Box<dyn Error>> {
 /// Tests basic AND, OR operations
 #[test]
 fn test_basic_logical_operators() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     
     // Test AND operator
-    assert_eq!(evaluate("1 && 1", &mut context)?, 1.0); // true AND true = true
-    assert_eq!(evaluate("1 && 0", &mut context)?, 0.0); // true AND false = false
-    assert_eq!(evaluate("0 && 1", &mut context)?, 0.0); // false AND true = false
-    assert_eq!(evaluate("0 && 0", &mut context)?, 0.0); // false AND false = false
+    assert_eq!(evaluate("1 && 1", &context)?, 1.0); // true AND true = true
+    assert_eq!(evaluate("1 && 0", &context)?, 0.0); // true AND false = false
+    assert_eq!(evaluate("0 && 1", &context)?, 0.0); // false AND true = false
+    assert_eq!(evaluate("0 && 0", &context)?, 0.0); // false AND false = false
     
     // Test OR operator
-    assert_eq!(evaluate("1 || 1", &mut context)?, 1.0); // true OR true = true
-    assert_eq!(evaluate("1 || 0", &mut context)?, 1.0); // true OR false = true
-    assert_eq!(evaluate("0 || 1", &mut context)?, 1.0); // false OR true = true
-    assert_eq!(evaluate("0 || 0", &mut context)?, 0.0); // false OR false = false
+    assert_eq!(evaluate("1 || 1", &context)?, 1.0); // true OR true = true
+    assert_eq!(evaluate("1 || 0", &context)?, 1.0); // true OR false = true
+    assert_eq!(evaluate("0 || 1", &context)?, 1.0); // false OR true = true
+    assert_eq!(evaluate("0 || 0", &context)?, 0.0); // false OR false = false
     
     Ok(())
 }
@@ -386,19 +694,19 @@ fn test_basic_logical_operators() -> Result<(), Box<dyn Error>> {
 /// Tests XOR, XNOR operations
 #[test]
 fn test_xor_operations() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     
     // Test XOR operator
-    assert_eq!(evaluate("1 ^^ 1", &mut context)?, 0.0); // true XOR true = false
-    assert_eq!(evaluate("1 ^^ 0", &mut context)?, 1.0); // true XOR false = true
-    assert_eq!(evaluate("0 ^^ 1", &mut context)?, 1.0); // false XOR true = true
-    assert_eq!(evaluate("0 ^^ 0", &mut context)?, 0.0); // false XOR false = false
+    assert_eq!(evaluate("1 ^^ 1", &context)?, 0.0); // true XOR true = false
+    assert_eq!(evaluate("1 ^^ 0", &context)?, 1.0); // true XOR false = true
+    assert_eq!(evaluate("0 ^^ 1", &context)?, 1.0); // false XOR true = true
+    assert_eq!(evaluate("0 ^^ 0", &context)?, 0.0); // false XOR false = false
     
     // Test XNOR operator
-    assert_eq!(evaluate("1 !^ 1", &mut context)?, 1.0); // true XNOR true = true
-    assert_eq!(evaluate("1 !^ 0", &mut context)?, 0.0); // true XNOR false = false
-    assert_eq!(evaluate("0 !^ 1", &mut context)?, 0.0); // false XNOR true = false
-    assert_eq!(evaluate("0 !^ 0", &mut context)?, 1.0); // false XNOR false = true
+    assert_eq!(evaluate("1 !^ 1", &context)?, 1.0); // true XNOR true = true
+    assert_eq!(evaluate("1 !^ 0", &context)?, 0.0); // true XNOR false = false
+    assert_eq!(evaluate("0 !^ 1", &context)?, 0.0); // false XNOR true = false
+    assert_eq!(evaluate("0 !^ 0", &context)?, 1.0); // false XNOR false = true
     
     Ok(())
 }
@@ -406,19 +714,19 @@ fn test_xor_operations() -> Result<(), Box<dyn Error>> {
 /// Tests NAND, NOR operations
 #[test]
 fn test_nand_nor_operations() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     
     // Test NAND operator
-    assert_eq!(evaluate("1 !& 1", &mut context)?, 0.0); // true NAND true = false
-    assert_eq!(evaluate("1 !& 0", &mut context)?, 1.0); // true NAND false = true
-    assert_eq!(evaluate("0 !& 1", &mut context)?, 1.0); // false NAND true = true
-    assert_eq!(evaluate("0 !& 0", &mut context)?, 1.0); // false NAND false = true
+    assert_eq!(evaluate("1 !& 1", &context)?, 0.0); // true NAND true = false
+    assert_eq!(evaluate("1 !& 0", &context)?, 1.0); // true NAND false = true
+    assert_eq!(evaluate("0 !& 1", &context)?, 1.0); // false NAND true = true
+    assert_eq!(evaluate("0 !& 0", &context)?, 1.0); // false NAND false = true
     
     // Test NOR operator
-    assert_eq!(evaluate("1 !| 1", &mut context)?, 0.0); // true NOR true = false
-    assert_eq!(evaluate("1 !| 0", &mut context)?, 0.0); // true NOR false = false
-    assert_eq!(evaluate("0 !| 1", &mut context)?, 0.0); // false NOR true = false
-    assert_eq!(evaluate("0 !| 0", &mut context)?, 1.0); // false NOR false = true
+    assert_eq!(evaluate("1 !| 1", &context)?, 0.0); // true NOR true = false
+    assert_eq!(evaluate("1 !| 0", &context)?, 0.0); // true NOR false = false
+    assert_eq!(evaluate("0 !| 1", &context)?, 0.0); // false NOR true = false
+    assert_eq!(evaluate("0 !| 0", &context)?, 1.0); // false NOR false = true
     
     Ok(())
 }
@@ -426,38 +734,63 @@ fn test_nand_nor_operations() -> Result<(), Box<dyn Error>> {
 /// Tests NOT operation and logical operator precedence
 #[test]
 fn test_not_and_precedence() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     
     // Test NOT operator
-    assert_eq!(evaluate("!1", &mut context)?, 0.0); // NOT true = false
-    assert_eq!(evaluate("!0", &mut context)?, 1.0); // NOT false = true
+    assert_eq!(evaluate("!1", &context)?, 0.0); // NOT true = false
+    assert_eq!(evaluate("!0", &context)?, 1.0); // NOT false = true
     
     // Test precedence
-    assert_eq!(evaluate("!0 && 1", &mut context)?, 1.0); // (NOT false) AND true = true
-    assert_eq!(evaluate("!(0 && 1)", &mut context)?, 1.0); // NOT (false AND true) = true
-    assert_eq!(evaluate("1 || 0 && 1", &mut context)?, 1.0); // true OR (false AND true) = true
+    assert_eq!(evaluate("!0 && 1", &context)?, 1.0); // (NOT false) AND true = true
+    assert_eq!(evaluate("!(0 && 1)", &context)?, 1.0); // NOT (false AND true) = true
+    assert_eq!(evaluate("1 || 0 && 1", &context)?, 1.0); // true OR (false AND true) = true
     
     Ok(())
 }
 
+/// Tests postfix factorial alongside prefix NOT and the negated operators
+#[test]
+fn test_postfix_factorial() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+
+    // Postfix `!` after an operand is factorial
+    assert_eq!(evaluate("5!", &context)?, 120.0);
+    assert_eq!(evaluate("0!", &context)?, 1.0);
+    assert_eq!(evaluate("(2 + 1)!", &context)?, 6.0);
+    assert_eq!(evaluate("3! + 1", &context)?, 7.0);
+
+    // Factorial binds tighter than prefix operators and powers
+    assert_eq!(evaluate("-3!", &context)?, -6.0);
+    assert_eq!(evaluate("2^3!", &context)?, 64.0);
+    assert_eq!(evaluate("!0!", &context)?, 0.0); // NOT (0!) = NOT 1
+
+    // Prefix `!` stays NOT and `!=` stays not-equal
+    assert_eq!(evaluate("!0", &context)?, 1.0);
+    assert_eq!(evaluate("5 != 3", &context)?, 1.0);
+    assert_eq!(evaluate("5!=3", &context)?, 1.0);
+    assert_eq!(evaluate("3! != 6", &context)?, 0.0);
+
+    Ok(())
+}
+
 /// Tests using true and false keywords
 #[test]
 fn test_true_false_keywords() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     
     // Test basic true/false
-    assert_eq!(evaluate("true", &mut context)?, 1.0);
-    assert_eq!(evaluate("false", &mut context)?, 0.0);
+    assert_eq!(evaluate("true", &context)?, 1.0);
+    assert_eq!(evaluate("false", &context)?, 0.0);
     
     // Test with operators
-    assert_eq!(evaluate("true && false", &mut context)?, 0.0);
-    assert_eq!(evaluate("true || false", &mut context)?, 1.0);
-    assert_eq!(evaluate("!true", &mut context)?, 0.0);
-    assert_eq!(evaluate("!false", &mut context)?, 1.0);
+    assert_eq!(evaluate("true && false", &context)?, 0.0);
+    assert_eq!(evaluate("true || false", &context)?, 1.0);
+    assert_eq!(evaluate("!true", &context)?, 0.0);
+    assert_eq!(evaluate("!false", &context)?, 1.0);
     
     // Test in expressions
-    assert_eq!(evaluate("true && (5 > 3)", &mut context)?, 1.0);
-    assert_eq!(evaluate("false || (5 < 3)", &mut context)?, 0.0);
+    assert_eq!(evaluate("true && (5 > 3)", &context)?, 1.0);
+    assert_eq!(evaluate("false || (5 < 3)", &context)?, 0.0);
     
     Ok(())
 }
@@ -506,6 +839,104 @@ fn test_logical_ops_in_program_flow() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests that evaluate ignores stray semicolons and runs statements without keeping them
+#[test]
+fn test_evaluate_semicolons_and_statements() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+
+    // Trailing and doubled semicolons are harmless
+    assert_eq!(evaluate("2+2;", &context)?, 4.0);
+    assert_eq!(evaluate("2+2;;", &context)?, 4.0);
+    assert_eq!(execute(";;2+2;;", &mut context)?, Some(4.0));
+    assert_eq!(execute(";", &mut context)?, None);
+    assert!(!is_statement_input("2+2;"));
+
+    // Statements run as a script, but their variables are not kept
+    assert!(is_statement_input("let x = 5; x * 2"));
+    assert_eq!(evaluate("let x = 5; x * 2", &context)?, 10.0);
+    assert_eq!(context.get("x"), None);
+
+    // Statements must still leave a value, and blank input is an error
+    assert!(evaluate("fn double(n) { n * 2 }", &context).is_err());
+    assert!(evaluate(" ; ", &context).is_err());
+
+    Ok(())
+}
+
+/// Tests that evaluate and execute give the same results and errors
+#[test]
+fn test_evaluate_matches_execute() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let x = 4; const K = 3; fn sq(n) { n * n }", &mut context)?;
+
+    let inputs = [
+        "x * K",
+        "sq(x) + 1;",
+        "x = x + 1",
+        "let y = 2; y * x",
+        "{ let z = sq(2); z - 1 }",
+        "if x > 3 { 1 } else { 0 }",
+        "y = 5",
+        "K = 4",
+        "1 / 0",
+        "end 7; 8",
+    ];
+
+    for input in inputs {
+        let mut scope = context.clone();
+        let evaluated = evaluate(input, &context).map_err(|error| error.to_string());
+        let executed = execute(input, &mut scope).map_err(|error| error.to_string());
+        assert_eq!(evaluated.map(Some), executed, "input: {}", input);
+    }
+
+    // Only execute keeps what the input changed
+    assert_eq!(evaluate("x = 10", &context)?, 10.0);
+    assert_eq!(context.get("x"), Some(&4.0));
+    assert_eq!(execute("x = 10", &mut context)?, Some(10.0));
+    assert_eq!(context.get("x"), Some(&10.0));
+
+    Ok(())
+}
+
+/// Tests that pure evaluation reads the context but refuses to change it
+#[test]
+fn test_evaluate_pure() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let x = 4; const K = 3; fn sq(n) { n * n }; memo fn twice(n) { n * 2 }; proc bump() { x = x + 1 }", &mut context)?;
+    let snapshot = |context: &SymbolTable<f32>| {
+        let mut values: Vec<(String, u32)> = context.values.iter().map(|(name, value)| (name.clone(), value.to_bits())).collect();
+        values.sort();
+        (values, context.constants.clone(), context.functions.len(), context.procedures.len())
+    };
+    let before = snapshot(&context);
+
+    assert_eq!(evaluate_pure("sq(x) + K", &context)?, 19.0);
+    assert_eq!(evaluate_pure("if x > 3 { 1 } else { 0 }", &context)?, 1.0);
+
+    // Memoized results stay with the evaluation, on either path
+    assert_eq!(evaluate_pure("twice(x) + 1", &context)?, 9.0);
+    assert_eq!(evaluate_pure("if x > 3 { twice(5) + 1 } else { 0 }", &context)?, 11.0);
+    assert_eq!(context.memo_cache.len("twice"), 0);
+
+    // Anything that would change the session is refused before it runs
+    for (input, effect) in [
+        ("x = 5", "assigns `x`"),
+        ("1 + (x = 5)", "assigns `x`"),
+        ("let y = 2; y", "declares `y`"),
+        ("fn g(n) { n }", "declares `g`"),
+        ("bump()", "calls the procedure `bump`"),
+        ("if x > 3 { x = 0 }", "assigns `x`"),
+    ] {
+        match evaluate_pure(input, &context) {
+            Err(InterpreterError::Exec(ExecutionError::SideEffect(found))) => assert_eq!(found, effect, "input: {}", input),
+            other => panic!("{} was not refused: {:?}", input, other),
+        }
+    }
+    assert_eq!(snapshot(&context), before);
+
+    Ok(())
+}
+
 //----------------------------------------------------------------------
 // Comment Support Tests
 //----------------------------------------------------------------------
@@ -513,9 +944,9 @@ fn test_logical_ops_in_program_flow() -> Result<(), Box<dyn Error>> {
 /// Tests line comments in expressions.
 #[test]
 fn test_line_comments() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     let input = "1 + 2 // This is a comment\n + 3";
-    let result = evaluate(input, &mut context)?;
+    let result = evaluate(input, &context)?;
     assert_eq!(result, 6.0);
     Ok(())
 }
@@ -523,9 +954,9 @@ fn test_line_comments() -> Result<(), Box<dyn Error>> {
 /// Tests block comments in expressions.
 #[test]
 fn test_block_comments() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     let input = "1 + /* This is a block comment */ 2";
-    let result = evaluate(input, &mut context)?;
+    let result = evaluate(input, &context)?;
     assert_eq!(result, 3.0);
     Ok(())
 }
@@ -534,9 +965,9 @@ fn test_block_comments() -> Result<(), Box<dyn Error>> {
 #[test]
 fn test_complex_comment_expression() -> Result<(), Box<dyn Error>> {
     // Since nested block comments aren't supported, we'll test with a simpler but more complex expression
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     let input = "1 + /* Block comment with symbols: +, -, *, / */ 2 * 3";
-    let result = evaluate(input, &mut context)?;
+    let result = evaluate(input, &context)?;
     assert_eq!(result, 7.0);
     Ok(())
 }
@@ -544,9 +975,9 @@ fn test_complex_comment_expression() -> Result<(), Box<dyn Error>> {
 /// Tests expressions with mixed line and block comments.
 #[test]
 fn test_mixed_comments() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     let input = "1 + /* Block comment */\n2 // Line comment\n + 3";
-    let result = evaluate(input, &mut context)?;
+    let result = evaluate(input, &context)?;
     assert_eq!(result, 6.0);
     Ok(())
 }
@@ -554,9 +985,9 @@ fn test_mixed_comments() -> Result<(), Box<dyn Error>> {
 /// Tests expressions with comments at the end of the line.
 #[test]
 fn test_comment_at_end() -> Result<(), Box<dyn Error>> {
-    let mut context = SymbolTable::<f32>::new();
+    let context = SymbolTable::<f32>::new();
     let input = "1 + 2 // End comment";
-    let result = evaluate(input, &mut context)?;
+    let result = evaluate(input, &context)?;
     assert_eq!(result, 3.0);
     Ok(())
 }
@@ -678,6 +1109,32 @@ fn test_while_loop() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests that empty blocks and blank programs run to no value.
+#[test]
+fn test_empty_blocks_and_programs() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    for code in ["{}", "{ }", "{ /* nothing */ }", "{ {} }", "if 1 {}", "if 0 {} else {}", "while 0 {}", "foreach i in 0..3 {}"] {
+        assert_eq!(execute(code, &mut context)?, None, "{}", code);
+    }
+
+    // An empty block gives no value, so an earlier one is kept
+    assert_eq!(execute("let x = 2; if x { } else { 5 }", &mut context)?, None);
+    assert_eq!(execute("let i = 0; while i < 3 { i += 1; {} }", &mut context)?, Some(3.0));
+
+    // Blank and comment-only programs are not errors
+    for code in ["", "   \n", "// just a note", "/* a */ ;; // b"] {
+        assert!(is_blank_input(code), "{:?}", code);
+        assert_eq!(execute(code, &mut context)?, None);
+    }
+    assert!(!is_blank_input("1"));
+    assert!(!is_blank_input("; {}"));
+
+    // An expression needs something to evaluate
+    assert!(evaluate("// nothing", &context).is_err());
+
+    Ok(())
+}
+
 /// Tests while with complex counter modifications.
 #[test]
 fn test_complex_loop_counter_modification() -> Result<(), Box<dyn Error>> {
@@ -748,6 +1205,52 @@ fn test_while_condition_reevaluation() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+//----------------------------------------------------------------------
+// Foreach Loop Tests
+//----------------------------------------------------------------------
+
+/// Tests foreach over a numeric range.
+#[test]
+fn test_foreach_range() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let sum = 0; foreach i in 0..5 { sum += i }", &mut context)?;
+    assert_eq!(context.get("sum"), Some(&10.0)); // 0 + 1 + 2 + 3 + 4 = 10
+    
+    // The loop variable is local to the loop
+    assert!(!context.contains("i"));
+    Ok(())
+}
+
+/// Tests that empty and reversed ranges run zero times.
+#[test]
+fn test_foreach_empty_and_reversed_range() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let runs = 0; foreach i in 3..3 { runs += 1 }", &mut context)?;
+    assert_eq!(context.get("runs"), Some(&0.0));
+    
+    execute("foreach i in 5..0 { runs += 1 }", &mut context)?;
+    assert_eq!(context.get("runs"), Some(&0.0));
+    Ok(())
+}
+
+/// Tests break and continue inside foreach, with range bounds from expressions.
+#[test]
+fn test_foreach_break_and_continue() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    let code = r#"
+        let n = 10;
+        let odd_sum = 0;
+        foreach i in 0..n * 2 {
+            if i >= n { break }
+            if i % 2 == 0 { continue }
+            odd_sum += i
+        }
+    "#;
+    execute(code, &mut context)?;
+    assert_eq!(context.get("odd_sum"), Some(&25.0)); // 1 + 3 + 5 + 7 + 9 = 25
+    Ok(())
+}
+
 //----------------------------------------------------------------------
 // Break and Continue Tests
 //----------------------------------------------------------------------
@@ -886,6 +1389,90 @@ fn test_continue_in_while() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests continue statements in deeply nested blocks within while loops.
+#[test]
+fn test_continue_in_nested_block_inside_while() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::new();
+    
+    let test_code = r#"
+        let counter = 0;
+        let sum = 0;
+        while counter < 6 {
+            counter = counter + 1;
+            {
+                if counter < 4 {
+                    counter = counter + 0;
+                } else {
+                    {
+                        continue;
+                    }
+                }
+            }
+            sum = sum + counter;
+        }
+        sum
+    "#;
+    
+    let result = execute(test_code, &mut context)?;
+    // Only 1, 2 and 3 are added
+    assert_eq!(result, Some(6.0));
+    assert_eq!(context.get("counter"), Some(&6.0));
+    Ok(())
+}
+
+/// Tests continue statements in nested blocks within foreach loops.
+#[test]
+fn test_continue_in_nested_block_inside_foreach() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::new();
+    
+    let test_code = r#"
+        let sum = 0;
+        foreach i in 0..10 {
+            {
+                if i % 3 != 0 {
+                    { continue }
+                }
+            }
+            sum = sum + i;
+        }
+        sum
+    "#;
+    
+    let result = execute(test_code, &mut context)?;
+    // Only multiples of three are added: 0 + 3 + 6 + 9 = 18
+    assert_eq!(result, Some(18.0));
+    Ok(())
+}
+
+/// Tests that continue in an inner loop only affects that loop.
+#[test]
+fn test_continue_in_nested_loops() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::new();
+    
+    let test_code = r#"
+        let pairs = 0;
+        let rows = 0;
+        foreach i in 0..4 {
+            let j = 0;
+            while j < 4 {
+                j = j + 1;
+                if j == i { continue }
+                pairs = pairs + 1;
+            }
+            if i == 0 { continue }
+            rows = rows + 1;
+        }
+        pairs * 10 + rows
+    "#;
+    
+    let result = execute(test_code, &mut context)?;
+    // Rows 1..3 each skip one of their four pairs, row 0 skips none
+    assert_eq!(context.get("pairs"), Some(&13.0));
+    assert_eq!(context.get("rows"), Some(&3.0));
+    assert_eq!(result, Some(133.0));
+    Ok(())
+}
+
 //----------------------------------------------------------------------
 // Return Statement Tests (Now using End)
 //----------------------------------------------------------------------
@@ -935,6 +1522,26 @@ fn test_return_in_block() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Tests that the detailed result reports whether the script ended early.
+#[test]
+fn test_execution_result_ended() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+
+    let ended = execute_detailed("let x = 5; if x > 1 { end x * 2; } x", &mut context)?;
+    assert_eq!(ended, ExecutionResult { value: Some(10.0), ended: true, output: Vec::new() });
+
+    // Normal completion, also right after a run that ended
+    let finished = execute_detailed("x + 1", &mut context)?;
+    assert_eq!(finished, ExecutionResult { value: Some(6.0), ended: false, output: Vec::new() });
+
+    // A bare `end` has no value but still counts
+    let bare = execute_detailed("end; x", &mut context)?;
+    assert!(bare.ended);
+    assert_eq!(bare.value, None);
+
+    Ok(())
+}
+
 /// Tests that return statements correctly short-circuit execution.
 #[test]
 fn test_return_short_circuit() -> Result<(), Box<dyn Error>> {
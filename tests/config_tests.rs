@@ -0,0 +1,70 @@
+use log::LevelFilter;
+use ppaaeedb::config::{Config, ConfigError};
+use std::collections::HashMap;
+use std::error::Error;
+
+/// Builds a synthetic environment from name and value pairs.
+fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+    pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+//----------------------------------------------------------------------
+// Startup Configuration Tests
+//----------------------------------------------------------------------
+
+/// Tests the defaults when only the token is set.
+#[test]
+fn test_config_defaults() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_vars(&vars(&[("DISCORD_TOKEN", "secret")]))?;
+
+    assert_eq!(config.discord_token, "secret");
+    assert_eq!(config.log_level, LevelFilter::Error);
+    assert!(config.admin_ids.is_empty());
+    assert_eq!(config.test_guild_id, None);
+    assert!(!format!("{:?}", config).contains("secret"));
+
+    Ok(())
+}
+
+/// Tests reading every setting.
+#[test]
+fn test_config_all_settings() -> Result<(), Box<dyn Error>> {
+    let config = Config::from_vars(&vars(&[
+        ("DISCORD_TOKEN", " secret "),
+        ("RUST_LOG", "Debug"),
+        ("ADMIN_IDS", "1, 2 3"),
+        ("TEST_GUILD_ID", "42"),
+    ]))?;
+
+    assert_eq!(config.discord_token, "secret");
+    assert_eq!(config.log_level, LevelFilter::Debug);
+    assert_eq!(config.admin_ids, [1, 2, 3].into_iter().collect());
+    assert_eq!(config.test_guild_id, Some(42));
+
+    Ok(())
+}
+
+/// Tests that a missing or blank token is reported.
+#[test]
+fn test_config_missing_token() -> Result<(), Box<dyn Error>> {
+    assert_eq!(Config::from_vars(&vars(&[])), Err(ConfigError::MissingToken));
+    assert_eq!(Config::from_vars(&vars(&[("DISCORD_TOKEN", "  ")])), Err(ConfigError::MissingToken));
+    assert!(ConfigError::MissingToken.to_string().contains("DISCORD_TOKEN"));
+
+    Ok(())
+}
+
+/// Tests that malformed values name the variable instead of being ignored.
+#[test]
+fn test_config_malformed_values() -> Result<(), Box<dyn Error>> {
+    for (name, value) in [("RUST_LOG", "loud"), ("ADMIN_IDS", "1,two"), ("ADMIN_IDS", "0"), ("TEST_GUILD_ID", "-5")] {
+        let error = Config::from_vars(&vars(&[("DISCORD_TOKEN", "secret"), (name, value)]))
+            .expect_err("malformed value was accepted");
+
+        assert!(matches!(&error, ConfigError::InvalidValue { name: reported, .. } if *reported == name));
+        assert!(error.to_string().contains(name));
+        assert!(error.to_string().contains(value));
+    }
+
+    Ok(())
+}
@@ -0,0 +1,559 @@
+use ppaaeedb::core::{execute, with_shared_constants, Limit, LimitOverrides, SnippetRegistry, SymbolTable};
+use ppaaeedb::discord::{GlobalConstants, HistoryKind, InvocationScope, SharedState, UserSession};
+use ppaaeedb::discord::commands::evaluate_buttons::{decode_custom_id, encode_custom_id, EvaluateAction, CUSTOM_ID_LIMIT};
+use ppaaeedb::discord::commands::executor::{check_code_file_size, decode_code_file, CodeFileError, MAX_CODE_FILE_SIZE};
+use ppaaeedb::discord::commands::help::help_examples;
+use ppaaeedb::discord::commands::plot::{render_plot, sample_series, value_range};
+use ppaaeedb::discord::commands::publish::format_publish_description;
+use ppaaeedb::discord::commands::profile::{format_error_rate, format_favorite_functions, format_session_age};
+use ppaaeedb::discord::commands::set_limit::parse_limit;
+use ppaaeedb::discord::commands::vars::{describe_binding, format_result, group_symbols};
+use ppaaeedb::discord::retry::{backoff_delay, retry_decision, ApiFailure, RetryDecision};
+use ppaaeedb::discord::theme::{format_hex_colour, parse_hex_colour, Theme, Tone};
+use ppaaeedb::utils::EMBED_DESCRIPTION_LIMIT;
+use serenity::all::Colour;
+use std::error::Error;
+use std::time::Duration;
+
+//----------------------------------------------------------------------
+// Invocation Scope Tests
+//----------------------------------------------------------------------
+
+/// Tests that guild features are only offered where the bot is installed.
+#[test]
+fn test_invocation_scope_detection() -> Result<(), Box<dyn Error>> {
+    let guild = InvocationScope::detect(Some(10), true);
+    assert_eq!(guild, InvocationScope::Guild(10));
+    assert!(guild.offers_guild_features());
+
+    // A user install in a server the bot is not a member of
+    let user_install = InvocationScope::detect(Some(10), false);
+    assert_eq!(user_install, InvocationScope::UserInstalledGuild(10));
+    assert!(!user_install.offers_guild_features());
+
+    // DMs never have a guild, whatever the install type
+    assert_eq!(InvocationScope::detect(None, true), InvocationScope::Private);
+    assert!(!InvocationScope::detect(None, false).offers_guild_features());
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Help Example Tests
+//----------------------------------------------------------------------
+
+/// Tests that every code example in the help embeds runs without errors.
+#[test]
+fn test_help_examples_execute() -> Result<(), Box<dyn Error>> {
+    for example in help_examples() {
+        let mut context = SymbolTable::<f32>::new();
+        if let Err(error) = execute(example.code, &mut context) {
+            panic!("Help example '{}' failed: {}\n{}", example.key, error, example.code);
+        }
+    }
+
+    Ok(())
+}
+
+/// Tests that every help example is shown in an embed.
+#[test]
+fn test_help_examples_are_shown() -> Result<(), Box<dyn Error>> {
+    let source = include_str!("../src/discord/commands/help.rs");
+
+    for example in help_examples() {
+        let usage = format!("example_field(\"{}\")", example.key);
+        assert!(source.contains(&usage), "help example '{}' is never shown", example.key);
+    }
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Profile Tests
+//----------------------------------------------------------------------
+
+/// Tests that evaluations update the session counters.
+#[test]
+fn test_record_evaluation() -> Result<(), Box<dyn Error>> {
+    let mut session = UserSession::new();
+    session.record_evaluation("sqrt(16) + sqrt(abs(-4))", true);
+    session.record_evaluation("my_function(1) + sqrt(1)", false);
+
+    assert_eq!(session.evaluations, 2);
+    assert_eq!(session.errors, 1);
+
+    // Only built-in functions are tracked
+    assert_eq!(session.favorite_functions(5), vec![("sqrt", 3), ("abs", 1)]);
+
+    Ok(())
+}
+
+/// Tests that replaying the history after clearing rebuilds the same variables.
+#[test]
+fn test_history_replay() -> Result<(), Box<dyn Error>> {
+    let mut session = UserSession::new();
+    for code in ["let x = 3", "fn double(n) { n * 2 }", "let y = double(x) + 1", "x = x + y"] {
+        let result = execute(code, &mut session.variables)?;
+        session.record_history(code, result.map(|value| value.to_string()), HistoryKind::Executed);
+    }
+    let before: Vec<(String, f32)> = session.variables.iter_sorted().map(|(name, value)| (name.to_string(), *value)).collect();
+
+    session.clear_variables();
+    assert!(session.variables.is_empty());
+    assert_eq!(session.history.len(), 4);
+
+    let replay = session.replay_history(&SnippetRegistry::new(), LimitOverrides::default());
+    assert_eq!(replay.succeeded, 4);
+    assert!(replay.failures.is_empty());
+
+    let after: Vec<(String, f32)> = replay.variables.iter_sorted().map(|(name, value)| (name.to_string(), *value)).collect();
+    assert_eq!(after, before);
+    assert_eq!(after, vec![("x".to_string(), 10.0), ("y".to_string(), 7.0)]);
+    assert!(replay.variables.functions.contains_key("double"));
+
+    // An input that relied on something outside the history fails on replay
+    session.record_history("z + 1", None, HistoryKind::Executed);
+    let replay = session.replay_history(&SnippetRegistry::new(), LimitOverrides::default());
+    assert_eq!(replay.failures.len(), 1);
+    assert_eq!(replay.failures[0].0, "z + 1");
+
+    Ok(())
+}
+
+/// Tests that evaluated inputs are left out of the replay and the exported script.
+#[test]
+fn test_history_skips_evaluated_inputs() -> Result<(), Box<dyn Error>> {
+    let mut session = UserSession::new();
+    session.record_history("let x = 3", None, HistoryKind::Executed);
+    // `/evaluate` ran this on a copy of the variables, so x stayed 3
+    session.record_history("x = 10; x", Some("10".to_string()), HistoryKind::Evaluated);
+    session.record_history("let y = x + 1", None, HistoryKind::Executed);
+
+    let replay = session.replay_history(&SnippetRegistry::new(), LimitOverrides::default());
+    assert_eq!(replay.succeeded, 2);
+    assert!(replay.failures.is_empty());
+    assert_eq!(replay.variables.get("y"), Some(&4.0));
+
+    let script = session.history_script();
+    assert!(!script.contains("x = 10"));
+    let mut rebuilt = SymbolTable::<f32>::new();
+    execute(&script, &mut rebuilt)?;
+    assert_eq!(rebuilt.get("y"), Some(&4.0));
+
+    Ok(())
+}
+
+/// Tests that the whole replay shares one time limit.
+#[test]
+fn test_history_replay_time_budget() -> Result<(), Box<dyn Error>> {
+    let mut session = UserSession::new();
+    for code in [
+        "let zero = 0",
+        "fn slow(n) { if n < 2 { n + zero } else { slow(n - 1) + slow(n - 2) } }",
+        "slow(30)",
+        "let after = 1",
+    ] {
+        session.record_history(code, None, HistoryKind::Executed);
+    }
+
+    let mut limits = LimitOverrides::default();
+    limits.set(Limit::TimeLimit, Some(100)).map_err(|_| "limit out of range")?;
+    let replay = session.replay_history(&SnippetRegistry::new(), limits);
+
+    // The slow input uses up the time, so the one after it does not run
+    assert_eq!(replay.succeeded, 2);
+    let failed: Vec<&str> = replay.failures.iter().map(|(input, _)| input.as_str()).collect();
+    assert_eq!(failed, vec!["slow(30)", "let after = 1"]);
+    assert_eq!(replay.failures[0].1, replay.failures[1].1);
+    assert!(replay.failures[1].1.contains("100"));
+    assert!(replay.variables.get("after").is_none());
+
+    Ok(())
+}
+
+/// Tests that each server has its own global constants, and each user outside servers.
+#[test]
+fn test_global_constants_per_server() -> Result<(), Box<dyn Error>> {
+    let mut constants = GlobalConstants::default();
+    let mut variables = SymbolTable::<f32>::new();
+    with_shared_constants(constants.table(Some(10), 1), 1, || execute("const global RATE = 2", &mut variables))?;
+
+    assert_eq!(constants.table(Some(10), 2).get("RATE").map(|constant| constant.value), Some(2.0));
+    assert!(constants.table(Some(20), 1).get("RATE").is_none());
+    assert!(constants.table(None, 1).get("RATE").is_none());
+
+    // Another server may publish the same name
+    with_shared_constants(constants.table(Some(20), 2), 2, || execute("const global RATE = 3", &mut variables))?;
+    assert_eq!(constants.table(Some(20), 1).get("RATE").map(|constant| constant.value), Some(3.0));
+    assert_eq!(constants.table(Some(10), 1).get("RATE").map(|constant| constant.value), Some(2.0));
+
+    // Outside servers users do not share a table
+    with_shared_constants(constants.table(None, 1), 1, || execute("const global OWN = 1", &mut variables))?;
+    assert!(constants.table(None, 2).get("OWN").is_none());
+
+    Ok(())
+}
+
+/// Tests formatting the profile fields.
+#[test]
+fn test_profile_formatting() -> Result<(), Box<dyn Error>> {
+    let mut session = UserSession::new();
+    assert_eq!(format_error_rate(&session), "`0`");
+    assert_eq!(format_favorite_functions(&session), "_No built-in functions used yet._");
+    assert!(format_session_age(&session).starts_with('0'));
+
+    session.record_evaluation("sin(1) + sin(2) + cos(3)", true);
+    session.record_evaluation("1 / 0", false);
+    session.record_evaluation("2 + 2", true);
+    session.record_evaluation("x", false);
+
+    assert_eq!(format_error_rate(&session), "`2` (50.0%)");
+    assert_eq!(format_favorite_functions(&session), "1. `sin` - 2 calls\n2. `cos` - 1 call");
+
+    Ok(())
+}
+
+/// Tests that /vars groups symbols by kind and formats values like results.
+#[test]
+fn test_vars_grouping() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    assert!(group_symbols(&context).is_empty());
+
+    execute("let b = 0.5; let a = 2; const c = 3", &mut context)?;
+    execute("let d := a * 2", &mut context)?;
+    execute("fn f(x, y) { x + y }", &mut context)?;
+    execute("proc p() { let q = 1 }", &mut context)?;
+
+    let sections = group_symbols(&context);
+    let titles: Vec<&str> = sections.iter().map(|section| section.title).collect();
+    assert_eq!(titles, ["Constants", "Variables", "Functions", "Procedures"]);
+
+    let names = |index: usize| sections[index].entries.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>();
+    assert_eq!(names(0), ["c"]);
+    assert_eq!(names(1), ["a", "b", "d"]);
+    assert_eq!(sections[0].entries[0].1, "**const** c = 3");
+    assert_eq!(sections[1].entries[1].1, format!("**let** b = {}", format_result(0.5)));
+    assert!(sections[1].entries[2].1.ends_with("→ 4"));
+    assert_eq!(sections[2].entries[0].1, "**fn** f(x, y)");
+    assert_eq!(sections[3].entries[0].1, "**proc** p()");
+
+    // Empty groups are left out
+    let mut context = SymbolTable::<f32>::new();
+    execute("fn g(n) { n }", &mut context)?;
+    assert_eq!(group_symbols(&context).iter().map(|section| section.title).collect::<Vec<_>>(), ["Functions"]);
+
+    Ok(())
+}
+
+/// Tests that results read with `_1` or `_` during a script are not listed by /vars.
+#[test]
+fn test_vars_hide_result_history() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let a = 2; a * 3; let b = _2 + _", &mut context)?;
+
+    let sections = group_symbols(&context);
+    let names: Vec<&str> = sections.iter().flat_map(|section| section.entries.iter().map(|(name, _)| name.as_str())).collect();
+    assert_eq!(names, ["a", "b"]);
+    assert_eq!(context.get("b"), Some(&12.0));
+
+    Ok(())
+}
+
+/// Tests that /vars name:<x> describes one binding of each kind.
+#[test]
+fn test_vars_describe_binding() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let a = 2; const c = 3; let d := a * 2", &mut context)?;
+    execute("memo fn f(x, y) { x + y }; proc p() { let q = 1 }", &mut context)?;
+
+    assert_eq!(describe_binding(&context, "a").as_deref(), Some("**let** a = 2\n_variable_"));
+    assert_eq!(describe_binding(&context, "c").as_deref(), Some("**const** c = 3\n_constant_"));
+    assert_eq!(describe_binding(&context, "d").as_deref(), Some("**let** d := a \\* 2 → 4\n_formula, re-evaluated on every read_"));
+    assert_eq!(describe_binding(&context, "f").as_deref(), Some("**memo fn** f(x, y)\n_function_"));
+    assert_eq!(describe_binding(&context, "p").as_deref(), Some("**proc** p()\n_procedure_"));
+    assert_eq!(describe_binding(&context, "missing"), None);
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Plot Sampling Tests
+//----------------------------------------------------------------------
+
+/// Tests sampling several series over a shared range with their own gaps.
+#[test]
+fn test_sample_series() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let offset = 1", &mut context)?;
+
+    let series = sample_series(&["x + offset", "sqrt(x)"], &context, -1.0, 1.0, 5)?;
+    assert_eq!(series.len(), 2);
+    assert_eq!(series[0].label, "x + offset");
+
+    // Both series share the x values
+    let xs: Vec<f32> = series[0].points.iter().map(|(x, _)| *x).collect();
+    assert_eq!(xs, vec![-1.0, -0.5, 0.0, 0.5, 1.0]);
+    assert_eq!(series[1].points.iter().map(|(x, _)| *x).collect::<Vec<_>>(), xs);
+
+    // The square root is undefined left of zero, the line nowhere
+    assert!(series[0].points.iter().all(|(_, y)| y.is_some()));
+    assert_eq!(series[1].points[0].1, None);
+    assert_eq!(series[1].points[1].1, None);
+    assert_eq!(series[1].points[4].1, Some(1.0));
+    assert_eq!(value_range(&series), Some((0.0, 2.0)));
+
+    // Syntax errors are reported rather than plotted as gaps
+    assert!(sample_series(&["x +", "x"], &context, 0.0, 1.0, 3).is_err());
+
+    Ok(())
+}
+
+/// Tests that a chart is only rendered when something is defined.
+#[test]
+fn test_render_plot_legend() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+
+    let series = sample_series(&["x", "sqrt(0 - 1)"], &context, -2.0, 2.0, 9)?;
+    let chart = render_plot(&series, 5).ok_or("nothing plotted")?;
+    assert!(chart.contains("* x\n"));
+    assert!(chart.contains("o sqrt(0 - 1) (undefined on this range)"));
+
+    let undefined = sample_series(&["sqrt(0 - 1)"], &context, -2.0, 2.0, 9)?;
+    assert!(render_plot(&undefined, 5).is_none());
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Evaluate Button Tests
+//----------------------------------------------------------------------
+
+/// Tests that button ids carry the expression and action through a round trip.
+#[test]
+fn test_evaluate_custom_id_round_trip() -> Result<(), Box<dyn Error>> {
+    for action in EvaluateAction::ALL {
+        for expression in ["2 + 2", "a:b ? 1 : 0", "√(x) × 2 // ünïcode", ""] {
+            let custom_id = encode_custom_id(action, expression).ok_or("expression should fit")?;
+            assert_eq!(decode_custom_id(&custom_id), Some((action, expression)));
+        }
+    }
+
+    // Ids of other components are not mistaken for buttons
+    assert_eq!(decode_custom_id("help_command_select"), None);
+    assert_eq!(decode_custom_id("evaluate:unknown:2"), None);
+    assert_eq!(decode_custom_id("evaluate"), None);
+
+    Ok(())
+}
+
+/// Tests that expressions only fit when the id stays within Discord's limit.
+#[test]
+fn test_evaluate_custom_id_length_limit() -> Result<(), Box<dyn Error>> {
+    for length in 0..=CUSTOM_ID_LIMIT {
+        if let Some(custom_id) = encode_custom_id(EvaluateAction::Graph, &"1".repeat(length)) {
+            assert!(custom_id.chars().count() <= CUSTOM_ID_LIMIT);
+        }
+    }
+    assert!(encode_custom_id(EvaluateAction::Graph, &"1".repeat(80)).is_some());
+    assert!(encode_custom_id(EvaluateAction::Graph, &"1".repeat(CUSTOM_ID_LIMIT)).is_none());
+
+    // The limit counts characters, not bytes
+    assert!(encode_custom_id(EvaluateAction::Graph, &"√".repeat(80)).is_some());
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Response Retry Tests
+//----------------------------------------------------------------------
+
+/// Tests that retry delays double up to a cap, with jitter in the upper half.
+#[test]
+fn test_backoff_schedule() -> Result<(), Box<dyn Error>> {
+    let base = Duration::from_millis(250);
+
+    assert_eq!(backoff_delay(base, 0, 1.0), Duration::from_millis(250));
+    assert_eq!(backoff_delay(base, 1, 1.0), Duration::from_millis(500));
+    assert_eq!(backoff_delay(base, 2, 1.0), Duration::from_millis(1000));
+    assert_eq!(backoff_delay(base, 10, 1.0), Duration::from_secs(2));
+    assert_eq!(backoff_delay(base, 1, 0.0), Duration::from_millis(250));
+    assert_eq!(backoff_delay(base, 1, 0.5), Duration::from_millis(375));
+
+    Ok(())
+}
+
+/// Tests which failures are retried, followed up or given up on.
+#[test]
+fn test_retry_decision() -> Result<(), Box<dyn Error>> {
+    let status = |status: u16, code: isize| ApiFailure { status: Some(status), code: Some(code), network: false };
+    let network = ApiFailure { status: None, code: None, network: true };
+    let acknowledged = status(400, 40060);
+
+    assert_eq!(retry_decision(&status(500, 0), 0, 3), RetryDecision::Retry);
+    assert_eq!(retry_decision(&status(429, 0), 1, 3), RetryDecision::Retry);
+    assert_eq!(retry_decision(&network, 0, 3), RetryDecision::Retry);
+    assert_eq!(retry_decision(&status(503, 0), 2, 3), RetryDecision::GiveUp);
+    assert_eq!(retry_decision(&status(400, 50035), 0, 3), RetryDecision::GiveUp);
+    assert_eq!(retry_decision(&status(404, 10062), 0, 3), RetryDecision::GiveUp);
+
+    // Answered elsewhere before the first attempt, or by an attempt that seemed to fail
+    assert_eq!(retry_decision(&acknowledged, 0, 3), RetryDecision::FollowUp);
+    assert_eq!(retry_decision(&acknowledged, 1, 3), RetryDecision::Delivered);
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Code File Tests
+//----------------------------------------------------------------------
+
+/// Tests that code files over the size limit are refused before and after downloading.
+#[test]
+fn test_code_file_size_limit() -> Result<(), Box<dyn Error>> {
+    assert_eq!(check_code_file_size(0), Ok(()));
+    assert_eq!(check_code_file_size(MAX_CODE_FILE_SIZE), Ok(()));
+    assert_eq!(
+        check_code_file_size(MAX_CODE_FILE_SIZE + 1),
+        Err(CodeFileError::TooLarge { size: MAX_CODE_FILE_SIZE + 1 })
+    );
+
+    // The downloaded bytes are checked too, since the reported size may be wrong
+    assert_eq!(decode_code_file(b"let x = 2; x * 3".to_vec())?, "let x = 2; x * 3");
+    assert!(matches!(decode_code_file(vec![b'1'; MAX_CODE_FILE_SIZE + 1]), Err(CodeFileError::TooLarge { .. })));
+    assert_eq!(decode_code_file(vec![0xff, 0xfe]), Err(CodeFileError::NotText));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Publish Tests
+//----------------------------------------------------------------------
+
+/// Tests that the publish confirmation escapes the name and fits long code in the embed.
+#[test]
+fn test_publish_description() -> Result<(), Box<dyn Error>> {
+    let description = format_publish_description("my_lib", "fn f(x) = x ```");
+    assert!(description.contains("fn f(x) = x `\u{200B}`\u{200B}`"));
+    assert!(description.contains("import \"my\\_lib\""));
+
+    let mention = format_publish_description("@everyone", "const a = 1;");
+    assert!(!mention.contains("@everyone"));
+
+    let long = format_publish_description("long", &"const a = 1;\n".repeat(1000));
+    assert!(long.chars().count() <= EMBED_DESCRIPTION_LIMIT);
+    assert!(long.contains("import \"long\""));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Theme Tests
+//----------------------------------------------------------------------
+
+/// Tests reading and writing hexadecimal colours.
+#[test]
+fn test_hex_colour_parsing() -> Result<(), Box<dyn Error>> {
+    assert_eq!(parse_hex_colour("#5865F2")?, Colour::new(0x5865F2));
+    assert_eq!(parse_hex_colour(" 5865f2 ")?, Colour::new(0x5865F2));
+    assert_eq!(format_hex_colour(parse_hex_colour("#00ff0a")?), "#00FF0A");
+
+    for invalid in ["", "#", "#12345", "#1234567", "GGGGGG", "##123456", "+12345"] {
+        let error = parse_hex_colour(invalid).expect_err("invalid colour was accepted");
+        assert!(error.to_string().contains(invalid.trim()));
+    }
+
+    Ok(())
+}
+
+/// Tests that an accent only replaces the neutral colours.
+#[test]
+fn test_theme_accent() -> Result<(), Box<dyn Error>> {
+    let accent = Colour::new(0x123456);
+    let theme = Theme { accent: Some(accent) };
+
+    assert_eq!(theme.colour(Tone::Info), accent);
+    assert_eq!(theme.colour(Tone::Help), accent);
+    for tone in [Tone::Success, Tone::Error, Tone::Warning] {
+        assert_eq!(theme.colour(tone), tone.default_colour());
+    }
+
+    Ok(())
+}
+
+/// Tests that servers without a theme get the default colours.
+#[test]
+fn test_guild_theme_fallback() -> Result<(), Box<dyn Error>> {
+    let mut state = SharedState::default();
+    assert_eq!(state.theme_for(None), Theme::default());
+    assert_eq!(state.theme_for(Some(7)), Theme::default());
+
+    let theme = Theme { accent: Some(Colour::new(0xABCDEF)) };
+    state.guild_themes.insert(7, theme);
+    assert_eq!(state.theme_for(Some(7)), theme);
+    assert_eq!(state.theme_for(Some(8)), Theme::default());
+    assert_eq!(state.theme_for(None), Theme::default());
+    assert_eq!(Theme::default().colour(Tone::Info), Tone::Info.default_colour());
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Server Limit Tests
+//----------------------------------------------------------------------
+
+/// Tests that a server's limits take precedence over the defaults, and only in that server.
+#[test]
+fn test_guild_limit_precedence() -> Result<(), Box<dyn Error>> {
+    let mut state = SharedState::default();
+    for guild in [None, Some(7)] {
+        for limit in Limit::ALL {
+            assert_eq!(state.limits_for(guild).value(limit), limit.value());
+        }
+    }
+
+    let mut limits = LimitOverrides::default();
+    limits.set(Limit::LoopIterations, Some(5000)).map_err(|_| "limit out of range")?;
+    state.guild_limits.insert(7, limits);
+
+    assert_eq!(state.limits_for(Some(7)).value(Limit::LoopIterations), 5000);
+    assert_eq!(state.limits_for(Some(7)).value(Limit::NestingDepth), Limit::NestingDepth.value());
+    assert_eq!(state.limits_for(Some(8)).value(Limit::LoopIterations), Limit::LoopIterations.value());
+    assert_eq!(state.limits_for(None).value(Limit::LoopIterations), Limit::LoopIterations.value());
+
+    // Resetting the override brings the default back
+    limits.set(Limit::LoopIterations, None).map_err(|_| "limit out of range")?;
+    assert!(limits.is_empty());
+    assert_eq!(limits.value(Limit::LoopIterations), Limit::LoopIterations.value());
+
+    Ok(())
+}
+
+/// Tests which limits /set-limit accepts and the values it allows.
+#[test]
+fn test_set_limit_ranges() -> Result<(), Box<dyn Error>> {
+    assert_eq!(parse_limit("loop_iterations"), Some(Limit::LoopIterations));
+    assert_eq!(parse_limit("nesting_depth"), Some(Limit::NestingDepth));
+    assert_eq!(parse_limit("call_depth"), Some(Limit::CallDepth));
+    assert_eq!(parse_limit("time_limit"), Some(Limit::TimeLimit));
+    assert_eq!(parse_limit("import_depth"), None);
+
+    let mut limits = LimitOverrides::default();
+    assert!(limits.set(Limit::LoopIterations, Some(0)).is_err());
+    assert!(limits.set(Limit::NestingDepth, Some(Limit::NestingDepth.value() + 1)).is_err());
+    assert!(limits.set(Limit::ImportDepth, Some(Limit::ImportDepth.value())).is_err());
+    assert!(limits.is_empty());
+
+    // Loops may run longer than by default, nesting may only get shallower
+    assert!(limits.set(Limit::LoopIterations, Some(Limit::LoopIterations.value() * 2)).is_ok());
+    assert!(limits.set(Limit::NestingDepth, Some(32)).is_ok());
+    assert_eq!(limits.get(Limit::NestingDepth), Some(32));
+
+    // Calls may only get shallower and executions shorter
+    assert!(limits.set(Limit::CallDepth, Some(Limit::CallDepth.value() + 1)).is_err());
+    assert!(limits.set(Limit::TimeLimit, Some(Limit::TimeLimit.value() + 1)).is_err());
+    assert!(limits.set(Limit::CallDepth, Some(50)).is_ok());
+    assert!(limits.set(Limit::TimeLimit, Some(500)).is_ok());
+    assert_eq!(limits.value(Limit::TimeLimit), 500);
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use ppaaeedb::core::{execute, evaluate, SymbolTable};
+use ppaaeedb::i18n::{localize_error, lookup, resolve_locale, translate, translate_with};
+use std::error::Error;
+
+//----------------------------------------------------------------------
+// Lookup and Fallback Tests
+//----------------------------------------------------------------------
+
+/// Tests that Discord locale codes resolve to the supported locales.
+#[test]
+fn test_resolve_locale() -> Result<(), Box<dyn Error>> {
+    assert_eq!(resolve_locale("en-US"), "en");
+    assert_eq!(resolve_locale("es-ES"), "es");
+    assert_eq!(resolve_locale("es-419"), "es");
+    assert_eq!(resolve_locale("es"), "es");
+
+    // Unsupported locales fall back to English
+    assert_eq!(resolve_locale("ja"), "en");
+    assert_eq!(resolve_locale(""), "en");
+
+    Ok(())
+}
+
+/// Tests translating keys and falling back to English.
+#[test]
+fn test_translate_fallback() -> Result<(), Box<dyn Error>> {
+    assert_eq!(translate("en", "command.help.description"), "Shows detailed help for the calculator");
+    assert_eq!(translate("es-ES", "command.help.description"), "Muestra la ayuda detallada de la calculadora");
+
+    // Unsupported locales use English
+    assert_eq!(translate("de", "command.help.description"), "Shows detailed help for the calculator");
+
+    // Unknown keys are returned as-is
+    assert_eq!(translate("es", "no.such.key"), "no.such.key");
+    assert!(lookup("es", "no.such.key").is_none());
+
+    Ok(())
+}
+
+/// Tests that placeholders are substituted.
+#[test]
+fn test_translate_with_placeholders() -> Result<(), Box<dyn Error>> {
+    let text = translate_with("en", "message.language_set", &[("language", "English".to_string())]);
+    assert_eq!(text, "I will answer you in English from now on.");
+
+    Ok(())
+}
+
+/// Tests that the Spanish file only uses keys English also defines.
+#[test]
+fn test_every_spanish_key_exists_in_english() -> Result<(), Box<dyn Error>> {
+    let spanish: toml::Table = include_str!("../locales/es.toml").parse()?;
+    let english: toml::Table = include_str!("../locales/en.toml").parse()?;
+
+    // Sections shared with English must not contain keys English lacks,
+    // except for error messages whose English text comes from Display
+    for (section, value) in &spanish {
+        if section == "error" {
+            continue;
+        }
+        assert!(english.contains_key(section), "section '{}' is missing from en.toml", section);
+        if let (Some(spanish_table), Some(english_table)) = (value.as_table(), english[section].as_table()) {
+            for key in spanish_table.keys() {
+                assert!(english_table.contains_key(key), "key '{}.{}' is missing from en.toml", section, key);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Error Translation Tests
+//----------------------------------------------------------------------
+
+/// Tests that interpreter errors are translated.
+#[test]
+fn test_translated_error() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    let error = evaluate("1 / 0", &context).unwrap_err();
+
+    assert_eq!(localize_error("en-US", &error), "Division by zero error: divisor `0` evaluated to 0.");
+    assert_eq!(localize_error("es-ES", &error), "Error de división por cero: el divisor `0` vale 0.");
+
+    // Placeholders are filled from the error
+    let error = evaluate("missing + 1", &context).unwrap_err();
+    assert!(localize_error("es", &error).contains("'missing'"));
+
+    Ok(())
+}
+
+/// Tests that untranslated errors fall back to their English text.
+#[test]
+fn test_untranslated_error_falls_back() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    let error = execute("return 1", &mut context).unwrap_err();
+
+    let english = localize_error("en", &error);
+    assert_eq!(localize_error("es", &error), english);
+
+    Ok(())
+}
@@ -0,0 +1,482 @@
+use ppaaeedb::core::{evaluate, execute, SymbolTable};
+use ppaaeedb::utils::{convert_base, escape_markdown, select_expression, extract_code_from_message, format_factorization, fit_code_echoes, format_relative_time, normalize_input, parse_bindings, prime_factors, sanitize_for_codeblock, to_csv, transcript_to_script, truncate_for_discord, BaseConversionError, BindingParseError, CODE_ECHO_LIMIT, Extreme, Pick, Selection, TRUNCATION_NOTICE};
+use std::error::Error;
+use std::time::Duration;
+
+//----------------------------------------------------------------------
+// Base Conversion Tests
+//----------------------------------------------------------------------
+
+/// Tests converting between common bases.
+#[test]
+fn test_convert_base() -> Result<(), Box<dyn Error>> {
+    assert_eq!(convert_base("255", 10, 16)?, "FF");
+    assert_eq!(convert_base("1010", 2, 10)?, "10");
+    assert_eq!(convert_base("0", 10, 2)?, "0");
+    assert_eq!(convert_base("-42", 10, 2)?, "-101010");
+    assert_eq!(convert_base("zz", 36, 10)?, "1295");
+
+    Ok(())
+}
+
+/// Tests that converting there and back returns the original digits.
+#[test]
+fn test_convert_base_round_trip() -> Result<(), Box<dyn Error>> {
+    for base in 2..=36 {
+        let converted = convert_base("123456789", 10, base)?;
+        assert_eq!(convert_base(&converted, base, 10)?, "123456789");
+    }
+
+    Ok(())
+}
+
+/// Tests that letter digits are accepted in either case.
+#[test]
+fn test_convert_base_case_insensitive() -> Result<(), Box<dyn Error>> {
+    assert_eq!(convert_base("ff", 16, 10)?, "255");
+    assert_eq!(convert_base("FF", 16, 10)?, "255");
+    assert_eq!(convert_base("aBc", 16, 16)?, "ABC");
+
+    Ok(())
+}
+
+/// Tests rejecting invalid digits and bases.
+#[test]
+fn test_convert_base_errors() -> Result<(), Box<dyn Error>> {
+    assert_eq!(convert_base("102", 2, 10), Err(BaseConversionError::InvalidDigit { digit: '2', base: 2 }));
+    assert_eq!(convert_base("g", 16, 10), Err(BaseConversionError::InvalidDigit { digit: 'g', base: 16 }));
+    assert_eq!(convert_base("1", 1, 10), Err(BaseConversionError::InvalidBase(1)));
+    assert_eq!(convert_base("1", 10, 37), Err(BaseConversionError::InvalidBase(37)));
+    assert_eq!(convert_base("", 10, 2), Err(BaseConversionError::Empty));
+    assert_eq!(convert_base("z".repeat(40).as_str(), 36, 10), Err(BaseConversionError::Overflow));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Input Normalization Tests
+//----------------------------------------------------------------------
+
+/// Tests that fullwidth digits and operators are folded to ASCII.
+#[test]
+fn test_normalize_fullwidth() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    let input = normalize_input("１２３ ＋ ７");
+    assert_eq!(input, "123 + 7");
+    assert_eq!(evaluate(&input, &context)?, 130.0);
+
+    Ok(())
+}
+
+/// Tests that Unicode minus signs, multiplication signs and special spaces are folded.
+#[test]
+fn test_normalize_minus_and_spaces() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    let input = normalize_input("10\u{00A0}−\u{2009}4 × 2");
+    assert_eq!(input, "10 - 4 * 2");
+    assert_eq!(evaluate(&input, &context)?, 2.0);
+
+    // Operators the calculator understands natively are kept
+    assert_eq!(normalize_input("√9"), "√9");
+
+    Ok(())
+}
+
+/// Tests that smart quotes become straight quotes.
+#[test]
+fn test_normalize_smart_quotes() -> Result<(), Box<dyn Error>> {
+    assert_eq!(normalize_input("import “geometry”"), "import \"geometry\"");
+    assert_eq!(normalize_input("‘a’"), "'a'");
+
+    Ok(())
+}
+
+/// Tests that zero-width characters are removed.
+#[test]
+fn test_normalize_zero_width() -> Result<(), Box<dyn Error>> {
+    let context = SymbolTable::<f32>::new();
+    let input = normalize_input("2\u{200D}0 +\u{200B} 1\u{FEFF}");
+    assert_eq!(input, "20 + 1");
+    assert_eq!(evaluate(&input, &context)?, 21.0);
+
+    Ok(())
+}
+
+/// Tests that code extracted from messages is normalized.
+#[test]
+fn test_extract_code_normalizes() -> Result<(), Box<dyn Error>> {
+    assert_eq!(extract_code_from_message("```rs\n５ − ２\n```"), Some("5 - 2\n".to_string()));
+    assert_eq!(extract_code_from_message("try `１ + １`"), Some("1 + 1".to_string()));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Prime Factorization Tests
+//----------------------------------------------------------------------
+
+/// Tests factoring small composites.
+#[test]
+fn test_prime_factors() -> Result<(), Box<dyn Error>> {
+    assert_eq!(prime_factors(360), vec![(2, 3), (3, 2), (5, 1)]);
+    assert_eq!(prime_factors(1024), vec![(2, 10)]);
+    assert_eq!(prime_factors(15), vec![(3, 1), (5, 1)]);
+
+    Ok(())
+}
+
+/// Tests that 0 and 1 have no factors and primes are their own factor.
+#[test]
+fn test_prime_factors_edge_cases() -> Result<(), Box<dyn Error>> {
+    assert!(prime_factors(0).is_empty());
+    assert!(prime_factors(1).is_empty());
+    assert_eq!(prime_factors(2), vec![(2, 1)]);
+    assert_eq!(prime_factors(97), vec![(97, 1)]);
+    assert_eq!(prime_factors(2_147_483_647), vec![(2_147_483_647, 1)]);
+
+    Ok(())
+}
+
+/// Tests factoring a large composite.
+#[test]
+fn test_prime_factors_large_composite() -> Result<(), Box<dyn Error>> {
+    assert_eq!(prime_factors(600_851_475_143), vec![(71, 1), (839, 1), (1471, 1), (6857, 1)]);
+
+    let n = 9_007_199_254_740_991;
+    let product: u64 = prime_factors(n).iter().map(|(p, e)| p.pow(*e)).product();
+    assert_eq!(product, n);
+
+    Ok(())
+}
+
+/// Tests formatting factorizations.
+#[test]
+fn test_format_factorization() -> Result<(), Box<dyn Error>> {
+    assert_eq!(format_factorization(360), "360 = 2^3 * 3^2 * 5");
+    assert_eq!(format_factorization(13), "13 = 13");
+    assert_eq!(format_factorization(1), "1 = 1");
+    assert_eq!(format_factorization(0), "0 has no prime factorization");
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Discord Length Limit Tests
+//----------------------------------------------------------------------
+
+/// Tests that text within the limit is left alone.
+#[test]
+fn test_truncate_under_and_at_limit() -> Result<(), Box<dyn Error>> {
+    assert_eq!(truncate_for_discord("short", 100), "short");
+    assert_eq!(truncate_for_discord("exactly10!", 10), "exactly10!");
+    assert_eq!(truncate_for_discord("", 0), "");
+
+    Ok(())
+}
+
+/// Tests cutting text over the limit.
+#[test]
+fn test_truncate_over_limit() -> Result<(), Box<dyn Error>> {
+    let text = "a".repeat(200);
+    let truncated = truncate_for_discord(&text, 100);
+
+    assert!(truncated.chars().count() <= 100);
+    assert!(truncated.ends_with(TRUNCATION_NOTICE));
+    assert!(truncated.starts_with("aaaa"));
+
+    // Limits too small for the notice still hold
+    assert_eq!(truncate_for_discord(&text, 3), "aaa");
+
+    Ok(())
+}
+
+/// Tests that truncation never splits a multi-byte character.
+#[test]
+fn test_truncate_multibyte() -> Result<(), Box<dyn Error>> {
+    let text = "é√🎲".repeat(50);
+    for limit in [1, 2, 30, 31, 32, 99, 149, 150] {
+        let truncated = truncate_for_discord(&text, limit);
+        assert!(truncated.chars().count() <= limit, "limit {} exceeded", limit);
+        assert!(text.starts_with(truncated.trim_end_matches(TRUNCATION_NOTICE)));
+    }
+    assert_eq!(truncate_for_discord(&text, 150), text);
+
+    Ok(())
+}
+
+/// Tests that the code and parsed echoes share one length budget.
+#[test]
+fn test_fit_code_echoes() -> Result<(), Box<dyn Error>> {
+    let (echo, parsed) = fit_code_echoes("1 + 2 * 3", Some("1 + (2 * 3)"));
+    assert_eq!(echo, "1 + 2 * 3");
+    assert_eq!(parsed.as_deref(), Some("1 + (2 * 3)"));
+
+    // Long code and a long parse are cut to fit together
+    let code = "x".repeat(2000);
+    let source = "y".repeat(3000);
+    let (echo, parsed) = fit_code_echoes(&code, Some(&source));
+    let parsed = parsed.ok_or("parsed echo left out")?;
+    assert_eq!(echo, code);
+    assert!(echo.chars().count() + parsed.chars().count() <= CODE_ECHO_LIMIT);
+    assert!(parsed.ends_with(TRUNCATION_NOTICE));
+
+    // Code that fills the budget leaves no room for the parse
+    let code = "z".repeat(CODE_ECHO_LIMIT * 2);
+    let (echo, parsed) = fit_code_echoes(&code, Some(&source));
+    assert!(echo.chars().count() <= CODE_ECHO_LIMIT);
+    assert_eq!(parsed, None);
+
+    Ok(())
+}
+
+/// Tests that a code block opened before the cut is closed.
+#[test]
+fn test_truncate_closes_code_block() -> Result<(), Box<dyn Error>> {
+    let message = format!("```fix\n{}\n```", "error ".repeat(100));
+    let truncated = truncate_for_discord(&message, 80);
+
+    assert!(truncated.chars().count() <= 80);
+    assert_eq!(truncated.matches("```").count(), 2);
+    assert!(truncated.ends_with(&format!("\n```{}", TRUNCATION_NOTICE)));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// CSV Export Tests
+//----------------------------------------------------------------------
+
+/// Tests serializing plain variables with a header row.
+#[test]
+fn test_to_csv() -> Result<(), Box<dyn Error>> {
+    let csv = to_csv(&[("x", "5", "let"), ("rate", "0.25", "const"), ("area", "78.53982", "formula")]);
+    assert_eq!(csv, "name,value,kind\nx,5,let\nrate,0.25,const\narea,78.53982,formula\n");
+
+    // No variables still gives the header
+    assert_eq!(to_csv(&[]), "name,value,kind\n");
+
+    Ok(())
+}
+
+/// Tests quoting of fields with commas, quotes and line breaks.
+#[test]
+fn test_to_csv_escaping() -> Result<(), Box<dyn Error>> {
+    assert_eq!(to_csv(&[("a,b", "1", "let")]), "name,value,kind\n\"a,b\",1,let\n");
+    assert_eq!(to_csv(&[("say \"hi\"", "", "formula")]), "name,value,kind\n\"say \"\"hi\"\"\",,formula\n");
+    assert_eq!(to_csv(&[("two\nlines", "-inf", "let")]), "name,value,kind\n\"two\nlines\",-inf,let\n");
+
+    // Quotes alone are enough to need quoting
+    assert_eq!(to_csv(&[("\"", "1", "let")]), "name,value,kind\n\"\"\"\",1,let\n");
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Transcript Tests
+//----------------------------------------------------------------------
+
+/// Tests the layout of an exported transcript.
+#[test]
+fn test_transcript_to_script() -> Result<(), Box<dyn Error>> {
+    let script = transcript_to_script(&[
+        ("let x = 5", Some("5")),
+        ("x * 2;", Some("10")),
+        ("fn double(n) {\n    return n * 2\n}", None),
+        ("double(x) // twice", Some("10")),
+    ]);
+
+    assert_eq!(
+        script,
+        "// PrattCalc session transcript (4 inputs)\n\
+         let x = 5; // = 5\n\
+         x * 2; // = 10\n\
+         fn double(n) {\n    return n * 2\n}\n\
+         double(x) // twice\n;\n// = 10\n"
+    );
+
+    Ok(())
+}
+
+/// Tests that running an exported transcript rebuilds the same session.
+#[test]
+fn test_transcript_round_trip() -> Result<(), Box<dyn Error>> {
+    let inputs = [
+        "let total = 0",
+        "let rate = 0.25 // tax",
+        "fn taxed(x) { x * (1 + rate) }",
+        "foreach i in 1 .. 4 {\n    total += taxed(i)\n}",
+        "const LIMIT = 100",
+        "total",
+    ];
+
+    let mut context = SymbolTable::<f32>::new();
+    let mut transcript = Vec::new();
+    for input in inputs {
+        let result = execute(input, &mut context)?;
+        transcript.push((input, result.map(|value| value.to_string())));
+    }
+
+    let entries: Vec<(&str, Option<&str>)> = transcript.iter().map(|(input, result)| (*input, result.as_deref())).collect();
+    let script = transcript_to_script(&entries);
+
+    let mut replayed = SymbolTable::<f32>::new();
+    let last = execute(&script, &mut replayed)?;
+
+    assert_eq!(last, execute("total", &mut context)?);
+    assert_eq!(replayed.iter_sorted().collect::<Vec<_>>(), context.iter_sorted().collect::<Vec<_>>());
+    assert!(replayed.is_constant("LIMIT"));
+    assert!(replayed.functions.contains_key("taxed"));
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Binding Tests
+//----------------------------------------------------------------------
+
+/// Tests parsing comma-separated name=value pairs.
+#[test]
+fn test_parse_bindings() -> Result<(), Box<dyn Error>> {
+    assert_eq!(parse_bindings("x=3, y=4")?, vec![("x".to_string(), 3.0), ("y".to_string(), 4.0)]);
+    assert_eq!(parse_bindings(" rate = -0.5 ,")?, vec![("rate".to_string(), -0.5)]);
+    assert_eq!(parse_bindings("")?, vec![]);
+
+    Ok(())
+}
+
+/// Tests that malformed bindings are rejected.
+#[test]
+fn test_parse_bindings_errors() -> Result<(), Box<dyn Error>> {
+    assert_eq!(parse_bindings("x 3"), Err(BindingParseError::MissingEquals("x 3".to_string())));
+    assert_eq!(parse_bindings("2x=3"), Err(BindingParseError::InvalidName("2x".to_string())));
+    assert_eq!(parse_bindings("x=two"), Err(BindingParseError::InvalidValue { name: "x".to_string(), value: "two".to_string() }));
+    assert_eq!(parse_bindings("x=inf"), Err(BindingParseError::InvalidValue { name: "x".to_string(), value: "inf".to_string() }));
+    assert_eq!(parse_bindings("x=1, x=2"), Err(BindingParseError::Duplicate("x".to_string())));
+
+    Ok(())
+}
+
+/// Tests that bindings apply to one evaluation and leave the session unchanged.
+#[test]
+fn test_bindings_overlay() -> Result<(), Box<dyn Error>> {
+    let mut context = SymbolTable::<f32>::new();
+    execute("let x = 10; const K = 2", &mut context)?;
+
+    let overlay = context.overlay(&parse_bindings("x=3, y=4")?)?;
+    assert_eq!(evaluate("x^2 + y * K", &overlay)?, 17.0);
+
+    // The session keeps its own values and never sees the one-off names
+    assert_eq!(evaluate("x", &context)?, 10.0);
+    assert!(!context.contains("y"));
+
+    // Constants cannot be overridden
+    assert!(context.overlay(&parse_bindings("K=5")?).is_err());
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Relative Time Tests
+//----------------------------------------------------------------------
+
+/// Tests that elapsed times are shown in their largest unit.
+#[test]
+fn test_format_relative_time() -> Result<(), Box<dyn Error>> {
+    assert_eq!(format_relative_time(Duration::from_secs(0)), "just now");
+    assert_eq!(format_relative_time(Duration::from_secs(59)), "just now");
+    assert_eq!(format_relative_time(Duration::from_secs(60)), "1m ago");
+    assert_eq!(format_relative_time(Duration::from_secs(3599)), "59m ago");
+    assert_eq!(format_relative_time(Duration::from_secs(2 * 3600 + 1800)), "2h ago");
+    assert_eq!(format_relative_time(Duration::from_secs(3 * 86400)), "3d ago");
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Markdown Escaping Tests
+//----------------------------------------------------------------------
+
+/// Tests that markdown and mentions in user text show as typed.
+#[test]
+fn test_escape_markdown() -> Result<(), Box<dyn Error>> {
+    assert_eq!(escape_markdown("plain text 12"), "plain text 12");
+    assert_eq!(escape_markdown("my_var"), "my\\_var");
+    assert_eq!(escape_markdown("a * b ** c"), "a \\* b \\*\\* c");
+    assert_eq!(escape_markdown("`code` > ~~x~~ || y ||"), "\\`code\\` \\> \\~\\~x\\~\\~ \\|\\| y \\|\\|");
+    assert_eq!(escape_markdown("[link](url)"), "\\[link\\](url)");
+    assert_eq!(escape_markdown("back\\slash"), "back\\\\slash");
+
+    // Mentions are broken up so they cannot ping anyone
+    for mention in ["@everyone", "@here", "<@1234>", "<@&5678>"] {
+        let escaped = escape_markdown(mention);
+        assert!(!escaped.contains(mention));
+        assert_eq!(escaped.replace(['\u{200B}', '\\'], ""), mention);
+    }
+
+    // Long text is escaped throughout without being cut
+    let long = "_".repeat(5000);
+    assert_eq!(escape_markdown(&long), "\\_".repeat(5000));
+
+    Ok(())
+}
+
+/// Tests that user text can never close the code block it is shown in.
+#[test]
+fn test_sanitize_for_codeblock() -> Result<(), Box<dyn Error>> {
+    assert_eq!(sanitize_for_codeblock("x = 1 + 2"), "x = 1 + 2");
+    assert_eq!(sanitize_for_codeblock("a `b` c"), "a `b` c");
+    assert_eq!(sanitize_for_codeblock("@everyone"), "@everyone");
+
+    for text in ["```", "````", "`````", "``````", "x```\n@everyone\n```rs\ny", "``"] {
+        let sanitized = sanitize_for_codeblock(text);
+        assert!(!sanitized.contains("``"), "{:?} gave {:?}", text, sanitized);
+        assert_eq!(sanitized.replace('\u{200B}', ""), text);
+    }
+
+    // Sanitized text keeps a truncated code block well formed
+    let long = format!("```{}", "a".repeat(5000));
+    let block = format!("```fix\n{}\n```", sanitize_for_codeblock(&long));
+    let truncated = truncate_for_discord(&block, 100);
+    assert_eq!(truncated.matches("```").count(), 2);
+
+    Ok(())
+}
+
+//----------------------------------------------------------------------
+// Expression Selection Tests
+//----------------------------------------------------------------------
+
+/// Tests picking the larger or smaller of two values, with the margin.
+#[test]
+fn test_select_expression() -> Result<(), Box<dyn Error>> {
+    let picked = |first, second, extreme| select_expression(Some(first), Some(second), extreme).ok_or("nothing picked");
+
+    assert_eq!(picked(3.0, 5.0, Extreme::Max)?, Selection { pick: Pick::Second, value: 5.0, margin: Some(2.0) });
+    assert_eq!(picked(3.0, 5.0, Extreme::Min)?, Selection { pick: Pick::First, value: 3.0, margin: Some(2.0) });
+    assert_eq!(picked(-1.0, -4.0, Extreme::Max)?, Selection { pick: Pick::First, value: -1.0, margin: Some(3.0) });
+    assert_eq!(picked(2.5, 2.5, Extreme::Min)?, Selection { pick: Pick::Tie, value: 2.5, margin: Some(0.0) });
+    assert_eq!(picked(f32::INFINITY, f32::INFINITY, Extreme::Max)?.margin, Some(0.0));
+    assert_eq!(picked(f32::INFINITY, 1.0, Extreme::Max)?.pick, Pick::First);
+
+    Ok(())
+}
+
+/// Tests that an expression without a value loses by default.
+#[test]
+fn test_select_expression_by_default() -> Result<(), Box<dyn Error>> {
+    for extreme in [Extreme::Max, Extreme::Min] {
+        assert_eq!(select_expression(None, Some(7.0), extreme), Some(Selection { pick: Pick::Second, value: 7.0, margin: None }));
+        assert_eq!(select_expression(Some(7.0), None, extreme), Some(Selection { pick: Pick::First, value: 7.0, margin: None }));
+        assert_eq!(select_expression(Some(f32::NAN), Some(-2.0), extreme), Some(Selection { pick: Pick::Second, value: -2.0, margin: None }));
+        assert_eq!(select_expression(None, None, extreme), None);
+        assert_eq!(select_expression(Some(f32::NAN), None, extreme), None);
+    }
+
+    // Failed evaluations feed in as missing values
+    let context = SymbolTable::<f32>::new();
+    let first = evaluate("1 / 0 + undefined_name", &context).ok();
+    let second = evaluate("2 ^ 3", &context).ok();
+    assert_eq!(select_expression(first, second, Extreme::Max).map(|selection| selection.pick), Some(Pick::Second));
+
+    Ok(())
+}